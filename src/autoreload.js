@@ -12,6 +12,9 @@
                 ? 'wss'
                 : 'ws';
     const url = protocol + '://' + address + base + '.well-known/trunk/ws';
+    // in milliseconds
+    const reconnectDelay = Number('{{__TRUNK_WS_RECONNECT_DELAY__}}') || 5000;
+    const reconnectMaxDelay = Math.max(Number('{{__TRUNK_WS_RECONNECT_MAX_DELAY__}}') || 0, reconnectDelay);
 
     class Overlay {
         constructor() {
@@ -73,13 +76,18 @@
     class Client {
         constructor(url) {
             this.url = url;
-            this.poll_interval = 5000;
+            this.reconnect_delay = reconnectDelay;
             this._overlay = null;
         }
 
         start() {
             const ws = new WebSocket(this.url);
+            ws.binaryType = "arraybuffer";
             ws.onmessage = (ev) => {
+                if (ev.data instanceof ArrayBuffer) {
+                    this.payload(ev.data);
+                    return;
+                }
                 const msg = JSON.parse(ev.data);
                 switch (msg.type) {
                     case "reload":
@@ -94,6 +102,9 @@
         }
 
         onclose() {
+            const delay = this.reconnect_delay;
+            // back off while trunk can't be reached, e.g. after suspending the machine
+            this.reconnect_delay = Math.min(delay * 2, reconnectMaxDelay);
             window.setTimeout(
                 () => {
                     // when we successfully reconnect, we'll force a
@@ -104,13 +115,24 @@
                     ws.onopen = () => window.location.reload();
                     ws.onclose = () => this.onclose();
                 },
-                this.poll_interval);
+                delay);
         }
 
         reload() {
             window.location.reload();
         }
 
+        async payload(data) {
+            // the first byte marks the encoding of the payload: 0 = none, 1 = gzip
+            const encoding = new Uint8Array(data, 0, 1)[0];
+            let content = data.slice(1);
+            if (encoding === 1) {
+                const stream = new Blob([content]).stream().pipeThrough(new DecompressionStream("gzip"));
+                content = await new Response(stream).arrayBuffer();
+            }
+            window.dispatchEvent(new CustomEvent("TrunkPayload", {detail: {data: content}}));
+        }
+
         buildFailure({reason}) {
             // also log the console
             console.error("Build failed:", reason);
@@ -1,20 +1,58 @@
 //! Build system & asset pipelines.
 
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
-use anyhow::{Context, Result};
+use anyhow::{ensure, Context, Result};
+use flate2::{write::GzEncoder, Compression};
 use futures_util::stream::StreamExt;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 use tokio::fs;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReadDirStream;
 
-use crate::common::{remove_dir_all, BUILDING, ERROR, SUCCESS};
-use crate::config::{rt::RtcBuild, types::WsProtocol, STAGE_DIR};
-use crate::pipelines::HtmlPipeline;
+use crate::common::{dist_relative, remove_dir_all, BUILDING, ERROR, SUCCESS};
+use crate::config::{
+    rt::RtcBuild,
+    types::{CompressionFormat, WsProtocol},
+    STAGE_DIR,
+};
+use crate::pipelines::{AssetKind, HtmlPipeline, OutputRecord};
+use crate::processing::integrity::{IntegrityType, OutputDigest};
+use crate::progress;
+
+/// The name of the build manifest, in the dist dir.
+///
+/// This must not collide with files of the application, like the `manifest.json` of a PWA.
+const MANIFEST_FILE: &str = ".trunk-manifest.json";
 
 pub type BuildResult = Result<()>;
 
+/// The summary of a successful build.
+#[derive(Clone, Debug)]
+pub struct BuildOutput {
+    /// The dist dir the application was written to.
+    pub dist: PathBuf,
+    /// The files written to the dist dir by the asset pipelines, sorted by name.
+    pub artifacts: Vec<OutputRecord>,
+}
+
+/// Build the application described by the configuration, like `trunk build` does.
+///
+/// This checks the required version of Trunk first, and returns the files which were written to
+/// the dist dir, rather than only logging them.
+pub async fn build(cfg: Arc<RtcBuild>) -> Result<BuildOutput> {
+    cfg.core.enforce_version()?;
+
+    let mut system = BuildSystem::new(cfg, None, None, None).await?;
+    system.build().await
+}
+
 /// A system used for building a Rust WASM app & bundling its assets.
 ///
 /// This unit of data should be used throughout the system for driving build processes and
@@ -36,21 +74,28 @@ impl BuildSystem {
     pub async fn new(
         cfg: Arc<RtcBuild>,
         ignore_chan: Option<mpsc::Sender<PathBuf>>,
+        watch_chan: Option<mpsc::UnboundedSender<PathBuf>>,
         ws_protocol: Option<WsProtocol>,
     ) -> Result<Self> {
-        let html_pipeline = Arc::new(HtmlPipeline::new(cfg.clone(), ignore_chan, ws_protocol)?);
+        let html_pipeline = Arc::new(HtmlPipeline::new(
+            cfg.clone(),
+            ignore_chan,
+            watch_chan,
+            ws_protocol,
+        )?);
         Ok(Self { cfg, html_pipeline })
     }
 
     /// Build the application described in the given build data.
     #[tracing::instrument(level = "trace", skip(self))]
-    pub async fn build(&mut self) -> Result<()> {
+    pub async fn build(&mut self) -> Result<BuildOutput> {
         tracing::info!("{}starting build", BUILDING);
         let res = self.do_build().await;
+        progress::finish();
         match res {
-            Ok(_) => {
+            Ok(output) => {
                 tracing::info!("{}success", SUCCESS);
-                Ok(())
+                Ok(output)
             }
             Err(err) => {
                 tracing::error!("{}error\n{:?}", ERROR, err);
@@ -60,7 +105,7 @@ impl BuildSystem {
     }
 
     /// Internal business logic of `build`.
-    async fn do_build(&mut self) -> Result<()> {
+    async fn do_build(&mut self) -> Result<BuildOutput> {
         // Ensure the output dist directories are in place.
         fs::create_dir_all(self.cfg.final_dist.as_path())
             .await
@@ -75,9 +120,12 @@ impl BuildSystem {
             .await
             .context("error preparing build environment")?;
 
+        progress::phase("assets");
+
         // Spawn the source HTML pipeline. This will spawn all other pipelines derived from
         // the source HTML, and will ultimately generate and write the final HTML.
-        self.html_pipeline
+        let output = self
+            .html_pipeline
             .clone()
             .spawn()
             .await
@@ -86,10 +134,175 @@ impl BuildSystem {
             // what makes more sense to the user
             .context("error from build pipeline")?;
 
+        progress::phase("dist");
+
+        if !self.cfg.compression.is_empty() {
+            self.precompress()
+                .await
+                .context("error precompressing output files")?;
+        }
+
+        let preserved = self
+            .preserved_files(&output.preserved)
+            .await
+            .context("error evaluating the preserved files of the dist dir")?;
+
+        // Check the budget before replacing the previous build
+        self.check_dist_size(&preserved).await?;
+
         // Move distribution from staging dist to final dist
-        self.finalize_dist()
+        self.finalize_dist(&preserved)
             .await
             .context("error applying built distribution")?;
+
+        if self.cfg.emit_manifest {
+            self.write_manifest(output.records.clone())
+                .await
+                .context("error writing build manifest")?;
+        }
+
+        if self.cfg.reproducible {
+            self.normalize_timestamps()
+                .await
+                .context("error normalizing timestamps of dist dir")?;
+        }
+
+        if self.cfg.log_sizes {
+            if let Err(err) = self.log_sizes().await {
+                tracing::warn!("unable to evaluate output sizes: {err:#}");
+            }
+        }
+
+        let mut artifacts = output.records;
+        artifacts.sort_by(|a, b| a.file.cmp(&b.file));
+
+        Ok(BuildOutput {
+            dist: self.cfg.final_dist.clone(),
+            artifacts,
+        })
+    }
+
+    /// Write the precompressed variants of the output files in the staging dist dir.
+    async fn precompress(&self) -> Result<()> {
+        let cfg = self.cfg.clone();
+        tokio::task::spawn_blocking(move || {
+            precompress(
+                &cfg.staging_dist,
+                &cfg.compression,
+                Compression::new(cfg.compression_level),
+                cfg.compression_min_size,
+            )
+        })
+        .await
+        .context("error awaiting precompression")?
+    }
+
+    /// Write the manifest of the files of the final dist dir.
+    async fn write_manifest(&self, records: Vec<OutputRecord>) -> Result<()> {
+        let path = self.cfg.final_dist.join(MANIFEST_FILE);
+        ensure!(
+            !path.exists(),
+            "the build wrote a {MANIFEST_FILE} file to the dist dir, which collides with the build manifest"
+        );
+
+        let cfg = self.cfg.clone();
+        let manifest = tokio::task::spawn_blocking(move || {
+            manifest(
+                &cfg.final_dist,
+                &records,
+                &cfg.compression,
+                IntegrityType::default_unless(cfg.no_sri),
+            )
+        })
+        .await
+        .context("error awaiting manifest evaluation")??;
+
+        fs::write(&path, serde_json::to_vec_pretty(&manifest)?)
+            .await
+            .with_context(|| format!("error writing {}", path.display()))?;
+        tracing::info!("wrote build manifest of {} files", manifest.files.len());
+
+        Ok(())
+    }
+
+    /// Set the timestamps of the final dist dir to `SOURCE_DATE_EPOCH`, for reproducible builds.
+    async fn normalize_timestamps(&self) -> Result<()> {
+        let time = source_date_epoch()?;
+        let final_dist = self.cfg.final_dist.clone();
+        tokio::task::spawn_blocking(move || set_timestamps(&final_dist, time))
+            .await
+            .context("error awaiting timestamp normalization")?
+    }
+
+    /// Log the total size and file count of the new dist dir, and enforce the dist budget.
+    ///
+    /// The new dist dir consists of the staging dir and the preserved files of the previous build.
+    /// It is checked before replacing the previous build, which stays in place when exceeding the
+    /// budget.
+    async fn check_dist_size(&self, preserved: &HashSet<PathBuf>) -> Result<()> {
+        let staging_dist = self.cfg.staging_dist.clone();
+        let preserved = preserved.clone();
+        let total = match tokio::task::spawn_blocking(move || {
+            let mut total = dist_size(&staging_dist)?;
+            for file in preserved {
+                // precompressed variants of preserved files might not exist
+                match std::fs::metadata(&file) {
+                    Ok(metadata) if metadata.is_file() => {
+                        total.files += 1;
+                        total.bytes += metadata.len();
+                    }
+                    Ok(_) => {}
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(err) => {
+                        return Err(
+                            anyhow::Error::new(err).context(format!("error reading {file:?}"))
+                        )
+                    }
+                }
+            }
+            Ok(total)
+        })
+        .await
+        .context("error awaiting dist size evaluation")?
+        {
+            Ok(total) => total,
+            Err(err) if self.cfg.dist_budget.is_none() => {
+                tracing::warn!("unable to evaluate dist size: {err:#}");
+                return Ok(());
+            }
+            Err(err) => return Err(err.context("error evaluating dist size")),
+        };
+
+        tracing::info!(
+            "dist contains {} files, {} ({} bytes)",
+            total.files,
+            format_size(total.bytes),
+            total.bytes
+        );
+
+        if let Some(budget) = self.cfg.dist_budget {
+            ensure!(
+                total.bytes <= budget,
+                "dist size of {} bytes exceeds the budget of {budget} bytes",
+                total.bytes
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Log the raw and gzipped sizes of the WASM, JS and CSS files of the final dist dir.
+    async fn log_sizes(&self) -> Result<()> {
+        let final_dist = self.cfg.final_dist.clone();
+        let sizes = tokio::task::spawn_blocking(move || output_sizes(&final_dist))
+            .await
+            .context("error awaiting size evaluation")??;
+
+        tracing::info!("{:>12} {:>12}  file", "raw", "gzip");
+        for OutputSize { name, raw, gzip } in sizes {
+            tracing::info!("{:>12} {:>12}  {name}", format_size(raw), format_size(gzip));
+        }
+
         Ok(())
     }
 
@@ -118,72 +331,917 @@ impl BuildSystem {
 
     /// Moves the contents of dist/.stage into dist, signifying the application
     /// of a successful build. Also removes dist/.stage afterwards.
+    ///
+    /// The preserved files of the dist dir (see [`Self::preserved_files`]) are kept.
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn finalize_dist(&self) -> Result<()> {
+    async fn finalize_dist(&self, preserved: &HashSet<PathBuf>) -> Result<()> {
         let staging_dist = self.cfg.staging_dist.clone();
         tracing::info!("applying new distribution");
 
         // Build succeeded, so delete everything in `dist`, move everything
         // from `dist/.stage` to `dist`, and then delete `dist/.stage`.
-        self.clean_final().await?;
+        self.clean_final(preserved).await?;
         self.move_stage_to_final().await?;
-        fs::remove_dir(staging_dist)
+        // merged directories are left behind empty
+        remove_dir_all(staging_dist)
             .await
             .context("error deleting staging dist dir")?;
 
         Ok(())
     }
 
+    /// The paths of the files in the final dist dir which are kept when applying the build.
+    ///
+    /// These are the preserved files of the previous build, relative to the dist dir, including
+    /// their precompressed variants, and the files matching the `clean.keep` patterns.
+    async fn preserved_files(&self, preserved: &[PathBuf]) -> Result<HashSet<PathBuf>> {
+        let mut result = preserved
+            .iter()
+            .map(|file| self.cfg.final_dist.join(file))
+            .flat_map(|file| {
+                let compressed = self
+                    .cfg
+                    .compression
+                    .iter()
+                    .map(|format| compressed_path(&file, *format))
+                    .collect::<Vec<_>>();
+                std::iter::once(file).chain(compressed)
+            })
+            .collect::<HashSet<_>>();
+
+        if !self.cfg.keep.is_empty() {
+            let cfg = self.cfg.clone();
+            let kept = tokio::task::spawn_blocking(move || {
+                let mut kept = HashSet::new();
+                kept_files(
+                    &cfg.final_dist,
+                    &cfg.final_dist,
+                    &cfg.keep,
+                    false,
+                    &mut kept,
+                )?;
+                anyhow::Ok(kept)
+            })
+            .await
+            .context("error awaiting the evaluation of the kept files")??;
+            result.extend(kept);
+        }
+
+        Ok(result)
+    }
+
     /// Move contents of stage dir to final dist dir.
     async fn move_stage_to_final(&self) -> Result<()> {
-        let final_dist = self.cfg.final_dist.clone();
-        let staging_dist = self.cfg.staging_dist.clone();
+        // directories still existing in the final dist dir (due to preserved files) get merged
+        let mut dirs = vec![(self.cfg.staging_dist.clone(), self.cfg.final_dist.clone())];
+        while let Some((source, target)) = dirs.pop() {
+            let mut entries = fs::read_dir(&source)
+                .await
+                .map(ReadDirStream::new)
+                .context("error reading staging dist dir")?;
+            while let Some(entry) = entries.next().await {
+                let entry = entry.context("error reading contents of staging dist dir")?;
+                let target_path = target.join(entry.file_name());
 
-        let mut entries = fs::read_dir(&staging_dist)
-            .await
-            .map(ReadDirStream::new)
-            .context("error reading staging dist dir")?;
-        while let Some(entry) = entries.next().await {
-            let entry = entry.context("error reading contents of staging dist dir")?;
-            let target_path = final_dist.join(entry.file_name());
+                if entry.file_type().await?.is_dir() && target_path.is_dir() {
+                    dirs.push((entry.path(), target_path));
+                    continue;
+                }
+
+                fs::rename(entry.path(), &target_path)
+                    .await
+                    .with_context(|| {
+                        format!("error moving {:?} to {:?}", &entry.path(), &target_path)
+                    })?;
+            }
+        }
+        Ok(())
+    }
 
-            fs::rename(entry.path(), &target_path)
+    /// Clean the contents of the final dist dir, except for the preserved files.
+    async fn clean_final(&self, preserved: &HashSet<PathBuf>) -> Result<()> {
+        let mut dirs = vec![self.cfg.final_dist.clone()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = fs::read_dir(&dir)
                 .await
-                .with_context(|| {
-                    format!("error moving {:?} to {:?}", &entry.path(), &target_path)
-                })?;
+                .map(ReadDirStream::new)
+                .context("error reading final dist dir")?;
+            while let Some(entry) = entries.next().await {
+                let entry = entry.context("error reading contents of final dist dir")?;
+                if entry.file_name() == STAGE_DIR {
+                    continue;
+                }
+
+                let path = entry.path();
+                let file_type = entry
+                    .file_type()
+                    .await
+                    .context("error reading metadata of file in final dist dir")?;
+                if file_type.is_dir() {
+                    if preserved.iter().any(|file| file.starts_with(&path)) {
+                        dirs.push(path);
+                    } else {
+                        remove_dir_all(path)
+                            .await
+                            .context("error cleaning final dist")?;
+                    }
+                } else if (file_type.is_symlink() || file_type.is_file())
+                    && !preserved.contains(&path)
+                {
+                    fs::remove_file(path)
+                        .await
+                        .context("error cleaning final dist")?;
+                }
+            }
         }
         Ok(())
     }
+}
 
-    /// Clean the contents of the final dist dir.
-    async fn clean_final(&self) -> Result<()> {
-        let final_dist = self.cfg.final_dist.clone();
+/// File extensions of the outputs which are precompressed.
+///
+/// HTML files are left out, as `trunk serve` rewrites them for each request (e.g. the address of
+/// the autoreload websocket or the nonce), which requires the uncompressed content.
+const COMPRESS_EXTENSIONS: &[&str] = &[
+    "wasm", "js", "mjs", "css", "svg", "json", "map", "txt", "xml",
+];
 
-        let mut entries = fs::read_dir(&final_dist)
-            .await
-            .map(ReadDirStream::new)
-            .context("error reading final dist dir")?;
-        while let Some(entry) = entries.next().await {
-            let entry = entry.context("error reading contents of final dist dir")?;
+/// The path of the precompressed variant of a file.
+fn compressed_path(path: &Path, format: CompressionFormat) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(".");
+    path.push(format.extension());
+    path.into()
+}
+
+/// The brotli quality (from 0 to 11) matching a compression level (from 0 to 9).
+fn brotli_quality(level: Compression) -> i32 {
+    (level.level().min(9) * 11 / 9) as i32
+}
+
+/// Write the precompressed variants of all relevant files in the dist dir.
+fn precompress(
+    dist: &Path,
+    formats: &[CompressionFormat],
+    level: Compression,
+    min_size: u64,
+) -> Result<()> {
+    for entry in std::fs::read_dir(dist).with_context(|| format!("error reading {dist:?}"))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            precompress(&path, formats, level, min_size)?;
+            continue;
+        }
+
+        let relevant = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| COMPRESS_EXTENSIONS.contains(&ext));
+        if !relevant || std::fs::metadata(&path)?.len() < min_size {
+            continue;
+        }
+
+        let data = std::fs::read(&path).with_context(|| format!("error reading {path:?}"))?;
+        for format in formats {
+            let compressed = match format {
+                CompressionFormat::Brotli => {
+                    let mut compressed = Vec::new();
+                    let params = brotli::enc::BrotliEncoderParams {
+                        quality: brotli_quality(level),
+                        ..Default::default()
+                    };
+                    brotli::BrotliCompress(&mut data.as_slice(), &mut compressed, &params)?;
+                    compressed
+                }
+                CompressionFormat::Gzip => {
+                    let mut encoder = GzEncoder::new(Vec::new(), level);
+                    encoder.write_all(&data)?;
+                    encoder.finish()?
+                }
+            };
+
+            let target = compressed_path(&path, *format);
+            std::fs::write(&target, compressed)
+                .with_context(|| format!("error writing {target:?}"))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// File extensions of the outputs considered when logging sizes.
+const SIZE_EXTENSIONS: &[&str] = &["wasm", "js", "css"];
+
+/// The raw and compressed size of an output file.
+#[derive(Debug, PartialEq, Eq)]
+struct OutputSize {
+    /// The path of the file, relative to the dist dir.
+    name: String,
+    /// The size of the file.
+    raw: u64,
+    /// The size of the file, when compressed using gzip.
+    gzip: u64,
+}
+
+/// Evaluate the sizes of all relevant files in the dist dir, sorted by name.
+fn output_sizes(dist: &Path) -> Result<Vec<OutputSize>> {
+    fn collect(dist: &Path, dir: &Path, result: &mut Vec<OutputSize>) -> Result<()> {
+        for entry in std::fs::read_dir(dir).with_context(|| format!("error reading {dir:?}"))? {
+            let path = entry?.path();
+            if path.is_dir() {
+                collect(dist, &path, result)?;
+                continue;
+            }
+
+            let relevant = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| SIZE_EXTENSIONS.contains(&ext));
+            if !relevant {
+                continue;
+            }
+
+            let data = std::fs::read(&path).with_context(|| format!("error reading {path:?}"))?;
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&data)?;
+            let gzip = encoder.finish()?.len() as u64;
+
+            result.push(OutputSize {
+                name: dist_relative(dist, &path)?,
+                raw: data.len() as u64,
+                gzip,
+            });
+        }
+        Ok(())
+    }
+
+    let mut result = vec![];
+    collect(dist, dist, &mut result)?;
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(result)
+}
+
+/// The manifest of a build, describing the files of the dist dir.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    /// The size of the WASM file of the application, if any.
+    wasm_size: Option<u64>,
+    /// The files of the dist dir, sorted by name.
+    files: Vec<ManifestEntry>,
+}
+
+/// A single file of the build manifest.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct ManifestEntry {
+    /// The path of the file, relative to the dist dir.
+    file: String,
+    /// The kind of asset the file belongs to.
+    kind: AssetKind,
+    /// The source file the output was created from, if known.
+    source: Option<PathBuf>,
+    /// The size of the file.
+    size: u64,
+    /// The SHA-256 hash of the content, hex encoded.
+    hash: String,
+    /// The integrity value of the file, as used for SRI.
+    integrity: Option<String>,
+}
+
+/// Evaluate the manifest of all files in the dist dir.
+///
+/// The files are described using the records of the pipelines which wrote them. Integrity values
+/// which weren't recorded are generated using the given type.
+fn manifest(
+    dist: &Path,
+    records: &[OutputRecord],
+    compression: &[CompressionFormat],
+    integrity: IntegrityType,
+) -> Result<Manifest> {
+    fn collect(dist: &Path, dir: &Path, result: &mut Vec<(String, PathBuf)>) -> Result<()> {
+        for entry in std::fs::read_dir(dir).with_context(|| format!("error reading {dir:?}"))? {
+            let entry = entry?;
             if entry.file_name() == STAGE_DIR {
                 continue;
             }
 
-            let file_type = entry
-                .file_type()
-                .await
-                .context("error reading metadata of file in final dist dir")?;
-            if file_type.is_dir() {
-                remove_dir_all(entry.path())
-                    .await
-                    .context("error cleaning final dist")?;
-            } else if file_type.is_symlink() || file_type.is_file() {
-                fs::remove_file(entry.path())
-                    .await
-                    .context("error cleaning final dist")?;
+            let path = entry.path();
+            if path.is_dir() {
+                collect(dist, &path, result)?;
+            } else {
+                result.push((dist_relative(dist, &path)?, path));
             }
         }
         Ok(())
     }
+
+    let mut files = vec![];
+    collect(dist, dist, &mut files)?;
+
+    let record = |name: &str| records.iter().find(|record| record.file == name);
+
+    let mut entries = vec![];
+    for (file, path) in files {
+        if file == MANIFEST_FILE {
+            continue;
+        }
+
+        let data = std::fs::read(&path).with_context(|| format!("error reading {path:?}"))?;
+        let (kind, source, recorded) = match record(&file) {
+            Some(record) => (record.kind, Some(&record.source), record.integrity.clone()),
+            None => {
+                // precompressed variants share the source of the file they were created from
+                let original = compression
+                    .iter()
+                    .find_map(|format| file.strip_suffix(&format!(".{}", format.extension())))
+                    .and_then(record);
+                match original {
+                    Some(original) => (AssetKind::Precompressed, Some(&original.source), None),
+                    None => (AssetKind::Other, None, None),
+                }
+            }
+        };
+        let integrity = recorded.or_else(|| {
+            OutputDigest::generate_from(integrity, &data)
+                .to_integrity_value()
+                .map(|value| value.to_string())
+        });
+
+        entries.push(ManifestEntry {
+            file,
+            kind,
+            source: source.map(|source| crate::common::strip_prefix(source).to_path_buf()),
+            size: data.len() as u64,
+            hash: format!("{:x}", Sha256::digest(&data)),
+            integrity,
+        });
+    }
+    entries.sort_by(|a, b| a.file.cmp(&b.file));
+
+    Ok(Manifest {
+        wasm_size: entries
+            .iter()
+            .find(|entry| entry.kind == AssetKind::RustWasm)
+            .map(|entry| entry.size),
+        files: entries,
+    })
+}
+
+/// The timestamp of the outputs of reproducible builds, from `SOURCE_DATE_EPOCH` or the Unix epoch.
+fn source_date_epoch() -> Result<SystemTime> {
+    let secs = match std::env::var("SOURCE_DATE_EPOCH") {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid SOURCE_DATE_EPOCH '{value}'"))?,
+        Err(_) => 0,
+    };
+    Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Set the modification time of all files (and directories) of the dist dir.
+fn set_timestamps(dist: &Path, time: SystemTime) -> Result<()> {
+    for entry in std::fs::read_dir(dist).with_context(|| format!("error reading {dist:?}"))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            set_timestamps(&path, time)?;
+        } else {
+            std::fs::OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(time))
+                .with_context(|| format!("error setting timestamp of {path:?}"))?;
+        }
+    }
+
+    // directories can only be opened for changing their timestamps on unix
+    #[cfg(unix)]
+    std::fs::File::open(dist)
+        .and_then(|dir| dir.set_modified(time))
+        .with_context(|| format!("error setting timestamp of {dist:?}"))?;
+
+    Ok(())
+}
+
+/// The total size and number of files of a dist dir.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct DistSize {
+    /// The number of files.
+    files: u64,
+    /// The sum of the file sizes.
+    bytes: u64,
+}
+
+/// Collect the files of a directory in the dist dir which match one of the patterns to keep.
+///
+/// All files of a matching directory are kept.
+fn kept_files(
+    dist: &Path,
+    dir: &Path,
+    keep: &[glob::Pattern],
+    matched: bool,
+    result: &mut HashSet<PathBuf>,
+) -> Result<()> {
+    for entry in std::fs::read_dir(dir).with_context(|| format!("error reading {dir:?}"))? {
+        let entry = entry?;
+        if entry.file_name() == STAGE_DIR {
+            continue;
+        }
+
+        let path = entry.path();
+        let name = dist_relative(dist, &path)?;
+        let matched = matched || keep.iter().any(|pattern| pattern.matches(&name));
+        if entry.file_type()?.is_dir() {
+            kept_files(dist, &path, keep, matched, result)?;
+        } else if matched {
+            result.insert(path);
+        }
+    }
+    Ok(())
+}
+
+/// Evaluate the total size of all files in the dist dir.
+fn dist_size(dist: &Path) -> Result<DistSize> {
+    fn collect(dir: &Path, result: &mut DistSize) -> Result<()> {
+        for entry in std::fs::read_dir(dir).with_context(|| format!("error reading {dir:?}"))? {
+            let entry = entry?;
+            if entry.file_name() == STAGE_DIR {
+                continue;
+            }
+
+            let path = entry.path();
+            let metadata =
+                std::fs::metadata(&path).with_context(|| format!("error reading {path:?}"))?;
+            if metadata.is_dir() {
+                collect(&path, result)?;
+            } else {
+                result.files += 1;
+                result.bytes += metadata.len();
+            }
+        }
+        Ok(())
+    }
+
+    let mut result = DistSize::default();
+    collect(dist, &mut result)?;
+    Ok(result)
+}
+
+/// Format a size in bytes as a human readable string.
+fn format_size(size: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB"];
+
+    if size < 1024 {
+        return format!("{size} B");
+    }
+
+    let mut value = size as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+
+    format!("{value:.2} {unit}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sizes_are_formatted() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1024), "1.00 KiB");
+        assert_eq!(format_size(1536), "1.50 KiB");
+        assert_eq!(format_size(5 * 1024 * 1024), "5.00 MiB");
+    }
+
+    #[test]
+    fn sizes_are_collected() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("snippets"))?;
+        std::fs::write(dir.path().join("app_bg.wasm"), [0u8; 4096])?;
+        std::fs::write(dir.path().join("snippets/app.js"), "console.log(1);")?;
+        std::fs::write(dir.path().join("index.html"), "<html></html>")?;
+
+        let sizes = output_sizes(dir.path())?;
+        let names = sizes.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["app_bg.wasm", "snippets/app.js"]);
+        assert_eq!(sizes[0].raw, 4096);
+        assert!(sizes[0].gzip < sizes[0].raw);
+
+        Ok(())
+    }
+
+    #[test]
+    fn outputs_are_precompressed() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("snippets"))?;
+        std::fs::write(dir.path().join("app_bg.wasm"), [0u8; 4096])?;
+        std::fs::write(dir.path().join("snippets/app.js"), "console.log(1);")?;
+        std::fs::write(dir.path().join("image.png"), [0u8; 4096])?;
+        std::fs::write(dir.path().join("index.html"), [b' '; 4096])?;
+
+        precompress(
+            dir.path(),
+            &[CompressionFormat::Brotli, CompressionFormat::Gzip],
+            Compression::best(),
+            1024,
+        )?;
+
+        assert!(dir.path().join("app_bg.wasm.gz").is_file());
+        let mut decompressed = vec![];
+        brotli::BrotliDecompress(
+            &mut std::fs::read(dir.path().join("app_bg.wasm.br"))?.as_slice(),
+            &mut decompressed,
+        )?;
+        assert_eq!(decompressed, [0u8; 4096]);
+        // too small
+        assert!(!dir.path().join("snippets/app.js.gz").exists());
+        assert!(!dir.path().join("snippets/app.js.br").exists());
+        // not compressible
+        assert!(!dir.path().join("image.png.gz").exists());
+        // rewritten when serving
+        assert!(!dir.path().join("index.html.gz").exists());
+        assert!(!dir.path().join("index.html.br").exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn timestamps_are_normalized() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("snippets"))?;
+        std::fs::write(dir.path().join("index.html"), "<html></html>")?;
+        std::fs::write(dir.path().join("snippets/app.js"), "console.log(1);")?;
+
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        set_timestamps(dir.path(), time)?;
+
+        let mut paths = vec!["index.html", "snippets/app.js"];
+        if cfg!(unix) {
+            paths.extend(["snippets", ""]);
+        }
+        for path in paths {
+            let modified = std::fs::metadata(dir.path().join(path))?.modified()?;
+            ensure!(modified == time, "timestamp of '{path}' wasn't set");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn dist_size_is_collected() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("snippets"))?;
+        std::fs::create_dir_all(dir.path().join(STAGE_DIR))?;
+        std::fs::write(dir.path().join("app_bg.wasm"), [0u8; 4096])?;
+        std::fs::write(dir.path().join("snippets/app.js"), "console.log(1);")?;
+        std::fs::write(
+            dir.path().join(STAGE_DIR).join("index.html"),
+            "<html></html>",
+        )?;
+
+        assert_eq!(
+            dist_size(dir.path())?,
+            DistSize {
+                files: 2,
+                bytes: 4096 + 15
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_describes_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("snippets"))?;
+        std::fs::write(dir.path().join("app_bg.wasm"), [0u8; 4096])?;
+        std::fs::write(dir.path().join("app_bg.wasm.gz"), [0u8; 16])?;
+        std::fs::write(dir.path().join("snippets/app.js"), "console.log(1);")?;
+        std::fs::write(dir.path().join("robots.txt"), "")?;
+        std::fs::write(dir.path().join(MANIFEST_FILE), "{}")?;
+
+        let records = [
+            OutputRecord {
+                file: "app_bg.wasm".into(),
+                kind: AssetKind::RustWasm,
+                source: "Cargo.toml".into(),
+                integrity: Some("sha384-recorded".into()),
+            },
+            OutputRecord {
+                file: "snippets/app.js".into(),
+                kind: AssetKind::Rust,
+                source: "Cargo.toml".into(),
+                integrity: None,
+            },
+        ];
+        let manifest = manifest(
+            dir.path(),
+            &records,
+            &[CompressionFormat::Gzip],
+            IntegrityType::Sha256,
+        )?;
+
+        let files = manifest
+            .files
+            .iter()
+            .map(|entry| (entry.file.as_str(), entry.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            [
+                ("app_bg.wasm", AssetKind::RustWasm),
+                ("app_bg.wasm.gz", AssetKind::Precompressed),
+                ("robots.txt", AssetKind::Other),
+                ("snippets/app.js", AssetKind::Rust),
+            ]
+        );
+        assert_eq!(manifest.wasm_size, Some(4096));
+
+        let wasm = &manifest.files[0];
+        assert_eq!(wasm.integrity.as_deref(), Some("sha384-recorded"));
+        assert_eq!(wasm.hash, format!("{:x}", Sha256::digest([0u8; 4096])));
+        assert_eq!(
+            manifest.files[1].source.as_deref(),
+            Some(Path::new("Cargo.toml"))
+        );
+        assert_eq!(manifest.files[2].source, None);
+        assert_eq!(
+            manifest.files[2].integrity.as_deref(),
+            Some("sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=")
+        );
+
+        Ok(())
+    }
+
+    /// Create a build system for a project with an empty `index.html`.
+    async fn build_system(dir: &Path) -> Result<BuildSystem> {
+        std::fs::write(dir.join("index.html"), "<html></html>")?;
+        let mut cfg = RtcBuild::new_test(dir).await?;
+        cfg.compression = vec![CompressionFormat::Gzip];
+        BuildSystem::new(Arc::new(cfg), None, None, None).await
+    }
+
+    /// The files of a dir, relative to it.
+    fn files(dir: &Path) -> Result<Vec<String>> {
+        fn collect(dir: &Path, base: &Path, result: &mut Vec<String>) -> Result<()> {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    collect(&path, base, result)?;
+                } else {
+                    result.push(path.strip_prefix(base)?.display().to_string());
+                }
+            }
+            Ok(())
+        }
+
+        let mut result = vec![];
+        collect(dir, dir, &mut result)?;
+        result.sort();
+        Ok(result)
+    }
+
+    #[tokio::test]
+    async fn dist_is_replaced() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let system = build_system(dir.path()).await?;
+        let (dist, stage) = (&system.cfg.final_dist, &system.cfg.staging_dist);
+        std::fs::create_dir_all(dist.join("assets"))?;
+        std::fs::write(dist.join("index.html"), "old")?;
+        std::fs::write(dist.join("assets/style.css"), "")?;
+        std::fs::write(stage.join("index.html"), "new")?;
+        std::fs::write(stage.join("app.js"), "")?;
+
+        system.finalize_dist(&HashSet::new()).await?;
+
+        assert_eq!(files(dist)?, ["app.js", "index.html"]);
+        assert_eq!(std::fs::read_to_string(dist.join("index.html"))?, "new");
+        assert!(!stage.exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dist_keeps_preserved_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let system = build_system(dir.path()).await?;
+        let (dist, stage) = (&system.cfg.final_dist, &system.cfg.staging_dist);
+        std::fs::create_dir_all(dist.join("assets"))?;
+        std::fs::create_dir_all(dist.join("old"))?;
+        std::fs::write(dist.join("index.html"), "old")?;
+        std::fs::write(dist.join("assets/style.css"), "")?;
+        std::fs::write(dist.join("assets/style.css.gz"), "")?;
+        std::fs::write(dist.join("assets/image.png"), "")?;
+        std::fs::write(dist.join("old/app.js"), "")?;
+        std::fs::create_dir_all(stage.join("assets"))?;
+        std::fs::write(stage.join("index.html"), "new")?;
+        std::fs::write(stage.join("assets/app.wasm"), "")?;
+
+        system
+            .finalize_dist(
+                &system
+                    .preserved_files(&[PathBuf::from("assets/style.css")])
+                    .await?,
+            )
+            .await?;
+
+        assert_eq!(
+            files(dist)?,
+            [
+                "assets/app.wasm",
+                "assets/style.css",
+                "assets/style.css.gz",
+                "index.html"
+            ]
+        );
+        assert_eq!(std::fs::read_to_string(dist.join("index.html"))?, "new");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dist_keeps_files_to_keep() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut system = build_system(dir.path()).await?;
+        Arc::make_mut(&mut system.cfg).keep = ["CNAME", ".nojekyll", "keep/*", "static"]
+            .into_iter()
+            .map(glob::Pattern::new)
+            .collect::<Result<_, _>>()?;
+        let (dist, stage) = (&system.cfg.final_dist, &system.cfg.staging_dist);
+        for file in [
+            "CNAME",
+            ".nojekyll",
+            "index.html",
+            "keep/a.txt",
+            "static/img/a.png",
+            "old/app.js",
+        ] {
+            let path = dist.join(file);
+            std::fs::create_dir_all(path.parent().context("no parent")?)?;
+            std::fs::write(path, "old")?;
+        }
+        std::fs::write(stage.join("index.html"), "new")?;
+
+        let preserved = system.preserved_files(&[]).await?;
+        system.finalize_dist(&preserved).await?;
+
+        assert_eq!(
+            files(dist)?,
+            [
+                ".nojekyll",
+                "CNAME",
+                "index.html",
+                "keep/a.txt",
+                "static/img/a.png"
+            ]
+        );
+        assert_eq!(std::fs::read_to_string(dist.join("index.html"))?, "new");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn dist_budget_is_checked_before_replacing_dist() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut system = build_system(dir.path()).await?;
+        let cfg = Arc::make_mut(&mut system.cfg);
+        cfg.compression = vec![];
+        let (dist, stage) = (cfg.final_dist.clone(), cfg.staging_dist.clone());
+        std::fs::write(dist.join("index.html"), "old")?;
+        std::fs::write(dist.join("style.css"), [0u8; 64])?;
+        std::fs::write(stage.join("index.html"), [0u8; 32])?;
+        let preserved = system
+            .preserved_files(&[PathBuf::from("style.css")])
+            .await?;
+
+        // the preserved files of the previous build are part of the new dist dir
+        Arc::make_mut(&mut system.cfg).dist_budget = Some(96);
+        system.check_dist_size(&preserved).await?;
+        Arc::make_mut(&mut system.cfg).dist_budget = Some(95);
+        let err = system
+            .check_dist_size(&preserved)
+            .await
+            .expect_err("must exceed the budget");
+        assert!(err.to_string().contains("exceeds the budget"), "{err}");
+
+        // a failing build keeps the previous files
+        std::fs::write(dir.path().join("index.html"), "<html>".repeat(32))?;
+        system.build().await.expect_err("must exceed the budget");
+        let previous = files(&dist)?
+            .into_iter()
+            .filter(|file| !file.starts_with(STAGE_DIR))
+            .collect::<Vec<_>>();
+        assert_eq!(previous, ["index.html", "style.css"]);
+        assert_eq!(std::fs::read_to_string(dist.join("index.html"))?, "old");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn inline_once_is_inlined_at_the_first_occurrence() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("index.html"),
+            concat!(
+                r#"<html><body>"#,
+                r#"<link data-trunk rel="inline" href="part.html" data-inline="once">"#,
+                r#"<link data-trunk rel="inline" href="part.html" data-inline="once">"#,
+                r#"<link data-trunk rel="inline" href="part.html">"#,
+                r#"</body></html>"#
+            ),
+        )?;
+        std::fs::write(dir.path().join("part.html"), "<p>part</p>")?;
+        let cfg = RtcBuild::new_test(dir.path()).await?;
+        let dist = cfg.final_dist.clone();
+        let mut system = BuildSystem::new(Arc::new(cfg), None, None, None).await?;
+
+        system.build().await?;
+        let html = std::fs::read_to_string(dist.join("index.html"))?;
+        assert!(
+            html.starts_with("<html><body><p>part</p><p>part</p><script>"),
+            "only the repeated inline-once link must be removed: {html}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn skip_assets_reuses_outputs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("index.html"),
+            r#"<html><head><link data-trunk rel="css" href="style.css"></head></html>"#,
+        )?;
+        std::fs::write(dir.path().join("style.css"), "body { color: red; }")?;
+        let mut cfg = RtcBuild::new_test(dir.path()).await?;
+        let dist = cfg.final_dist.clone();
+        let build = |cfg: RtcBuild| async {
+            BuildSystem::new(Arc::new(cfg), None, None, None)
+                .await?
+                .build()
+                .await?;
+            let files = files(&dist)?;
+            let css = files
+                .iter()
+                .find(|file| file.ends_with(".css"))
+                .context("missing CSS output")?
+                .clone();
+            Ok::<_, anyhow::Error>((css, std::fs::read_to_string(dist.join("index.html"))?))
+        };
+
+        let (css, html) = build(cfg.clone()).await?;
+        assert!(html.contains(&css));
+        // mark the output, to see that it isn't built again
+        std::fs::write(dist.join(&css), "/* previous */")?;
+
+        cfg.skip_assets = true;
+        let (skipped_css, skipped_html) = build(cfg.clone()).await?;
+        assert_eq!(skipped_css, css);
+        assert_eq!(skipped_html, html);
+        assert_eq!(std::fs::read_to_string(dist.join(&css))?, "/* previous */");
+
+        // a changed source requires building the assets again
+        std::fs::write(dir.path().join("style.css"), "body { color: blue; }")?;
+        let (changed_css, changed_html) = build(cfg).await?;
+        assert_ne!(changed_css, css);
+        assert!(changed_html.contains(&changed_css));
+        assert_eq!(
+            std::fs::read_to_string(dist.join(&changed_css))?,
+            "body { color: blue; }"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn manifest_must_not_overwrite_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let system = build_system(dir.path()).await?;
+        let dist = &system.cfg.final_dist;
+        std::fs::write(dist.join("manifest.json"), "{}")?;
+
+        system.write_manifest(vec![]).await?;
+        assert!(dist.join(MANIFEST_FILE).is_file());
+        assert_eq!(std::fs::read_to_string(dist.join("manifest.json"))?, "{}");
+
+        std::fs::write(dist.join(MANIFEST_FILE), "{}")?;
+        let err = system
+            .write_manifest(vec![])
+            .await
+            .expect_err("an existing file must not be overwritten");
+        assert!(
+            err.to_string().contains("collides"),
+            "unexpected error: {err}"
+        );
+
+        Ok(())
+    }
 }
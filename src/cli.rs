@@ -0,0 +1,122 @@
+//! The command line interface of Trunk.
+
+use crate::{cmd, version};
+use anyhow::Result;
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+/// Build, bundle & ship your Rust WASM application to the web.
+#[derive(Parser)]
+#[command(about, author, version)]
+pub struct Trunk {
+    #[command(subcommand)]
+    pub(crate) action: TrunkSubcommands,
+    /// Path to the Trunk config file
+    #[arg(long, env = "TRUNK_CONFIG", global(true))]
+    pub config: Option<PathBuf>,
+    /// Enable verbose logging.
+    #[arg(short, long, global(true), action=ArgAction::Count)]
+    pub verbose: u8,
+    /// Be more quiet, conflicts with --verbose
+    #[arg(short, long, global(true), conflicts_with("verbose"))]
+    pub quiet: bool,
+    /// Provide a RUST_LOG filter, conflicts with --verbose and --quiet
+    #[arg(long, global(true), conflicts_with_all(["verbose", "quiet"]), env("RUST_LOG"))]
+    pub log: Option<String>,
+
+    /// Skip the version check
+    #[arg(long, global(true), env = "TRUNK_SKIP_VERSION_CHECK")]
+    pub skip_version_check: bool,
+
+    /// Run without accessing the network
+    #[arg(long, global(true), env = "TRUNK_OFFLINE")]
+    #[arg(default_missing_value = "true", num_args=0..=1)]
+    pub offline: Option<bool>,
+
+    /// Color mode
+    #[arg(long, env = "TRUNK_COLOR", global(true), value_enum, conflicts_with = "no_color", default_value_t = ColorMode::Auto)]
+    pub color: ColorMode,
+
+    /// Support for `NO_COLOR` environment variable
+    #[arg(long, env = "NO_COLOR", global(true))]
+    pub no_color: bool,
+}
+
+impl Trunk {
+    pub fn prefer_silence(&self) -> bool {
+        #[allow(clippy::match_like_matches_macro)]
+        match self.action {
+            TrunkSubcommands::Config(_) => true,
+            TrunkSubcommands::Tools(_) => true,
+            TrunkSubcommands::Doctor(_) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum ColorMode {
+    /// Enable color when running on a TTY
+    #[default]
+    Auto,
+    /// Always enable color
+    Always,
+    /// Never enable color
+    Never,
+}
+
+impl Trunk {
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn run(self) -> Result<()> {
+        version::update_check(self.skip_version_check | self.offline.unwrap_or_default());
+
+        match self.action {
+            TrunkSubcommands::Build(inner) => inner.run(self.config).await,
+            TrunkSubcommands::Clean(inner) => inner.run(self.config).await,
+            TrunkSubcommands::Serve(inner) => inner.run(self.config).await,
+            TrunkSubcommands::Watch(inner) => inner.run(self.config).await,
+            TrunkSubcommands::Config(inner) => inner.run(self.config).await,
+            TrunkSubcommands::Tools(inner) => {
+                inner
+                    .run(self.config, self.offline.unwrap_or_default())
+                    .await
+            }
+            TrunkSubcommands::Doctor(inner) => {
+                inner
+                    .run(self.config, self.offline.unwrap_or_default())
+                    .await
+            }
+        }
+    }
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum TrunkSubcommands {
+    /// Build the Rust WASM app and all of its assets.
+    Build(cmd::build::Build),
+    /// Build & watch the Rust WASM app and all of its assets.
+    Watch(cmd::watch::Watch),
+    /// Build, watch & serve the Rust WASM app and all of its assets.
+    Serve(cmd::serve::Serve),
+    /// Clean output artifacts.
+    Clean(cmd::clean::Clean),
+    /// Trunk config controls.
+    Config(cmd::config::Config),
+    /// Working with tools
+    Tools(cmd::tools::Tools),
+    /// Diagnose common problems of the environment.
+    Doctor(cmd::doctor::Doctor),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Trunk;
+
+    #[test]
+    fn verify_cli() {
+        use clap::CommandFactory;
+        Trunk::command().debug_assert();
+    }
+}
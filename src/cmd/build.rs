@@ -1,13 +1,13 @@
 use crate::{
-    build::BuildSystem,
+    build,
     config::{
         self,
         rt::{self, RtcBuild, RtcBuilder},
-        types::{BaseUrl, Minify},
+        types::{BaseUrl, CompressionFormat, Minify},
         Configuration, Tools,
     },
 };
-use anyhow::Result;
+use anyhow::{ensure, Context, Result};
 use clap::Args;
 use std::{path::PathBuf, sync::Arc};
 
@@ -49,6 +49,10 @@ pub struct Build {
     #[arg(default_missing_value="true", num_args=0..=1)]
     pub locked: Option<bool>,
 
+    /// A wrapper for rustc, e.g. `sccache` for build caching
+    #[arg(long, env = "TRUNK_BUILD_RUSTC_WRAPPER")]
+    pub rustc_wrapper: Option<String>,
+
     /// The public URL from which assets are to be served
     #[arg(long, env = "TRUNK_BUILD_PUBLIC_URL")]
     pub public_url: Option<BaseUrl>,
@@ -104,6 +108,16 @@ pub struct Build {
     #[arg(default_missing_value="true", num_args=0..=1)]
     pub minify: Option<bool>,
 
+    /// Also minify the JS snippets of wasm-bindgen, when minifying
+    #[arg(long, env = "TRUNK_BUILD_MINIFY_SNIPPETS")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub minify_snippets: Option<bool>,
+
+    /// Write source maps of minified JS and CSS files next to them
+    #[arg(long, env = "TRUNK_BUILD_SOURCE_MAPS")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub source_maps: Option<bool>,
+
     /// Allows disabling sub-resource integrity (SRI)
     #[arg(long, env = "TRUNK_BUILD_NO_SRI")]
     #[arg(default_missing_value="true", num_args=0..=1)]
@@ -117,6 +131,91 @@ pub struct Build {
     #[arg(default_missing_value="true", num_args=0..=1)]
     pub allow_self_closing_script: Option<bool>,
 
+    /// Log the raw and gzipped sizes of the WASM, JS and CSS output after a build
+    #[arg(long, env = "TRUNK_BUILD_LOG_SIZES")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub log_sizes: Option<bool>,
+
+    /// Compile the WASM file using streaming instantiation, requires the server to send it as application/wasm
+    #[arg(long, env = "TRUNK_BUILD_STREAMING_INIT")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub streaming_init: Option<bool>,
+
+    /// Emit TypeScript bindings for all rust apps, unless disabled using data-typescript="false" [default: false]
+    #[arg(long, env = "TRUNK_BUILD_TYPESCRIPT")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub typescript: Option<bool>,
+
+    /// Origins to add preconnect and DNS prefetch hints for, like https://cdn.example.com
+    #[arg(long, env = "TRUNK_BUILD_PRECONNECT", value_delimiter = ',')]
+    pub preconnect: Option<Vec<String>>,
+
+    /// Directories sass/scss assets resolve `@use` and `@import` loads from
+    #[arg(long, env = "TRUNK_BUILD_SASS_LOAD_PATH", value_delimiter = ',')]
+    pub sass_load_path: Option<Vec<PathBuf>>,
+
+    /// Write a .trunk-manifest.json file, describing the files of the dist dir, after a build
+    #[arg(long = "manifest", env = "TRUNK_BUILD_MANIFEST")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub emit_manifest: Option<bool>,
+
+    /// Only build the Rust application, keeping the other asset outputs of the previous build
+    #[arg(long, env = "TRUNK_BUILD_SKIP_ASSETS")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub skip_assets: Option<bool>,
+
+    /// Abort the remaining asset pipelines on the first failing one
+    #[arg(long, env = "TRUNK_BUILD_FAIL_FAST")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub fail_fast: Option<bool>,
+
+    /// Fail the build if cargo emits any compiler warnings [default: false]
+    #[arg(long, env = "TRUNK_BUILD_DENY_WARNINGS")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub deny_warnings: Option<bool>,
+
+    /// Produce byte-identical dist output for the same sources [default: false]
+    #[arg(long, env = "TRUNK_BUILD_REPRODUCIBLE")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub reproducible: Option<bool>,
+
+    /// The base directory of the intermediate output of wasm-bindgen and wasm-opt [default: cargo's target dir]
+    #[arg(long, env = "TRUNK_BUILD_TARGET_DIR")]
+    pub target_dir: Option<PathBuf>,
+
+    /// The directory wasm-bindgen writes its intermediate output to [default: <target-dir>/wasm-bindgen]
+    #[arg(long, env = "TRUNK_BUILD_BINDGEN_OUT_DIR")]
+    pub bindgen_out_dir: Option<PathBuf>,
+
+    /// Fail the build if the total size of the dist dir exceeds this number of bytes
+    #[arg(long, env = "TRUNK_BUILD_DIST_BUDGET")]
+    pub dist_budget: Option<u64>,
+
+    /// Write precompressed variants of the output files [enum: brotli, gzip]
+    #[arg(long, env = "TRUNK_BUILD_COMPRESSION", value_delimiter = ',')]
+    pub compression: Option<Vec<CompressionFormat>>,
+
+    /// The level (0-9) used for precompressing output files [default: 9]
+    #[arg(long, env = "TRUNK_BUILD_COMPRESSION_LEVEL")]
+    pub compression_level: Option<u32>,
+
+    /// Don't precompress output files smaller than this number of bytes [default: 1024]
+    #[arg(long, env = "TRUNK_BUILD_COMPRESSION_MIN_SIZE")]
+    pub compression_min_size: Option<u64>,
+
+    /// The maximum number of images to optimize in parallel [default: number of CPUs]
+    #[arg(long, env = "TRUNK_BUILD_IMAGE_CONCURRENCY")]
+    pub image_concurrency: Option<usize>,
+
+    /// The name of the wasm-opt parameter set (from the configuration) to use
+    #[arg(long, env = "TRUNK_BUILD_WASM_OPT_PARAM_SET")]
+    pub wasm_opt_param_set: Option<String>,
+
+    /// Build all variants of the build matrix from the configuration, instead of a single build
+    #[arg(long, env = "TRUNK_BUILD_MATRIX")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub matrix: Option<bool>,
+
     // NOTE: flattened structures come last
     #[command(flatten)]
     pub core: super::core::Core,
@@ -138,6 +237,7 @@ impl Build {
             offline,
             frozen,
             locked,
+            rustc_wrapper,
             public_url,
             public_url_no_trailing_slash_fix,
             no_default_features,
@@ -148,8 +248,29 @@ impl Build {
             root_certificate,
             accept_invalid_certs,
             minify,
+            minify_snippets,
+            source_maps,
             no_sri,
             allow_self_closing_script,
+            log_sizes,
+            streaming_init,
+            typescript,
+            preconnect,
+            sass_load_path,
+            emit_manifest,
+            skip_assets,
+            fail_fast,
+            deny_warnings,
+            reproducible,
+            target_dir,
+            bindgen_out_dir,
+            dist_budget,
+            compression,
+            compression_level,
+            compression_min_size,
+            image_concurrency,
+            wasm_opt_param_set,
+            matrix: _,
             tools,
         } = self;
 
@@ -161,6 +282,7 @@ impl Build {
         config.build.offline = offline.unwrap_or(config.build.offline);
         config.build.frozen = frozen.unwrap_or(config.build.frozen);
         config.build.locked = locked.unwrap_or(config.build.locked);
+        config.build.rustc_wrapper = rustc_wrapper.or(config.build.rustc_wrapper);
         config.build.public_url = public_url.unwrap_or(config.build.public_url);
         config.build.public_url_no_trailing_slash_fix = public_url_no_trailing_slash_fix
             .unwrap_or(config.build.public_url_no_trailing_slash_fix);
@@ -185,6 +307,27 @@ impl Build {
         config.build.no_sri = no_sri.unwrap_or(config.build.no_sri);
         config.build.allow_self_closing_script =
             allow_self_closing_script.unwrap_or(config.build.allow_self_closing_script);
+        config.build.minify_snippets = minify_snippets.unwrap_or(config.build.minify_snippets);
+        config.build.source_maps = source_maps.unwrap_or(config.build.source_maps);
+        config.build.log_sizes = log_sizes.unwrap_or(config.build.log_sizes);
+        config.build.streaming_init = streaming_init.unwrap_or(config.build.streaming_init);
+        config.build.typescript = typescript.unwrap_or(config.build.typescript);
+        config.build.preconnect = preconnect.unwrap_or(config.build.preconnect);
+        config.build.sass_load_paths = sass_load_path.unwrap_or(config.build.sass_load_paths);
+        config.build.emit_manifest = emit_manifest.unwrap_or(config.build.emit_manifest);
+        config.build.skip_assets = skip_assets.unwrap_or(config.build.skip_assets);
+        config.build.fail_fast = fail_fast.unwrap_or(config.build.fail_fast);
+        config.build.deny_warnings = deny_warnings.unwrap_or(config.build.deny_warnings);
+        config.build.reproducible = reproducible.unwrap_or(config.build.reproducible);
+        config.build.target_dir = target_dir.or(config.build.target_dir);
+        config.build.bindgen_out_dir = bindgen_out_dir.or(config.build.bindgen_out_dir);
+        config.build.dist_budget = dist_budget.or(config.build.dist_budget);
+        config.build.compression = compression.unwrap_or(config.build.compression);
+        config.build.compression_level = compression_level.or(config.build.compression_level);
+        config.build.compression_min_size =
+            compression_min_size.unwrap_or(config.build.compression_min_size);
+        config.build.image_concurrency = image_concurrency.or(config.build.image_concurrency);
+        config.build.wasm_opt_param_set = wasm_opt_param_set.or(config.build.wasm_opt_param_set);
 
         let config = core.apply_to(config)?;
         let config = tools.apply_to(config)?;
@@ -196,24 +339,54 @@ impl Build {
     pub async fn run(self, config: Option<PathBuf>) -> Result<()> {
         let (cfg, working_directory) = config::load(config).await?;
 
+        let matrix = self.matrix.unwrap_or_default();
         let cfg = self.apply_to(cfg)?;
+
+        if !matrix {
+            return Self::build(cfg, working_directory).await;
+        }
+
+        ensure!(
+            !cfg.build.matrix.is_empty(),
+            "A matrix build was requested, but no variants are configured in 'build.matrix'"
+        );
+
+        for variant in &cfg.build.matrix {
+            tracing::info!(
+                "building variant for '{}' (features: {:?})",
+                variant.dist.display(),
+                variant.features
+            );
+            Self::build(variant.apply_to(cfg.clone()), working_directory.clone())
+                .await
+                .with_context(|| {
+                    format!("failed building variant for '{}'", variant.dist.display())
+                })?;
+        }
+
+        Ok(())
+    }
+
+    async fn build(cfg: Configuration, working_directory: PathBuf) -> Result<()> {
         let cfg = RtcBuild::from_config(cfg, working_directory, |_, core| rt::BuildOptions {
             core,
             inject_autoloader: false,
         })
         .await?;
 
-        cfg.core.enforce_version()?;
-
-        let mut system = BuildSystem::new(Arc::new(cfg), None, None).await?;
-        system.build().await?;
+        let output = build::build(Arc::new(cfg)).await?;
+        tracing::debug!(
+            "wrote {} files to {}",
+            output.artifacts.len(),
+            output.dist.display()
+        );
         Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{Trunk, TrunkSubcommands};
+    use crate::cli::{Trunk, TrunkSubcommands};
     use clap::Parser;
     use rstest::rstest;
 
@@ -1,5 +1,5 @@
 use crate::{
-    common::remove_dir_all,
+    common::{dist_relative, path_exists, remove_dir_all},
     config::{
         self,
         rt::{self, RtcBuilder, RtcClean},
@@ -9,7 +9,11 @@ use crate::{
 };
 use anyhow::{ensure, Context, Result};
 use clap::Args;
-use std::{path::PathBuf, process::Stdio};
+use glob::Pattern;
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+};
 use tokio::process::Command;
 
 /// Clean output artifacts.
@@ -29,6 +33,9 @@ pub struct Clean {
     /// them to be downloaded by Trunk next time they are needed.
     #[arg(short, long, env = "TRUNK_CLEAN_TOOLS")]
     pub tools: bool,
+    /// Glob patterns of files in the dist dir to keep, like CNAME
+    #[arg(long, env = "TRUNK_CLEAN_KEEP", value_delimiter = ',')]
+    pub keep: Option<Vec<String>>,
 }
 
 impl Clean {
@@ -38,11 +45,13 @@ impl Clean {
             dist,
             cargo,
             tools: _, // used by the CLI only
+            keep,
         } = self;
 
         if cargo {
             config.clean.cargo = true;
         }
+        config.clean.keep = keep.unwrap_or(config.clean.keep);
 
         // the config.clean.dist is handled by migrations
         config.core.dist = dist.or(config.core.dist);
@@ -64,7 +73,7 @@ impl Clean {
 
         cfg.enforce_version()?;
 
-        remove_dir_all(cfg.dist.clone())
+        clean_dist(cfg.dist.clone(), cfg.keep.clone())
             .await
             .context("failed to clean dist directory")?;
         if cfg.cargo {
@@ -80,6 +89,13 @@ impl Clean {
                 "{}",
                 String::from_utf8_lossy(&output.stderr)
             );
+
+            if let Some(target_dir) = &cfg.target_dir {
+                tracing::debug!("cleaning trunk target dir");
+                clean_target_dir(target_dir)
+                    .await
+                    .context("failed to clean target directory")?;
+            }
         }
         if cfg.tools {
             tracing::debug!("cleaning trunk tools cache dir");
@@ -92,6 +108,65 @@ impl Clean {
     }
 }
 
+/// The directories Trunk writes to inside of its target dir.
+const TARGET_DIRS: [&str; 3] = ["wasm-bindgen", "wasm-opt", "trunk"];
+
+/// Remove the directories owned by Trunk from its target dir, which may be shared with others.
+async fn clean_target_dir(target_dir: &Path) -> Result<()> {
+    for dir in TARGET_DIRS {
+        remove_dir_all(target_dir.join(dir)).await?;
+    }
+    Ok(())
+}
+
+/// Remove the dist dir, except for the files matching one of the patterns to keep.
+async fn clean_dist(dist: PathBuf, keep: Vec<Pattern>) -> Result<()> {
+    if keep.is_empty() {
+        return remove_dir_all(dist).await;
+    }
+    if !path_exists(&dist).await? {
+        return Ok(());
+    }
+
+    tokio::task::spawn_blocking(move || {
+        if clean_dir(&dist, &dist, &keep)? {
+            std::fs::remove_dir(&dist)
+                .with_context(|| format!("error removing {}", dist.display()))?;
+        }
+        Ok(())
+    })
+    .await
+    .context("error awaiting spawned clean call")?
+}
+
+/// Remove the entries of a directory which aren't kept, returning whether it is empty afterwards.
+fn clean_dir(dist: &Path, dir: &Path, keep: &[Pattern]) -> Result<bool> {
+    let mut empty = true;
+    for entry in std::fs::read_dir(dir).with_context(|| format!("error reading {dir:?}"))? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = dist_relative(dist, &path)?;
+        if keep.iter().any(|pattern| pattern.matches(&name)) {
+            tracing::debug!("keeping {name}");
+            empty = false;
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            if clean_dir(dist, &path, keep)? {
+                std::fs::remove_dir(&path)
+                    .with_context(|| format!("error removing {}", path.display()))?;
+            } else {
+                empty = false;
+            }
+        } else {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("error removing {}", path.display()))?;
+        }
+    }
+    Ok(empty)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -104,6 +179,7 @@ mod test {
             clean: config::Clean {
                 dist: Some("foo".into()),
                 cargo: true,
+                keep: vec![],
             },
             ..Default::default()
         };
@@ -113,6 +189,7 @@ mod test {
             dist: Some("bar".into()),
             cargo: false,
             tools: true,
+            keep: None,
         }
         .apply_to(config)
         .expect("must not fail");
@@ -130,10 +207,83 @@ mod test {
                         // the dist field in the clean section must be empty
                         dist: None,
                         cargo: true,
+                        keep: vec![],
                     }
                 },
                 ..Default::default()
             }
         );
     }
+
+    #[tokio::test]
+    async fn clean_keeps_matching_files() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dist = tmpdir.path().join("dist");
+        for file in [
+            "CNAME",
+            ".nojekyll",
+            "index.html",
+            "app.wasm",
+            "img/a.png",
+            "keep/a.txt",
+        ] {
+            let path = dist.join(file);
+            tokio::fs::create_dir_all(path.parent().context("no parent")?).await?;
+            tokio::fs::write(path, b"").await?;
+        }
+
+        let keep = ["CNAME", ".nojekyll", "keep/*"]
+            .into_iter()
+            .map(Pattern::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        clean_dist(dist.clone(), keep.clone()).await?;
+
+        let mut remaining = vec![];
+        for file in [
+            "CNAME",
+            ".nojekyll",
+            "index.html",
+            "app.wasm",
+            "img",
+            "keep/a.txt",
+        ] {
+            if dist.join(file).exists() {
+                remaining.push(file);
+            }
+        }
+        assert_eq!(remaining, ["CNAME", ".nojekyll", "keep/a.txt"]);
+
+        // a missing dist dir is fine
+        clean_dist(tmpdir.path().join("missing"), keep).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn clean_only_trunk_target_dirs() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let target_dir = tmpdir.path();
+        for dir in [
+            "wasm-bindgen/debug",
+            "wasm-opt/release",
+            "trunk/assets",
+            "other",
+        ] {
+            tokio::fs::create_dir_all(target_dir.join(dir)).await?;
+        }
+        tokio::fs::write(target_dir.join("README"), b"").await?;
+
+        clean_target_dir(target_dir).await?;
+
+        let mut remaining = vec![];
+        let mut entries = tokio::fs::read_dir(target_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            remaining.push(entry.file_name());
+        }
+        remaining.sort();
+        assert_eq!(remaining, ["README", "other"]);
+
+        // missing directories are fine
+        clean_target_dir(target_dir).await?;
+        Ok(())
+    }
 }
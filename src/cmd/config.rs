@@ -15,6 +15,8 @@ pub struct Config {
 enum Command {
     /// Show Trunk's current config pre-CLI.
     Show,
+    /// Show which file provides Trunk's config.
+    Path,
     /// Generate the trunk configuration schema.
     GenerateSchema {
         /// Filename to write the schema to, defaults to `<stdout>`.
@@ -30,6 +32,10 @@ impl Config {
                 let (cfg, _working_directory) = config::load(config).await?;
                 println!("{:#?}", cfg);
             }
+            Command::Path => {
+                let (source, _working_directory) = config::find(config)?;
+                println!("{}", source.describe().await?);
+            }
             Command::GenerateSchema { output } => {
                 let schema = schemars::schema_for!(Configuration);
 
@@ -0,0 +1,304 @@
+use crate::{
+    common::{ERROR, SUCCESS},
+    config::{self, rt, Configuration},
+    tools::{self, cache_dir, find_system, Application, HttpClientOptions},
+};
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use clap::Args;
+use console::{style, Emoji};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use strum::IntoEnumIterator;
+use tokio::process::Command;
+
+static WARNING: Emoji = Emoji("⚠️  ", "");
+
+/// The target Rust applications are built for.
+const WASM_TARGET: &str = "wasm32-unknown-unknown";
+
+/// Diagnose common problems of the environment.
+#[derive(Clone, Debug, Args)]
+#[command(name = "doctor")]
+pub struct Doctor {}
+
+impl Doctor {
+    #[tracing::instrument(level = "trace", skip_all)]
+    pub async fn run(self, config: Option<PathBuf>, offline: bool) -> Result<()> {
+        let mut report = Report::default();
+
+        let (config, working_directory) = match config::load(config).await {
+            Ok((config, working_directory)) => {
+                report.pass("configuration", "loaded");
+                (config, working_directory)
+            }
+            Err(err) => {
+                report.fail("configuration", format!("{err:#}"));
+                let cwd = std::env::current_dir().context("unable to get current directory")?;
+                (Configuration::default(), cwd)
+            }
+        };
+        let offline = offline || config.build.offline;
+
+        report.check("cargo", version_of("cargo", &working_directory).await);
+        report.check("wasm32 target", wasm_target(&working_directory).await);
+        report.check("cache directory", writable_cache_dir().await);
+
+        for app in Application::iter() {
+            let version = configured_version(&config, app);
+            let path = config
+                .tools
+                .path(app)
+                .map(|path| working_directory.join(path));
+            let name = app.name().to_string();
+            match tool(app, version, path.as_deref()).await {
+                Ok(ToolState::Available(detail)) => report.pass(name, detail),
+                Ok(ToolState::Missing) if offline => report.fail(
+                    name,
+                    "not installed, and can't be downloaded in offline mode",
+                ),
+                Ok(ToolState::Missing) => {
+                    report.warn(name, "not installed, will be downloaded when needed")
+                }
+                Err(err) => report.fail(name, format!("{err:#}")),
+            }
+        }
+
+        if offline {
+            report.warn(
+                "downloads",
+                "skipped checking the download URLs in offline mode",
+            );
+        } else {
+            let client_options = rt::client_options(
+                &config.tools,
+                config.build.root_certificate.as_deref().map(Path::new),
+                config.build.accept_invalid_certs,
+                &working_directory,
+            );
+            match tools::get_http_client(&client_options).await {
+                Ok(client) => {
+                    for app in Application::iter() {
+                        let version = configured_version(&config, app)
+                            .unwrap_or_else(|| app.default_version());
+                        report.check(
+                            format!("download of {}", app.name()),
+                            reachable(&client, app, version, &client_options).await,
+                        );
+                    }
+                }
+                Err(err) => report.fail("downloads", format!("{err:#}")),
+            }
+        }
+
+        report.finish()
+    }
+}
+
+/// The results of all checks.
+#[derive(Default)]
+struct Report {
+    failed: usize,
+}
+
+impl Report {
+    fn check(&mut self, name: impl AsRef<str>, result: Result<String>) {
+        match result {
+            Ok(detail) => self.pass(name, detail),
+            Err(err) => self.fail(name, format!("{err:#}")),
+        }
+    }
+
+    fn pass(&mut self, name: impl AsRef<str>, detail: impl AsRef<str>) {
+        println!(
+            "{SUCCESS}{}: {}",
+            style(name.as_ref()).bold(),
+            detail.as_ref()
+        );
+    }
+
+    fn warn(&mut self, name: impl AsRef<str>, detail: impl AsRef<str>) {
+        println!(
+            "{WARNING}{}: {}",
+            style(name.as_ref()).bold(),
+            style(detail.as_ref()).yellow()
+        );
+    }
+
+    fn fail(&mut self, name: impl AsRef<str>, detail: impl AsRef<str>) {
+        self.failed += 1;
+        println!(
+            "{ERROR}{}: {}",
+            style(name.as_ref()).bold(),
+            style(detail.as_ref()).red()
+        );
+    }
+
+    fn finish(self) -> Result<()> {
+        println!();
+        ensure!(self.failed == 0, "{} checks failed", self.failed);
+        println!("all checks passed");
+        Ok(())
+    }
+}
+
+/// The version of a command, as reported by `--version`.
+async fn version_of(command: &str, working_directory: &Path) -> Result<String> {
+    let path = which::which(command).with_context(|| format!("{command} not found"))?;
+    let output = Command::new(&path)
+        .arg("--version")
+        .current_dir(working_directory)
+        .output()
+        .await
+        .with_context(|| format!("error running {}", path.display()))?;
+    ensure!(
+        output.status.success(),
+        "running `{} --version` failed",
+        path.display()
+    );
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Check that the standard library of the WASM target is installed for the active toolchain.
+async fn wasm_target(working_directory: &Path) -> Result<String> {
+    let output = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .current_dir(working_directory)
+        .output()
+        .await
+        .context("error running rustc")?;
+    ensure!(
+        output.status.success(),
+        "running `rustc --print sysroot` failed"
+    );
+
+    let sysroot = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    let target = sysroot.join("lib").join("rustlib").join(WASM_TARGET);
+    if !target.is_dir() {
+        bail!("{WASM_TARGET} is not installed, install it using `rustup target add {WASM_TARGET}`");
+    }
+
+    Ok(format!("{WASM_TARGET} installed"))
+}
+
+/// Check that tools can be stored in the cache directory.
+async fn writable_cache_dir() -> Result<String> {
+    let dir = cache_dir().await?;
+    let probe = dir.join(format!(".doctor-{}", std::process::id()));
+    tokio::fs::write(&probe, b"")
+        .await
+        .with_context(|| format!("{} is not writable", dir.display()))?;
+    tokio::fs::remove_file(&probe)
+        .await
+        .with_context(|| format!("error removing {}", probe.display()))?;
+    Ok(dir.display().to_string())
+}
+
+/// The availability of a tool.
+pub(super) enum ToolState {
+    Available(String),
+    Missing,
+}
+
+/// Check if a tool (of the required version) is installed on the system, or already downloaded.
+///
+/// A tool with a configured path must be available at that path.
+pub(super) async fn tool(
+    app: Application,
+    version: Option<&str>,
+    path: Option<&Path>,
+) -> Result<ToolState> {
+    if let Some(path) = path {
+        let info = tools::get_configured(app, version, path).await?;
+        return Ok(ToolState::Available(format!(
+            "{} at {} (configured)",
+            info.version,
+            info.path.display()
+        )));
+    }
+
+    if let Some((path, system_version)) = find_system(app).await {
+        if version.map_or(true, |version| version == system_version) {
+            return Ok(ToolState::Available(format!(
+                "{system_version} at {}",
+                path.display()
+            )));
+        }
+    }
+
+    let version = version.unwrap_or_else(|| app.default_version());
+    let path = cache_dir()
+        .await?
+        .join(format!("{}-{version}", app.name()))
+        .join(app.path());
+    Ok(match path.is_file() {
+        true => ToolState::Available(format!("{version} at {}", path.display())),
+        false => ToolState::Missing,
+    })
+}
+
+/// Check that the release of a tool can be downloaded.
+async fn reachable(
+    client: &reqwest::Client,
+    app: Application,
+    version: &str,
+    client_options: &HttpClientOptions,
+) -> Result<String> {
+    let url = tools::download_url(app, version, client_options)?;
+    let response = tokio::time::timeout(
+        Duration::from_secs(10),
+        tools::send_download_request(client, reqwest::Method::HEAD, url.clone(), client_options),
+    )
+    .await
+    .map_err(|_| anyhow!("unable to reach {url}: timed out"))?
+    .map_err(|err| anyhow!("unable to reach {url}: {}", err.root_cause()))?;
+    ensure!(
+        response.status().is_success(),
+        "{url} responded with {}",
+        response.status()
+    );
+    Ok(url.to_string())
+}
+
+/// The version of a tool set in the configuration.
+pub(super) fn configured_version(config: &Configuration, app: Application) -> Option<&str> {
+    match app {
+        Application::Sass => config.tools.sass.as_deref(),
+        Application::TailwindCss | Application::TailwindCssExtra => {
+            config.tools.tailwindcss.as_deref()
+        }
+        Application::WasmBindgen => config.tools.wasm_bindgen.as_deref(),
+        Application::WasmOpt => config.tools.wasm_opt.as_deref(),
+        Application::WasmTools => config.tools.wasm_tools.as_deref(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tailwind_extra_uses_tailwind_version() {
+        let mut config = Configuration::default();
+        config.tools.tailwindcss = Some("3.4.0".into());
+
+        assert_eq!(
+            configured_version(&config, Application::TailwindCssExtra),
+            Some("3.4.0")
+        );
+        assert_eq!(configured_version(&config, Application::Sass), None);
+    }
+
+    #[test]
+    fn failed_checks_fail_the_report() {
+        let mut report = Report::default();
+        report.pass("pass", "ok");
+        report.warn("warn", "not installed");
+        assert!(Report { failed: 0 }.finish().is_ok());
+
+        report.check("fail", Err(anyhow!("missing")));
+        assert_eq!(report.failed, 1);
+        assert!(report.finish().is_err());
+    }
+}
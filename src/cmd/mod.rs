@@ -4,6 +4,7 @@ pub mod build;
 pub mod clean;
 pub mod config;
 pub mod core;
+pub mod doctor;
 pub mod serve;
 pub mod tools;
 pub mod watch;
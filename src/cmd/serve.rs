@@ -3,12 +3,12 @@ use crate::{
         self,
         models::Proxy,
         rt::{self, RtcBuilder, RtcServe},
-        types::{AddressFamily, WsProtocol},
+        types::{AddressFamily, ConfigDuration, PreloadLocation, TlsVersion, WsProtocol},
         Configuration,
     },
     serve::ServeSystem,
 };
-use anyhow::{Context, Result};
+use anyhow::{ensure, Context, Result};
 use axum::http::Uri;
 use clap::Args;
 use std::{net::IpAddr, path::PathBuf, sync::Arc};
@@ -30,11 +30,14 @@ pub struct Serve {
     /// The aliases to serve on
     #[arg(long, env = "TRUNK_SERVE_ALIAS")]
     pub alias: Option<Vec<String>>,
+    /// The public host (and optional port) the server is reachable at, e.g. when using a tunnel
+    #[arg(long, env = "TRUNK_SERVE_PUBLIC_HOST")]
+    pub public_host: Option<String>,
     /// Disable the lookup of addresses serving on during startup
     #[arg(long, env = "TRUNK_SERVE_DISABLE_ADDRESS_LOOKUP")]
     #[arg(default_missing_value="true", num_args=0..=1)]
     pub disable_address_lookup: Option<bool>,
-    /// Open a browser tab once the initial build is complete [default: false]
+    /// Open a browser tab once the first successful build is complete [default: false]
     #[arg(long, env = "TRUNK_SERVE_OPEN")]
     #[arg(default_missing_value="true", num_args=0..=1)]
     pub open: Option<bool>,
@@ -50,18 +53,40 @@ pub struct Serve {
     #[arg(long, env = "TRUNK_SERVE_NO_SPA")]
     #[arg(default_missing_value="true", num_args=0..=1)]
     pub no_spa: Option<bool>,
+    /// Fall back to index.html for missing files with a file extension too, instead of only for
+    /// paths without one [default: false]
+    #[arg(long, env = "TRUNK_SERVE_SPA_FALLBACK_EXTENSIONS")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub spa_fallback_extensions: Option<bool>,
+    /// A backend to proxy all requests without a file in the dist dir to, instead of falling back
+    /// to the index.html
+    #[arg(long, env = "TRUNK_SERVE_DEFAULT_BACKEND")]
+    pub default_backend: Option<Uri>,
+    /// Follow symlinks in the dist directory, even when they point outside of it [default: false]
+    #[arg(long, env = "TRUNK_SERVE_FOLLOW_SYMLINKS")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub follow_symlinks: Option<bool>,
     /// Protocol used for the auto-reload WebSockets connection [enum: ws, wss]
     #[arg(long, env = "TRUNK_SERVE_WS_PROTOCOL")]
     pub ws_protocol: Option<WsProtocol>,
     /// The path to the trunk web-socket [default: <serve-base>]
     #[arg(long, env = "TRUNK_SERVE_WS_BASE")]
     pub ws_base: Option<String>,
+    /// The delay before the auto-reload client reconnects to Trunk, e.g. 500ms [default: 5s]
+    #[arg(long, env = "TRUNK_SERVE_WS_RECONNECT_DELAY")]
+    pub ws_reconnect_delay: Option<ConfigDuration>,
+    /// The maximum delay between reconnects of the auto-reload client, doubling the delay on each attempt [default: the reconnect delay]
+    #[arg(long, env = "TRUNK_SERVE_WS_RECONNECT_MAX_DELAY")]
+    pub ws_reconnect_max_delay: Option<ConfigDuration>,
     /// The TLS key file to enable TLS encryption [default: None]
     #[arg(long, env = "TRUNK_SERVE_TLS_KEY_PATH")]
     pub tls_key_path: Option<PathBuf>,
     /// The TLS cert file to enable TLS encryption [default: None]
     #[arg(long, env = "TRUNK_SERVE_TLS_CERT_PATH")]
     pub tls_cert_path: Option<PathBuf>,
+    /// The minimum TLS version to accept [default: TLS stack's default]
+    #[arg(long, env = "TRUNK_SERVE_TLS_MIN_VERSION")]
+    pub tls_min_version: Option<TlsVersion>,
     /// A base path to serve the application from [default: <public-url>]
     #[arg(long, env = "TRUNK_SERVE_SERVE_BASE")]
     pub serve_base: Option<String>,
@@ -69,6 +94,53 @@ pub struct Serve {
     #[arg(long)]
     #[arg(default_missing_value="false", num_args=0..=1)]
     pub disable_csp: Option<bool>,
+    /// Add a `Repr-Digest` header to responses of static assets [default: false]
+    #[arg(long, env = "TRUNK_SERVE_DIGEST_HEADER")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub digest_header: Option<bool>,
+    /// Where to announce the preloads of the served index.html [default: head]
+    #[arg(long, env = "TRUNK_SERVE_PRELOAD_LOCATION")]
+    pub preload_location: Option<PreloadLocation>,
+    /// Send a `103 Early Hints` response with the preloads of the served index.html [default: false]
+    #[arg(long, env = "TRUNK_SERVE_EARLY_HINTS")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub early_hints: Option<bool>,
+    /// Send the headers required for cross-origin isolation (COOP/COEP) with all responses [default: false]
+    #[arg(long, env = "TRUNK_SERVE_CROSS_ORIGIN_ISOLATION")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub cross_origin_isolation: Option<bool>,
+    /// Add integrity attributes when serving [default: only for release builds]
+    #[arg(long, env = "TRUNK_SERVE_SRI")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub sri: Option<bool>,
+    /// Keep connections open for further requests [default: true]
+    #[arg(long, env = "TRUNK_SERVE_KEEPALIVE")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub keepalive: Option<bool>,
+    /// Close connections which didn't send a complete request head within this time [default: none]
+    #[arg(long, env = "TRUNK_SERVE_HTTP_TIMEOUT")]
+    pub http_timeout: Option<ConfigDuration>,
+    /// Accept HTTP/2 without TLS (h2c) [default: false]
+    #[arg(long, env = "TRUNK_SERVE_HTTP2")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub http2: Option<bool>,
+    /// Run a command once the first build succeeded and the server is listening, and stop the
+    /// server with its exit code when it exits, e.g. for running integration tests
+    #[arg(long, env = "TRUNK_SERVE_ON_READY")]
+    pub on_ready: Option<String>,
+    /// Build only once, and serve the result without watching for changes [default: false]
+    #[arg(long, env = "TRUNK_SERVE_BUILD_ONLY_ONCE")]
+    pub build_only_once: bool,
+    /// Periodically send a reload to auto-reload clients and echo back their messages, for
+    /// testing clients [default: 5s]
+    #[arg(
+        long,
+        hide = true,
+        env = "TRUNK_SERVE_WS_ECHO",
+        value_name = "INTERVAL"
+    )]
+    #[arg(default_missing_value="5s", num_args=0..=1)]
+    pub ws_echo: Option<ConfigDuration>,
 
     // NOTE: flattened structures come last
     #[command(flatten)]
@@ -108,6 +180,10 @@ pub struct ProxyArgs {
         requires = "proxy_backend"
     )]
     pub proxy_no_redirect: bool,
+    /// Accept invalid TLS certificates of the backends of all proxies, overriding their
+    /// configuration
+    #[arg(long, env = "TRUNK_SERVE_PROXY_INSECURE_ALL")]
+    pub proxy_insecure_all: bool,
 }
 
 impl Serve {
@@ -118,6 +194,7 @@ impl Serve {
             prefer_address_family,
             port,
             alias,
+            public_host,
             disable_address_lookup,
             open,
             proxy:
@@ -128,17 +205,37 @@ impl Serve {
                     proxy_insecure,
                     proxy_no_system_proxy,
                     proxy_no_redirect,
+                    // only a runtime option, handled in the serve options
+                    proxy_insecure_all: _,
                 },
             no_autoreload,
             no_error_reporting,
             no_spa,
+            spa_fallback_extensions,
+            default_backend,
+            follow_symlinks,
             ws_protocol,
             ws_base,
+            ws_reconnect_delay,
+            ws_reconnect_max_delay,
             tls_key_path,
             tls_cert_path,
+            tls_min_version,
             serve_base,
             watch,
             disable_csp,
+            digest_header,
+            preload_location,
+            early_hints,
+            cross_origin_isolation,
+            sri,
+            keepalive,
+            http_timeout,
+            http2,
+            on_ready,
+            // only a runtime option, handled in the serve options
+            ws_echo: _,
+            build_only_once: _,
         } = self;
 
         // apply overrides
@@ -146,6 +243,7 @@ impl Serve {
         config.serve.addresses = address.unwrap_or(config.serve.addresses);
         config.serve.port = port.unwrap_or(config.serve.port);
         config.serve.aliases = alias.unwrap_or(config.serve.aliases);
+        config.serve.public_host = public_host.or(config.serve.public_host);
         config.serve.disable_address_lookup =
             disable_address_lookup.unwrap_or(config.serve.disable_address_lookup);
         config.serve.open = open.unwrap_or(config.serve.open);
@@ -155,15 +253,35 @@ impl Serve {
 
         config.serve.tls_key_path = tls_key_path.or(config.serve.tls_key_path);
         config.serve.tls_cert_path = tls_cert_path.or(config.serve.tls_cert_path);
+        config.serve.tls_min_version = tls_min_version.or(config.serve.tls_min_version);
 
         config.serve.no_autoreload = no_autoreload.unwrap_or(config.serve.no_autoreload);
         config.serve.no_error_reporting =
             no_error_reporting.unwrap_or(config.serve.no_error_reporting);
         config.serve.no_spa = no_spa.unwrap_or(config.serve.no_spa);
+        config.serve.spa_fallback_extensions =
+            spa_fallback_extensions.unwrap_or(config.serve.spa_fallback_extensions);
+        config.serve.default_backend = default_backend
+            .map(Into::into)
+            .or(config.serve.default_backend);
+        config.serve.follow_symlinks = follow_symlinks.unwrap_or(config.serve.follow_symlinks);
 
         config.serve.ws_protocol = ws_protocol.or(config.serve.ws_protocol);
         config.serve.ws_base = ws_base.or(config.serve.ws_base);
+        config.serve.ws_reconnect_delay = ws_reconnect_delay.or(config.serve.ws_reconnect_delay);
+        config.serve.ws_reconnect_max_delay =
+            ws_reconnect_max_delay.or(config.serve.ws_reconnect_max_delay);
         config.serve.disable_csp = disable_csp.unwrap_or(config.serve.disable_csp);
+        config.serve.digest_header = digest_header.unwrap_or(config.serve.digest_header);
+        config.serve.preload_location = preload_location.unwrap_or(config.serve.preload_location);
+        config.serve.early_hints = early_hints.unwrap_or(config.serve.early_hints);
+        config.serve.cross_origin_isolation =
+            cross_origin_isolation.unwrap_or(config.serve.cross_origin_isolation);
+        config.serve.sri = sri.or(config.serve.sri);
+        config.serve.keepalive = keepalive.unwrap_or(config.serve.keepalive);
+        config.serve.http_timeout = http_timeout.or(config.serve.http_timeout);
+        config.serve.http2 = http2.unwrap_or(config.serve.http2);
+        config.serve.on_ready = on_ready.or(config.serve.on_ready);
 
         if let Some(backend) = proxy_backend {
             // we have a single proxy from the command line
@@ -175,12 +293,24 @@ impl Serve {
                 insecure: proxy_insecure,
                 no_system_proxy: proxy_no_system_proxy,
                 no_redirect: proxy_no_redirect,
+                enabled: true,
+                response_rewrite: vec![],
+                response_rewrite_types: Proxy::default_response_rewrite_types(),
             });
         }
 
         // apply base layer
 
-        let config = watch.apply_to(config)?;
+        ensure!(
+            !watch.watch_once,
+            "Rebuilding once is only supported by 'trunk watch'"
+        );
+        let mut config = watch.apply_to(config)?;
+
+        // integrity attributes get in the way during development, so only release builds get them
+        if !config.serve.sri.unwrap_or(config.build.release) {
+            config.build.no_sri = true;
+        }
 
         // done
 
@@ -201,10 +331,14 @@ impl Serve {
                 poll: self.watch.poll.then_some(self.watch.poll_interval.0),
                 enable_cooldown: self.watch.enable_cooldown,
                 clear_screen: self.watch.clear_screen,
+                print_watches: self.watch.print_watches,
                 no_error_reporting: cfg.serve.no_error_reporting,
             },
             // This will be the effective value for `serve.open` during runtime.
             open: self.open.unwrap_or(cfg.serve.open),
+            ws_echo: self.ws_echo.map(|interval| interval.0),
+            build_only_once: self.build_only_once,
+            proxy_insecure_all: self.proxy.proxy_insecure_all,
         })
         .await?;
 
@@ -232,3 +366,29 @@ impl Serve {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        cli::{Trunk, TrunkSubcommands},
+        config::Configuration,
+    };
+    use clap::Parser;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(&["trunk", "serve"], true)]
+    #[case(&["trunk", "serve", "--release"], false)]
+    #[case(&["trunk", "serve", "--sri"], false)]
+    #[case(&["trunk", "serve", "--release", "--sri", "false"], true)]
+    fn sri_only_for_release(#[case] input: &[&str], #[case] no_sri: bool) {
+        let cli = Trunk::parse_from(input);
+        let TrunkSubcommands::Serve(serve) = cli.action else {
+            panic!("must be a serve command");
+        };
+        let config = serve
+            .apply_to(Configuration::default())
+            .expect("must apply");
+        assert_eq!(config.build.no_sri, no_sri);
+    }
+}
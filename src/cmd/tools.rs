@@ -1,9 +1,13 @@
-use crate::tools::{self, find_system};
-use anyhow::Result;
+use super::doctor::{configured_version, tool, ToolState};
+use crate::common::{ERROR, SUCCESS};
+use crate::config::{self, rt, CargoMetadata};
+use crate::pipelines::find_wasm_bindgen_version;
+use crate::tools::{self, find_system, Application};
+use anyhow::{ensure, Result};
 use clap::{Args, Subcommand};
 use console::style;
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use strum::IntoEnumIterator;
 
 #[derive(Clone, Debug, Args)]
@@ -15,11 +19,14 @@ pub struct Tools {
 
 impl Tools {
     #[tracing::instrument(level = "trace", skip_all)]
-    pub async fn run(self, _config: Option<PathBuf>) -> Result<()> {
+    pub async fn run(self, config: Option<PathBuf>, offline: bool) -> Result<()> {
         match self.action {
             None | Some(ToolsSubcommands::Show) => {
                 show_tools().await;
             }
+            Some(ToolsSubcommands::Install) => {
+                install_tools(config, offline).await?;
+            }
         }
         Ok(())
     }
@@ -29,6 +36,80 @@ impl Tools {
 pub enum ToolsSubcommands {
     /// Show Trunk's tool versions
     Show,
+    /// Download the configured versions of the tools into the cache, unless already available
+    Install,
+}
+
+/// Make the tools used by a build available, downloading the missing ones.
+///
+/// Besides wasm-bindgen (in the version of the project), sass, tailwindcss and wasm-opt, this
+/// includes the other tools when a version of them is configured.
+async fn install_tools(config: Option<PathBuf>, offline: bool) -> Result<()> {
+    let (config, working_directory) = config::load(config).await?;
+    let offline = offline || config.build.offline;
+    let client_options = rt::client_options(
+        &config.tools,
+        config.build.root_certificate.as_deref().map(Path::new),
+        config.build.accept_invalid_certs,
+        &working_directory,
+    );
+
+    let manifest = working_directory.join("Cargo.toml");
+    let manifest = match manifest.is_file() {
+        true => Some(CargoMetadata::new(&manifest).await?),
+        false => None,
+    };
+
+    let mut failed = vec![];
+    for app in Application::iter() {
+        let version = match app {
+            Application::WasmBindgen => manifest
+                .as_ref()
+                .and_then(|manifest| find_wasm_bindgen_version(&config.tools, manifest))
+                .or_else(|| config.tools.wasm_bindgen.as_deref().map(Into::into)),
+            _ => configured_version(&config, app).map(Into::into),
+        };
+        let default = matches!(
+            app,
+            Application::WasmBindgen
+                | Application::Sass
+                | Application::TailwindCss
+                | Application::WasmOpt
+        );
+        if !default && version.is_none() {
+            continue;
+        }
+
+        let path = config
+            .tools
+            .path(app)
+            .map(|path| working_directory.join(path));
+        let name = style(app.name()).bold();
+        let result = match tool(app, version.as_deref(), path.as_deref()).await {
+            Ok(ToolState::Available(detail)) => Ok(format!("already available, {detail}")),
+            Ok(ToolState::Missing) => {
+                tools::get_info(app, version.as_deref(), None, offline, &client_options)
+                    .await
+                    .map(|info| format!("downloaded {} to {}", info.version, info.path.display()))
+            }
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(detail) => println!("{SUCCESS}{name}: {detail}"),
+            Err(err) => {
+                failed.push(app.name().to_string());
+                println!("{ERROR}{name}: {}", style(format!("{err:#}")).red());
+            }
+        }
+    }
+
+    ensure!(
+        failed.is_empty(),
+        "{} tools couldn't be installed: {}",
+        failed.len(),
+        failed.join(", ")
+    );
+    Ok(())
 }
 
 async fn show_tools() {
@@ -79,3 +160,35 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn install_fails_offline_for_missing_tools() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let config = tmpdir.path().join("Trunk.toml");
+        // versions which are neither installed nor cached
+        tokio::fs::write(
+            &config,
+            r#"[tools]
+wasm_bindgen = "0.0.0-missing"
+sass = "0.0.0-missing"
+tailwindcss = "0.0.0-missing"
+wasm_opt = "version_0"
+"#,
+        )
+        .await?;
+
+        let err = install_tools(Some(config), true)
+            .await
+            .expect_err("must fail in offline mode");
+        assert_eq!(
+            err.to_string(),
+            "5 tools couldn't be installed: sass, tailwindcss, tailwindcss-extra, wasm-bindgen, wasm-opt"
+        );
+
+        Ok(())
+    }
+}
@@ -7,7 +7,7 @@ use crate::{
     },
     watch::WatchSystem,
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use clap::Args;
 use std::{path::PathBuf, sync::Arc};
 use tokio::sync::broadcast;
@@ -23,6 +23,17 @@ pub struct Watch {
     /// Paths to ignore [default: []]
     #[arg(short, long, value_name = "path", env = "TRUNK_WATCH_IGNORE")]
     pub ignore: Option<Vec<PathBuf>>,
+    /// Additional glob patterns of paths to ignore, relative to the project root
+    #[arg(
+        long,
+        visible_alias = "watch.ignore",
+        value_name = "glob",
+        env = "TRUNK_WATCH_WATCH_IGNORE"
+    )]
+    pub watch_ignore: Option<Vec<String>>,
+    /// Additional file(s) or folder(s) to watch, next to the watched paths
+    #[arg(long, value_name = "path", env = "TRUNK_WATCH_EXTRA_PATHS")]
+    pub watch_extra: Option<Vec<PathBuf>>,
     /// Using polling mode for detecting changes
     #[arg(long, env = "TRUNK_WATCH_POLL")]
     pub poll: bool,
@@ -35,6 +46,12 @@ pub struct Watch {
     /// Clear the screen before each run
     #[arg(short, long = "clear", env = "TRUNK_WATCH_CLEAR")]
     pub clear_screen: bool,
+    /// Log the watched and ignored paths as JSON, at startup and when they change
+    #[arg(long, env = "TRUNK_WATCH_PRINT_WATCHES")]
+    pub print_watches: bool,
+    /// Rebuild once on the first change after the initial build, then exit with its result
+    #[arg(long, env = "TRUNK_WATCH_ONCE")]
+    pub watch_once: bool,
 
     // NOTE: flattened structures come last
     #[command(flatten)]
@@ -47,15 +64,34 @@ impl Watch {
         let Self {
             watch,
             ignore,
+            watch_ignore,
+            watch_extra,
             poll: _,
             poll_interval: _,
             enable_cooldown: _,
             clear_screen: _,
+            print_watches: _,
+            watch_once: _,
             build,
         } = self;
 
         config.watch.watch = watch.unwrap_or(config.watch.watch);
         config.watch.ignore = ignore.unwrap_or(config.watch.ignore);
+        // runtime ignore patterns are added to the configured ones
+        config
+            .watch
+            .ignore_globs
+            .extend(watch_ignore.unwrap_or_default());
+        // same for additional watch paths
+        config
+            .watch
+            .extra_paths
+            .extend(watch_extra.unwrap_or_default());
+
+        ensure!(
+            !build.matrix.unwrap_or_default(),
+            "Building a matrix is only supported by 'trunk build'"
+        );
 
         let config = build.apply_to(config)?;
 
@@ -75,6 +111,7 @@ impl Watch {
             poll: self.poll.then_some(self.poll_interval.0),
             enable_cooldown: self.enable_cooldown,
             clear_screen: self.clear_screen,
+            print_watches: self.print_watches,
             // in watch mode we can't report errors
             no_error_reporting: false,
         })
@@ -86,6 +123,19 @@ impl Watch {
 
         let mut system = WatchSystem::new(Arc::new(cfg), shutdown_tx.clone(), None, None).await?;
 
+        if self.watch_once {
+            if let Err(err) = system.build().await {
+                tracing::warn!("initial build failed, waiting for a change: {err:#}");
+            }
+            return tokio::select! {
+                result = system.run_once() => result,
+                result = tokio::signal::ctrl_c() => {
+                    result.context("error awaiting shutdown signal")?;
+                    bail!("interrupted before a change was detected")
+                }
+            };
+        }
+
         system.build().await.ok();
         let system_handle = tokio::spawn(system.run());
         tokio::signal::ctrl_c()
@@ -101,3 +151,27 @@ impl Watch {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        cli::{Trunk, TrunkSubcommands},
+        config::Configuration,
+    };
+    use clap::Parser;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(&["trunk", "watch", "--watch-ignore", "docs/**"])]
+    #[case(&["trunk", "watch", "--watch.ignore", "docs/**"])]
+    fn watch_ignore_globs_are_added(#[case] input: &[&str]) {
+        let cli = Trunk::parse_from(input);
+        let TrunkSubcommands::Watch(watch) = cli.action else {
+            panic!("must be a watch command");
+        };
+        let mut config = Configuration::default();
+        config.watch.ignore_globs = vec!["*.bak".into()];
+        let config = watch.apply_to(config).expect("must apply");
+        assert_eq!(config.watch.ignore_globs, ["*.bak", "docs/**"]);
+    }
+}
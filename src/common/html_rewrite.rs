@@ -116,6 +116,14 @@ In case this is a false positive, the "--allow-self-closing-script" flag can be
         })
     }
 
+    /// Will silently fail when attempting to prepend to [Void Element](https://developer.mozilla.org/en-US/docs/Glossary/Void_element).
+    pub fn prepend_html(&mut self, selector: &str, html: &str) -> Result<()> {
+        self.select_mut(selector, |el| {
+            el.prepend(html, lol_html::html_content::ContentType::Html);
+            Ok(())
+        })
+    }
+
     pub fn replace_with_html(&mut self, selector: &str, html: &str) -> Result<()> {
         self.select_mut(selector, |el| {
             el.replace(html, lol_html::html_content::ContentType::Html);
@@ -188,6 +196,23 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_prepend_html() {
+        let mut doc = Document::new(
+            r#"<html><body><script src="user.js"></script></body></html>"#,
+            Default::default(),
+        )
+        .expect("this is valid HTML");
+
+        doc.prepend_html("body", r#"<script src="init.js"></script>"#)
+            .expect("not expected to fail");
+
+        assert_eq!(
+            String::from_utf8_lossy(&doc.0),
+            r#"<html><body><script src="init.js"></script><script src="user.js"></script></body></html>"#
+        );
+    }
+
     /// Ensure we get an error for any self-closing script tag
     #[test]
     fn test_self_closing_script_tag() {
@@ -4,7 +4,8 @@ pub mod html_rewrite;
 use anyhow::{anyhow, bail, Context, Result};
 use base64::{engine::general_purpose, Engine};
 use console::Emoji;
-use once_cell::sync::Lazy;
+use futures_util::future::{BoxFuture, FutureExt};
+use once_cell::sync::{Lazy, OnceCell};
 use rand::RngCore;
 use std::collections::HashSet;
 use std::ffi::OsStr;
@@ -26,6 +27,19 @@ pub static STARTING: Emoji = Emoji("🚀 ", "");
 #[cfg(feature = "update_check")]
 pub static UPDATE: Emoji = Emoji("⏫ ", "");
 
+/// The color mode (`auto`, `always` or `never`) to pass on to cargo and other tools.
+static COLOR_MODE: OnceCell<&'static str> = OnceCell::new();
+
+/// Set the color mode to pass on to cargo and other tools, may only be set once.
+pub fn set_color_mode(mode: &'static str) {
+    let _ = COLOR_MODE.set(mode);
+}
+
+/// The color mode to pass on to cargo and other tools, `auto` unless set otherwise.
+pub fn color_mode() -> &'static str {
+    COLOR_MODE.get().copied().unwrap_or("auto")
+}
+
 // If we fail to get the current_dir, we can't do much and just fail, so we can use expect(..).
 #[allow(clippy::expect_used)]
 static CWD: Lazy<PathBuf> =
@@ -36,6 +50,24 @@ pub async fn copy_dir_recursive<F, T>(from_dir: F, to_dir: T) -> Result<HashSet<
 where
     F: AsRef<Path> + Debug + Send + 'static,
     T: AsRef<Path> + Send + 'static,
+{
+    copy_dir_recursive_filtered(from_dir, to_dir, |_, _| true).await
+}
+
+/// A utility function to recursively copy the entries of a directory accepted by a filter.
+///
+/// The filter gets the path of an entry relative to `from_dir`, and whether it is a directory.
+/// Directories which are filtered out are skipped entirely, and directories which only contain
+/// filtered out entries are not created in the target directory.
+pub async fn copy_dir_recursive_filtered<F, T, P>(
+    from_dir: F,
+    to_dir: T,
+    filter: P,
+) -> Result<HashSet<PathBuf>>
+where
+    F: AsRef<Path> + Debug + Send + 'static,
+    T: AsRef<Path> + Send + 'static,
+    P: Fn(&Path, bool) -> bool + Send + Sync,
 {
     let from = from_dir.as_ref();
     let to: &Path = to_dir.as_ref();
@@ -58,31 +90,64 @@ where
     }
 
     let mut collector = HashSet::new();
+    copy_dir_entries(from, to, Path::new(""), &filter, &mut collector).await?;
 
-    // Copy files and recursively handle nested directories.
-    let mut read_dir = tokio::fs::read_dir(from)
-        .await
-        .context(anyhow!("Unable to read dir"))?;
-    while let Some(entry) = read_dir
-        .next_entry()
-        .await
-        .context(anyhow!("Unable to read next dir entry"))?
-    {
-        if entry.file_type().await?.is_dir() {
-            let files = Box::pin(async move {
-                copy_dir_recursive(entry.path(), to.join(entry.file_name())).await
-            })
-            .await?;
-            collector.extend(files);
-        } else {
-            let to = to.join(entry.file_name());
-            // Does overwrite!
-            tokio::fs::copy(entry.path(), &to).await?;
-            collector.insert(to);
+    Ok(collector)
+}
+
+/// Copy the entries of a directory accepted by the filter, and recursively handle nested
+/// directories.
+fn copy_dir_entries<'a>(
+    from: &'a Path,
+    to: &'a Path,
+    relative: &'a Path,
+    filter: &'a (dyn Fn(&Path, bool) -> bool + Send + Sync),
+    collector: &'a mut HashSet<PathBuf>,
+) -> BoxFuture<'a, Result<()>> {
+    async move {
+        let mut read_dir = tokio::fs::read_dir(from)
+            .await
+            .context(anyhow!("Unable to read dir"))?;
+        let mut empty = true;
+        let mut created = false;
+        while let Some(entry) = read_dir
+            .next_entry()
+            .await
+            .context(anyhow!("Unable to read next dir entry"))?
+        {
+            empty = false;
+            let relative = relative.join(entry.file_name());
+            let is_dir = entry.file_type().await?.is_dir();
+            if !filter(&relative, is_dir) {
+                continue;
+            }
+
+            let target = to.join(entry.file_name());
+            if is_dir {
+                copy_dir_entries(&entry.path(), &target, &relative, filter, collector).await?;
+            } else {
+                if !created {
+                    tokio::fs::create_dir_all(to)
+                        .await
+                        .with_context(|| format!("Unable to create target directory '{to:?}'."))?;
+                    created = true;
+                }
+                // Does overwrite!
+                tokio::fs::copy(entry.path(), &target).await?;
+                collector.insert(target);
+            }
         }
-    }
 
-    Ok(collector)
+        // Empty directories are copied too.
+        if empty {
+            tokio::fs::create_dir_all(to)
+                .await
+                .with_context(|| format!("Unable to create target directory '{to:?}'."))?;
+        }
+
+        Ok(())
+    }
+    .boxed()
 }
 
 /// A utility function to recursively delete a directory.
@@ -168,15 +233,29 @@ pub async fn run_command(
     args: &[impl AsRef<OsStr> + Debug],
     working_dir: impl AsRef<Path> + Debug,
 ) -> Result<()> {
-    tracing::debug!(?args, "{name} args");
+    run_command_with_env::<&str>(name, path, args, &[], working_dir).await
+}
+
+/// Run a global command with the given arguments and additional environment variables.
+pub async fn run_command_with_env<V: AsRef<OsStr> + Debug>(
+    name: &str,
+    path: impl AsRef<Path> + Debug,
+    args: &[impl AsRef<OsStr> + Debug],
+    envs: &[(&str, V)],
+    working_dir: impl AsRef<Path> + Debug,
+) -> Result<()> {
+    tracing::debug!(?args, ?envs, "{name} args");
 
     let path = path.as_ref();
 
     let status = Command::new(path)
         .current_dir(working_dir.as_ref())
         .args(args)
+        .envs(envs.iter().map(|(k, v)| (k, v)))
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit())
+        // stop the command when its pipeline gets aborted, e.g. by `build.fail_fast`
+        .kill_on_drop(true)
         .spawn()
         .with_context(|| {
             format!(
@@ -1,6 +1,7 @@
-use std::path::Path;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use cargo_metadata::{Metadata, MetadataCommand, Package};
 use tokio::task::spawn_blocking;
 
@@ -44,3 +45,164 @@ impl CargoMetadata {
         })
     }
 }
+
+impl CargoMetadata {
+    /// Ensure a cargo profile exists, before invoking cargo with it.
+    ///
+    /// Besides the built-in profiles, profiles can be defined in the manifest of the workspace
+    /// root, in the cargo configuration files of the workspace, its parent directories and
+    /// `$CARGO_HOME`, or by `CARGO_PROFILE_<name>_*` environment variables.
+    pub async fn check_profile(&self, profile: &str) -> Result<()> {
+        if BUILTIN_PROFILES.contains(&profile) {
+            return Ok(());
+        }
+
+        let profiles = self
+            .defined_profiles()
+            .await
+            .context("unable to evaluate the cargo profiles")?;
+        if !profiles.contains(profile) {
+            bail!(
+                "cargo profile '{profile}' is not defined for '{}', available profiles: {}",
+                self.manifest_path,
+                profiles.into_iter().collect::<Vec<_>>().join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    /// The profiles defined for the workspace.
+    async fn defined_profiles(&self) -> Result<BTreeSet<String>> {
+        let root = self.metadata.workspace_root.as_std_path();
+        let mut profiles = BTreeSet::from(BUILTIN_PROFILES.map(str::to_string));
+        profiles.extend(defined_profiles(
+            &read_toml(&root.join("Cargo.toml")).await?,
+        ));
+
+        let mut config_dirs = root
+            .ancestors()
+            .map(|dir| dir.join(".cargo"))
+            .collect::<Vec<_>>();
+        config_dirs.extend(cargo_home());
+        for dir in config_dirs {
+            for file in ["config.toml", "config"] {
+                let path = dir.join(file);
+                if path.is_file() {
+                    profiles.extend(defined_profiles(&read_toml(&path).await?));
+                }
+            }
+        }
+
+        profiles.extend(env_profiles(std::env::vars().map(|(name, _)| name)));
+        Ok(profiles)
+    }
+}
+
+/// The cargo home directory, `$CARGO_HOME` or `~/.cargo`.
+fn cargo_home() -> Option<PathBuf> {
+    match std::env::var_os("CARGO_HOME") {
+        Some(home) => Some(home.into()),
+        None => homedir::my_home()
+            .ok()
+            .flatten()
+            .map(|home| home.join(".cargo")),
+    }
+}
+
+/// The names of the profiles configured by `CARGO_PROFILE_<name>_<key>` environment variables.
+///
+/// As the names are upper case and use `_` instead of `-`, they are returned in the lower case
+/// form, with `_` replaced by `-`, which matches the typical profile names.
+fn env_profiles(names: impl Iterator<Item = String>) -> BTreeSet<String> {
+    const KEYS: &[&str] = &[
+        "CODEGEN_BACKEND",
+        "CODEGEN_UNITS",
+        "DEBUG_ASSERTIONS",
+        "DEBUG",
+        "INCREMENTAL",
+        "INHERITS",
+        "LTO",
+        "OPT_LEVEL",
+        "OVERFLOW_CHECKS",
+        "PANIC",
+        "RPATH",
+        "SPLIT_DEBUGINFO",
+        "STRIP",
+    ];
+
+    names
+        .filter_map(|name| {
+            let name = name.strip_prefix("CARGO_PROFILE_")?;
+            let profile = match name.split_once("_BUILD_OVERRIDE_") {
+                Some((profile, _)) => profile,
+                None => KEYS
+                    .iter()
+                    .find_map(|key| name.strip_suffix(key)?.strip_suffix('_'))?,
+            };
+            Some(profile.to_lowercase().replace('_', "-"))
+        })
+        .collect()
+}
+
+/// The profiles cargo always provides.
+const BUILTIN_PROFILES: [&str; 4] = ["dev", "release", "test", "bench"];
+
+async fn read_toml(path: &Path) -> Result<toml::Table> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("error reading {}", path.display()))?;
+    toml::from_str(&content).with_context(|| format!("error parsing {}", path.display()))
+}
+
+/// The names of the profiles defined in a manifest or cargo configuration file.
+fn defined_profiles(content: &toml::Table) -> impl Iterator<Item = String> + '_ {
+    content
+        .get("profile")
+        .and_then(|profiles| profiles.as_table())
+        .into_iter()
+        .flat_map(|profiles| profiles.keys().cloned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn profiles_are_defined() -> Result<()> {
+        let manifest: toml::Table = toml::from_str(
+            r#"
+            [package]
+            name = "app"
+
+            [profile.release]
+            lto = true
+
+            [profile.wasm-release]
+            inherits = "release"
+            opt-level = "z"
+            "#,
+        )?;
+        assert_eq!(
+            defined_profiles(&manifest).collect::<Vec<_>>(),
+            ["release", "wasm-release"]
+        );
+        assert_eq!(defined_profiles(&toml::Table::new()).count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn profiles_from_env() {
+        let names = [
+            "CARGO_PROFILE_WASM_RELEASE_OPT_LEVEL",
+            "CARGO_PROFILE_DIST_INHERITS",
+            "CARGO_PROFILE_RELEASE_BUILD_OVERRIDE_DEBUG",
+            "CARGO_PROFILE_UNKNOWN",
+            "CARGO_HOME",
+        ]
+        .map(str::to_string);
+        assert_eq!(
+            env_profiles(names.into_iter()),
+            BTreeSet::from(["dist", "release", "wasm-release"].map(str::to_string))
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use crate::config::{
-    models::ConfigModel,
-    types::{BaseUrl, Minify},
+    models::{ConfigModel, Configuration},
+    types::{BaseUrl, CompressionFormat, Minify},
 };
 use schemars::JsonSchema;
 use serde::{de, Deserialize, Deserializer, Serialize};
@@ -50,6 +50,11 @@ pub struct Build {
     #[serde(default)]
     pub locked: bool,
 
+    /// A wrapper for rustc, passed to cargo as `RUSTC_WRAPPER`, e.g. `sccache` for build caching
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rustc_wrapper: Option<String>,
+
     /// The public URL from which assets are to be served
     #[serde(default)]
     pub public_url: BaseUrl,
@@ -141,6 +146,16 @@ pub struct Build {
     #[serde(default)]
     pub minify: Minify,
 
+    /// Also minify the JS snippets of wasm-bindgen, when minifying.
+    ///
+    /// Snippets may be hand-written code of dependencies, which is why this is opt-in.
+    #[serde(default)]
+    pub minify_snippets: bool,
+
+    /// Write source maps of minified JS and CSS files next to them.
+    #[serde(default)]
+    pub source_maps: bool,
+
     /// Allows disabling sub-resource integrity (SRI)
     #[serde(default)]
     pub no_sri: bool,
@@ -159,6 +174,205 @@ pub struct Build {
     /// The placeholder which is used in the 'nonce' attribute.
     #[serde(default = "default::nonce_placeholder")]
     pub nonce_placeholder: String,
+
+    /// Options of the `es-module-shims` import map polyfill, injected as an `esms-options` script
+    /// ahead of the first script of the head.
+    ///
+    /// These values can only be provided via config file.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub esms_options: Option<serde_json::Map<String, serde_json::Value>>,
+
+    /// Log the raw and gzipped sizes of the WASM, JS and CSS output after a build.
+    #[serde(default)]
+    pub log_sizes: bool,
+
+    /// Compile the WASM file using streaming instantiation, requires the server to send it as
+    /// `application/wasm`.
+    #[serde(default)]
+    pub streaming_init: bool,
+
+    /// Emit TypeScript bindings for all rust apps, unless disabled using `data-typescript="false"`.
+    #[serde(default)]
+    pub typescript: bool,
+
+    /// Origins to add `<link rel="preconnect">` and `<link rel="dns-prefetch">` hints for, e.g.
+    /// `["https://cdn.example.com"]`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub preconnect: Vec<String>,
+
+    /// Directories the sass/scss assets resolve `@use`, `@forward` and `@import` loads from, after
+    /// the ones of their `data-load-path`. Relative paths are resolved against the working
+    /// directory.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sass_load_paths: Vec<PathBuf>,
+
+    /// Write a `.trunk-manifest.json` file, describing the files of the dist dir, after a build.
+    #[serde(default)]
+    pub emit_manifest: bool,
+
+    /// Only build the Rust application, re-using the outputs of the other assets from the previous
+    /// build.
+    #[serde(default)]
+    pub skip_assets: bool,
+
+    /// Abort the remaining asset pipelines on the first failing one, instead of waiting for all of
+    /// them to finish.
+    #[serde(default)]
+    pub fail_fast: bool,
+
+    /// Fail the build if cargo emits any compiler warnings [default: false]
+    #[serde(default)]
+    pub deny_warnings: bool,
+
+    /// Produce byte-identical dist output for the same sources [default: false]
+    ///
+    /// Assets are finalized in the order of the HTML file, and the timestamps of all files of the
+    /// dist dir are set to `SOURCE_DATE_EPOCH` (or the Unix epoch).
+    #[serde(default)]
+    pub reproducible: bool,
+
+    /// The base directory of the intermediate output of wasm-bindgen and wasm-opt [default: cargo's target dir]
+    ///
+    /// Unlike cargo's target dir, this is only used by Trunk.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_dir: Option<PathBuf>,
+
+    /// The directory wasm-bindgen writes its intermediate output to [default: <target-dir>/wasm-bindgen]
+    ///
+    /// A `release` or `debug` sub-directory is used, depending on the build mode. This takes
+    /// precedence over the `target_dir` for the output of wasm-bindgen.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bindgen_out_dir: Option<PathBuf>,
+
+    /// Fail the build if the total size of the dist dir exceeds this number of bytes.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dist_budget: Option<u64>,
+
+    /// Write precompressed variants of the output files next to them, e.g. `["brotli", "gzip"]`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub compression: Vec<CompressionFormat>,
+
+    /// The level (`0` to `9`) used for precompressing output files [default: 9]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_level: Option<u32>,
+
+    /// Don't precompress output files smaller than this number of bytes [default: 1024]
+    #[serde(default = "default::compression_min_size")]
+    pub compression_min_size: u64,
+
+    /// The maximum number of images to optimize in parallel [default: number of CPUs]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_concurrency: Option<usize>,
+
+    /// Named sets of additional parameters for wasm-opt.
+    ///
+    /// A set can be selected using `wasm_opt_param_set`, or the `data-wasm-opt-param-set`
+    /// attribute in the `index.html`.
+    ///
+    /// These values can only be provided via config file.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub wasm_opt_param_sets: HashMap<String, Vec<String>>,
+
+    /// The name of the wasm-opt parameter set to use. Ignored if the 'index.html' has one
+    /// configured.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wasm_opt_param_set: Option<String>,
+
+    /// Variants of the application to build using `trunk build --matrix`.
+    ///
+    /// Each variant is built sequentially, using the rest of the build configuration.
+    ///
+    /// These values can only be provided via config file.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub matrix: Vec<BuildVariant>,
+
+    /// The sub directories of the dist dir to place assets in, per category.
+    ///
+    /// A `data-target-path` of an asset takes precedence.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "BuildLayout::is_empty")]
+    pub layout: BuildLayout,
+}
+
+/// The sub directories of the dist dir, per category of asset.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct BuildLayout {
+    /// The directory of JavaScript files
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub js: Option<PathBuf>,
+
+    /// The directory of CSS files, including the output of SASS and Tailwind
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub css: Option<PathBuf>,
+
+    /// The directory of icons and copied image files
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub img: Option<PathBuf>,
+
+    /// The directory of the WASM files of Rust applications, and their JavaScript bindings
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wasm: Option<PathBuf>,
+}
+
+impl BuildLayout {
+    /// Whether no directory is configured.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// A variant of the application, built as part of the build matrix.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct BuildVariant {
+    /// The cargo features to activate for this variant
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(deserialize_with = "string_or_vec")]
+    #[schemars(schema_with = "schema::features")]
+    pub features: Vec<String>,
+
+    /// Build this variant without default features
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_default_features: Option<bool>,
+
+    /// The public URL from which the assets of this variant are to be served
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_url: Option<BaseUrl>,
+
+    /// The output dir for the final assets of this variant
+    pub dist: PathBuf,
+}
+
+impl BuildVariant {
+    /// Apply the variant to a configuration.
+    pub fn apply_to(&self, mut config: Configuration) -> Configuration {
+        config.build.features = self.features.clone();
+        config.build.no_default_features = self
+            .no_default_features
+            .unwrap_or(config.build.no_default_features);
+        if let Some(public_url) = &self.public_url {
+            config.build.public_url = public_url.clone();
+        }
+        config.build.dist = self.dist.clone();
+        config
+    }
 }
 
 fn string_or_vec<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
@@ -228,10 +442,35 @@ impl Default for Build {
             root_certificate: None,
             accept_invalid_certs: false,
             minify: Default::default(),
+            minify_snippets: false,
+            source_maps: false,
             no_sri: false,
             allow_self_closing_script: false,
             create_nonce: false,
+            esms_options: None,
             nonce_placeholder: default::nonce_placeholder(),
+            log_sizes: false,
+            streaming_init: false,
+            typescript: false,
+            preconnect: vec![],
+            sass_load_paths: vec![],
+            emit_manifest: false,
+            skip_assets: false,
+            fail_fast: false,
+            deny_warnings: false,
+            reproducible: false,
+            target_dir: None,
+            bindgen_out_dir: None,
+            dist_budget: None,
+            compression: vec![],
+            compression_level: None,
+            compression_min_size: default::compression_min_size(),
+            image_concurrency: None,
+            wasm_opt_param_sets: Default::default(),
+            wasm_opt_param_set: None,
+            rustc_wrapper: None,
+            matrix: vec![],
+            layout: Default::default(),
         }
     }
 }
@@ -259,6 +498,10 @@ mod default {
     pub fn nonce_placeholder() -> String {
         "{{__TRUNK NONCE__}}".to_string()
     }
+
+    pub const fn compression_min_size() -> u64 {
+        1024
+    }
 }
 
 mod schema {
@@ -14,6 +14,9 @@ pub struct Clean {
     /// Optionally perform a cargo clean
     #[serde(default)]
     pub cargo: bool,
+    /// Glob patterns of files in the dist dir to keep, like `CNAME`, relative to the dist dir
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keep: Vec<String>,
 }
 
 impl ConfigModel for Clean {}
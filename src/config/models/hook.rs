@@ -122,6 +122,21 @@ mod test {
         )
     }
 
+    #[test]
+    pub fn test_rebuild_stage() {
+        let mock: Mock = serde_json::from_value(json!({
+            "hooks": [
+                {
+                    "stage": "rebuild",
+                    "command": "foo",
+                }
+            ]
+        }))
+        .expect("must deserialize");
+
+        assert_eq!(mock.hooks.0[0].stage, PipelineStage::Rebuild);
+    }
+
     #[test]
     pub fn test_serde_empty() {
         let value = serde_json::to_value(Mock {
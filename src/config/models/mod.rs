@@ -107,6 +107,9 @@ impl ConfigModel for Configuration {
                 insecure: self.serve.proxy_insecure.unwrap_or_default(),
                 no_system_proxy: self.serve.proxy_no_system_proxy.unwrap_or_default(),
                 no_redirect: self.serve.proxy_no_redirect.unwrap_or_default(),
+                enabled: true,
+                response_rewrite: vec![],
+                response_rewrite_types: Proxy::default_response_rewrite_types(),
             })
         }
 
@@ -117,8 +120,17 @@ impl ConfigModel for Configuration {
 /// Locate and load the configuration, given an optional file or directory. Falling back to the
 /// current directory.
 pub async fn load(path: Option<PathBuf>) -> Result<(Configuration, PathBuf)> {
+    let (source, cwd) = find(path)?;
+    Ok((source.load().await?, cwd))
+}
+
+/// Locate the source of the configuration, given an optional file or directory. Falling back to
+/// the current directory.
+///
+/// Returns the source, along with the working directory of the project.
+pub fn find(path: Option<PathBuf>) -> Result<(Source, PathBuf)> {
     match path {
-        // if we have a file, load it
+        // if we have a file, use it
         Some(path) if path.is_file() => {
             // Canonicalize the path to the configuration, so that we get a proper parent.
             // Otherwise, we might end up with a parent of '', which won't work later on.
@@ -133,16 +145,16 @@ pub async fn load(path: Option<PathBuf>) -> Result<(Configuration, PathBuf)> {
             };
             let cwd = cwd.to_path_buf();
 
-            Ok((Source::File(path).load().await?, cwd))
+            Ok((Source::File(path), cwd))
         }
-        // if we have a directory, try finding a file and load it
-        Some(path) if path.is_dir() => Ok((Source::find(&path)?.load().await?, path)),
+        // if we have a directory, try finding a file in it
+        Some(path) if path.is_dir() => Ok((Source::find(&path)?, path)),
         // if we have something else, we can't deal with it
         Some(path) => bail!("{} is neither a file nor a directory", path.display()),
-        // if we have nothing, try to find a file in the current directory and load it
+        // if we have nothing, try to find a file in the current directory
         None => {
             let cwd = std::env::current_dir().context("unable to get current directory")?;
-            Ok((Source::find(&cwd)?.load().await?, cwd))
+            Ok((Source::find(&cwd)?, cwd))
         }
     }
 }
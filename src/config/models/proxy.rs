@@ -32,6 +32,44 @@ pub struct Proxy {
     /// `false`, i.e. yes, follow redirects automatically.
     #[serde(default)]
     pub no_redirect: bool,
+    /// Whether the proxy is enabled. A disabled proxy is not registered, and requests to its path
+    /// are handled like any other request.
+    #[serde(default = "default::enabled")]
+    pub enabled: bool,
+    /// Replacements applied to the bodies of text responses, e.g. to rewrite absolute URLs of
+    /// the backend.
+    #[serde(default)]
+    pub response_rewrite: Vec<ResponseRewrite>,
+    /// The content types of responses the `response_rewrite` replacements are applied to
+    /// [default: ["application/json", "text/html"]]
+    #[serde(default = "default::response_rewrite_types")]
+    pub response_rewrite_types: Vec<String>,
+}
+
+/// A replacement of a string in the body of a proxied response.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, JsonSchema)]
+pub struct ResponseRewrite {
+    /// The string to replace.
+    pub from: String,
+    /// The replacement.
+    pub to: String,
+}
+
+mod default {
+    pub const fn enabled() -> bool {
+        true
+    }
+
+    pub fn response_rewrite_types() -> Vec<String> {
+        vec!["application/json".to_string(), "text/html".to_string()]
+    }
+}
+
+impl Proxy {
+    /// The default content types to rewrite response bodies of.
+    pub fn default_response_rewrite_types() -> Vec<String> {
+        default::response_rewrite_types()
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, JsonSchema)]
@@ -1,6 +1,6 @@
 use crate::config::{
     models::ConfigModel,
-    types::{AddressFamily, Uri, WsProtocol},
+    types::{AddressFamily, ConfigDuration, PreloadLocation, TlsVersion, Uri, WsProtocol},
 };
 use schemars::JsonSchema;
 use serde::Deserialize;
@@ -29,7 +29,13 @@ pub struct Serve {
     /// The aliases to serve on.
     #[serde(default)]
     pub aliases: Vec<String>,
-    /// Open a browser tab once the initial build is complete [default: false]
+    /// The public host (and optional port) the server is reachable at, e.g. when using a tunnel
+    ///
+    /// This overrides the addresses shown during startup and the address the auto-reload client
+    /// connects to, without changing the addresses to bind to.
+    #[serde(default)]
+    pub public_host: Option<String>,
+    /// Open a browser tab once the first successful build is complete [default: false]
     #[serde(default)]
     pub open: bool,
     /// Disable auto-reload of the web app
@@ -44,17 +50,40 @@ pub struct Serve {
     /// Disable fallback to index.html for missing files
     #[serde(default)]
     pub no_spa: bool,
+    /// Fall back to index.html for missing files with a file extension too, instead of only for
+    /// paths without one
+    #[serde(default)]
+    pub spa_fallback_extensions: bool,
+    /// A backend to proxy all requests without a file in the dist dir to, instead of falling back
+    /// to the index.html (like a server side rendering backend)
+    #[serde(default)]
+    pub default_backend: Option<Uri>,
+    /// Follow symlinks in the dist directory, even when they point outside of it [default: false]
+    #[serde(default)]
+    pub follow_symlinks: bool,
     /// Protocol used for the auto-reload WebSockets connection
     pub ws_protocol: Option<WsProtocol>,
     /// The path to the trunk web-socket
     #[serde(default)]
     pub ws_base: Option<String>,
+    /// The delay before the auto-reload client reconnects to Trunk, e.g. "500ms" [default: 5s]
+    #[serde(default)]
+    #[schemars(with = "Option<String>")]
+    pub ws_reconnect_delay: Option<ConfigDuration>,
+    /// The maximum delay between reconnects, each failed attempt doubles the delay until this is
+    /// reached [default: the reconnect delay]
+    #[serde(default)]
+    #[schemars(with = "Option<String>")]
+    pub ws_reconnect_max_delay: Option<ConfigDuration>,
     /// The TLS key file to enable TLS encryption
     #[serde(default)]
     pub tls_key_path: Option<PathBuf>,
     /// The TLS cert file to enable TLS encryption
     #[serde(default)]
     pub tls_cert_path: Option<PathBuf>,
+    /// The minimum TLS version to accept, `1.2` or `1.3` [default: TLS stack's default]
+    #[serde(default)]
+    pub tls_min_version: Option<TlsVersion>,
     /// A base path to serve the application from
     #[serde(default)]
     pub serve_base: Option<String>,
@@ -88,6 +117,53 @@ pub struct Serve {
     /// The CSP;  {{NONE}} is replaced by a random nonce
     #[serde(default = "default::csp")]
     pub csp: Vec<String>,
+    /// Add a `Repr-Digest` header to responses of static assets
+    #[serde(default)]
+    pub digest_header: bool,
+    /// Where to announce the preloads of the served index.html [default: head]
+    ///
+    /// Using `header` or `both` adds a `Link` header for each preload link of the document's head.
+    /// Using `header` also removes those links from the served document.
+    #[serde(default)]
+    pub preload_location: PreloadLocation,
+    /// Send a `103 Early Hints` response with the preloads of the served index.html, ahead of the
+    /// document [default: false]
+    ///
+    /// This is only supported for HTTP/1.1 connections.
+    #[serde(default)]
+    pub early_hints: bool,
+    /// Send the `Cross-Origin-Opener-Policy` and `Cross-Origin-Embedder-Policy` headers required
+    /// for cross-origin isolation with all responses [default: false]
+    ///
+    /// This enables `SharedArrayBuffer`, as required by WASM threads.
+    #[serde(default)]
+    pub cross_origin_isolation: bool,
+    /// Add `integrity` attributes when serving [default: only for release builds]
+    ///
+    /// Assets changing rapidly, or proxies rewriting bodies, break the digests during development.
+    /// Explicit `data-integrity` attributes still apply, and `build.no_sri` disables them anyway.
+    #[serde(default)]
+    pub sri: Option<bool>,
+    /// Keep connections open for further requests [default: true]
+    #[serde(default = "default::keepalive")]
+    pub keepalive: bool,
+    /// Close connections which didn't send a complete request head within this time, e.g. "30s"
+    /// [default: none]
+    ///
+    /// This never limits the time sending a response takes, so large files can be served over
+    /// slow connections.
+    #[serde(default)]
+    #[schemars(with = "Option<String>")]
+    pub http_timeout: Option<ConfigDuration>,
+    /// Accept HTTP/2 without TLS (h2c, with prior knowledge) [default: false]
+    ///
+    /// When serving with TLS, HTTP/2 is always negotiated using ALPN.
+    #[serde(default)]
+    pub http2: bool,
+    /// A command to run once the first build succeeded and the server is listening, shutting
+    /// down the server when it exits
+    #[serde(default)]
+    pub on_ready: Option<String>,
 }
 
 impl Default for Serve {
@@ -97,6 +173,7 @@ impl Default for Serve {
             address: None,
             addresses: vec![],
             aliases: vec![],
+            public_host: None,
             prefer_address_family: None,
             port: default::port(),
             disable_address_lookup: false,
@@ -105,10 +182,16 @@ impl Default for Serve {
             headers: Default::default(),
             no_error_reporting: false,
             no_spa: false,
+            spa_fallback_extensions: false,
+            default_backend: None,
+            follow_symlinks: false,
             ws_protocol: None,
             ws_base: None,
+            ws_reconnect_delay: None,
+            ws_reconnect_max_delay: None,
             tls_key_path: None,
             tls_cert_path: None,
+            tls_min_version: None,
             serve_base: None,
             proxy_backend: None,
             proxy_rewrite: None,
@@ -118,6 +201,15 @@ impl Default for Serve {
             proxy_no_redirect: None,
             disable_csp: false,
             csp: default::csp(),
+            digest_header: false,
+            preload_location: Default::default(),
+            early_hints: false,
+            cross_origin_isolation: false,
+            sri: None,
+            keepalive: default::keepalive(),
+            http_timeout: None,
+            http2: false,
+            on_ready: None,
         }
     }
 }
@@ -127,6 +219,10 @@ mod default {
         8080
     }
 
+    pub const fn keepalive() -> bool {
+        true
+    }
+
     pub fn csp() -> Vec<String> {
         [
             "script-src 'wasm-unsafe-eval' 'nonce-{{NONCE}}'",
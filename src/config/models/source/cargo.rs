@@ -18,6 +18,12 @@ pub async fn from_manifest(file: impl AsRef<Path>) -> anyhow::Result<Configurati
     Ok(configuration)
 }
 
+/// Check if the cargo manifest contains a trunk configuration
+pub async fn has_configuration(file: impl AsRef<Path>) -> anyhow::Result<bool> {
+    let manifest = manifest::CargoMetadata::new(file.as_ref()).await?;
+    Ok(manifest.package.metadata.get("trunk").is_some())
+}
+
 #[cfg(test)]
 mod test {
     use crate::config::models::source::cargo::TrunkMetadata;
@@ -42,6 +42,19 @@ impl Source {
         }
     }
 
+    /// Describe which file provides the configuration.
+    ///
+    /// A cargo manifest without any Trunk metadata results in the default configuration.
+    pub async fn describe(&self) -> anyhow::Result<String> {
+        Ok(match self {
+            Self::File(file) => file.display().to_string(),
+            Self::Manifest { file } => match cargo::has_configuration(file).await? {
+                true => format!("Cargo.toml metadata ({})", file.display()),
+                false => format!("defaults (no Trunk metadata in {})", file.display()),
+            },
+        })
+    }
+
     /// Load the configuration from the source.
     ///
     /// This will validate and migrate anything that's required. It does not store any migrations.
@@ -42,6 +42,7 @@ async fn err_bad_trunk_toml_watch_path() {
         poll: None,
         enable_cooldown: false,
         clear_screen: false,
+        print_watches: false,
         no_error_reporting: false,
     })
     .await
@@ -70,6 +71,7 @@ async fn err_bad_trunk_toml_watch_ignore() {
         poll: None,
         enable_cooldown: false,
         clear_screen: false,
+        print_watches: false,
         no_error_reporting: false,
     })
     .await
@@ -238,3 +240,168 @@ async fn example_config() {
         .await
         .expect("example config should be parsable");
 }
+
+/// Ensure that watch ignore globs are relative to the project root
+#[tokio::test]
+async fn watch_ignore_globs() {
+    let dir = tempdir().expect("should be able to create temp directory");
+
+    let target = dir.path().join("Trunk.toml");
+    fs::write(
+        &target,
+        r#"
+[watch]
+ignore_globs = ["docs/**", "*.tmp"]
+"#,
+    )
+    .expect("should be able to write temporary file");
+    fs::write(dir.path().join("index.html"), r#""#)
+        .expect("should be able to write temporary file");
+
+    let (cfg, working_directory) = load(Some(target)).await.expect("config to parse");
+    let cfg = RtcWatch::from_config(cfg, working_directory, |_, core| WatchOptions {
+        build: BuildOptions {
+            core,
+            inject_autoloader: false,
+        },
+        poll: None,
+        enable_cooldown: false,
+        clear_screen: false,
+        print_watches: false,
+        no_error_reporting: false,
+    })
+    .await
+    .expect("configuration to build runtime");
+
+    let root = dir.path().canonicalize().expect("canonical temp dir");
+    let ignored = |path: &str| {
+        cfg.ignored_globs
+            .iter()
+            .any(|pattern| pattern.matches_path(&root.join(path)))
+    };
+
+    assert!(ignored("docs/index.md"));
+    assert!(ignored("docs/nested/index.md"));
+    assert!(ignored("file.tmp"));
+    assert!(!ignored("src/main.rs"));
+}
+
+/// Ensure that build matrix variants override the build configuration
+#[tokio::test]
+async fn build_matrix_variants() {
+    let dir = tempdir().expect("should be able to create temp directory");
+
+    let target = dir.path().join("Trunk.toml");
+    fs::write(
+        &target,
+        r#"
+[build]
+features = ["base"]
+public_url = "/"
+
+[[build.matrix]]
+features = "free"
+dist = "dist/free"
+public_url = "/free/"
+
+[[build.matrix]]
+features = ["pro", "extra"]
+no_default_features = true
+dist = "dist/pro"
+"#,
+    )
+    .expect("should be able to write temporary file");
+
+    let (cfg, _) = load(Some(target)).await.expect("config to parse");
+    assert_eq!(cfg.build.matrix.len(), 2);
+
+    let free = cfg.build.matrix[0].apply_to(cfg.clone());
+    assert_eq!(free.build.features, vec!["free"]);
+    assert_eq!(free.build.dist, Path::new("dist/free"));
+    assert_eq!(free.build.public_url.to_string(), "/free/");
+    assert!(!free.build.no_default_features);
+
+    let pro = cfg.build.matrix[1].apply_to(cfg.clone());
+    assert_eq!(pro.build.features, vec!["pro", "extra"]);
+    assert_eq!(pro.build.dist, Path::new("dist/pro"));
+    assert_eq!(pro.build.public_url.to_string(), "/");
+    assert!(pro.build.no_default_features);
+}
+
+#[tokio::test]
+async fn build_layout() {
+    let dir = tempdir().expect("should be able to create temp directory");
+
+    let target = dir.path().join("Trunk.toml");
+    fs::write(
+        &target,
+        r#"
+[build.layout]
+js = "js"
+css = "css"
+wasm = "wasm"
+"#,
+    )
+    .expect("should be able to write temporary file");
+
+    let (cfg, _) = load(Some(target)).await.expect("config to parse");
+    assert_eq!(cfg.build.layout.js.as_deref(), Some(Path::new("js")));
+    assert_eq!(cfg.build.layout.css.as_deref(), Some(Path::new("css")));
+    assert_eq!(cfg.build.layout.img, None);
+    assert_eq!(cfg.build.layout.wasm.as_deref(), Some(Path::new("wasm")));
+    assert!(!cfg.build.layout.is_empty());
+    assert!(Build::default().layout.is_empty());
+}
+
+#[tokio::test]
+async fn err_build_layout_outside_dist() {
+    let dir = tempdir().expect("should be able to create temp directory");
+    fs::write(dir.path().join("index.html"), "<html></html>")
+        .expect("should be able to write temporary file");
+
+    let target = dir.path().join("Trunk.toml");
+    fs::write(
+        &target,
+        r#"
+[build.layout]
+css = "../css"
+"#,
+    )
+    .expect("should be able to write temporary file");
+
+    let (cfg, working_directory) = load(Some(target)).await.expect("config to parse");
+    let err = RtcBuild::from_config(cfg, working_directory, |_, core| BuildOptions {
+        core,
+        inject_autoloader: false,
+    })
+    .await
+    .expect_err("expected config to err");
+
+    assert_eq!(
+        err.to_string(),
+        "the layout directory '../css' must be a relative path inside the dist directory"
+    );
+}
+
+#[tokio::test]
+async fn config_source_precedence() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    fs::write(dir.path().join("Trunk.yaml"), "")?;
+    fs::write(dir.path().join(".trunk.toml"), "")?;
+
+    let (source, cwd) = find(Some(dir.path().into()))?;
+    assert_eq!(cwd, dir.path());
+    assert_eq!(
+        source.describe().await?,
+        dir.path().join(".trunk.toml").display().to_string()
+    );
+
+    fs::remove_file(dir.path().join(".trunk.toml"))?;
+    let (source, _) = find(Some(dir.path().into()))?;
+    assert_eq!(
+        source.describe().await?,
+        dir.path().join("Trunk.yaml").display().to_string()
+    );
+
+    Ok(())
+}
@@ -1,8 +1,13 @@
 use crate::config::models::ConfigModel;
 use crate::config::Configuration;
+use crate::tools::Application;
 use clap::Args;
 use schemars::JsonSchema;
 use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 /// Config options for automatic application downloads.
 // **NOTE:** As there are no differences between the persistent configuration and the CLI overrides
@@ -29,6 +34,72 @@ pub struct Tools {
     #[serde(default)]
     #[arg(env = "TRUNK_TOOLS_TAILWINDCSS")]
     pub tailwindcss: Option<String>,
+
+    /// Version of `wasm-tools` to use.
+    #[serde(default)]
+    #[arg(env = "TRUNK_TOOLS_WASM_TOOLS")]
+    pub wasm_tools: Option<String>,
+
+    /// Path of the `dart-sass` binary to use, instead of a system installed or downloaded one.
+    #[serde(default)]
+    #[arg(long, env = "TRUNK_TOOLS_SASS_PATH")]
+    pub sass_path: Option<PathBuf>,
+
+    /// Path of the `wasm-bindgen` binary to use, instead of a system installed or downloaded one.
+    #[serde(default)]
+    #[arg(long, env = "TRUNK_TOOLS_WASM_BINDGEN_PATH")]
+    pub wasm_bindgen_path: Option<PathBuf>,
+
+    /// Path of the `wasm-opt` binary to use, instead of a system installed or downloaded one.
+    #[serde(default)]
+    #[arg(long, env = "TRUNK_TOOLS_WASM_OPT_PATH")]
+    pub wasm_opt_path: Option<PathBuf>,
+
+    /// Path of the `tailwindcss` binary to use, instead of a system installed or downloaded one.
+    #[serde(default)]
+    #[arg(long, env = "TRUNK_TOOLS_TAILWINDCSS_PATH")]
+    pub tailwindcss_path: Option<PathBuf>,
+
+    /// Path of the `tailwindcss-extra` binary to use, instead of a system installed or downloaded
+    /// one.
+    #[serde(default)]
+    #[arg(long, env = "TRUNK_TOOLS_TAILWINDCSS_EXTRA_PATH")]
+    pub tailwindcss_extra_path: Option<PathBuf>,
+
+    /// Path of the `wasm-tools` binary to use, instead of a system installed or downloaded one.
+    #[serde(default)]
+    #[arg(long, env = "TRUNK_TOOLS_WASM_TOOLS_PATH")]
+    pub wasm_tools_path: Option<PathBuf>,
+
+    /// A scratch directory for downloading and extracting tools, instead of the cache directory.
+    #[serde(default)]
+    #[arg(long, env = "TRUNK_TOOLS_DOWNLOAD_TMP")]
+    pub download_tmp: Option<PathBuf>,
+
+    /// The base URL of a mirror of the GitHub releases to download tools from, replacing
+    /// `https://github.com/` in the download URLs.
+    #[serde(default)]
+    #[arg(long, env = "TRUNK_TOOLS_DOWNLOAD_MIRROR")]
+    pub download_mirror: Option<String>,
+
+    /// Additional headers to send when downloading tools from the download mirror, e.g. an
+    /// `Authorization` header.
+    ///
+    /// The headers are only sent to the host of the download mirror. Values may reference
+    /// environment variables using `${NAME}`, to keep secrets out of the configuration. These
+    /// values can only be provided via config file.
+    #[serde(default)]
+    #[arg(skip)]
+    pub download_headers: HashMap<String, String>,
+
+    /// The expected SHA-256 checksums of the downloaded archives, by the name of the tool, e.g.
+    /// `wasm-bindgen`.
+    ///
+    /// Downloads with a different checksum are rejected. These values can only be provided via
+    /// config file.
+    #[serde(default)]
+    #[arg(skip)]
+    pub download_checksums: HashMap<String, String>,
 }
 
 impl Tools {
@@ -37,9 +108,32 @@ impl Tools {
         config.tools.wasm_bindgen = self.wasm_bindgen.or(config.tools.wasm_bindgen);
         config.tools.wasm_opt = self.wasm_opt.or(config.tools.wasm_opt);
         config.tools.tailwindcss = self.tailwindcss.or(config.tools.tailwindcss);
+        config.tools.wasm_tools = self.wasm_tools.or(config.tools.wasm_tools);
+        config.tools.sass_path = self.sass_path.or(config.tools.sass_path);
+        config.tools.wasm_bindgen_path = self.wasm_bindgen_path.or(config.tools.wasm_bindgen_path);
+        config.tools.wasm_opt_path = self.wasm_opt_path.or(config.tools.wasm_opt_path);
+        config.tools.tailwindcss_path = self.tailwindcss_path.or(config.tools.tailwindcss_path);
+        config.tools.tailwindcss_extra_path = self
+            .tailwindcss_extra_path
+            .or(config.tools.tailwindcss_extra_path);
+        config.tools.wasm_tools_path = self.wasm_tools_path.or(config.tools.wasm_tools_path);
+        config.tools.download_tmp = self.download_tmp.or(config.tools.download_tmp);
+        config.tools.download_mirror = self.download_mirror.or(config.tools.download_mirror);
 
         Ok(config)
     }
+
+    /// The configured path of an application, if any.
+    pub fn path(&self, app: Application) -> Option<&Path> {
+        match app {
+            Application::Sass => self.sass_path.as_deref(),
+            Application::TailwindCss => self.tailwindcss_path.as_deref(),
+            Application::TailwindCssExtra => self.tailwindcss_extra_path.as_deref(),
+            Application::WasmBindgen => self.wasm_bindgen_path.as_deref(),
+            Application::WasmOpt => self.wasm_opt_path.as_deref(),
+            Application::WasmTools => self.wasm_tools_path.as_deref(),
+        }
+    }
 }
 
 impl ConfigModel for Tools {}
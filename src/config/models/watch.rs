@@ -13,6 +13,17 @@ pub struct Watch {
     /// Paths to ignore [default: []]
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub ignore: Vec<PathBuf>,
+
+    /// Glob patterns of paths to ignore, relative to the project root [default: []]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_globs: Vec<String>,
+
+    /// Additional file(s) or folder(s) to watch, next to the watched paths [default: []]
+    ///
+    /// Unlike `watch`, this keeps watching the default paths. Useful for build inputs outside of
+    /// the source tree, like data files read by build scripts.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_paths: Vec<PathBuf>,
 }
 
 impl ConfigModel for Watch {}
@@ -1,15 +1,24 @@
 use super::{super::STAGE_DIR, RtcBuilder};
 use crate::{
     config::{
-        models::{Configuration, Hook, Tools},
-        rt::{CoreOptions, RtcCore},
-        types::{BaseUrl, Minify},
+        models::{BuildLayout, Configuration, Hook, Tools},
+        rt::{keep_patterns, CoreOptions, RtcCore},
+        types::{BaseUrl, CompressionFormat, Minify},
         Hooks,
     },
-    tools::HttpClientOptions,
+    processing::minify::MinifyLevel,
+    tools::{Application, HttpClientOptions},
 };
 use anyhow::{ensure, Context};
-use std::{collections::HashMap, ops::Deref, path::PathBuf};
+use glob::Pattern;
+use std::{
+    collections::HashMap,
+    ops::Deref,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+use strum::IntoEnumIterator;
+use tokio::sync::Semaphore;
 
 /// Config options for the cargo build command
 #[derive(Clone, Debug)]
@@ -45,6 +54,8 @@ pub struct RtcBuild {
     pub frozen: bool,
     /// Require Cargo.lock is up to date
     pub locked: bool,
+    /// A wrapper for rustc, passed to cargo as `RUSTC_WRAPPER`
+    pub rustc_wrapper: Option<String>,
     /// The public URL from which assets are to be served.
     pub public_url: BaseUrl,
     /// If `true`, then files being processed should be hashed and the hash should be
@@ -86,12 +97,61 @@ pub struct RtcBuild {
     pub accept_invalid_certs: bool,
     /// Control minification
     pub minify: Minify,
+    /// Also minify the JS snippets of wasm-bindgen
+    pub minify_snippets: bool,
+    /// Write source maps of minified JS and CSS files
+    pub source_maps: bool,
     /// Allow disabling SRI
     pub no_sri: bool,
     /// Ignore error's due to self-closed script tags, instead will issue a warning.
     pub allow_self_closing_script: bool,
     /// When set, create nonce attributes with the option as placeholder
     pub create_nonce: Option<String>,
+    /// The options of the import map polyfill, serialized for embedding into a script element
+    pub esms_options: Option<String>,
+    /// Log the raw and gzipped sizes of the output after a build.
+    pub log_sizes: bool,
+    /// Compile the WASM file using `WebAssembly.compileStreaming`.
+    pub streaming_init: bool,
+    /// Emit TypeScript bindings for rust apps by default.
+    pub typescript: bool,
+    /// Origins to add preconnect and DNS prefetch hints for.
+    pub preconnect: Vec<String>,
+    /// Absolute directories sass/scss assets resolve loads from.
+    pub sass_load_paths: Vec<PathBuf>,
+    /// Write a manifest of the files of the dist dir after a build.
+    pub emit_manifest: bool,
+    /// Only build the Rust application, re-using the other asset outputs of the previous build.
+    pub skip_assets: bool,
+    /// Abort the remaining asset pipelines on the first failing one.
+    pub fail_fast: bool,
+    /// Fail the build if cargo emits any compiler warnings.
+    pub deny_warnings: bool,
+    /// Produce deterministic dist output, with ordered assets and normalized timestamps.
+    pub reproducible: bool,
+    /// The directory wasm-bindgen writes its intermediate output to, instead of the target dir.
+    pub bindgen_out_dir: Option<PathBuf>,
+    /// The base directory of the intermediate output of wasm-bindgen and wasm-opt, instead of
+    /// cargo's target dir.
+    pub target_dir: Option<PathBuf>,
+    /// Fail the build if the total size of the dist dir exceeds this number of bytes.
+    pub dist_budget: Option<u64>,
+    /// The formats to precompress the output files with.
+    pub compression: Vec<CompressionFormat>,
+    /// The level used for precompressing output files.
+    pub compression_level: u32,
+    /// The minimum size of output files to precompress.
+    pub compression_min_size: u64,
+    /// Limits the number of images being optimized in parallel, shared by all pipelines.
+    pub image_pool: Arc<Semaphore>,
+    /// Named sets of additional parameters for wasm-opt.
+    pub wasm_opt_param_sets: HashMap<String, Vec<String>>,
+    /// The wasm-opt parameter set to use, unless overridden by the asset.
+    pub wasm_opt_param_set: Option<String>,
+    /// The sub directories of the dist dir, per category of asset.
+    pub layout: BuildLayout,
+    /// Patterns of files in the dist dir to keep when applying a build, from `clean.keep`.
+    pub keep: Vec<Pattern>,
 }
 
 impl Deref for RtcBuild {
@@ -121,6 +181,7 @@ impl RtcBuild {
             build,
             tools,
             hooks: Hooks(hooks),
+            clean,
             ..
         } = config;
 
@@ -185,6 +246,12 @@ impl RtcBuild {
             }
         };
 
+        if tools.download_mirror.is_none() && !tools.download_headers.is_empty() {
+            tracing::warn!(
+                "the download headers are ignored, as they are only sent to the download mirror, which isn't configured"
+            );
+        }
+
         let mut public_url = build.public_url;
         if !build.public_url_no_trailing_slash_fix {
             public_url = public_url.fix_trailing_slash();
@@ -192,6 +259,100 @@ impl RtcBuild {
 
         let create_nonce = build.create_nonce.then_some(build.nonce_placeholder);
 
+        let esms_options = build
+            .esms_options
+            .map(|options| serde_json::to_string(&options))
+            .transpose()
+            .context("error serializing the es-module-shims options")?
+            // a closing tag must not end the script element early
+            .map(|options| options.replace("</", "<\\/"));
+
+        for origin in &build.preconnect {
+            let uri = origin
+                .parse::<http::Uri>()
+                .with_context(|| format!("invalid preconnect origin: {origin}"))?;
+            ensure!(
+                uri.scheme().is_some() && uri.authority().is_some(),
+                "the preconnect origin must be an absolute URL, like 'https://cdn.example.com', found: {origin}"
+            );
+        }
+
+        let compression_level = build.compression_level.unwrap_or(9);
+        ensure!(
+            compression_level <= 9,
+            "the compression level must be between 0 and 9, found: {compression_level}"
+        );
+
+        let image_concurrency = match build.image_concurrency {
+            Some(concurrency) => concurrency,
+            None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        };
+        ensure!(
+            image_concurrency > 0,
+            "the image concurrency must be at least 1"
+        );
+
+        for dir in [
+            &build.layout.js,
+            &build.layout.css,
+            &build.layout.img,
+            &build.layout.wasm,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            ensure!(
+                dir.is_relative()
+                    && !dir
+                        .components()
+                        .any(|component| matches!(component, Component::ParentDir)),
+                "the layout directory '{}' must be a relative path inside the dist directory",
+                dir.display()
+            );
+            ensure!(
+                !dir.starts_with(".well-known/trunk"),
+                "the layout directory '{}' is reserved for Trunk's own use",
+                dir.display()
+            );
+        }
+
+        for (name, checksum) in &tools.download_checksums {
+            ensure!(
+                Application::iter().any(|app| app.name() == name),
+                "unknown tool '{name}' in tools.download_checksums"
+            );
+            ensure!(
+                checksum.len() == 64 && checksum.chars().all(|c| c.is_ascii_hexdigit()),
+                "the download checksum of '{name}' must be a hex encoded SHA-256"
+            );
+        }
+
+        // configured tool paths are relative to the project root
+        let mut tools = tools;
+        for path in [
+            &mut tools.sass_path,
+            &mut tools.wasm_bindgen_path,
+            &mut tools.wasm_opt_path,
+            &mut tools.tailwindcss_path,
+            &mut tools.tailwindcss_extra_path,
+            &mut tools.wasm_tools_path,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = core.working_directory.join(&*path);
+        }
+
+        let bindgen_out_dir = build
+            .bindgen_out_dir
+            .map(|dir| core.working_directory.join(dir));
+        let target_dir = build.target_dir.map(|dir| core.working_directory.join(dir));
+        let sass_load_paths = build
+            .sass_load_paths
+            .iter()
+            .map(|path| core.working_directory.join(path))
+            .collect();
+
         Ok(Self {
             core,
             target,
@@ -215,14 +376,39 @@ impl RtcBuild {
             offline: build.offline,
             frozen: build.frozen,
             locked: build.locked,
+            rustc_wrapper: build.rustc_wrapper,
             #[cfg(any(feature = "native-tls", feature = "rustls"))]
             root_certificate: build.root_certificate.map(PathBuf::from),
             #[cfg(any(feature = "native-tls", feature = "rustls"))]
             accept_invalid_certs: build.accept_invalid_certs,
             minify: build.minify,
+            minify_snippets: build.minify_snippets,
+            source_maps: build.source_maps,
             no_sri: build.no_sri,
             allow_self_closing_script: build.allow_self_closing_script,
             create_nonce,
+            esms_options,
+            log_sizes: build.log_sizes,
+            streaming_init: build.streaming_init,
+            typescript: build.typescript,
+            preconnect: build.preconnect,
+            sass_load_paths,
+            emit_manifest: build.emit_manifest,
+            skip_assets: build.skip_assets,
+            fail_fast: build.fail_fast,
+            deny_warnings: build.deny_warnings,
+            reproducible: build.reproducible,
+            bindgen_out_dir,
+            target_dir,
+            dist_budget: build.dist_budget,
+            compression: build.compression,
+            compression_level,
+            compression_min_size: build.compression_min_size,
+            image_pool: Arc::new(Semaphore::new(image_concurrency)),
+            wasm_opt_param_sets: build.wasm_opt_param_sets,
+            wasm_opt_param_set: build.wasm_opt_param_set,
+            layout: build.layout,
+            keep: keep_patterns(&clean.keep)?,
         })
     }
 
@@ -260,12 +446,37 @@ impl RtcBuild {
             offline: false,
             frozen: false,
             locked: false,
+            rustc_wrapper: None,
             root_certificate: None,
             accept_invalid_certs: false,
             minify: Minify::Never,
+            minify_snippets: false,
+            source_maps: false,
             no_sri: false,
             allow_self_closing_script: false,
             create_nonce: None,
+            esms_options: None,
+            log_sizes: false,
+            streaming_init: false,
+            typescript: false,
+            preconnect: vec![],
+            sass_load_paths: vec![],
+            emit_manifest: false,
+            skip_assets: false,
+            fail_fast: false,
+            deny_warnings: false,
+            reproducible: false,
+            bindgen_out_dir: None,
+            target_dir: None,
+            dist_budget: None,
+            compression: vec![],
+            compression_level: 9,
+            compression_min_size: 1024,
+            image_pool: Arc::new(Semaphore::new(1)),
+            wasm_opt_param_sets: Default::default(),
+            wasm_opt_param_set: None,
+            layout: Default::default(),
+            keep: vec![],
         })
     }
 
@@ -274,6 +485,14 @@ impl RtcBuild {
         !no_minify && self.should_minify()
     }
 
+    /// Evaluate the minification level with an asset's level.
+    pub fn minify_level(&self, level: MinifyLevel) -> MinifyLevel {
+        match self.should_minify() {
+            true => level,
+            false => MinifyLevel::None,
+        }
+    }
+
     /// Evaluate a global minify state, assets might override this.
     pub fn should_minify(&self) -> bool {
         match (self.minify, self.release) {
@@ -285,12 +504,45 @@ impl RtcBuild {
 
     /// Build [`HttpClientOptions`] options form configuration.
     pub fn client_options(&self) -> HttpClientOptions {
-        HttpClientOptions {
-            #[cfg(any(feature = "native-tls", feature = "rustls"))]
-            root_certificate: self.root_certificate.clone(),
-            #[cfg(any(feature = "native-tls", feature = "rustls"))]
-            accept_invalid_certificates: self.accept_invalid_certs,
-        }
+        #[cfg(any(feature = "native-tls", feature = "rustls"))]
+        let (root_certificate, accept_invalid_certs) =
+            (self.root_certificate.as_deref(), self.accept_invalid_certs);
+        #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+        let (root_certificate, accept_invalid_certs) = (None, false);
+
+        client_options(
+            &self.tools,
+            root_certificate,
+            accept_invalid_certs,
+            &self.working_directory,
+        )
+    }
+}
+
+/// Build [`HttpClientOptions`] from the tools and certificate configuration, also for commands
+/// which don't build, like `trunk doctor`.
+#[cfg_attr(
+    not(any(feature = "native-tls", feature = "rustls")),
+    allow(unused_variables)
+)]
+pub fn client_options(
+    tools: &Tools,
+    root_certificate: Option<&Path>,
+    accept_invalid_certs: bool,
+    working_directory: &Path,
+) -> HttpClientOptions {
+    HttpClientOptions {
+        #[cfg(any(feature = "native-tls", feature = "rustls"))]
+        root_certificate: root_certificate.map(PathBuf::from),
+        #[cfg(any(feature = "native-tls", feature = "rustls"))]
+        accept_invalid_certificates: accept_invalid_certs,
+        download_tmp: tools
+            .download_tmp
+            .as_ref()
+            .map(|dir| working_directory.join(dir)),
+        download_mirror: tools.download_mirror.clone(),
+        download_headers: tools.download_headers.clone(),
+        download_checksums: tools.download_checksums.clone(),
     }
 }
 
@@ -2,6 +2,8 @@ use crate::config::{
     rt::{RtcBuilder, RtcCore},
     Clean, Configuration,
 };
+use anyhow::Context;
+use glob::Pattern;
 use std::ops::Deref;
 
 /// Runtime config for the clean system.
@@ -10,8 +12,12 @@ pub struct RtcClean {
     pub core: RtcCore,
     /// Optionally perform a cargo clean.
     pub cargo: bool,
+    /// Trunk's own target dir, cleaned along with cargo's.
+    pub target_dir: Option<std::path::PathBuf>,
     /// Optionally clean tools.
     pub tools: bool,
+    /// Patterns of files in the dist dir to keep.
+    pub keep: Vec<Pattern>,
 }
 
 impl Deref for RtcClean {
@@ -36,12 +42,15 @@ impl RtcClean {
             tools,
         } = opts;
 
+        let target_dir = config.build.target_dir.clone();
+
         #[allow(deprecated)]
         let Configuration {
             core: core_config,
             clean:
                 Clean {
                     cargo,
+                    keep,
                     // We ignore the legacy `dist` field from the configuration for now.
                     // We have a warning in place, and at some point remove this field.
                     dist: _,
@@ -50,8 +59,16 @@ impl RtcClean {
         } = config;
 
         let core = RtcCore::new(core_config, core_opts)?;
+        let target_dir = target_dir.map(|dir| core.working_directory.join(dir));
+        let keep = keep_patterns(&keep)?;
 
-        Ok(Self { core, cargo, tools })
+        Ok(Self {
+            core,
+            cargo,
+            target_dir,
+            tools,
+            keep,
+        })
     }
 }
 
@@ -62,3 +79,13 @@ impl RtcBuilder for RtcClean {
         Self::new(configuration, options)
     }
 }
+
+/// Parse the patterns of files in the dist dir to keep, relative to the dist dir.
+pub(crate) fn keep_patterns(keep: &[String]) -> anyhow::Result<Vec<Pattern>> {
+    keep.iter()
+        .map(|glob| {
+            Pattern::new(glob.trim_start_matches("./"))
+                .with_context(|| format!("invalid clean.keep pattern: {glob}"))
+        })
+        .collect()
+}
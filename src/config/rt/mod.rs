@@ -18,6 +18,8 @@ use crate::config::Configuration;
 use std::path::PathBuf;
 
 /// Build a runtime configuration from configuration and options.
+// the futures are only awaited directly, not spawned, so they don't need to be `Send`
+#[allow(async_fn_in_trait)]
 pub trait RtcBuilder: Sized {
     type Options: Sized;
 
@@ -2,7 +2,7 @@ use crate::{
     config::{
         models::{Proxy, Serve},
         rt::{RtcBuilder, RtcWatch, WatchOptions},
-        types::{AddressFamily, BaseUrl, WsProtocol},
+        types::{AddressFamily, BaseUrl, PreloadLocation, TlsVersion, Uri, WsProtocol},
         Configuration,
     },
     tls::TlsConfig,
@@ -16,9 +16,13 @@ use std::{
     ops::Deref,
     path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 use tracing::log;
 
+/// The delay before the autoreload client reconnects, unless configured.
+const DEFAULT_WS_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
 /// Runtime config for the serve system.
 #[derive(Clone, Debug)]
 pub struct RtcServe {
@@ -30,26 +34,60 @@ pub struct RtcServe {
     pub port: u16,
     /// The aliases to serve on.
     pub aliases: Vec<String>,
+    /// The public host the server is reachable at, overriding the local addresses for clients.
+    pub public_host: Option<String>,
     /// Disable the DNS lookup during startup
     pub disable_address_lookup: bool,
-    /// Open a browser tab once the initial build is complete.
+    /// Open a browser tab once the first successful build is complete.
     pub open: bool,
     /// Any proxies configured to run along with the server.
     pub proxies: Vec<Proxy>,
     /// Whether to disable fallback to index.html for missing files.
     pub no_spa: bool,
+    /// Whether to fall back to index.html for missing files with a file extension too.
+    pub spa_fallback_extensions: bool,
+    /// The backend to proxy requests without a file in the dist dir to.
+    pub default_backend: Option<Uri>,
+    /// Whether to serve files symlinked to locations outside the dist dir.
+    pub follow_symlinks: bool,
     /// Additional headers to include in responses.
     pub headers: HashMap<String, String>,
     /// Protocol used for autoreload WebSockets connection.
     pub ws_protocol: Option<WsProtocol>,
     /// Path used for autoreload WebSockets connection.
     pub ws_base: Option<String>,
+    /// The delay before the autoreload client reconnects.
+    pub ws_reconnect_delay: Duration,
+    /// The maximum delay between reconnects of the autoreload client.
+    pub ws_reconnect_max_delay: Duration,
     /// The TLS config containing the certificate and private key. TLS is activated if both are set.
     pub tls: Option<TlsConfig>,
     /// A base path to serve the application from
     pub serve_base: Option<String>,
     /// Disable Content-Security-Policy
     pub csp: Option<Vec<String>>,
+    /// Add a `Repr-Digest` header to responses of static assets
+    pub digest_header: bool,
+    /// Where to announce the preloads of the served index.html
+    pub preload_location: PreloadLocation,
+    /// Send a `103 Early Hints` response with the preloads of the served index.html
+    pub early_hints: bool,
+    /// Send the headers required for cross-origin isolation with all responses
+    pub cross_origin_isolation: bool,
+    /// The interval to send synthetic reloads to auto-reload clients, for testing clients
+    pub ws_echo: Option<Duration>,
+    /// Only build once, and serve the result without watching for changes
+    pub build_only_once: bool,
+    /// Accept invalid TLS certificates of the backends of all proxies
+    pub proxy_insecure_all: bool,
+    /// Keep connections open for further requests
+    pub keepalive: bool,
+    /// The time a client has to send the head of a request
+    pub http_timeout: Option<Duration>,
+    /// Accept HTTP/2 on listeners without TLS
+    pub http2: bool,
+    /// The command to run once the server is ready, stopping the server when it exits
+    pub on_ready: Option<String>,
 }
 
 impl Deref for RtcServe {
@@ -64,6 +102,9 @@ impl Deref for RtcServe {
 pub struct ServeOptions {
     pub watch: WatchOptions,
     pub open: bool,
+    pub ws_echo: Option<Duration>,
+    pub build_only_once: bool,
+    pub proxy_insecure_all: bool,
 }
 
 impl RtcServe {
@@ -72,6 +113,9 @@ impl RtcServe {
         let ServeOptions {
             watch: watch_opts,
             open,
+            ws_echo,
+            build_only_once,
+            proxy_insecure_all,
         } = opts;
 
         let watch = Arc::new(RtcWatch::new(config.clone(), watch_opts)?);
@@ -83,6 +127,7 @@ impl RtcServe {
             prefer_address_family,
             port,
             aliases,
+            public_host,
             disable_address_lookup,
             open: _,
             // auto-reload is handle by the builder options
@@ -90,10 +135,16 @@ impl RtcServe {
             headers,
             no_error_reporting: _, // handled via the options, as it's only a configuration option in the case of "serve"
             no_spa,
+            spa_fallback_extensions,
+            default_backend,
+            follow_symlinks,
             ws_protocol,
             ws_base,
+            ws_reconnect_delay,
+            ws_reconnect_max_delay,
             tls_key_path,
             tls_cert_path,
+            tls_min_version,
             serve_base,
             // single proxy config is being transformed into global proxies vec
             proxy_backend: _,
@@ -104,11 +155,31 @@ impl RtcServe {
             proxy_no_redirect: _,
             disable_csp,
             csp,
+            digest_header,
+            preload_location,
+            early_hints,
+            cross_origin_isolation,
+            // applied to the build configuration by the CLI
+            sri: _,
+            keepalive,
+            http_timeout,
+            http2,
+            on_ready,
         } = config.serve;
 
+        if let Some(public_host) = &public_host {
+            check_public_host(public_host)?;
+        }
+
+        let (ws_reconnect_delay, ws_reconnect_max_delay) = ws_reconnect_delays(
+            ws_reconnect_delay.map(|delay| delay.0),
+            ws_reconnect_max_delay.map(|delay| delay.0),
+        )?;
+
         let tls = tls_config(
             absolute_path_if_some(tls_key_path, "tls_key_path")?,
             absolute_path_if_some(tls_cert_path, "tls_cert_path")?,
+            tls_min_version,
         )
         .await?;
 
@@ -117,20 +188,75 @@ impl RtcServe {
             addresses: build_address_list(prefer_address_family, addresses),
             port,
             aliases,
+            public_host,
             disable_address_lookup,
             open,
             proxies: config.proxies.0,
             no_spa,
+            spa_fallback_extensions,
+            default_backend,
+            follow_symlinks,
             headers,
             ws_protocol,
             ws_base,
+            ws_reconnect_delay,
+            ws_reconnect_max_delay,
             tls,
             serve_base,
             csp: (!disable_csp).then_some(csp),
+            digest_header,
+            preload_location,
+            early_hints,
+            cross_origin_isolation,
+            ws_echo,
+            build_only_once,
+            proxy_insecure_all,
+            keepalive,
+            http_timeout: http_timeout.map(|timeout| timeout.0),
+            http2,
+            on_ready,
         })
     }
 
-    fn common_base(&self) -> Result<Cow<str>> {
+    /// Construct a new instance for testing.
+    #[cfg(test)]
+    pub async fn new_test(tmpdir: &std::path::Path) -> Result<Self> {
+        Ok(Self {
+            watch: Arc::new(RtcWatch::new_test(tmpdir).await?),
+            addresses: vec![IpAddr::V4(Ipv4Addr::LOCALHOST)],
+            port: 0,
+            aliases: vec![],
+            public_host: None,
+            disable_address_lookup: true,
+            open: false,
+            proxies: vec![],
+            no_spa: false,
+            spa_fallback_extensions: false,
+            default_backend: None,
+            follow_symlinks: false,
+            headers: Default::default(),
+            ws_protocol: None,
+            ws_base: None,
+            ws_reconnect_delay: DEFAULT_WS_RECONNECT_DELAY,
+            ws_reconnect_max_delay: DEFAULT_WS_RECONNECT_DELAY,
+            tls: None,
+            serve_base: None,
+            csp: None,
+            digest_header: false,
+            preload_location: Default::default(),
+            early_hints: false,
+            cross_origin_isolation: false,
+            ws_echo: None,
+            build_only_once: false,
+            proxy_insecure_all: false,
+            keepalive: true,
+            http_timeout: None,
+            http2: false,
+            on_ready: None,
+        })
+    }
+
+    fn common_base(&self) -> Result<Cow<'_, str>> {
         let base = match &self.watch.build.public_url {
             BaseUrl::Default => "/",
             BaseUrl::Absolute(url) => {
@@ -164,7 +290,7 @@ impl RtcServe {
         Ok(base.into())
     }
 
-    pub(crate) fn ws_base(&self) -> Result<Cow<str>> {
+    pub(crate) fn ws_base(&self) -> Result<Cow<'_, str>> {
         if let Some(ws_path) = &self.ws_base {
             ensure!(ws_path.starts_with('/'), "ws-path must start with a '/'");
             return Ok(ws_path.into());
@@ -173,7 +299,7 @@ impl RtcServe {
         self.common_base()
     }
 
-    pub(crate) fn serve_base(&self) -> Result<Cow<str>> {
+    pub(crate) fn serve_base(&self) -> Result<Cow<'_, str>> {
         if let Some(serve_base) = &self.serve_base {
             ensure!(
                 serve_base.starts_with('/'),
@@ -229,6 +355,7 @@ fn build_address_list(preference: Option<AddressFamily>, addresses: Vec<IpAddr>)
 async fn tls_config(
     tls_key_path: Option<PathBuf>,
     tls_cert_path: Option<PathBuf>,
+    tls_min_version: Option<TlsVersion>,
 ) -> Result<Option<TlsConfig>, anyhow::Error> {
     match (tls_key_path, tls_cert_path) {
         (Some(tls_key_path), Some(tls_cert_path)) => {
@@ -237,7 +364,7 @@ async fn tls_config(
 
             #[cfg(feature = "rustls")]
             return Ok(Some(
-                axum_server::tls_rustls::RustlsConfig::from_pem_file(tls_cert_path, tls_key_path)
+                crate::tls::rustls_config(&tls_cert_path, &tls_key_path, tls_min_version)
                     .await
                     .with_context(|| "loading TLS cert/key failed")?
                     .into(),
@@ -245,7 +372,7 @@ async fn tls_config(
 
             #[cfg(feature = "native-tls")]
             return Ok(Some(
-                axum_server::tls_openssl::OpenSSLConfig::from_pem_file(tls_cert_path, tls_key_path)
+                crate::tls::openssl_config(&tls_cert_path, &tls_key_path, tls_min_version)
                     .with_context(|| "loading TLS cert/key failed")?
                     .into(),
             ));
@@ -254,7 +381,12 @@ async fn tls_config(
         }
         (None, Some(_)) => Err(anyhow!("TLS cert path provided without key path")),
         (Some(_), None) => Err(anyhow!("TLS key path provided without cert path")),
-        (None, None) => Ok(None),
+        (None, None) => {
+            if tls_min_version.is_some() {
+                tracing::warn!("tls_min_version has no effect without a TLS cert and key");
+            }
+            Ok(None)
+        }
     }
 }
 
@@ -289,3 +421,102 @@ fn absolute_path(path: PathBuf, file_description: &str) -> anyhow::Result<PathBu
         )
     })
 }
+
+/// Check that the public host is a host name or IP address, with an optional port.
+///
+/// The value is injected into the auto-reload script as a JavaScript string, so it must not
+/// contain anything else.
+fn check_public_host(public_host: &str) -> Result<()> {
+    let (host, port) = match public_host.rsplit_once(':') {
+        // an IPv6 address without a port
+        Some((_, port)) if public_host.ends_with(']') && port.ends_with(']') => (public_host, None),
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => (host, Some(port)),
+        _ => (public_host, None),
+    };
+
+    let valid_host = match host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+    {
+        Some(ipv6) => ipv6.parse::<std::net::Ipv6Addr>().is_ok(),
+        None => {
+            !host.is_empty()
+                && host
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+        }
+    };
+    ensure!(
+        valid_host && port.map_or(true, |port| port.parse::<u16>().is_ok()),
+        "invalid public_host '{public_host}', expected a host name or IP address, with an optional port"
+    );
+    Ok(())
+}
+
+/// Evaluate the reconnect delay of the auto-reload client and its maximum, applying the defaults.
+fn ws_reconnect_delays(
+    delay: Option<Duration>,
+    max_delay: Option<Duration>,
+) -> Result<(Duration, Duration)> {
+    let delay = delay.unwrap_or(DEFAULT_WS_RECONNECT_DELAY);
+    let max_delay = max_delay.unwrap_or(delay);
+    ensure!(!delay.is_zero(), "ws_reconnect_delay must not be zero");
+    ensure!(
+        max_delay >= delay,
+        "ws_reconnect_max_delay must not be less than ws_reconnect_delay"
+    );
+    Ok((delay, max_delay))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn public_host_is_checked() {
+        for valid in [
+            "example.com",
+            "example.com:8080",
+            "my-tunnel.example.com:443",
+            "127.0.0.1:8080",
+            "[::1]",
+            "[::1]:8080",
+        ] {
+            assert!(check_public_host(valid).is_ok(), "{valid} must be valid");
+        }
+
+        for invalid in [
+            "",
+            "example.com:",
+            "example.com:99999",
+            "example.com'; alert(1); '",
+            "example.com/path",
+            "https://example.com",
+            "::1",
+            "[not-an-ip]:8080",
+        ] {
+            assert!(
+                check_public_host(invalid).is_err(),
+                "{invalid} must be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn ws_reconnect_delays_are_checked() -> Result<()> {
+        let secs = Duration::from_secs;
+        assert_eq!(ws_reconnect_delays(None, None)?, (secs(5), secs(5)));
+        assert_eq!(
+            ws_reconnect_delays(Some(secs(1)), None)?,
+            (secs(1), secs(1))
+        );
+        assert_eq!(
+            ws_reconnect_delays(Some(secs(1)), Some(secs(30)))?,
+            (secs(1), secs(30))
+        );
+        // the default delay exceeds the maximum
+        assert!(ws_reconnect_delays(None, Some(secs(1))).is_err());
+        assert!(ws_reconnect_delays(Some(Duration::ZERO), None).is_err());
+        Ok(())
+    }
+}
@@ -2,7 +2,8 @@ use crate::config::{
     rt::{BuildOptions, RtcBuild, RtcBuilder},
     Configuration, Watch,
 };
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
+use glob::Pattern;
 use std::{ops::Deref, path::PathBuf, sync::Arc, time::Duration};
 
 /// Runtime config for the watch system.
@@ -14,12 +15,16 @@ pub struct RtcWatch {
     pub paths: Vec<PathBuf>,
     /// Paths to ignore.
     pub ignored_paths: Vec<PathBuf>,
+    /// Glob patterns of paths to ignore.
+    pub ignored_globs: Vec<Pattern>,
     /// Polling mode for detecting changes if set to `Some(_)`.
     pub poll: Option<Duration>,
     /// Allow enabling a cooldown
     pub enable_cooldown: bool,
     /// Clear the screen before each run
     pub clear_screen: bool,
+    /// Log the watched and ignored paths
+    pub print_watches: bool,
     /// No error reporting.
     pub no_error_reporting: bool,
 }
@@ -41,6 +46,8 @@ pub struct WatchOptions {
     pub enable_cooldown: bool,
     /// Clear the screen before each run
     pub clear_screen: bool,
+    /// Log the watched and ignored paths
+    pub print_watches: bool,
     /// No error reporting.
     pub no_error_reporting: bool,
 }
@@ -53,10 +60,16 @@ impl RtcWatch {
             poll,
             enable_cooldown,
             clear_screen,
+            print_watches,
             no_error_reporting,
         } = opts;
 
-        let Watch { watch, ignore } = config.watch.clone();
+        let Watch {
+            watch,
+            ignore,
+            ignore_globs,
+            extra_paths,
+        } = config.watch.clone();
 
         let build = RtcBuild::new(config, build_opts)?;
 
@@ -80,6 +93,20 @@ impl RtcWatch {
             paths.push(build.target_parent.clone());
         }
 
+        // Additional paths are watched in any case.
+        for path in extra_paths {
+            let path = build.working_directory.join(path);
+            let canon_path = path.canonicalize().map_err(|_| {
+                anyhow!(
+                    "error taking the canonical path to the extra watch path: {:?}",
+                    path
+                )
+            })?;
+            if !paths.contains(&canon_path) {
+                paths.push(canon_path);
+            }
+        }
+
         // Take the canonical path of each of the specified ignore targets.
         let mut ignored_paths = ignore
             .into_iter()
@@ -96,17 +123,59 @@ impl RtcWatch {
 
         // Ensure the final dist dir is always ignored.
         ignored_paths.push(build.final_dist.clone());
+        // Cargo's target dir is ignored once known, but Trunk's own target dir may be elsewhere.
+        if let Some(target_dir) = &build.target_dir {
+            ignored_paths.push(
+                target_dir
+                    .canonicalize()
+                    .unwrap_or_else(|_| target_dir.clone()),
+            );
+        }
+
+        // Glob patterns are relative to the working directory, and get matched against the
+        // canonical path of an event.
+        let root = build
+            .working_directory
+            .canonicalize()
+            .unwrap_or_else(|_| build.working_directory.clone());
+        let root = Pattern::escape(&root.to_string_lossy());
+        let ignored_globs = ignore_globs
+            .iter()
+            .map(|glob| {
+                Pattern::new(&format!("{root}/{}", glob.trim_start_matches("./")))
+                    .with_context(|| format!("invalid watch ignore pattern: {glob}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(Self {
             build: Arc::new(build),
             paths,
             ignored_paths,
+            ignored_globs,
             poll,
             enable_cooldown,
             clear_screen,
+            print_watches,
             no_error_reporting,
         })
     }
+
+    /// Construct a new instance for testing.
+    #[cfg(test)]
+    pub async fn new_test(tmpdir: &std::path::Path) -> anyhow::Result<Self> {
+        let build = RtcBuild::new_test(tmpdir).await?;
+        Ok(Self {
+            paths: vec![build.target_parent.clone()],
+            ignored_paths: vec![build.final_dist.clone()],
+            build: Arc::new(build),
+            ignored_globs: vec![],
+            poll: None,
+            enable_cooldown: false,
+            clear_screen: false,
+            print_watches: false,
+            no_error_reporting: false,
+        })
+    }
 }
 
 impl RtcBuilder for RtcWatch {
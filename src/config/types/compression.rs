@@ -0,0 +1,25 @@
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A format for precompressing the output files of a build
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize, ValueEnum, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionFormat {
+    /// Using brotli, written to `<file>.br`
+    Brotli,
+    /// Using gzip, written to `<file>.gz`
+    Gzip,
+}
+
+impl CompressionFormat {
+    /// The file extension appended to the name of the compressed file
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Brotli => "br",
+            Self::Gzip => "gz",
+        }
+    }
+}
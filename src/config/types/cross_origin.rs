@@ -1,4 +1,8 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
 
 /// Cross origin setting
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
@@ -8,8 +12,10 @@ pub enum CrossOrigin {
     UseCredentials,
 }
 
-impl CrossOrigin {
-    pub fn from_str(s: &str) -> anyhow::Result<Self, CrossOriginParseError> {
+impl FromStr for CrossOrigin {
+    type Err = CrossOriginParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
             "" | "anonymous" => CrossOrigin::Anonymous,
             "use-credentials" => CrossOrigin::UseCredentials,
@@ -18,6 +24,13 @@ impl CrossOrigin {
     }
 }
 
+impl CrossOrigin {
+    /// Insert as a `crossorigin` attribute into a set of attributes.
+    pub fn insert_into(&self, attrs: &mut HashMap<String, String>) {
+        attrs.insert("crossorigin".to_string(), self.to_string());
+    }
+}
+
 impl Display for CrossOrigin {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
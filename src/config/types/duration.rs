@@ -3,7 +3,7 @@ use std::str::FromStr;
 use std::time::Duration;
 
 /// A newtype to allow using humantime durations as clap and serde values.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ConfigDuration(pub Duration);
 
 impl<'de> Deserialize<'de> for ConfigDuration {
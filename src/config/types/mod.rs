@@ -2,16 +2,22 @@
 
 mod address_family;
 mod base_url;
+mod compression;
 mod cross_origin;
 mod duration;
 mod minify;
+mod preload;
+mod tls;
 mod uri;
 mod ws;
 
 pub use address_family::*;
 pub use base_url::*;
+pub use compression::*;
 pub use cross_origin::*;
 pub use duration::*;
 pub use minify::*;
+pub use preload::*;
+pub use tls::*;
 pub use uri::*;
 pub use ws::*;
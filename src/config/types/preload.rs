@@ -0,0 +1,16 @@
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// Where to announce the preloads of the application
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, ValueEnum, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PreloadLocation {
+    /// Using `<link>` elements in the head of the document
+    #[default]
+    Head,
+    /// Using `Link` headers on the response of the document
+    Header,
+    /// Using both, `<link>` elements and `Link` headers
+    Both,
+}
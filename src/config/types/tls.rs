@@ -0,0 +1,30 @@
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+
+/// A version of the TLS protocol
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, ValueEnum, JsonSchema,
+)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    #[value(name = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    #[value(name = "1.3")]
+    Tls13,
+}
+
+impl Display for TlsVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                TlsVersion::Tls12 => "1.2",
+                TlsVersion::Tls13 => "1.3",
+            }
+        )
+    }
+}
@@ -0,0 +1,55 @@
+//! Build, bundle & ship your Rust WASM application to the web.
+//!
+//! Besides the `trunk` command line tool, the build can be run from other tools using
+//! [`build::build`], with a runtime configuration created from a configuration file:
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//! use trunk::config::rt::{BuildOptions, RtcBuild, RtcBuilder};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let (config, working_directory) = trunk::config::load(Some("Trunk.toml".into())).await?;
+//! let cfg = RtcBuild::from_config(config, working_directory, |_, core| BuildOptions {
+//!     core,
+//!     inject_autoloader: false,
+//! })
+//! .await?;
+//!
+//! let output = trunk::build::build(Arc::new(cfg)).await?;
+//! for artifact in output.artifacts {
+//!     println!("{}", artifact.file);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only [`build`] and [`config`] are meant to be used by other crates, the other modules are
+//! internals of the command line tool.
+
+#![deny(clippy::expect_used)]
+#![deny(clippy::unwrap_used)]
+
+pub mod build;
+pub mod config;
+
+#[doc(hidden)]
+pub mod cli;
+mod cmd;
+#[doc(hidden)]
+pub mod common;
+mod hooks;
+mod pipelines;
+mod processing;
+#[doc(hidden)]
+pub mod progress;
+mod proxy;
+#[doc(hidden)]
+pub mod serve;
+mod tls;
+mod tools;
+mod version;
+mod watch;
+mod ws;
+
+pub use build::BuildOutput;
+pub use pipelines::{AssetKind, OutputRecord};
@@ -1,28 +1,14 @@
 #![deny(clippy::expect_used)]
 #![deny(clippy::unwrap_used)]
 
-mod build;
-mod cmd;
-mod common;
-mod config;
-mod hooks;
-mod pipelines;
-mod processing;
-mod proxy;
-mod serve;
-mod tls;
-mod tools;
-mod version;
-mod watch;
-mod ws;
-
 use anyhow::{Context, Result};
-use clap::{ArgAction, Parser, Subcommand, ValueEnum};
-use common::STARTING;
+use clap::Parser;
 use std::io::IsTerminal;
-use std::path::PathBuf;
 use std::process::ExitCode;
 use tracing_subscriber::prelude::*;
+use trunk::cli::{ColorMode, Trunk};
+use trunk::common::STARTING;
+use trunk::{common, progress, serve};
 
 #[tokio::main]
 async fn main() -> Result<ExitCode> {
@@ -30,12 +16,15 @@ async fn main() -> Result<ExitCode> {
 
     let colored = init_color(&cli);
 
+    progress::init(!cli.quiet && !cli.prefer_silence());
+
     tracing_subscriber::registry()
         // Filter spans based on the RUST_LOG env var.
         .with(eval_logging(&cli))
         // Send a copy of all spans to stdout as JSON.
         .with(
             tracing_subscriber::fmt::layer()
+                .with_writer(progress::LogWriter)
                 .with_ansi(colored)
                 .with_target(false)
                 .with_level(true)
@@ -58,7 +47,9 @@ async fn main() -> Result<ExitCode> {
             for (n, cause) in err.chain().enumerate().skip(1) {
                 tracing::info!("  {n}: {cause}");
             }
-            ExitCode::FAILURE
+            // exit with the code of the failed on-ready command of `trunk serve`
+            err.downcast_ref::<serve::OnReadyFailed>()
+                .map_or(ExitCode::FAILURE, |failed| ExitCode::from(failed.code))
         }
         Ok(()) => ExitCode::SUCCESS,
     })
@@ -66,9 +57,16 @@ async fn main() -> Result<ExitCode> {
 
 fn init_color(cli: &Trunk) -> bool {
     if cli.no_color {
+        common::set_color_mode("never");
         return false;
     }
 
+    common::set_color_mode(match cli.color {
+        ColorMode::Auto => "auto",
+        ColorMode::Always => "always",
+        ColorMode::Never => "never",
+    });
+
     let colored = match cli.color {
         ColorMode::Always => true,
         ColorMode::Never => false,
@@ -108,106 +106,3 @@ fn eval_logging(cli: &Trunk) -> tracing_subscriber::EnvFilter {
 
     tracing_subscriber::EnvFilter::new(directives)
 }
-
-/// Build, bundle & ship your Rust WASM application to the web.
-#[derive(Parser)]
-#[command(about, author, version)]
-struct Trunk {
-    #[command(subcommand)]
-    action: TrunkSubcommands,
-    /// Path to the Trunk config file
-    #[arg(long, env = "TRUNK_CONFIG", global(true))]
-    pub config: Option<PathBuf>,
-    /// Enable verbose logging.
-    #[arg(short, long, global(true), action=ArgAction::Count)]
-    pub verbose: u8,
-    /// Be more quiet, conflicts with --verbose
-    #[arg(short, long, global(true), conflicts_with("verbose"))]
-    pub quiet: bool,
-    /// Provide a RUST_LOG filter, conflicts with --verbose and --quiet
-    #[arg(long, global(true), conflicts_with_all(["verbose", "quiet"]), env("RUST_LOG"))]
-    pub log: Option<String>,
-
-    /// Skip the version check
-    #[arg(long, global(true), env = "TRUNK_SKIP_VERSION_CHECK")]
-    pub skip_version_check: bool,
-
-    /// Run without accessing the network
-    #[arg(long, global(true), env = "TRUNK_OFFLINE")]
-    #[arg(default_missing_value = "true", num_args=0..=1)]
-    pub offline: Option<bool>,
-
-    /// Color mode
-    #[arg(long, env = "TRUNK_COLOR", global(true), value_enum, conflicts_with = "no_color", default_value_t = ColorMode::Auto)]
-    pub color: ColorMode,
-
-    /// Support for `NO_COLOR` environment variable
-    #[arg(long, env = "NO_COLOR", global(true))]
-    pub no_color: bool,
-}
-
-impl Trunk {
-    pub fn prefer_silence(&self) -> bool {
-        #[allow(clippy::match_like_matches_macro)]
-        match self.action {
-            TrunkSubcommands::Config(_) => true,
-            TrunkSubcommands::Tools(_) => true,
-            _ => false,
-        }
-    }
-}
-
-#[derive(Clone, Debug, Default, ValueEnum)]
-#[value(rename_all = "lower")]
-enum ColorMode {
-    /// Enable color when running on a TTY
-    #[default]
-    Auto,
-    /// Always enable color
-    Always,
-    /// Never enable color
-    Never,
-}
-
-impl Trunk {
-    #[tracing::instrument(level = "trace", skip(self))]
-    pub async fn run(self) -> Result<()> {
-        version::update_check(self.skip_version_check | self.offline.unwrap_or_default());
-
-        match self.action {
-            TrunkSubcommands::Build(inner) => inner.run(self.config).await,
-            TrunkSubcommands::Clean(inner) => inner.run(self.config).await,
-            TrunkSubcommands::Serve(inner) => inner.run(self.config).await,
-            TrunkSubcommands::Watch(inner) => inner.run(self.config).await,
-            TrunkSubcommands::Config(inner) => inner.run(self.config).await,
-            TrunkSubcommands::Tools(inner) => inner.run(self.config).await,
-        }
-    }
-}
-
-#[derive(Subcommand)]
-enum TrunkSubcommands {
-    /// Build the Rust WASM app and all of its assets.
-    Build(cmd::build::Build),
-    /// Build & watch the Rust WASM app and all of its assets.
-    Watch(cmd::watch::Watch),
-    /// Build, watch & serve the Rust WASM app and all of its assets.
-    Serve(cmd::serve::Serve),
-    /// Clean output artifacts.
-    Clean(cmd::clean::Clean),
-    /// Trunk config controls.
-    Config(cmd::config::Config),
-    /// Working with tools
-    Tools(cmd::tools::Tools),
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::Trunk;
-
-    #[test]
-    fn verify_cli() {
-        use clap::CommandFactory;
-        Trunk::command().debug_assert();
-    }
-}
@@ -0,0 +1,265 @@
+//! Cache of the asset outputs of a previous build, used when skipping the asset pipelines.
+
+use crate::{common::path_exists, config::rt::RtcBuild, pipelines::OutputRecord};
+use anyhow::{Context, Result};
+use seahash::SeaHasher;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+use tokio::fs;
+
+/// The outputs of the non-Rust asset pipelines of a build.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AssetCache {
+    /// A hash of the inputs the outputs were created from.
+    source: u64,
+    /// The HTML document, with the outputs of all non-Rust assets finalized.
+    pub html: String,
+    /// The files written by the asset pipelines, relative to the dist dir.
+    pub files: Vec<PathBuf>,
+    /// The records of the files written by the asset pipelines, for the build manifest.
+    pub records: Vec<OutputRecord>,
+}
+
+impl AssetCache {
+    /// Create a new, empty instance for the source HTML and the source files of the non-Rust
+    /// assets of a build.
+    pub async fn new<'a>(
+        cfg: &RtcBuild,
+        source_html: &[u8],
+        sources: impl IntoIterator<Item = &'a Path>,
+    ) -> Result<Self> {
+        let mut hasher = SeaHasher::new();
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        source_html.hash(&mut hasher);
+        hash_config(cfg, &mut hasher);
+        for source in sources {
+            hash_source(source, &mut hasher).await?;
+        }
+
+        Ok(Self {
+            source: hasher.finish(),
+            ..Default::default()
+        })
+    }
+
+    /// The location of the cache file, which is specific to the dist dir.
+    ///
+    /// It is located in the target dir of Trunk, or the `target` dir of the project.
+    fn path(cfg: &RtcBuild) -> PathBuf {
+        let mut hasher = SeaHasher::new();
+        cfg.final_dist.hash(&mut hasher);
+        cfg.target_dir
+            .clone()
+            .unwrap_or_else(|| cfg.working_directory.join("target"))
+            .join("trunk")
+            .join("assets")
+            .join(format!("{:016x}.json", hasher.finish()))
+    }
+
+    /// Load the outputs of the previous build, if they are still valid for this build.
+    pub async fn load(&self, cfg: &RtcBuild) -> Result<Option<Self>> {
+        let path = Self::path(cfg);
+        if !path_exists(&path).await? {
+            return Ok(None);
+        }
+
+        let data = fs::read(&path)
+            .await
+            .with_context(|| format!("error reading {}", path.display()))?;
+        let previous: Self = serde_json::from_slice(&data)
+            .with_context(|| format!("error parsing {}", path.display()))?;
+
+        if previous.source != self.source {
+            return Ok(None);
+        }
+        for file in &previous.files {
+            if !path_exists(cfg.final_dist.join(file)).await? {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(previous))
+    }
+
+    /// Record all files of the staging dir, except the excluded ones, and store the cache.
+    pub async fn store(mut self, cfg: &RtcBuild, exclude: &HashSet<PathBuf>) -> Result<()> {
+        let mut dirs = vec![cfg.staging_dist.clone()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = fs::read_dir(&dir)
+                .await
+                .with_context(|| format!("error reading {}", dir.display()))?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    dirs.push(path);
+                } else if !exclude.contains(&path) {
+                    self.files
+                        .push(path.strip_prefix(&cfg.staging_dist)?.to_path_buf());
+                }
+            }
+        }
+
+        let path = Self::path(cfg);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("error creating {}", parent.display()))?;
+        }
+        fs::write(&path, serde_json::to_vec(&self)?)
+            .await
+            .with_context(|| format!("error writing {}", path.display()))?;
+
+        Ok(())
+    }
+}
+
+/// Hash the configuration the outputs of the non-Rust assets depend on.
+fn hash_config(cfg: &RtcBuild, hasher: &mut SeaHasher) {
+    format!(
+        "{:?}",
+        (
+            &cfg.target,
+            &cfg.html_output_filename,
+            cfg.public_url.to_string(),
+            cfg.release,
+            cfg.filehash,
+            cfg.should_minify(),
+            cfg.source_maps,
+            cfg.no_sri,
+            &cfg.create_nonce,
+            cfg.reproducible,
+            &cfg.sass_load_paths,
+            &cfg.layout,
+        )
+    )
+    .hash(hasher);
+
+    let tools = &cfg.tools;
+    format!(
+        "{:?}",
+        (
+            &tools.sass,
+            &tools.sass_path,
+            &tools.tailwindcss,
+            &tools.tailwindcss_path,
+            &tools.tailwindcss_extra_path,
+        )
+    )
+    .hash(hasher);
+}
+
+/// Hash the content of a source file, or of all files of a source directory.
+async fn hash_source(source: &Path, hasher: &mut SeaHasher) -> Result<()> {
+    let mut paths = vec![source.to_path_buf()];
+    while let Some(path) = paths.pop() {
+        path.hash(hasher);
+        if !fs::metadata(&path)
+            .await
+            .with_context(|| format!("error reading metadata of {}", path.display()))?
+            .is_dir()
+        {
+            fs::read(&path)
+                .await
+                .with_context(|| format!("error reading {}", path.display()))?
+                .hash(hasher);
+            continue;
+        }
+
+        let mut entries = fs::read_dir(&path)
+            .await
+            .with_context(|| format!("error reading {}", path.display()))?;
+        let mut children = vec![];
+        while let Some(entry) = entries.next_entry().await? {
+            children.push(entry.path());
+        }
+        // visit the entries in a stable order, popping the first one next
+        children.sort_unstable_by(|a, b| b.cmp(a));
+        paths.extend(children);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn setup(dir: &Path) -> Result<RtcBuild> {
+        let cfg = RtcBuild::new_test(dir).await?;
+        fs::write(dir.join("style.css"), "body { color: red; }").await?;
+        fs::write(cfg.staging_dist.join("style-1234.css"), "").await?;
+        Ok(cfg)
+    }
+
+    async fn store(cfg: &RtcBuild, sources: &[&Path]) -> Result<()> {
+        let mut cache = AssetCache::new(cfg, b"<html></html>", sources.iter().copied()).await?;
+        cache.html = "<html><link></html>".into();
+        cache.store(cfg, &HashSet::new()).await?;
+        fs::rename(
+            cfg.staging_dist.join("style-1234.css"),
+            cfg.final_dist.join("style-1234.css"),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn load(cfg: &RtcBuild, sources: &[&Path]) -> Result<Option<AssetCache>> {
+        AssetCache::new(cfg, b"<html></html>", sources.iter().copied())
+            .await?
+            .load(cfg)
+            .await
+    }
+
+    #[tokio::test]
+    async fn cache_hit() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cfg = setup(dir.path()).await?;
+        let css = dir.path().join("style.css");
+        store(&cfg, &[&css]).await?;
+
+        let previous = load(&cfg, &[&css]).await?.context("expected a cache hit")?;
+        assert_eq!(previous.html, "<html><link></html>");
+        assert_eq!(previous.files, [PathBuf::from("style-1234.css")]);
+        assert!(AssetCache::path(&cfg).starts_with(dir.path().join("target")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cache_miss_after_source_change() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cfg = setup(dir.path()).await?;
+        let css = dir.path().join("style.css");
+        store(&cfg, &[&css]).await?;
+
+        fs::write(&css, "body { color: blue; }").await?;
+        assert!(load(&cfg, &[&css]).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cache_miss_after_config_change() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut cfg = setup(dir.path()).await?;
+        let css = dir.path().join("style.css");
+        store(&cfg, &[&css]).await?;
+
+        cfg.filehash = false;
+        assert!(load(&cfg, &[&css]).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cache_miss_after_output_removal() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let cfg = setup(dir.path()).await?;
+        let css = dir.path().join("style.css");
+        store(&cfg, &[&css]).await?;
+
+        fs::remove_file(cfg.final_dist.join("style-1234.css")).await?;
+        assert!(load(&cfg, &[&css]).await?.is_none());
+        Ok(())
+    }
+}
@@ -2,11 +2,15 @@
 
 use super::{data_target_path, Attrs, TrunkAssetPipelineOutput, ATTR_HREF};
 use crate::{
-    common::{copy_dir_recursive, html_rewrite::Document, target_path},
+    common::{
+        copy_dir_recursive_filtered, dist_relative, html_rewrite::Document, path_to_href,
+        target_path,
+    },
     config::rt::RtcBuild,
 };
 use anyhow::{Context, Result};
-use std::path::PathBuf;
+use glob::{MatchOptions, Pattern};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
 use tokio::task::JoinHandle;
@@ -18,9 +22,11 @@ pub struct CopyDir {
     /// Runtime build config.
     cfg: Arc<RtcBuild>,
     /// The path to the dir being copied.
-    path: PathBuf,
+    pub(super) path: PathBuf,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// The patterns selecting the copied files.
+    filter: CopyFilter,
 }
 
 impl CopyDir {
@@ -41,13 +47,15 @@ impl CopyDir {
         if !path.is_absolute() {
             path = html_dir.join(path);
         }
-        let target_path = data_target_path(&attrs)?;
+        let target_path = data_target_path(&attrs, &None)?;
+        let filter = CopyFilter::new(&attrs)?;
 
         Ok(Self {
             id,
             cfg,
             path,
             target_path,
+            filter,
         })
     }
 
@@ -76,18 +84,99 @@ impl CopyDir {
             Some(dir_name),
         )
         .await?;
-        copy_dir_recursive(canonical_path, dir_out).await?;
+        let filter = self.filter;
+        let copied = copy_dir_recursive_filtered(
+            canonical_path.clone(),
+            dir_out.clone(),
+            move |path, is_dir| filter.copies(path, is_dir),
+        )
+        .await?;
+
+        let mut files = Vec::with_capacity(copied.len());
+        for file in copied {
+            let source = match file.strip_prefix(&dir_out) {
+                Ok(rel) => canonical_path.join(rel),
+                Err(_) => canonical_path.clone(),
+            };
+            files.push((dist_relative(&self.cfg.staging_dist, &file)?, source));
+        }
+        files.sort();
 
         tracing::debug!(path = ?rel_path, "finished copying directory");
-        Ok(TrunkAssetPipelineOutput::CopyDir(CopyDirOutput(self.id)))
+        Ok(TrunkAssetPipelineOutput::CopyDir(CopyDirOutput {
+            id: self.id,
+            files,
+        }))
+    }
+}
+
+/// The `data-include` and `data-exclude` glob patterns of a copied directory.
+///
+/// Patterns are matched against the paths relative to the copied directory. Like in a
+/// `.gitignore` file, patterns without a `/` are matched against the name of a file at any depth.
+#[derive(Debug, Default)]
+struct CopyFilter {
+    /// The files to copy, all files if empty.
+    include: Vec<Pattern>,
+    /// The files and directories to skip.
+    exclude: Vec<Pattern>,
+}
+
+impl CopyFilter {
+    fn new(attrs: &Attrs) -> Result<Self> {
+        let patterns = |attr: &str| {
+            attrs
+                .get(attr)
+                .iter()
+                .flat_map(|val| val.split_whitespace())
+                .map(|glob| {
+                    Pattern::new(glob).with_context(|| format!("invalid {attr} pattern: {glob}"))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+
+        Ok(Self {
+            include: patterns("data-include")?,
+            exclude: patterns("data-exclude")?,
+        })
+    }
+
+    /// Whether a file or directory, relative to the copied directory, gets copied.
+    ///
+    /// Directories are only checked against the excluded patterns, so that the files inside can
+    /// still be included.
+    fn copies(&self, path: &Path, is_dir: bool) -> bool {
+        let path = path_to_href(path);
+        if self.exclude.iter().any(|glob| matches(glob, &path)) {
+            return false;
+        }
+        is_dir || self.include.is_empty() || self.include.iter().any(|glob| matches(glob, &path))
+    }
+}
+
+fn matches(glob: &Pattern, path: &str) -> bool {
+    match glob.as_str().contains('/') {
+        true => glob.matches_with(
+            path,
+            MatchOptions {
+                require_literal_separator: true,
+                ..Default::default()
+            },
+        ),
+        false => glob.matches(path.rsplit('/').next().unwrap_or(path)),
     }
 }
 
 /// The output of a CopyDir build pipeline.
-pub struct CopyDirOutput(usize);
+pub struct CopyDirOutput {
+    /// The ID of this pipeline.
+    pub(super) id: usize,
+    /// The copied files, relative to the dist dir, along with their source files.
+    pub(super) files: Vec<(String, PathBuf)>,
+}
 
 impl CopyDirOutput {
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
-        dom.remove(&super::trunk_id_selector(self.0))
+        dom.remove(&super::trunk_id_selector(self.id))
     }
 }
@@ -6,7 +6,7 @@ use anyhow::{Context, Result};
 
 use crate::config::rt::RtcBuild;
 use crate::pipelines::copy_dir::*;
-use crate::pipelines::ATTR_HREF;
+use crate::pipelines::{ATTR_HREF, ATTR_TARGET_PATH};
 
 /// A fixture for setting up basic test config.
 async fn setup_test_config() -> Result<(tempfile::TempDir, Arc<RtcBuild>, PathBuf)> {
@@ -140,3 +140,83 @@ async fn ok_run_target_path_copy() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn err_new_file_name_pattern() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "test_dir".into());
+    attrs.insert(ATTR_TARGET_PATH.into(), "assets/[name]".into());
+
+    // Action.
+    let res = CopyDir::new(cfg, Arc::new(tmpdir.into_path()), attrs, 0).await;
+
+    // Assert.
+    anyhow::ensure!(
+        res.is_err(),
+        "unexpected success while constructing CopyDir pipeline, expected error on a file name \
+         pattern"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_run_include_exclude() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, asset_dir) = setup_test_config().await?;
+    for file in [
+        "img/logo.png",
+        "img/logo.psd",
+        "img/nested/icon.png",
+        "maps/app.js.map",
+    ] {
+        let path = asset_dir.join(file);
+        tokio::fs::create_dir_all(path.parent().context("missing parent dir")?).await?;
+        tokio::fs::write(&path, b"abc123").await?;
+    }
+
+    let copied = |include: &'static str, exclude: &'static str| {
+        let cfg = cfg.clone();
+        let html_dir = Arc::new(tmpdir.path().to_path_buf());
+        async move {
+            let dir = cfg.staging_dist.join("test_dir");
+            if dir.exists() {
+                tokio::fs::remove_dir_all(&dir).await?;
+            }
+            let mut attrs = HashMap::new();
+            attrs.insert(ATTR_HREF.into(), "test_dir".into());
+            attrs.insert("data-include".into(), include.into());
+            attrs.insert("data-exclude".into(), exclude.into());
+            CopyDir::new(cfg, html_dir, attrs, 0)
+                .await?
+                .spawn()
+                .await??;
+
+            let mut files = vec![];
+            for file in [
+                "test_file",
+                "img/logo.png",
+                "img/logo.psd",
+                "img/nested/icon.png",
+                "maps",
+            ] {
+                if dir.join(file).exists() {
+                    files.push(file);
+                }
+            }
+            Ok::<_, anyhow::Error>(files)
+        }
+    };
+
+    // Action & Assert.
+    anyhow::ensure!(
+        copied("", "*.psd maps").await? == ["test_file", "img/logo.png", "img/nested/icon.png"]
+    );
+    anyhow::ensure!(copied("*.png", "").await? == ["img/logo.png", "img/nested/icon.png"]);
+    anyhow::ensure!(copied("img/*.png", "").await? == ["img/logo.png"]);
+    anyhow::ensure!(copied("img/**/*.png", "img/nested").await? == ["img/logo.png"]);
+
+    Ok(())
+}
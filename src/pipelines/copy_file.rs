@@ -4,10 +4,14 @@ use crate::{
     common::{html_rewrite::Document, target_path},
     config::rt::RtcBuild,
     pipelines::{
-        data_target_path, AssetFile, AssetFileType, Attrs, TrunkAssetPipelineOutput, ATTR_HREF,
+        data_target_file, is_root_file,
+        preload::{Preload, PreloadLink},
+        AssetFile, AssetFileType, Attrs, FileNamePattern, TrunkAssetPipelineOutput, ATTR_HREF,
+        ATTR_INLINE_THRESHOLD,
     },
+    processing::minify::MinifyLevel,
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::task::JoinHandle;
@@ -19,9 +23,13 @@ pub struct CopyFile {
     /// Runtime build config.
     cfg: Arc<RtcBuild>,
     /// The asset file being processed.
-    asset: AssetFile,
+    pub(super) asset: AssetFile,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// The pattern for the name of the copied file.
+    file_name: FileNamePattern,
+    /// The preload link of the file, if requested.
+    preload: Option<Preload>,
 }
 
 impl CopyFile {
@@ -41,13 +49,35 @@ impl CopyFile {
         path.extend(href_attr.split('/'));
         let asset = AssetFile::new(&html_dir, path).await?;
 
-        let target_path = data_target_path(&attrs)?;
+        // the link of a copied file is removed, so there is no reference a data URI could replace
+        if attrs.contains_key(ATTR_INLINE_THRESHOLD) {
+            bail!(
+                r#"`{ATTR_INLINE_THRESHOLD}` is not supported on <link data-trunk rel="copy-file" .../> elements, as the copied file isn't referenced by the HTML output. Use it on <link data-trunk rel="icon" .../> elements instead"#
+            );
+        }
+
+        let is_image = mime_guess::from_path(&asset.path)
+            .first()
+            .is_some_and(|mime| mime.type_() == mime_guess::mime::IMAGE);
+        // copied files keep their name, unless a file name pattern is used
+        let (target_path, file_name) = data_target_file(
+            &attrs,
+            match is_image && !is_root_file(&asset.path) {
+                true => &cfg.layout.img,
+                false => &None,
+            },
+            false,
+        )?;
+
+        let preload = Preload::from_attrs(&attrs, &asset.path, &cfg)?;
 
         Ok(Self {
             id,
             cfg,
             asset,
             target_path,
+            file_name,
+            preload,
         })
     }
 
@@ -66,27 +96,50 @@ impl CopyFile {
         let dir_out =
             target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
 
-        let _ = self
+        let file = self
             .asset
             .copy(
                 &self.cfg.staging_dist,
                 &dir_out,
-                false,
-                false,
+                &self.file_name,
+                MinifyLevel::None,
                 AssetFileType::Other,
+                false,
             )
             .await?;
+        let preload = match &self.preload {
+            Some(preload) => Some(preload.link(&self.cfg, &file).await?),
+            None => None,
+        };
         tracing::debug!(path = ?rel_path, "finished copying file");
 
-        Ok(TrunkAssetPipelineOutput::CopyFile(CopyFileOutput(self.id)))
+        Ok(TrunkAssetPipelineOutput::CopyFile(CopyFileOutput {
+            id: self.id,
+            source: self.asset.path.clone(),
+            file,
+            preload,
+        }))
     }
 }
 
 /// The output of a CopyFile build pipeline.
-pub struct CopyFileOutput(usize);
+pub struct CopyFileOutput {
+    /// The ID of this pipeline.
+    pub(super) id: usize,
+    /// The source file of the asset.
+    pub(super) source: PathBuf,
+    /// Name of the copied file, relative to the dist dir.
+    pub(super) file: String,
+    /// The preload link of the copied file, if requested.
+    pub(super) preload: Option<PreloadLink>,
+}
 
 impl CopyFileOutput {
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
-        dom.remove(&super::trunk_id_selector(self.0))
+        let selector = super::trunk_id_selector(self.id);
+        match self.preload {
+            Some(preload) => dom.replace_with_html(&selector, &preload.to_string()),
+            None => dom.remove(&selector),
+        }
     }
 }
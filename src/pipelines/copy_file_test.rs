@@ -4,9 +4,13 @@ use std::sync::Arc;
 
 use anyhow::{Context, Result};
 
+use crate::common::html_rewrite::Document;
 use crate::config::rt::RtcBuild;
 use crate::pipelines::copy_file::*;
-use crate::pipelines::ATTR_HREF;
+use crate::pipelines::{
+    FileNamePattern, TrunkAssetPipelineOutput, ATTR_HREF, ATTR_INLINE_THRESHOLD, ATTR_PRELOAD,
+    ATTR_TARGET_PATH,
+};
 
 /// A fixture for setting up basic test config.
 async fn setup_test_config() -> Result<(tempfile::TempDir, Arc<RtcBuild>, PathBuf)> {
@@ -90,3 +94,186 @@ async fn ok_run_basic_copy() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn err_new_preload_unknown_type() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+    tokio::fs::write(tmpdir.path().join("data.bin"), b"abc123")
+        .await
+        .context("error writing test file contents")?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "data.bin".into());
+    attrs.insert(ATTR_PRELOAD.into(), "".into());
+
+    // Action.
+    let res = CopyFile::new(cfg, Arc::new(tmpdir.into_path()), attrs, 0).await;
+
+    // Assert.
+    anyhow::ensure!(
+        res.is_err(),
+        "unexpected success while constructing CopyFile pipeline, expected error on preloading a \
+         file without `data-preload-as` and an unknown type"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_run_preload_font() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+    tokio::fs::write(tmpdir.path().join("font.woff2"), b"abc123")
+        .await
+        .context("error writing test file contents")?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "font.woff2".into());
+    attrs.insert(ATTR_PRELOAD.into(), "".into());
+    let cmd = CopyFile::new(cfg, Arc::new(tmpdir.into_path()), attrs, 0)
+        .await
+        .context("error constructing CopyFile pipeline")?;
+
+    // Action.
+    let out = cmd
+        .spawn()
+        .await
+        .context("unexpected task join error from pipeline")?
+        .context("unexpected pipeline error")?;
+    let mut dom = Document::new(
+        r#"<html><head><link data-trunk-id="0"></head><body></body></html>"#,
+        Default::default(),
+    )?;
+    let TrunkAssetPipelineOutput::CopyFile(out) = out else {
+        anyhow::bail!("unexpected pipeline output");
+    };
+    out.finalize(&mut dom).await?;
+
+    // Assert.
+    let html = String::from_utf8(dom.into_inner()).context("output is not valid UTF-8")?;
+    anyhow::ensure!(
+        html.contains(
+            r#"<head><link rel="preload" href="/font.woff2" as="font" type="font/woff2" crossorigin></head>"#
+        ),
+        "unexpected output: {html}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_new_reserved_target_path() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "test_file".into());
+    attrs.insert(ATTR_TARGET_PATH.into(), ".well-known/trunk".into());
+
+    // Action.
+    let res = CopyFile::new(cfg, Arc::new(tmpdir.into_path()), attrs, 0).await;
+
+    // Assert.
+    anyhow::ensure!(
+        res.is_err(),
+        "unexpected success while constructing CopyFile pipeline, expected error on a reserved \
+         target path"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn err_new_inline_threshold() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "test_file".into());
+    attrs.insert(ATTR_INLINE_THRESHOLD.into(), "4096".into());
+
+    // Action.
+    let res = CopyFile::new(cfg, Arc::new(tmpdir.into_path()), attrs, 0).await;
+
+    // Assert.
+    anyhow::ensure!(
+        res.is_err(),
+        "unexpected success while constructing CopyFile pipeline, expected error on \
+         `data-inline-threshold`"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ok_run_file_name_pattern() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+    tokio::fs::write(tmpdir.path().join("vendor.js"), b"abc123")
+        .await
+        .context("error writing test file contents")?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "vendor.js".into());
+    attrs.insert(ATTR_TARGET_PATH.into(), "assets/[name]-[hash][ext]".into());
+    let cmd = CopyFile::new(cfg.clone(), Arc::new(tmpdir.into_path()), attrs, 0)
+        .await
+        .context("error constructing CopyFile pipeline")?;
+
+    // Action.
+    let out = cmd
+        .spawn()
+        .await
+        .context("unexpected task join error from pipeline")?
+        .context("unexpected pipeline error")?;
+
+    // Assert.
+    let expected = format!("assets/vendor-{:x}.js", seahash::hash(b"abc123"));
+    let TrunkAssetPipelineOutput::CopyFile(out) = out else {
+        anyhow::bail!("unexpected pipeline output");
+    };
+    anyhow::ensure!(out.file == expected, "unexpected file name {}", out.file);
+    anyhow::ensure!(
+        cfg.staging_dist.join(&expected).is_file(),
+        "missing file {expected}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn file_name_pattern() {
+    let hash = 0xabc;
+    let stable = FileNamePattern::parse("[name][ext]").expect("pattern");
+    assert_eq!(stable.file_name("vendor", hash, Some("js")), "vendor.js");
+    assert_eq!(stable.file_name("LICENSE", hash, None), "LICENSE");
+
+    let custom = FileNamePattern::parse("[name].[hash].min[ext]").expect("pattern");
+    assert_eq!(
+        custom.file_name("vendor", hash, Some("js")),
+        "vendor.abc.min.js"
+    );
+
+    assert_eq!(
+        FileNamePattern::new(true).file_name("[name]", hash, Some("css")),
+        "[name]-abc.css"
+    );
+    assert!(FileNamePattern::parse("vendor[1].js").is_none());
+}
+
+#[tokio::test]
+async fn err_new_target_path_parent_dir() -> Result<()> {
+    // Assemble.
+    let (tmpdir, cfg, _) = setup_test_config().await?;
+    let mut attrs = HashMap::new();
+    attrs.insert(ATTR_HREF.into(), "test_file".into());
+    attrs.insert(ATTR_TARGET_PATH.into(), "../[name][ext]".into());
+
+    // Action.
+    let res = CopyFile::new(cfg, Arc::new(tmpdir.into_path()), attrs, 0).await;
+
+    // Assert.
+    anyhow::ensure!(
+        res.is_err(),
+        "unexpected success while constructing CopyFile pipeline, expected error on a target \
+         path outside the dist dir"
+    );
+
+    Ok(())
+}
@@ -1,17 +1,24 @@
 //! CSS asset pipeline.
 
 use super::{
-    data_target_path, AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_HREF,
-    ATTR_NO_MINIFY,
+    data_cross_origin, data_minify_level, data_public_url, data_target_file, AssetFile, AttrWriter,
+    Attrs, FileNamePattern, TrunkAssetPipelineOutput, ATTR_HASH_ASSETS_IN_CSS, ATTR_HREF,
 };
 use crate::{
     common::{html_rewrite::Document, target_path},
-    config::rt::RtcBuild,
+    config::{
+        rt::RtcBuild,
+        types::{BaseUrl, CrossOrigin},
+    },
     pipelines::AssetFileType,
-    processing::integrity::{IntegrityType, OutputDigest},
+    processing::{
+        integrity::{IntegrityType, OutputDigest},
+        minify::MinifyLevel,
+    },
 };
 use anyhow::{Context, Result};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::task::JoinHandle;
 
@@ -22,15 +29,25 @@ pub struct Css {
     /// Runtime build config.
     cfg: Arc<RtcBuild>,
     /// The asset file being processed.
-    asset: AssetFile,
+    pub(super) asset: AssetFile,
     /// E.g. `disabled`, `id="..."`
     attrs: Attrs,
     /// The required integrity setting
     integrity: IntegrityType,
-    /// Whether to minify or not
-    no_minify: bool,
+    /// The cross-origin setting for loading the resource
+    cross_origin: Option<CrossOrigin>,
+    /// The public URL of the output file
+    public_url: BaseUrl,
+    /// How to minify
+    minify_level: MinifyLevel,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// The pattern for the name of the output file.
+    file_name: FileNamePattern,
+    /// If references to other assets should be rewritten to their output files.
+    hash_assets: bool,
+    /// The output files of the other assets of the build, by their source file.
+    asset_outputs: Option<Arc<HashMap<PathBuf, String>>>,
 }
 
 impl Css {
@@ -51,8 +68,11 @@ impl Css {
         let asset = AssetFile::new(&html_dir, path).await?;
 
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
-        let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
-        let target_path = data_target_path(&attrs)?;
+        let cross_origin = data_cross_origin(&attrs)?;
+        let public_url = data_public_url(&attrs, &cfg)?;
+        let minify_level = data_minify_level(&attrs)?;
+        let (target_path, file_name) = data_target_file(&attrs, &cfg.layout.css, cfg.filehash)?;
+        let hash_assets = attrs.contains_key(ATTR_HASH_ASSETS_IN_CSS);
 
         Ok(Self {
             id,
@@ -60,11 +80,27 @@ impl Css {
             asset,
             attrs,
             integrity,
-            no_minify,
+            cross_origin,
+            public_url,
+            minify_level,
             target_path,
+            file_name,
+            hash_assets,
+            asset_outputs: None,
         })
     }
 
+    /// If this pipeline must run after the other asset pipelines, to rewrite the references to
+    /// their output files.
+    pub fn hashes_assets(&self) -> bool {
+        self.hash_assets
+    }
+
+    /// Set the output files of the other assets of the build, by their source file.
+    pub fn set_asset_outputs(&mut self, outputs: Arc<HashMap<PathBuf, String>>) {
+        self.asset_outputs = Some(outputs);
+    }
+
     /// Spawn the pipeline for this asset type.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
@@ -80,16 +116,37 @@ impl Css {
         let result_path =
             target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
 
-        let file = self
-            .asset
-            .copy(
-                &self.cfg.staging_dist,
-                &result_path,
-                self.cfg.filehash,
-                self.cfg.minify_asset(self.no_minify),
-                AssetFileType::Css,
-            )
-            .await?;
+        let minify = self.cfg.minify_level(self.minify_level);
+        let file = match &self.asset_outputs {
+            Some(outputs) => {
+                let css = self.asset.read_to_string().await?;
+                let dir = self.asset.path.parent().unwrap_or(Path::new(""));
+                let css = rewrite_asset_refs(&css, dir, outputs, &self.cfg.public_url).await;
+                self.asset
+                    .copy_content(
+                        css.into_bytes(),
+                        &self.cfg.staging_dist,
+                        &result_path,
+                        &self.file_name,
+                        minify,
+                        AssetFileType::Css,
+                        self.cfg.source_maps,
+                    )
+                    .await?
+            }
+            None => {
+                self.asset
+                    .copy(
+                        &self.cfg.staging_dist,
+                        &result_path,
+                        &self.file_name,
+                        minify,
+                        AssetFileType::Css,
+                        self.cfg.source_maps,
+                    )
+                    .await?
+            }
+        };
         tracing::debug!(path = ?rel_path, "finished copying & hashing css");
 
         let result_file = self.cfg.staging_dist.join(&file);
@@ -102,27 +159,91 @@ impl Css {
             })?;
 
         Ok(TrunkAssetPipelineOutput::Css(CssOutput {
-            cfg: self.cfg.clone(),
             id: self.id,
+            source: self.asset.path.clone(),
             file,
             other_attrs: self.attrs,
             integrity,
+            cross_origin: self.cross_origin,
+            public_url: self.public_url,
         }))
     }
 }
 
+/// Rewrite the `url(...)` references of a CSS file to the output files of other assets.
+///
+/// References are resolved relative to the directory of the CSS file. References which don't
+/// point to the source file of an asset (like remote or data URLs) are kept as they are.
+async fn rewrite_asset_refs(
+    css: &str,
+    dir: &Path,
+    outputs: &HashMap<PathBuf, String>,
+    public_url: &str,
+) -> String {
+    let mut result = String::with_capacity(css.len());
+    let mut rest = css;
+
+    while let Some(start) = rest.find("url(") {
+        let (before, after) = rest.split_at(start + "url(".len());
+        result.push_str(before);
+        let Some(end) = after.find(')') else {
+            rest = after;
+            break;
+        };
+
+        let raw = &after[..end];
+        let trimmed = raw.trim();
+        let (quote, url) = match trimmed.chars().next() {
+            Some(quote @ ('"' | '\'')) if trimmed.len() >= 2 && trimmed.ends_with(quote) => {
+                (Some(quote), &trimmed[1..trimmed.len() - 1])
+            }
+            _ => (None, trimmed),
+        };
+
+        // keep the query and fragment, like `font.svg#icon`
+        let (path, suffix) = url.split_at(url.find(['?', '#']).unwrap_or(url.len()));
+        let output = match path.is_empty() || path.starts_with('/') || path.contains(':') {
+            true => None,
+            false => {
+                let mut source = dir.to_path_buf();
+                source.extend(path.split('/'));
+                match tokio::fs::canonicalize(&source).await {
+                    Ok(source) => outputs.get(&source),
+                    Err(_) => None,
+                }
+            }
+        };
+
+        match output {
+            Some(file) => {
+                let quote = quote.map(String::from).unwrap_or_default();
+                result.push_str(&format!("{quote}{public_url}{file}{suffix}{quote}"));
+            }
+            None => result.push_str(raw),
+        }
+        rest = &after[end..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
 /// The output of a CSS build pipeline.
 pub struct CssOutput {
-    /// The runtime build config.
-    pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: usize,
+    /// The source file of the asset.
+    pub source: PathBuf,
     /// Name the finalized output file.
     pub file: String,
     /// The other attributes copied over from the original.
     pub other_attrs: Attrs,
     /// The digest for the integrity attribute
     pub integrity: OutputDigest,
+    /// The cross-origin setting for loading the resource
+    pub cross_origin: Option<CrossOrigin>,
+    /// The public URL of the output file
+    pub public_url: BaseUrl,
 }
 
 impl CssOutput {
@@ -130,15 +251,126 @@ impl CssOutput {
         let mut attrs = self.other_attrs.clone();
 
         self.integrity.insert_into(&mut attrs);
+        if let Some(cross_origin) = self.cross_origin {
+            cross_origin.insert_into(&mut attrs);
+        }
 
         dom.replace_with_html(
             &super::trunk_id_selector(self.id),
             &format!(
                 r#"<link rel="stylesheet" href="{base}{file}"{attrs}/>"#,
-                base = &self.cfg.public_url,
+                base = &self.public_url,
                 file = self.file,
                 attrs = AttrWriter::new(&attrs, AttrWriter::EXCLUDE_CSS_LINK),
             ),
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn asset_refs_are_rewritten() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path().canonicalize()?;
+        tokio::fs::create_dir(dir.join("img")).await?;
+        tokio::fs::write(dir.join("img/bg.png"), b"").await?;
+        tokio::fs::write(dir.join("font.svg"), b"").await?;
+        tokio::fs::write(dir.join("other.png"), b"").await?;
+
+        let outputs = HashMap::from([
+            (dir.join("img/bg.png"), "bg-1234.png".to_string()),
+            (dir.join("font.svg"), "fonts/font-5678.svg".to_string()),
+        ]);
+        let css = r#"a { background: url(img/bg.png); }
+b { src: url( "font.svg#icon" ), url('other.png'), url(data:image/png;base64,AA==); }
+c { background: url(/img/bg.png), url(https://example.com/bg.png); }"#;
+
+        assert_eq!(
+            rewrite_asset_refs(css, &dir, &outputs, "/app/").await,
+            r#"a { background: url(/app/bg-1234.png); }
+b { src: url("/app/fonts/font-5678.svg#icon"), url('other.png'), url(data:image/png;base64,AA==); }
+c { background: url(/img/bg.png), url(https://example.com/bg.png); }"#
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cross_origin_with_integrity() -> Result<()> {
+        let out = CssOutput {
+            id: 0,
+            source: PathBuf::from("style.css"),
+            file: "style.css".to_string(),
+            other_attrs: HashMap::from([(
+                "data-cross-origin".to_string(),
+                "anonymous".to_string(),
+            )]),
+            integrity: OutputDigest::generate_from(IntegrityType::Sha256, b"a {}"),
+            cross_origin: Some(CrossOrigin::Anonymous),
+            public_url: BaseUrl::Default,
+        };
+
+        let mut dom = Document::new(
+            r#"<html><head><link data-trunk-id="0"></head></html>"#,
+            Default::default(),
+        )?;
+        out.finalize(&mut dom).await?;
+        let html = String::from_utf8(dom.into_inner())?;
+        anyhow::ensure!(
+            html.contains(r#"<link rel="stylesheet" href="/style.css""#)
+                && html.contains(r#" integrity="sha256"#)
+                && html.contains(r#" crossorigin="anonymous""#)
+                && !html.contains("data-cross-origin"),
+            "unexpected output: {html}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn public_url_override() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.filehash = false;
+        cfg.public_url = "/app/".parse()?;
+        tokio::fs::create_dir_all(&cfg.staging_dist).await?;
+        tokio::fs::write(tmpdir.path().join("local.css"), b"a {}").await?;
+        tokio::fs::write(tmpdir.path().join("data.css"), b"b {}").await?;
+        let cfg = Arc::new(cfg);
+
+        let mut dom = Document::new(
+            r#"<html><head><link data-trunk-id="0"><link data-trunk-id="1"></head></html>"#,
+            Default::default(),
+        )?;
+        let links = [
+            vec![(ATTR_HREF, "local.css")],
+            vec![
+                (ATTR_HREF, "data.css"),
+                ("data-public-url", "https://cdn.example.com/assets"),
+            ],
+        ];
+        for (id, attrs) in links.into_iter().enumerate() {
+            let attrs = attrs
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            let css = Css::new(cfg.clone(), Arc::new(tmpdir.path().into()), attrs, id).await?;
+            let TrunkAssetPipelineOutput::Css(out) = css.run().await? else {
+                anyhow::bail!("unexpected pipeline output");
+            };
+            out.finalize(&mut dom).await?;
+        }
+
+        let html = String::from_utf8(dom.into_inner())?;
+        anyhow::ensure!(
+            html.contains(r#"<link rel="stylesheet" href="/app/local.css""#)
+                && html.contains(
+                    r#"<link rel="stylesheet" href="https://cdn.example.com/assets/data.css""#
+                )
+                && !html.contains("data-public-url"),
+            "unexpected output: {html}"
+        );
+        Ok(())
+    }
+}
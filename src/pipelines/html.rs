@@ -8,24 +8,38 @@ use crate::{
     config::{rt::RtcBuild, types::WsProtocol},
     hooks::{spawn_hooks, wait_hooks},
     pipelines::{
-        rust::RustApp, Attrs, PipelineStage, TrunkAsset, TrunkAssetPipelineOutput,
+        asset_cache::AssetCache,
+        rust::{RustApp, RustAppOutput},
+        AssetKind, Attrs, OutputRecord, PipelineStage, TrunkAsset, TrunkAssetPipelineOutput,
         TrunkAssetReference, TRUNK_ID,
     },
     processing::minify::minify_html,
 };
-use anyhow::{ensure, Context, Result};
-use futures_util::stream::{FuturesUnordered, StreamExt};
-use std::{path::PathBuf, sync::Arc};
+use anyhow::{bail, ensure, Context, Result};
+use futures_util::stream::{BoxStream, FuturesOrdered, FuturesUnordered, StreamExt};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+};
 use tokio::{
     fs,
     sync::mpsc,
-    task::{JoinError, JoinHandle},
+    task::{AbortHandle, JoinError, JoinHandle},
 };
 
 const PUBLIC_URL_MARKER_ATTR: &str = "data-trunk-public-url";
 const RELOAD_SCRIPT: &str = include_str!("../autoreload.js");
 
-type AssetPipelineHandles = FuturesUnordered<JoinHandle<Result<TrunkAssetPipelineOutput>>>;
+type AssetPipelineHandles = Vec<JoinHandle<Result<TrunkAssetPipelineOutput>>>;
+
+/// The result of an HTML pipeline.
+pub struct HtmlPipelineOutput {
+    /// The files of the previous build which are re-used, relative to the dist dir.
+    pub preserved: Vec<PathBuf>,
+    /// The records of the files written to the dist dir.
+    pub records: Vec<OutputRecord>,
+}
 
 /// An HTML assets build pipeline.
 ///
@@ -40,6 +54,8 @@ pub struct HtmlPipeline {
     target_html_dir: Arc<PathBuf>,
     /// An optional channel to be used to communicate ignore paths to the watcher.
     ignore_chan: Option<mpsc::Sender<PathBuf>>,
+    /// An optional channel to be used to communicate additional paths to watch to the watcher.
+    watch_chan: Option<mpsc::UnboundedSender<PathBuf>>,
     /// Protocol used for autoreload WebSockets connection.
     ws_protocol: Option<WsProtocol>,
 }
@@ -49,6 +65,7 @@ impl HtmlPipeline {
     pub fn new(
         cfg: Arc<RtcBuild>,
         ignore_chan: Option<mpsc::Sender<PathBuf>>,
+        watch_chan: Option<mpsc::UnboundedSender<PathBuf>>,
         ws_protocol: Option<WsProtocol>,
     ) -> Result<Self> {
         let target_html_path = cfg
@@ -67,19 +84,20 @@ impl HtmlPipeline {
             target_html_path,
             target_html_dir,
             ignore_chan,
+            watch_chan,
             ws_protocol,
         })
     }
 
     /// Spawn a new pipeline.
     #[tracing::instrument(level = "trace", skip(self))]
-    pub fn spawn(self: Arc<Self>) -> JoinHandle<Result<()>> {
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<Result<HtmlPipelineOutput>> {
         tokio::spawn(self.run())
     }
 
     /// Run this pipeline.
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn run(self: Arc<Self>) -> Result<()> {
+    async fn run(self: Arc<Self>) -> Result<HtmlPipelineOutput> {
         tracing::debug!("spawning asset pipelines");
 
         // Spawn and wait on pre-build hooks.
@@ -88,7 +106,7 @@ impl HtmlPipeline {
         // Open the source HTML file for processing.
         let raw_html = fs::read(&self.target_html_path).await?;
         let mut target_html = Document::new(
-            raw_html,
+            raw_html.clone(),
             DocumentOptions {
                 allow_self_closing_script: self.cfg.allow_self_closing_script,
             },
@@ -128,6 +146,7 @@ impl HtmlPipeline {
                     self.cfg.clone(),
                     self.target_html_dir.clone(),
                     self.ignore_chan.clone(),
+                    self.watch_chan.clone(),
                     asset_constructor(attrs),
                     id,
                 );
@@ -143,14 +162,44 @@ impl HtmlPipeline {
             .into_iter()
             .collect::<Result<Vec<_>>>()?;
 
+        // Only inline the first occurrence of files marked with `data-inline="once"`.
+        let mut inlined = HashSet::new();
+        for asset in &mut assets {
+            if let TrunkAsset::Inline(inline) = asset {
+                inline.mark_repeated(&mut inlined);
+            }
+        }
+
+        // Re-use the outputs of the previous build, instead of running the asset pipelines again.
+        let mut asset_cache = self.asset_cache(&raw_html, &assets).await;
+        let previous = match (&asset_cache, self.cfg.skip_assets) {
+            (Some(asset_cache), true) => self.load_asset_cache(asset_cache).await,
+            _ => None,
+        };
+        if let Some(previous) = &previous {
+            assets.retain(|asset| matches!(asset, TrunkAsset::RustApp(_)));
+            target_html = Document::new(
+                previous.html.as_str(),
+                DocumentOptions {
+                    allow_self_closing_script: self.cfg.allow_self_closing_script,
+                },
+            )?;
+        }
+
         // Ensure we have a Rust app pipeline to spawn.
-        let rust_app_nodes = target_html
-            .len(r#"link[data-trunk][rel="rust"][data-type="main"], link[data-trunk][rel="rust"]:not([data-type])"#)?;
-        ensure!(
-            rust_app_nodes <= 1,
-            r#"only one <link data-trunk rel="rust" data-type="main" .../> may be specified"#
-        );
-        if rust_app_nodes == 0 {
+        let main_apps = assets
+            .iter()
+            .filter_map(|asset| match asset {
+                TrunkAsset::RustApp(app) if app.is_main() => Some(app),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        check_main_apps(
+            main_apps
+                .iter()
+                .map(|app| (app.bin(), app.mount(), app.bindings_name())),
+        )?;
+        if main_apps.is_empty() {
             if let Some(app) = RustApp::new_default(
                 self.cfg.clone(),
                 self.target_html_dir.clone(),
@@ -160,20 +209,86 @@ impl HtmlPipeline {
             {
                 assets.push(TrunkAsset::RustApp(app));
             } else {
-                tracing::warn!("no rust project found")
+                check_missing_rust_app(&mut target_html)?;
+                tracing::warn!(
+                    "no rust project found, no Cargo.toml next to the HTML file in {:?}",
+                    self.target_html_dir
+                );
+                tracing::warn!(
+                    r#"add a <link data-trunk rel="rust" href="path/to/Cargo.toml"/> to the HTML file, in case the project is located elsewhere"#
+                );
             };
         }
 
+        link_shared_wasm(&mut assets)?;
+
+        // CSS files rewriting the references to other assets need their outputs first.
+        let (deferred, assets): (Vec<_>, Vec<_>) = assets
+            .into_iter()
+            .partition(|asset| matches!(asset, TrunkAsset::Css(css) if css.hashes_assets()));
+
         // Spawn all asset pipelines.
-        let mut pipelines: AssetPipelineHandles = FuturesUnordered::new();
-        pipelines.extend(assets.into_iter().map(TrunkAsset::spawn));
+        let pipelines: AssetPipelineHandles = assets.into_iter().map(TrunkAsset::spawn).collect();
         // Spawn all build hooks.
         let build_hooks = spawn_hooks(self.cfg.clone(), PipelineStage::Build);
 
-        // Finalize asset pipelines.
-        self.finalize_asset_pipelines(&mut target_html, pipelines)
+        // Finalize asset pipelines, except for the Rust apps, which are finalized last.
+        let mut rust_apps = vec![];
+        let mut records = vec![];
+        self.finalize_asset_pipelines(&mut target_html, pipelines, &mut rust_apps, &mut records)
             .await?;
 
+        if !deferred.is_empty() {
+            let mut outputs = HashMap::new();
+            for record in &records {
+                outputs
+                    .entry(record.source.clone())
+                    .or_insert_with(|| record.file.clone());
+            }
+            let outputs = Arc::new(outputs);
+
+            let pipelines: AssetPipelineHandles = deferred
+                .into_iter()
+                .map(|mut asset| {
+                    if let TrunkAsset::Css(css) = &mut asset {
+                        css.set_asset_outputs(outputs.clone());
+                    }
+                    asset.spawn()
+                })
+                .collect();
+            self.finalize_asset_pipelines(
+                &mut target_html,
+                pipelines,
+                &mut rust_apps,
+                &mut records,
+            )
+            .await?;
+        }
+
+        // The document now contains the outputs of all non-Rust assets, which can be re-used.
+        if let Some(asset_cache) = &mut asset_cache {
+            asset_cache.html = String::from_utf8_lossy(target_html.as_ref()).into_owned();
+            asset_cache.records.clone_from(&records);
+        }
+        let mut rust_files = HashSet::new();
+        for app in rust_apps {
+            rust_files.extend(app.files.iter().cloned());
+            let app = TrunkAssetPipelineOutput::RustApp(app);
+            records.extend(app.records());
+            app.finalize(&mut target_html)
+                .await
+                .context("failed to finalize asset pipeline")?;
+        }
+        records.insert(
+            0,
+            OutputRecord {
+                file: self.cfg.html_output_filename.clone(),
+                kind: AssetKind::Html,
+                source: self.target_html_path.clone(),
+                integrity: None,
+            },
+        );
+
         // Wait for all build hooks to finish.
         wait_hooks(build_hooks).await?;
 
@@ -196,14 +311,68 @@ impl HtmlPipeline {
         // Spawn and wait on post-build hooks.
         wait_hooks(spawn_hooks(self.cfg.clone(), PipelineStage::PostBuild)).await?;
 
-        Ok(())
+        if let Some(previous) = previous {
+            records.extend(previous.records);
+            return Ok(HtmlPipelineOutput {
+                preserved: previous.files,
+                records,
+            });
+        }
+
+        // Record the asset outputs, for builds skipping the asset pipelines later on.
+        rust_files.insert(self.cfg.staging_dist.join(&self.cfg.html_output_filename));
+        if let Some(asset_cache) = asset_cache {
+            if let Err(err) = asset_cache.store(&self.cfg, &rust_files).await {
+                tracing::warn!("unable to store the asset outputs: {err:#}");
+            }
+        }
+
+        Ok(HtmlPipelineOutput {
+            preserved: vec![],
+            records,
+        })
+    }
+
+    /// Create the cache of the asset outputs of this build, keyed by its inputs.
+    async fn asset_cache(&self, raw_html: &[u8], assets: &[TrunkAsset]) -> Option<AssetCache> {
+        let sources = assets.iter().filter_map(TrunkAsset::source);
+        match AssetCache::new(&self.cfg, raw_html, sources).await {
+            Ok(asset_cache) => Some(asset_cache),
+            Err(err) => {
+                tracing::warn!("unable to evaluate the inputs of the asset outputs: {err:#}");
+                None
+            }
+        }
+    }
+
+    /// Load the asset outputs of the previous build, if they can be re-used.
+    async fn load_asset_cache(&self, asset_cache: &AssetCache) -> Option<AssetCache> {
+        match asset_cache.load(&self.cfg).await {
+            Ok(Some(previous)) => {
+                tracing::info!("skipping asset pipelines, re-using the previous outputs");
+                Some(previous)
+            }
+            Ok(None) => {
+                tracing::info!("no re-usable outputs of a previous build, building all assets");
+                None
+            }
+            Err(err) => {
+                tracing::warn!("unable to load the previous asset outputs: {err:#}");
+                None
+            }
+        }
     }
 
     /// Finalize asset pipelines & prep the DOM for final output.
+    ///
+    /// The outputs of Rust apps are not finalized, but returned, so that the document can be
+    /// re-used without them.
     async fn finalize_asset_pipelines(
         &self,
         target_html: &mut Document,
-        mut pipelines: AssetPipelineHandles,
+        pipelines: AssetPipelineHandles,
+        rust_apps: &mut Vec<RustAppOutput>,
+        records: &mut Vec<OutputRecord>,
     ) -> Result<()> {
         let mut errors = Vec::new();
 
@@ -211,24 +380,53 @@ impl HtmlPipeline {
         async fn finalize(
             asset_res: std::result::Result<Result<TrunkAssetPipelineOutput>, JoinError>,
             target_html: &mut Document,
+            rust_apps: &mut Vec<RustAppOutput>,
+            records: &mut Vec<OutputRecord>,
         ) -> Result<()> {
             let asset = asset_res
                 .context("failed to await asset pipeline")?
                 .context("error from asset pipeline")?;
 
+            if let TrunkAssetPipelineOutput::RustApp(app) = asset {
+                rust_apps.push(app);
+                return Ok(());
+            }
+
+            records.extend(asset.records());
             asset
                 .finalize(target_html)
                 .await
-                .context("failed to finalize asset pipeline")?;
-
-            Ok(())
+                .context("failed to finalize asset pipeline")
         }
 
+        let aborts = pipelines
+            .iter()
+            .map(JoinHandle::abort_handle)
+            .collect::<Vec<_>>();
+        let mut remaining = pipelines.len();
+        let mut results: BoxStream<'_, _> = match self.cfg.reproducible {
+            // finalize in the order of the HTML file, so that the output doesn't depend on timing
+            true => pipelines.into_iter().collect::<FuturesOrdered<_>>().boxed(),
+            false => pipelines
+                .into_iter()
+                .collect::<FuturesUnordered<_>>()
+                .boxed(),
+        };
+
         // pull all results and store their errors
-        while let Some(asset_res) = pipelines.next().await {
-            if let Err(err) = finalize(asset_res, target_html).await {
+        while let Some(asset_res) = results.next().await {
+            remaining -= 1;
+            if matches!(&asset_res, Err(err) if err.is_cancelled()) {
+                // aborted due to an earlier error
+                continue;
+            }
+            if let Err(err) = finalize(asset_res, target_html, rust_apps, records).await {
                 // store the error, but don't return, so that we can still await all others
                 errors.push(err);
+                if self.cfg.fail_fast && remaining > 0 {
+                    tracing::info!("aborting {remaining} remaining asset pipelines");
+                    aborts.iter().for_each(AbortHandle::abort);
+                }
             }
         }
 
@@ -259,6 +457,16 @@ impl HtmlPipeline {
             },
         )?;
 
+        if !self.cfg.preconnect.is_empty() {
+            inject_resource_hints(target_html, &self.cfg.preconnect)?;
+        }
+
+        // Inject the options of the import map polyfill, which must precede the polyfill and the
+        // import map.
+        if let Some(options) = &self.cfg.esms_options {
+            inject_esms_options(target_html, options, &self.cfg.create_nonce)?;
+        }
+
         // Inject the WebSocket autoloader.
         if self.cfg.inject_autoloader {
             target_html.append_html(
@@ -277,3 +485,316 @@ impl HtmlPipeline {
         Ok(())
     }
 }
+
+/// Insert the `es-module-shims` options ahead of the first script of the head, so that they
+/// precede both the polyfill and the import map. Otherwise, they are inserted ahead of the first
+/// import map, or appended to the head.
+fn inject_esms_options(
+    target_html: &mut Document,
+    options: &str,
+    nonce: &Option<String>,
+) -> Result<()> {
+    let script = format!(
+        r#"<script type="esms-options"{}>{options}</script>"#,
+        nonce_attr(nonce)
+    );
+
+    for selector in ["html head script", r#"script[type="importmap"]"#] {
+        let mut injected = false;
+        target_html.select_mut(selector, |el| {
+            if !injected {
+                el.before(&script, lol_html::html_content::ContentType::Html);
+                injected = true;
+            }
+            Ok(())
+        })?;
+        if injected {
+            return Ok(());
+        }
+    }
+
+    target_html.append_html("html head", &script)
+}
+
+/// Append preconnect and DNS prefetch hints for the origins to the head, unless the source HTML
+/// already has them.
+fn inject_resource_hints(target_html: &mut Document, origins: &[String]) -> Result<()> {
+    let mut existing = HashSet::new();
+    target_html.select(
+        r#"html head link[rel="preconnect"], html head link[rel="dns-prefetch"]"#,
+        |el| {
+            if let (Some(rel), Some(href)) = (el.get_attribute("rel"), el.get_attribute("href")) {
+                existing.insert((rel, href.trim_end_matches('/').to_string()));
+            }
+            Ok(())
+        },
+    )?;
+
+    let mut hints = String::new();
+    for origin in origins {
+        let origin = origin.trim_end_matches('/');
+        for rel in ["preconnect", "dns-prefetch"] {
+            if existing.insert((rel.to_string(), origin.to_string())) {
+                hints.push_str(&format!(r#"<link rel="{rel}" href="{origin}">"#));
+            }
+        }
+    }
+
+    if hints.is_empty() {
+        return Ok(());
+    }
+    target_html.append_html("html head", &hints)
+}
+
+/// Fail with a hint on the likely mistake, in case the HTML file has a Rust app link which Trunk
+/// does not process.
+fn check_missing_rust_app(target_html: &mut Document) -> Result<()> {
+    if target_html.len(r#"link[rel="rust"]:not([data-trunk])"#)? > 0 {
+        bail!(
+            r#"found a <link rel="rust" .../> element without the `data-trunk` attribute, Trunk only processes elements marked with `data-trunk`"#
+        );
+    }
+    if target_html.len(r#"[data-trunk][rel="rust"]:not(link)"#)? > 0 {
+        bail!(
+            r#"found a `data-trunk rel="rust"` element which is not a <link>, use <link data-trunk rel="rust" .../> instead"#
+        );
+    }
+    Ok(())
+}
+
+/// Connect the workers using `data-shared-wasm` to the main app building the same binary.
+fn link_shared_wasm(assets: &mut [TrunkAsset]) -> Result<()> {
+    let wanted = assets
+        .iter()
+        .filter_map(|asset| match asset {
+            TrunkAsset::RustApp(app) if app.uses_shared_wasm() => Some(app.binary()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    if wanted.is_empty() {
+        return Ok(());
+    }
+
+    let mut shared = HashMap::new();
+    for asset in assets.iter_mut() {
+        if let TrunkAsset::RustApp(app) = asset {
+            if app.is_main() && wanted.contains(&app.binary()) {
+                shared.insert(app.binary(), app.share_wasm());
+            }
+        }
+    }
+
+    for asset in assets.iter_mut() {
+        if let TrunkAsset::RustApp(app) = asset {
+            if app.uses_shared_wasm() {
+                let rx = shared.get(&app.binary()).with_context(|| {
+                    format!(
+                        "`data-shared-wasm` requires a main application building the same binary ({})",
+                        app.bin().unwrap_or("the default binary")
+                    )
+                })?;
+                app.use_shared_wasm(rx.clone());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Ensure the main applications of the document don't collide.
+///
+/// More than one main application is only supported if each one builds a distinct binary, and
+/// is mounted at a distinct element. The applications are given by their binary and mount point.
+fn check_main_apps<'a>(
+    apps: impl ExactSizeIterator<Item = (Option<&'a str>, Option<&'a str>, Option<&'a str>)>,
+) -> Result<()> {
+    if apps.len() <= 1 {
+        return Ok(());
+    }
+
+    let mut bins = HashSet::new();
+    let mut mounts = HashSet::new();
+    let mut bindings = HashSet::new();
+    for (bin, mount, bindings_name) in apps {
+        let (Some(bin), Some(mount)) = (bin, mount) else {
+            bail!(
+                r#"only one <link data-trunk rel="rust" data-type="main" .../> may be specified, unless each one has a distinct `data-bin` and `data-mount`"#
+            );
+        };
+        ensure!(
+            bins.insert(bin),
+            r#"multiple main applications use `data-bin="{bin}"`"#
+        );
+        ensure!(
+            mounts.insert(mount),
+            r#"multiple main applications use `data-mount="{mount}"`"#
+        );
+        if let Some(name) = bindings_name {
+            ensure!(
+                bindings.insert(name),
+                r#"multiple main applications assign their bindings to `window.{name}`, use a distinct `data-wasm-import-name` (or `data-wasm-no-import`) for each one"#
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pipelines::rust::DEFAULT_BINDINGS_NAME;
+
+    #[test]
+    fn missing_rust_app_hints() -> Result<()> {
+        for (html, ok) in [
+            (r#"<html><head></head></html>"#, true),
+            (
+                r#"<html><head><link data-trunk rel="css" href="a.css"></head></html>"#,
+                true,
+            ),
+            (r#"<html><head><link rel="rust"></head></html>"#, false),
+            (
+                r#"<html><head><script data-trunk rel="rust"></script></head></html>"#,
+                false,
+            ),
+        ] {
+            let mut doc = Document::new(html, Default::default())?;
+            ensure!(
+                check_missing_rust_app(&mut doc).is_ok() == ok,
+                "unexpected result for: {html}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn esms_options_precede_scripts() -> Result<()> {
+        let options = r#"{"shimMode":true}"#;
+        let nonce = Some("{{nonce}}".to_string());
+        for (html, expected) in [
+            (
+                r#"<html><head><script async src="shims.js"></script><script type="importmap">{}</script></head></html>"#,
+                r#"<html><head><script type="esms-options" nonce="{{nonce}}">{"shimMode":true}</script><script async src="shims.js"></script><script type="importmap">{}</script></head></html>"#,
+            ),
+            (
+                r#"<html><head></head><body><script type="importmap">{}</script><script type="importmap">{}</script></body></html>"#,
+                r#"<html><head></head><body><script type="esms-options" nonce="{{nonce}}">{"shimMode":true}</script><script type="importmap">{}</script><script type="importmap">{}</script></body></html>"#,
+            ),
+            (
+                r#"<html><head><title>app</title><script src="shims.js"></script></head></html>"#,
+                r#"<html><head><title>app</title><script type="esms-options" nonce="{{nonce}}">{"shimMode":true}</script><script src="shims.js"></script></head></html>"#,
+            ),
+            (
+                r#"<html><head><title>app</title></head></html>"#,
+                r#"<html><head><title>app</title><script type="esms-options" nonce="{{nonce}}">{"shimMode":true}</script></head></html>"#,
+            ),
+        ] {
+            let mut doc = Document::new(html, Default::default())?;
+            inject_esms_options(&mut doc, options, &nonce)?;
+            let result = String::from_utf8(doc.into_inner())?;
+            ensure!(result == expected, "unexpected result: {result}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn resource_hints_are_deduplicated() -> Result<()> {
+        let origins = [
+            "https://cdn.example.com/".to_string(),
+            "https://fonts.example.com".to_string(),
+            "https://cdn.example.com".to_string(),
+        ];
+        let mut doc = Document::new(
+            r#"<html><head><link rel="preconnect" href="https://fonts.example.com/" crossorigin></head></html>"#,
+            Default::default(),
+        )?;
+        inject_resource_hints(&mut doc, &origins)?;
+        let result = String::from_utf8(doc.into_inner())?;
+        ensure!(
+            result
+                == concat!(
+                    r#"<html><head><link rel="preconnect" href="https://fonts.example.com/" crossorigin>"#,
+                    r#"<link rel="preconnect" href="https://cdn.example.com">"#,
+                    r#"<link rel="dns-prefetch" href="https://cdn.example.com">"#,
+                    r#"<link rel="dns-prefetch" href="https://fonts.example.com">"#,
+                    r#"</head></html>"#
+                ),
+            "unexpected result: {result}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fail_fast_aborts_pipelines() -> Result<()> {
+        use std::time::{Duration, Instant};
+
+        let tmpdir = tempfile::tempdir()?;
+        fs::write(tmpdir.path().join("index.html"), "<html></html>").await?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.fail_fast = true;
+        let pipeline = HtmlPipeline::new(Arc::new(cfg), None, None, None)?;
+
+        let pipelines: AssetPipelineHandles = [
+            tokio::spawn(async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(TrunkAssetPipelineOutput::None)
+            }),
+            tokio::spawn(async { bail!("broken asset") }),
+        ]
+        .into_iter()
+        .collect();
+
+        let started = Instant::now();
+        let mut doc = Document::new("<html></html>", Default::default())?;
+        let err = pipeline
+            .finalize_asset_pipelines(&mut doc, pipelines, &mut vec![], &mut vec![])
+            .await
+            .expect_err("the pipeline must fail");
+
+        ensure!(
+            started.elapsed() < Duration::from_secs(30),
+            "the remaining pipeline wasn't aborted"
+        );
+        ensure!(
+            format!("{err:#}").contains("broken asset"),
+            "unexpected error: {err:#}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn multiple_main_apps() {
+        let app = |bin, mount, bindings| (Some(bin), Some(mount), bindings);
+        assert!(check_main_apps([].into_iter()).is_ok());
+        assert!(check_main_apps([(None, None, None)].into_iter()).is_ok());
+        assert!(check_main_apps([(None, None, None), (None, None, None)].into_iter()).is_err());
+        assert!(check_main_apps(
+            [app("a", "#a", Some("a")), app("b", "#b", Some("b"))].into_iter()
+        )
+        .is_ok());
+        assert!(check_main_apps([app("a", "#a", None), app("b", "#b", None)].into_iter()).is_ok());
+        // missing mount point
+        assert!(check_main_apps(
+            [app("a", "#a", Some("a")), (Some("b"), None, Some("b"))].into_iter()
+        )
+        .is_err());
+        // colliding binaries, mount points or bindings
+        assert!(check_main_apps(
+            [app("a", "#a", Some("a")), app("a", "#b", Some("b"))].into_iter()
+        )
+        .is_err());
+        assert!(check_main_apps(
+            [app("a", "#a", Some("a")), app("b", "#a", Some("b"))].into_iter()
+        )
+        .is_err());
+        assert!(check_main_apps(
+            [
+                app("a", "#a", Some(DEFAULT_BINDINGS_NAME)),
+                app("b", "#b", Some(DEFAULT_BINDINGS_NAME))
+            ]
+            .into_iter()
+        )
+        .is_err());
+    }
+}
@@ -1,16 +1,25 @@
 //! Icon asset pipeline.
 
 use super::{
-    data_target_path, trunk_id_selector, AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput,
-    ATTR_HREF, ATTR_NO_MINIFY,
+    data_cross_origin, data_public_url, data_target_file, is_root_file,
+    preload::{Preload, PreloadLink},
+    trunk_id_selector, AssetFile, AttrWriter, Attrs, FileNamePattern, TrunkAssetPipelineOutput,
+    ATTR_HREF, ATTR_INLINE_THRESHOLD, ATTR_NO_MINIFY,
 };
 use crate::{
     common::{html_rewrite::Document, nonce_attr, target_path},
-    config::rt::RtcBuild,
+    config::{
+        rt::RtcBuild,
+        types::{BaseUrl, CrossOrigin},
+    },
     pipelines::{AssetFileType, ImageType},
-    processing::integrity::{IntegrityType, OutputDigest},
+    processing::{
+        integrity::{IntegrityType, OutputDigest},
+        minify::MinifyLevel,
+    },
 };
 use anyhow::{Context, Result};
+use base64::{engine::general_purpose, Engine};
 use std::{collections::HashMap, path::PathBuf, sync::Arc};
 use tokio::task::JoinHandle;
 
@@ -21,13 +30,23 @@ pub struct Icon {
     /// Runtime build config.
     cfg: Arc<RtcBuild>,
     /// The asset file being processed.
-    asset: AssetFile,
+    pub(super) asset: AssetFile,
     /// The required integrity setting
     integrity: IntegrityType,
     /// Whether to minify or not
     no_minify: bool,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// The pattern for the name of the output file.
+    file_name: FileNamePattern,
+    /// Inline icons smaller than this number of bytes as data URI.
+    inline_threshold: Option<u64>,
+    /// The preload link of the icon file, if requested.
+    preload: Option<Preload>,
+    /// The cross-origin setting for loading the icon.
+    cross_origin: Option<CrossOrigin>,
+    /// The public URL of the icon file.
+    public_url: BaseUrl,
 }
 
 impl Icon {
@@ -49,7 +68,21 @@ impl Icon {
 
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
         let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
-        let target_path = data_target_path(&attrs)?;
+        let (target_path, file_name) = match is_root_file(&asset.path) {
+            true => data_target_file(&attrs, &None, false)?,
+            false => data_target_file(&attrs, &cfg.layout.img, cfg.filehash)?,
+        };
+        let inline_threshold = attrs
+            .get(ATTR_INLINE_THRESHOLD)
+            .map(|value| {
+                value.trim().parse::<u64>().with_context(|| {
+                    format!("invalid value of `{ATTR_INLINE_THRESHOLD}`, expected a number of bytes: {value}")
+                })
+            })
+            .transpose()?;
+        let preload = Preload::from_attrs(&attrs, &asset.path, &cfg)?;
+        let cross_origin = data_cross_origin(&attrs)?;
+        let public_url = data_public_url(&attrs, &cfg)?;
 
         Ok(Self {
             id,
@@ -58,6 +91,11 @@ impl Icon {
             integrity,
             no_minify,
             target_path,
+            file_name,
+            inline_threshold,
+            preload,
+            cross_origin,
+            public_url,
         })
     }
 
@@ -73,22 +111,67 @@ impl Icon {
         let rel_path = crate::common::strip_prefix(&self.asset.path);
         tracing::debug!(path = ?rel_path, "copying & hashing icon");
         let mime_type = mime_guess::from_path(&self.asset.path).first_or_octet_stream();
-        let image_type = match mime_type.type_().as_str() {
+        let image_type = match mime_type.essence_str() {
             "image/png" => ImageType::Png,
             _ => ImageType::Other,
         };
 
+        if let Some(threshold) = self.inline_threshold {
+            let size = tokio::fs::metadata(&self.asset.path)
+                .await
+                .with_context(|| format!("error reading metadata of {:?}", &self.asset.path))?
+                .len();
+            if size < threshold {
+                let content = tokio::fs::read(&self.asset.path)
+                    .await
+                    .with_context(|| format!("error reading icon {:?}", &self.asset.path))?;
+                let data_uri = format!(
+                    "data:{};base64,{}",
+                    mime_type.essence_str(),
+                    general_purpose::STANDARD.encode(content)
+                );
+
+                tracing::debug!(path = ?rel_path, "inlined icon of {size} bytes");
+                return Ok(TrunkAssetPipelineOutput::Icon(IconOutput {
+                    cfg: self.cfg.clone(),
+                    id: self.id,
+                    source: self.asset.path.clone(),
+                    icon_ref: IconRef::Inline(data_uri),
+                    preload: None,
+                    cross_origin: None,
+                    public_url: self.public_url.clone(),
+                }));
+            }
+        }
+
         let result_dir =
             target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
 
+        // bound the number of images being optimized in parallel, across all icon pipelines
+        let minify = match self.cfg.minify_asset(self.no_minify) {
+            true => MinifyLevel::default(),
+            false => MinifyLevel::None,
+        };
+        let _permit = match image_type {
+            ImageType::Png if minify != MinifyLevel::None => Some(
+                self.cfg
+                    .image_pool
+                    .acquire()
+                    .await
+                    .context("error waiting for image optimization")?,
+            ),
+            _ => None,
+        };
+
         let file = self
             .asset
             .copy(
                 &self.cfg.staging_dist,
                 &result_dir,
-                self.cfg.filehash,
-                self.cfg.minify_asset(self.no_minify),
+                &self.file_name,
+                minify,
                 AssetFileType::Icon(image_type),
+                false,
             )
             .await?;
 
@@ -101,12 +184,20 @@ impl Icon {
                 )
             })?;
 
+        let preload = match &self.preload {
+            Some(preload) => Some(preload.link(&self.cfg, &file).await?),
+            None => None,
+        };
+
         tracing::debug!(path = ?rel_path, "finished copying & hashing icon");
         Ok(TrunkAssetPipelineOutput::Icon(IconOutput {
             cfg: self.cfg.clone(),
             id: self.id,
-            file,
-            integrity,
+            source: self.asset.path.clone(),
+            icon_ref: IconRef::File(file, integrity),
+            preload,
+            cross_origin: self.cross_origin,
+            public_url: self.public_url,
         }))
     }
 }
@@ -117,27 +208,169 @@ pub struct IconOutput {
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: usize,
-    /// Name of the finalized output file.
-    pub file: String,
-    /// The digest for the integrity attribute
-    pub integrity: OutputDigest,
+    /// The source file of the asset.
+    pub source: PathBuf,
+    /// The reference to the icon.
+    pub icon_ref: IconRef,
+    /// The preload link of the icon file, if requested (and not inlined).
+    pub preload: Option<PreloadLink>,
+    /// The cross-origin setting for loading the icon (if not inlined).
+    pub cross_origin: Option<CrossOrigin>,
+    /// The public URL of the icon file (if not inlined).
+    pub public_url: BaseUrl,
+}
+
+/// The reference to an icon.
+pub enum IconRef {
+    /// A data URI of the icon (for `data-inline-threshold`).
+    Inline(String),
+    /// The name of the finalized output file, with the digest for the integrity attribute
+    /// (default).
+    File(String, OutputDigest),
 }
 
 impl IconOutput {
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
         let mut attrs = HashMap::new();
-        self.integrity.insert_into(&mut attrs);
+        let href = match self.icon_ref {
+            IconRef::Inline(data_uri) => data_uri,
+            IconRef::File(file, integrity) => {
+                integrity.insert_into(&mut attrs);
+                if let Some(cross_origin) = self.cross_origin {
+                    cross_origin.insert_into(&mut attrs);
+                }
+                format!("{base}{file}", base = &self.public_url)
+            }
+        };
 
         dom.replace_with_html(
             &trunk_id_selector(self.id),
             &format!(
-                r#"<link rel="icon" href="{base}{file}"{attrs}{nonce}/>"#,
-                base = &self.cfg.public_url,
-                file = self.file,
+                r#"<link rel="icon" href="{href}"{attrs}{nonce}/>{preload}"#,
                 attrs = AttrWriter::new(&attrs, &[]),
                 nonce = nonce_attr(&self.cfg.create_nonce),
+                preload = self
+                    .preload
+                    .map(|preload| preload.to_string())
+                    .unwrap_or_default(),
             ),
         )?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    async fn icon_ref(threshold: &str) -> Result<IconRef> {
+        let tmpdir = tempfile::tempdir()?;
+        let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+        tokio::fs::write(tmpdir.path().join("icon.svg"), b"<svg/>").await?;
+        let attrs = HashMap::from([
+            (ATTR_HREF.to_string(), "icon.svg".to_string()),
+            (ATTR_INLINE_THRESHOLD.to_string(), threshold.to_string()),
+        ]);
+        let icon = Icon::new(cfg, Arc::new(tmpdir.path().into()), attrs, 0).await?;
+        match icon.run().await? {
+            TrunkAssetPipelineOutput::Icon(out) => Ok(out.icon_ref),
+            _ => anyhow::bail!("unexpected pipeline output"),
+        }
+    }
+
+    #[tokio::test]
+    async fn preload_follows_icon() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.filehash = false;
+        tokio::fs::create_dir_all(&cfg.staging_dist).await?;
+        tokio::fs::write(tmpdir.path().join("icon.svg"), b"<svg/>").await?;
+        let attrs = HashMap::from([
+            (ATTR_HREF.to_string(), "icon.svg".to_string()),
+            ("data-integrity".to_string(), "none".to_string()),
+            ("data-preload".to_string(), String::new()),
+        ]);
+        let icon = Icon::new(Arc::new(cfg), Arc::new(tmpdir.path().into()), attrs, 0).await?;
+        let TrunkAssetPipelineOutput::Icon(out) = icon.run().await? else {
+            anyhow::bail!("unexpected pipeline output");
+        };
+
+        let mut dom = Document::new(
+            r#"<html><head><link data-trunk-id="0"></head></html>"#,
+            Default::default(),
+        )?;
+        out.finalize(&mut dom).await?;
+        let html = String::from_utf8(dom.into_inner())?;
+        anyhow::ensure!(
+            html.contains(
+                r#"<link rel="icon" href="/icon.svg"/><link rel="preload" href="/icon.svg" as="image" type="image/svg+xml">"#
+            ),
+            "unexpected output: {html}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cross_origin_with_integrity() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.filehash = false;
+        tokio::fs::create_dir_all(&cfg.staging_dist).await?;
+        tokio::fs::write(tmpdir.path().join("icon.svg"), b"<svg/>").await?;
+        let attrs = HashMap::from([
+            (ATTR_HREF.to_string(), "icon.svg".to_string()),
+            ("data-integrity".to_string(), "sha256".to_string()),
+            ("data-cross-origin".to_string(), "anonymous".to_string()),
+        ]);
+        let icon = Icon::new(Arc::new(cfg), Arc::new(tmpdir.path().into()), attrs, 0).await?;
+        let TrunkAssetPipelineOutput::Icon(out) = icon.run().await? else {
+            anyhow::bail!("unexpected pipeline output");
+        };
+
+        let mut dom = Document::new(
+            r#"<html><head><link data-trunk-id="0"></head></html>"#,
+            Default::default(),
+        )?;
+        out.finalize(&mut dom).await?;
+        let html = String::from_utf8(dom.into_inner())?;
+        anyhow::ensure!(
+            html.contains(r#"<link rel="icon" href="/icon.svg""#)
+                && html.contains(r#" integrity="sha256"#)
+                && html.contains(r#" crossorigin="anonymous""#),
+            "unexpected output: {html}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn favicon_stays_at_root() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.filehash = true;
+        cfg.layout.img = Some("img".into());
+        tokio::fs::create_dir_all(&cfg.staging_dist).await?;
+        tokio::fs::write(tmpdir.path().join("favicon.ico"), b"ico").await?;
+        let attrs = HashMap::from([(ATTR_HREF.to_string(), "favicon.ico".to_string())]);
+        let icon = Icon::new(Arc::new(cfg), Arc::new(tmpdir.path().into()), attrs, 0).await?;
+        let TrunkAssetPipelineOutput::Icon(out) = icon.run().await? else {
+            anyhow::bail!("unexpected pipeline output");
+        };
+        match out.icon_ref {
+            IconRef::File(file, _) => assert_eq!(file, "favicon.ico"),
+            IconRef::Inline(_) => anyhow::bail!("expected the icon to be copied"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn inline_threshold() -> Result<()> {
+        match icon_ref("7").await? {
+            IconRef::Inline(data_uri) => assert_eq!(data_uri, "data:image/svg+xml;base64,PHN2Zy8+"),
+            IconRef::File(..) => anyhow::bail!("expected the icon to be inlined"),
+        }
+        // files at the threshold are copied
+        assert!(matches!(icon_ref("6").await?, IconRef::File(..)));
+        assert!(icon_ref("small").await.is_err());
+        Ok(())
+    }
+}
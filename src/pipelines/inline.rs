@@ -1,10 +1,14 @@
 //! Inline asset pipeline.
 
-use super::{trunk_id_selector, AssetFile, Attrs, TrunkAssetPipelineOutput, ATTR_HREF, ATTR_TYPE};
+use super::{
+    trunk_id_selector, AssetFile, Attrs, TrunkAssetPipelineOutput, ATTR_HREF, ATTR_INLINE,
+    ATTR_TYPE,
+};
 use crate::common::html_rewrite::Document;
 use crate::common::nonce_attr;
 use crate::config::rt::RtcBuild;
 use anyhow::{bail, Context, Result};
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -17,10 +21,14 @@ pub struct Inline {
     /// Runtime build config.
     cfg: Arc<RtcBuild>,
     /// The asset file being processed.
-    asset: AssetFile,
+    pub(super) asset: AssetFile,
     /// The type of the asset file that determines how the content of the file
     /// is inserted into `index.html`.
     content_type: ContentType,
+    /// Whether the content should only be inlined at the first occurrence of the file.
+    once: bool,
+    /// Whether the content was already inlined by a previous occurrence of the file.
+    repeated: bool,
 }
 
 impl Inline {
@@ -42,15 +50,32 @@ impl Inline {
         let asset = AssetFile::new(&html_dir, path).await?;
         let content_type =
             ContentType::from_attr_or_ext(attrs.get(ATTR_TYPE), asset.ext.as_deref())?;
+        let once = match attrs.get(ATTR_INLINE).map(|s| s.as_str()) {
+            None | Some("") => false,
+            Some("once") => true,
+            Some(s) => bail!(
+                r#"unknown `{ATTR_INLINE}="{s}"` value for <link data-trunk rel="inline" .../> attr; only "once" is supported"#
+            ),
+        };
 
         Ok(Self {
             id,
             cfg,
             asset,
             content_type,
+            once,
+            repeated: false,
         })
     }
 
+    /// Mark the asset as repeated, if it should only be inlined once and the file was already
+    /// seen before.
+    pub fn mark_repeated(&mut self, seen: &mut HashSet<PathBuf>) {
+        if self.once && !seen.insert(self.asset.path.clone()) {
+            self.repeated = true;
+        }
+    }
+
     /// Spawn the pipeline for this asset type.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
@@ -60,6 +85,15 @@ impl Inline {
     /// Run this pipeline.
     #[tracing::instrument(level = "trace", skip(self))]
     async fn run(self) -> Result<TrunkAssetPipelineOutput> {
+        if self.repeated {
+            return Ok(TrunkAssetPipelineOutput::Inline(InlineOutput {
+                id: self.id,
+                cfg: self.cfg,
+                content: None,
+                content_type: self.content_type,
+            }));
+        }
+
         let rel_path = crate::common::strip_prefix(&self.asset.path);
         tracing::debug!(path = ?rel_path, "reading file content");
         let content = self.asset.read_to_string().await?;
@@ -68,7 +102,7 @@ impl Inline {
         Ok(TrunkAssetPipelineOutput::Inline(InlineOutput {
             id: self.id,
             cfg: self.cfg,
-            content,
+            content: Some(content),
             content_type: self.content_type,
         }))
     }
@@ -128,21 +162,25 @@ pub struct InlineOutput {
     pub id: usize,
     /// Runtime build config.
     pub cfg: Arc<RtcBuild>,
-    /// The content of the target file.
-    pub content: String,
+    /// The content of the target file, or `None` if it was already inlined before.
+    pub content: Option<String>,
     /// The content type of the target file.
     pub content_type: ContentType,
 }
 
 impl InlineOutput {
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
+        let Some(content) = self.content else {
+            return dom.remove(&trunk_id_selector(self.id));
+        };
+
         let nonce = nonce_attr(&self.cfg.create_nonce);
         let html = match self.content_type {
-            ContentType::Html | ContentType::Svg => self.content,
-            ContentType::Css => format!(r#"<style{nonce}>{}</style>"#, self.content),
-            ContentType::Js => format!(r#"<script{nonce}>{}</script>"#, self.content),
+            ContentType::Html | ContentType::Svg => content,
+            ContentType::Css => format!(r#"<style{nonce}>{content}</style>"#),
+            ContentType::Js => format!(r#"<script{nonce}>{content}</script>"#),
             #[rustfmt::skip]
-            ContentType::Module => format!(r#"<script type="module"{nonce}>{}</script>"#, self.content),
+            ContentType::Module => format!(r#"<script type="module"{nonce}>{content}</script>"#),
         };
 
         dom.replace_with_html(&trunk_id_selector(self.id), &html)
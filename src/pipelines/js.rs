@@ -1,14 +1,20 @@
 //! JS asset pipeline.
 
 use super::{
-    data_target_path, AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_NO_MINIFY,
-    ATTR_SRC,
+    data_cross_origin, data_minify_level, data_public_url, data_target_file, AssetFile, AttrWriter,
+    Attrs, FileNamePattern, TrunkAssetPipelineOutput, ATTR_SRC,
 };
 use crate::{
     common::{html_rewrite::Document, nonce_attr, target_path},
-    config::rt::RtcBuild,
+    config::{
+        rt::RtcBuild,
+        types::{BaseUrl, CrossOrigin},
+    },
     pipelines::AssetFileType,
-    processing::integrity::{IntegrityType, OutputDigest},
+    processing::{
+        integrity::{IntegrityType, OutputDigest},
+        minify::MinifyLevel,
+    },
 };
 use anyhow::{Context, Result};
 use std::path::PathBuf;
@@ -22,17 +28,23 @@ pub struct Js {
     /// Runtime build config.
     cfg: Arc<RtcBuild>,
     /// The asset file being processed.
-    asset: AssetFile,
+    pub(super) asset: AssetFile,
     /// The attributes to be placed on the output script tag.
     attrs: Attrs,
     /// The required integrity setting
     integrity: IntegrityType,
+    /// The cross-origin setting for loading the resource
+    cross_origin: Option<CrossOrigin>,
+    /// The public URL of the output file
+    public_url: BaseUrl,
     /// If it's a JavaScript module (vs a classic script)
     module: bool,
-    /// Whether to minify or not
-    no_minify: bool,
+    /// How to minify
+    minify_level: MinifyLevel,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// The pattern for the name of the output file.
+    file_name: FileNamePattern,
 }
 
 impl Js {
@@ -51,9 +63,11 @@ impl Js {
         let asset = AssetFile::new(&html_dir, path).await?;
 
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
+        let cross_origin = data_cross_origin(&attrs)?;
+        let public_url = data_public_url(&attrs, &cfg)?;
         let module = attrs.get("type").map(|s| s.as_str()) == Some("module");
-        let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
-        let target_path = data_target_path(&attrs)?;
+        let minify_level = data_minify_level(&attrs)?;
+        let (target_path, file_name) = data_target_file(&attrs, &cfg.layout.js, cfg.filehash)?;
 
         Ok(Self {
             id,
@@ -62,8 +76,11 @@ impl Js {
             module,
             attrs,
             integrity,
-            no_minify,
+            cross_origin,
+            public_url,
+            minify_level,
             target_path,
+            file_name,
         })
     }
 
@@ -87,13 +104,14 @@ impl Js {
             .copy(
                 &self.cfg.staging_dist,
                 &result_dir,
-                self.cfg.filehash,
-                self.cfg.minify_asset(self.no_minify),
+                &self.file_name,
+                self.cfg.minify_level(self.minify_level),
                 if self.module {
                     AssetFileType::Mjs
                 } else {
                     AssetFileType::Js
                 },
+                self.cfg.source_maps,
             )
             .await?;
         tracing::debug!(path = ?rel_path, file = ?file, "finished copying & hashing js");
@@ -110,9 +128,12 @@ impl Js {
         Ok(TrunkAssetPipelineOutput::Js(JsOutput {
             cfg: self.cfg.clone(),
             id: self.id,
+            source: self.asset.path.clone(),
             file,
             attrs: self.attrs,
             integrity,
+            cross_origin: self.cross_origin,
+            public_url: self.public_url,
         }))
     }
 }
@@ -123,25 +144,34 @@ pub struct JsOutput {
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: usize,
+    /// The source file of the asset.
+    pub source: PathBuf,
     /// Name of the finalized output file.
     pub file: String,
     /// The attributes to be added to the script tag.
     pub attrs: Attrs,
     /// The digest for the integrity attribute
     pub integrity: OutputDigest,
+    /// The cross-origin setting for loading the resource
+    pub cross_origin: Option<CrossOrigin>,
+    /// The public URL of the output file
+    pub public_url: BaseUrl,
 }
 
 impl JsOutput {
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
         let mut attrs = self.attrs;
         self.integrity.insert_into(&mut attrs);
+        if let Some(cross_origin) = self.cross_origin {
+            cross_origin.insert_into(&mut attrs);
+        }
 
         dom.replace_with_html(
             &super::trunk_script_id_selector(self.id),
             &format!(
                 r#"<script src="{base}{file}"{attrs}{nonce}></script>"#,
                 attrs = AttrWriter::new(&attrs, AttrWriter::EXCLUDE_SCRIPT),
-                base = &self.cfg.public_url,
+                base = &self.public_url,
                 file = self.file,
                 nonce = nonce_attr(&self.cfg.create_nonce),
             ),
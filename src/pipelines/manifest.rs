@@ -0,0 +1,151 @@
+//! Records of the files written by the asset pipelines, for the build manifest.
+
+use crate::{
+    common::dist_relative,
+    pipelines::{
+        icon, rust::RustAppOutput, sass, tailwind_css, tailwind_css_extra, TrunkAssetPipelineOutput,
+    },
+    processing::integrity::OutputDigest,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The kind of asset a file of the dist dir originates from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AssetKind {
+    Html,
+    RustWasm,
+    RustJs,
+    Rust,
+    Css,
+    Sass,
+    TailwindCss,
+    Js,
+    Icon,
+    CopyFile,
+    CopyDir,
+    Precompressed,
+    Other,
+}
+
+/// A file written to the dist dir by an asset pipeline.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputRecord {
+    /// The name of the file, relative to the dist dir.
+    pub file: String,
+    /// The kind of asset the file belongs to.
+    pub kind: AssetKind,
+    /// The source file the output was created from.
+    pub source: PathBuf,
+    /// The integrity value referencing the file uses, if any.
+    pub integrity: Option<String>,
+}
+
+impl OutputRecord {
+    fn new(
+        file: impl Into<String>,
+        kind: AssetKind,
+        source: impl Into<PathBuf>,
+        integrity: Option<&OutputDigest>,
+    ) -> Self {
+        Self {
+            file: file.into(),
+            kind,
+            source: source.into(),
+            integrity: integrity
+                .and_then(|digest| digest.to_integrity_value())
+                .map(|value| value.to_string()),
+        }
+    }
+}
+
+impl TrunkAssetPipelineOutput {
+    /// The records of the files this output wrote to the dist dir.
+    pub fn records(&self) -> Vec<OutputRecord> {
+        match self {
+            Self::Css(out) => vec![OutputRecord::new(
+                &out.file,
+                AssetKind::Css,
+                &out.source,
+                Some(&out.integrity),
+            )],
+            Self::Sass(out) => match &out.css_ref {
+                sass::CssRef::File(file, integrity) => vec![OutputRecord::new(
+                    file,
+                    AssetKind::Sass,
+                    &out.source,
+                    Some(integrity),
+                )],
+                sass::CssRef::Inline(_) => vec![],
+            },
+            Self::TailwindCss(out) => match &out.css_ref {
+                tailwind_css::CssRef::File(file, integrity) => vec![OutputRecord::new(
+                    file,
+                    AssetKind::TailwindCss,
+                    &out.source,
+                    Some(integrity),
+                )],
+                tailwind_css::CssRef::Inline(_) => vec![],
+            },
+            Self::TailwindCssExtra(out) => match &out.css_ref {
+                tailwind_css_extra::CssExtraRef::File(file, integrity) => {
+                    vec![OutputRecord::new(
+                        file,
+                        AssetKind::TailwindCss,
+                        &out.source,
+                        Some(integrity),
+                    )]
+                }
+                tailwind_css_extra::CssExtraRef::Inline(_) => vec![],
+            },
+            Self::Js(out) => vec![OutputRecord::new(
+                &out.file,
+                AssetKind::Js,
+                &out.source,
+                Some(&out.integrity),
+            )],
+            Self::Icon(out) => match &out.icon_ref {
+                icon::IconRef::File(file, integrity) => vec![OutputRecord::new(
+                    file,
+                    AssetKind::Icon,
+                    &out.source,
+                    Some(integrity),
+                )],
+                icon::IconRef::Inline(_) => vec![],
+            },
+            Self::CopyFile(out) => vec![OutputRecord::new(
+                &out.file,
+                AssetKind::CopyFile,
+                &out.source,
+                None,
+            )],
+            Self::CopyDir(out) => out
+                .files
+                .iter()
+                .map(|(file, source)| OutputRecord::new(file, AssetKind::CopyDir, source, None))
+                .collect(),
+            Self::RustApp(out) => rust_records(out),
+            Self::Inline(_) | Self::None => vec![],
+        }
+    }
+}
+
+/// The records of the files of a Rust app, with the integrities which were recorded for them.
+fn rust_records(out: &RustAppOutput) -> Vec<OutputRecord> {
+    out.files
+        .iter()
+        .filter_map(|file| dist_relative(&out.cfg.staging_dist, file).ok())
+        .map(|file| {
+            let kind = if file == out.wasm_output {
+                AssetKind::RustWasm
+            } else if file == out.js_output {
+                AssetKind::RustJs
+            } else {
+                AssetKind::Rust
+            };
+            let integrity = out.integrities.digest(&file);
+            OutputRecord::new(file.as_str(), kind, &out.source, integrity)
+        })
+        .collect()
+}
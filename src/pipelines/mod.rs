@@ -1,3 +1,4 @@
+mod asset_cache;
 mod copy_dir;
 #[cfg(test)]
 mod copy_dir_test;
@@ -9,16 +10,23 @@ mod html;
 mod icon;
 mod inline;
 mod js;
+mod manifest;
+mod preload;
 mod rust;
 mod sass;
 mod tailwind_css;
 mod tailwind_css_extra;
 
 pub use html::HtmlPipeline;
+pub use manifest::{AssetKind, OutputRecord};
+pub use rust::find_wasm_bindgen_version;
 
 use crate::{
     common::{dist_relative, html_rewrite::Document, path_exists},
-    config::rt::RtcBuild,
+    config::{
+        rt::RtcBuild,
+        types::{BaseUrl, CrossOrigin},
+    },
     pipelines::{
         copy_dir::{CopyDir, CopyDirOutput},
         copy_file::{CopyFile, CopyFileOutput},
@@ -31,7 +39,10 @@ use crate::{
         tailwind_css::{TailwindCss, TailwindCssOutput},
         tailwind_css_extra::{TailwindCssExtra, TailwindCssExtraOutput},
     },
-    processing::minify::{minify_css, minify_js},
+    processing::minify::{
+        css_source_mapping_url, js_source_mapping_url, minify_css, minify_css_with_source_map,
+        minify_js, minify_js_with_source_map, MinifyLevel,
+    },
 };
 use anyhow::{bail, ensure, Context, Result};
 use minify_js::TopLevelMode;
@@ -54,11 +65,20 @@ const ATTR_SRC: &str = "src";
 const ATTR_TYPE: &str = "type";
 const ATTR_REL: &str = "rel";
 const ATTR_NO_MINIFY: &str = "data-no-minify";
+const ATTR_MINIFY_LEVEL: &str = "data-minify-level";
 const ATTR_TARGET_PATH: &str = "data-target-path";
+const ATTR_CROSS_ORIGIN: &str = "data-cross-origin";
+const ATTR_PRELOAD: &str = "data-preload";
+const ATTR_INLINE_THRESHOLD: &str = "data-inline-threshold";
+const ATTR_HASH_ASSETS_IN_CSS: &str = "data-hash-assets-in-css";
+const ATTR_LOAD_PATH: &str = "data-load-path";
+const ATTR_PUBLIC_URL: &str = "data-public-url";
 
 const SNIPPETS_DIR: &str = "snippets";
 const TRUNK_ID: &str = "data-trunk-id";
 const PNG_OPTIMIZATION_LEVEL: u8 = 6;
+/// Files which clients look up at the root of a site, rather than following a reference.
+const ROOT_FILES: [&str; 3] = ["robots.txt", "sitemap.xml", "favicon.ico"];
 
 /// A mapping of all attrs associated with a specific `<link data-trunk .../>` element.
 pub type Attrs = HashMap<String, String>;
@@ -95,6 +115,7 @@ impl TrunkAsset {
         cfg: Arc<RtcBuild>,
         html_dir: Arc<PathBuf>,
         ignore_chan: Option<mpsc::Sender<PathBuf>>,
+        watch_chan: Option<mpsc::UnboundedSender<PathBuf>>,
         reference: TrunkAssetReference,
         id: usize,
     ) -> Result<Self> {
@@ -106,7 +127,7 @@ impl TrunkAsset {
                 )?;
                 Ok(match rel.as_str() {
                     Sass::TYPE_SASS | Sass::TYPE_SCSS => {
-                        Self::Sass(Sass::new(cfg, html_dir, attrs, id).await?)
+                        Self::Sass(Sass::new(cfg, html_dir, watch_chan, attrs, id).await?)
                     }
                     Icon::TYPE_ICON => Self::Icon(Icon::new(cfg, html_dir, attrs, id).await?),
                     Inline::TYPE_INLINE => {
@@ -155,6 +176,22 @@ impl TrunkAsset {
             Self::RustApp(inner) => inner.spawn(),
         }
     }
+
+    /// The source file or directory of this asset, or `None` for Rust apps.
+    pub fn source(&self) -> Option<&Path> {
+        match self {
+            Self::Css(inner) => Some(&inner.asset.path),
+            Self::Sass(inner) => Some(&inner.asset.path),
+            Self::TailwindCss(inner) => Some(&inner.asset.path),
+            Self::TailwindCssExtra(inner) => Some(&inner.asset.path),
+            Self::Js(inner) => Some(&inner.asset.path),
+            Self::Icon(inner) => Some(&inner.asset.path),
+            Self::Inline(inner) => Some(&inner.asset.path),
+            Self::CopyFile(inner) => Some(&inner.asset.path),
+            Self::CopyDir(inner) => Some(&inner.path),
+            Self::RustApp(_) => None,
+        }
+    }
 }
 
 /// The output of a `<trunk-link/>` asset pipeline.
@@ -260,8 +297,11 @@ impl AssetFile {
         })
     }
 
-    /// Copy this asset to the target dir. If hashing is enabled, create a hash from the file
-    /// contents and include it as hex string in the destination file name.
+    /// Copy this asset to the target dir, naming the destination file using the given pattern.
+    /// The hash of the pattern is created from the file contents, as a hex string.
+    ///
+    /// When `source_map` is set, minified JS and CSS files get a source map next to them, named
+    /// like the file with an additional `.map` extension.
     ///
     /// The base file name (stripped path, relative to the base dist dir) is returned if the operation
     /// was successful.
@@ -269,45 +309,94 @@ impl AssetFile {
         &self,
         dist: &Path,
         to_dir: &Path,
-        with_hash: bool,
-        minify: bool,
+        file_name: &FileNamePattern,
+        minify: MinifyLevel,
         file_type: AssetFileType,
+        source_map: bool,
     ) -> Result<String> {
-        let mut bytes = fs::read(&self.path)
+        let bytes = fs::read(&self.path)
             .await
             .with_context(|| format!("error reading file for copying {:?}", &self.path))?;
 
-        bytes = if minify {
-            match file_type {
-                AssetFileType::Css => minify_css(bytes),
+        self.copy_content(
+            bytes, dist, to_dir, file_name, minify, file_type, source_map,
+        )
+        .await
+    }
+
+    /// Like [`Self::copy`], but using the given content instead of the content of the file.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn copy_content(
+        &self,
+        mut bytes: Vec<u8>,
+        dist: &Path,
+        to_dir: &Path,
+        file_name: &FileNamePattern,
+        minify: MinifyLevel,
+        file_type: AssetFileType,
+        source_map: bool,
+    ) -> Result<String> {
+        let source = self.file_name.to_string_lossy();
+        let mut map = None;
+        bytes = if minify != MinifyLevel::None {
+            match &file_type {
+                AssetFileType::Css if source_map => {
+                    let (bytes, css_map) = minify_css_with_source_map(bytes, minify, &source);
+                    map = css_map;
+                    bytes
+                }
+                AssetFileType::Js | AssetFileType::Mjs if source_map => {
+                    let mode = match file_type {
+                        AssetFileType::Mjs => TopLevelMode::Module,
+                        _ => TopLevelMode::Global,
+                    };
+                    let (bytes, js_map) = minify_js_with_source_map(bytes, mode, minify, &source);
+                    map = js_map;
+                    bytes
+                }
+                AssetFileType::Css => minify_css(bytes, minify),
                 AssetFileType::Icon(image_type) => match image_type {
-                    ImageType::Png => oxipng::optimize_from_memory(
-                        bytes.as_ref(),
-                        &Options::from_preset(PNG_OPTIMIZATION_LEVEL),
-                    )
+                    // this is CPU bound, so it must not block the async runtime
+                    ImageType::Png => tokio::task::spawn_blocking(move || {
+                        oxipng::optimize_from_memory(
+                            bytes.as_ref(),
+                            &Options::from_preset(PNG_OPTIMIZATION_LEVEL),
+                        )
+                    })
+                    .await
+                    .context("error joining PNG optimization")?
                     .with_context(|| format!("error optimizing PNG {:?}", &self.path))?,
                     ImageType::Other => bytes,
                 },
-                AssetFileType::Js => minify_js(bytes, TopLevelMode::Global),
-                AssetFileType::Mjs => minify_js(bytes, TopLevelMode::Module),
+                AssetFileType::Js => minify_js(bytes, TopLevelMode::Global, minify),
+                AssetFileType::Mjs => minify_js(bytes, TopLevelMode::Module, minify),
                 _ => bytes,
             }
         } else {
             bytes
         };
 
-        let file_name = if with_hash {
-            format!(
-                "{}-{:x}.{}",
-                &self.file_stem.to_string_lossy(),
-                seahash::hash(bytes.as_ref()),
-                &self.ext.as_deref().unwrap_or_default()
-            )
-        } else {
-            self.file_name.to_string_lossy().into_owned()
-        };
+        let file_name = file_name.file_name(
+            &self.file_stem.to_string_lossy(),
+            seahash::hash(bytes.as_ref()),
+            self.ext.as_deref(),
+        );
 
         let file_path = to_dir.join(&file_name);
+
+        if let Some(map) = map {
+            let map_name = format!("{file_name}.map");
+            let map_path = to_dir.join(&map_name);
+            fs::write(&map_path, map)
+                .await
+                .with_context(|| format!("error writing source map {map_path:?}"))?;
+            let mapping_url = match file_type {
+                AssetFileType::Css => css_source_mapping_url(&map_name),
+                _ => js_source_mapping_url(&map_name),
+            };
+            bytes.extend_from_slice(mapping_url.as_bytes());
+        }
+
         let file_name = dist_relative(dist, &file_path)?;
 
         fs::write(&file_path, bytes)
@@ -337,6 +426,9 @@ pub enum PipelineStage {
     Build,
     /// The stage after asset builds are executed.
     PostBuild,
+    /// The stage after a successful rebuild of `trunk watch` or `trunk serve`, once the dist dir
+    /// is in place. It isn't executed for the initial build.
+    Rebuild,
 }
 
 /// Create the CSS selector for selecting a trunk link by ID.
@@ -376,7 +468,11 @@ impl<'a> AttrWriter<'a> {
         ATTR_SRC,
         ATTR_TYPE,
         ATTR_NO_MINIFY,
+        ATTR_MINIFY_LEVEL,
         ATTR_TARGET_PATH,
+        ATTR_CROSS_ORIGIN,
+        ATTR_LOAD_PATH,
+        ATTR_PUBLIC_URL,
     ];
     /// Whereas on link elements, the MIME type for css is A-OK. You can even specify a custom
     /// MIME type.
@@ -387,12 +483,23 @@ impl<'a> AttrWriter<'a> {
         ATTR_INLINE,
         ATTR_SRC,
         ATTR_NO_MINIFY,
+        ATTR_MINIFY_LEVEL,
         ATTR_TARGET_PATH,
+        ATTR_CROSS_ORIGIN,
+        ATTR_HASH_ASSETS_IN_CSS,
+        ATTR_LOAD_PATH,
+        ATTR_PUBLIC_URL,
     ];
 
     /// Attributes to ignore for <script> tags
-    pub(self) const EXCLUDE_SCRIPT: &'static [&'static str] =
-        &[ATTR_SRC, ATTR_NO_MINIFY, ATTR_TARGET_PATH];
+    pub(self) const EXCLUDE_SCRIPT: &'static [&'static str] = &[
+        ATTR_SRC,
+        ATTR_NO_MINIFY,
+        ATTR_MINIFY_LEVEL,
+        ATTR_TARGET_PATH,
+        ATTR_CROSS_ORIGIN,
+        ATTR_PUBLIC_URL,
+    ];
 
     pub(self) fn new(attrs: &'a Attrs, exclude: &'a [&'a str]) -> Self {
         Self { attrs, exclude }
@@ -424,11 +531,186 @@ impl fmt::Display for AttrWriter<'_> {
     }
 }
 
-/// Get the target path for an asset
-fn data_target_path(attrs: &Attrs) -> Result<Option<PathBuf>> {
+/// A pattern for the name of an output file.
+///
+/// The placeholders `[name]`, `[hash]` and `[ext]` are replaced with the file stem of the source,
+/// the hash of the content and the extension of the source, including its leading dot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileNamePattern(String);
+
+impl FileNamePattern {
+    const NAME: &'static str = "[name]";
+    const HASH: &'static str = "[hash]";
+    const EXT: &'static str = "[ext]";
+
+    /// The pattern used when there is none in the target path, depending on `build.filehash`.
+    pub fn new(filehash: bool) -> Self {
+        match filehash {
+            true => Self(format!("{}-{}{}", Self::NAME, Self::HASH, Self::EXT)),
+            false => Self(format!("{}{}", Self::NAME, Self::EXT)),
+        }
+    }
+
+    /// Parse a pattern from a file name, if it contains any of the placeholders.
+    fn parse(value: &str) -> Option<Self> {
+        [Self::NAME, Self::HASH, Self::EXT]
+            .iter()
+            .any(|placeholder| value.contains(placeholder))
+            .then(|| Self(value.to_string()))
+    }
+
+    /// Create the file name for a source file, and the hash of its content.
+    pub fn file_name(&self, name: &str, hash: u64, ext: Option<&str>) -> String {
+        let mut result = String::with_capacity(self.0.len() + name.len());
+        let mut rest = self.0.as_str();
+        while let Some(start) = rest.find('[') {
+            result.push_str(&rest[..start]);
+            rest = &rest[start..];
+            if let Some(tail) = rest.strip_prefix(Self::NAME) {
+                result.push_str(name);
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix(Self::HASH) {
+                result.push_str(&format!("{hash:x}"));
+                rest = tail;
+            } else if let Some(tail) = rest.strip_prefix(Self::EXT) {
+                if let Some(ext) = ext {
+                    result.push('.');
+                    result.push_str(ext);
+                }
+                rest = tail;
+            } else {
+                result.push('[');
+                rest = &rest[1..];
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+}
+
+/// Get the target path for an asset, falling back to the directory of its category in the layout
+///
+/// The target path must not contain a file name pattern, see [`data_target_file`] for assets
+/// supporting one.
+fn data_target_path(attrs: &Attrs, layout: &Option<PathBuf>) -> Result<Option<PathBuf>> {
+    let (target_path, file_name) = parse_target_path(attrs)?;
+    if file_name.is_some() {
+        bail!(
+            "the `{ATTR_TARGET_PATH}` of this asset must be a directory, file name patterns are only supported for single files"
+        );
+    }
+
+    Ok(target_path.or_else(|| layout.clone()))
+}
+
+/// Get the target path for an asset producing a single file, and the pattern for its file name
+///
+/// The last component of the target path is used as the pattern for the file name, if it contains
+/// any of the `[name]`, `[hash]` or `[ext]` placeholders. Otherwise, the file is named like the
+/// source, with a hash if `filehash` is enabled.
+fn data_target_file(
+    attrs: &Attrs,
+    layout: &Option<PathBuf>,
+    filehash: bool,
+) -> Result<(Option<PathBuf>, FileNamePattern)> {
+    let (target_path, file_name) = parse_target_path(attrs)?;
+    Ok((
+        target_path.or_else(|| layout.clone()),
+        file_name.unwrap_or_else(|| FileNamePattern::new(filehash)),
+    ))
+}
+
+/// Whether a file is looked up at the root of a site, like `robots.txt`
+///
+/// Such files keep their name, and are placed at the root of the dist dir instead of a layout
+/// directory, unless a target path is set.
+fn is_root_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| ROOT_FILES.contains(&name.to_ascii_lowercase().as_str()))
+}
+
+/// Parse the target path of an asset, splitting off a trailing file name pattern
+fn parse_target_path(attrs: &Attrs) -> Result<(Option<PathBuf>, Option<FileNamePattern>)> {
+    let Some(value) = attrs.get(ATTR_TARGET_PATH) else {
+        return Ok((None, None));
+    };
+    let mut target_path: PathBuf = value.trim_end_matches('/').parse()?;
+
+    if target_path.is_absolute()
+        || target_path
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir))
+    {
+        bail!(
+            "Invalid {ATTR_TARGET_PATH} '{}'. Must be a relative path without '..'.",
+            target_path.display()
+        );
+    }
+
+    let file_name = target_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(FileNamePattern::parse);
+    if file_name.is_some() {
+        target_path.pop();
+    }
+
+    ensure!(
+        !target_path.starts_with(".well-known/trunk"),
+        "the target path '{}' is reserved for Trunk's own use",
+        target_path.display()
+    );
+
+    Ok((Some(target_path), file_name))
+}
+
+/// Get a flag of an asset, which may override a global setting
+///
+/// The flag is set when the attribute is present without a value, or with a value of `true`, and
+/// unset with a value of `false`.
+fn data_flag(attrs: &Attrs, name: &str) -> Result<Option<bool>> {
+    attrs
+        .get(name)
+        .map(|val| match val.as_str() {
+            "" | "true" => Ok(true),
+            "false" => Ok(false),
+            _ => bail!(r#"invalid value for `{name}`, expected "true", "false" or no value"#),
+        })
+        .transpose()
+}
+
+/// Get the minification level of an asset, `data-no-minify` takes precedence
+fn data_minify_level(attrs: &Attrs) -> Result<MinifyLevel> {
+    if attrs.contains_key(ATTR_NO_MINIFY) {
+        return Ok(MinifyLevel::None);
+    }
+
     Ok(attrs
-        .get(ATTR_TARGET_PATH)
-        .map(|val| val.trim_end_matches('/'))
+        .get(ATTR_MINIFY_LEVEL)
         .map(|val| val.parse())
+        .transpose()?
+        .unwrap_or_default())
+}
+
+/// Get the cross-origin setting for an asset
+fn data_cross_origin(attrs: &Attrs) -> Result<Option<CrossOrigin>> {
+    Ok(attrs
+        .get(ATTR_CROSS_ORIGIN)
+        .map(|val| val.parse::<CrossOrigin>())
         .transpose()?)
 }
+
+/// Get the public URL of an asset's output file, which `data-public-url` overrides for assets
+/// served from a different host
+fn data_public_url(attrs: &Attrs, cfg: &RtcBuild) -> Result<BaseUrl> {
+    let Some(value) = attrs.get(ATTR_PUBLIC_URL).map(|val| val.trim()) else {
+        return Ok(cfg.public_url.clone());
+    };
+    ensure!(
+        !value.is_empty(),
+        "the value of `{ATTR_PUBLIC_URL}` must not be empty"
+    );
+    // the file name gets appended directly, so the URL must always end with a slash
+    Ok(value.parse::<BaseUrl>()?.fix_trailing_slash())
+}
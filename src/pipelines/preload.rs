@@ -0,0 +1,213 @@
+//! Preload links for the output files of copied files and icons.
+
+use super::{data_cross_origin, data_public_url, Attrs, ATTR_PRELOAD};
+use crate::{
+    config::{
+        rt::RtcBuild,
+        types::{BaseUrl, CrossOrigin},
+    },
+    processing::integrity::{IntegrityType, OutputDigest, ATTR_INTEGRITY},
+};
+use anyhow::{bail, ensure, Context, Result};
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+const ATTR_PRELOAD_AS: &str = "data-preload-as";
+
+/// A `<link rel="preload">` requested for the output file of an asset, using `data-preload`.
+#[derive(Clone, Debug)]
+pub struct Preload {
+    /// The `as` attribute, like `font` or `image`.
+    r#as: String,
+    /// The MIME type of the file, if known.
+    r#type: Option<String>,
+    /// The `crossorigin` attribute.
+    cross_origin: Option<CrossOrigin>,
+    /// The integrity of the file, only if requested explicitly.
+    integrity: IntegrityType,
+    /// The public URL of the file.
+    public_url: BaseUrl,
+}
+
+impl Preload {
+    /// The preload of an asset, if it has a `data-preload` attribute.
+    ///
+    /// Unless set using `data-preload-as`, the `as` attribute is inferred from the extension of
+    /// the file.
+    pub fn from_attrs(attrs: &Attrs, path: &Path, cfg: &RtcBuild) -> Result<Option<Self>> {
+        if !attrs.contains_key(ATTR_PRELOAD) {
+            return Ok(None);
+        }
+
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        let font_type = ext.as_deref().and_then(font_type);
+        let image_type = mime_guess::from_path(path)
+            .first()
+            .filter(|mime| mime.type_() == mime_guess::mime::IMAGE)
+            .map(|mime| mime.essence_str().to_string());
+
+        let r#as = match attrs.get(ATTR_PRELOAD_AS).map(|value| value.trim()) {
+            Some(value) => {
+                ensure!(
+                    !value.is_empty() && !value.contains(['"', '<', '>']),
+                    "invalid value of `{ATTR_PRELOAD_AS}`: {value}"
+                );
+                value.to_string()
+            }
+            None if font_type.is_some() => "font".to_string(),
+            None if image_type.is_some() => "image".to_string(),
+            None => bail!(
+                "unable to infer the type of the preload of '{}' from its extension, set it using `{ATTR_PRELOAD_AS}`",
+                path.display()
+            ),
+        };
+
+        // the integrity of a preload must match the one of the request using the file, which
+        // CSS (like `@font-face`) can't set, so it's not added by default
+        let integrity = match attrs.contains_key(ATTR_INTEGRITY) {
+            true => IntegrityType::from_attrs(attrs, cfg)?,
+            false => IntegrityType::None,
+        };
+
+        // fonts must always be fetched in CORS mode, even for the same origin, as do files
+        // with an integrity
+        let cross_origin = data_cross_origin(attrs)?.or_else(|| {
+            (r#as == "font" || integrity != IntegrityType::None).then_some(CrossOrigin::Anonymous)
+        });
+
+        Ok(Some(Self {
+            r#type: font_type.map(str::to_string).or(image_type),
+            r#as,
+            cross_origin,
+            integrity,
+            public_url: data_public_url(attrs, cfg)?,
+        }))
+    }
+
+    /// The link to an output file, relative to the dist dir.
+    pub async fn link(&self, cfg: &RtcBuild, file: &str) -> Result<PreloadLink> {
+        let path = cfg.staging_dist.join(file);
+        let integrity = OutputDigest::generate_async(self.integrity, || tokio::fs::read(&path))
+            .await
+            .with_context(|| format!("failed to generate digest for '{}'", path.display()))?;
+
+        Ok(PreloadLink {
+            href: format!("{}{file}", self.public_url),
+            preload: self.clone(),
+            integrity,
+        })
+    }
+}
+
+/// The MIME type of a font, based on its file extension.
+fn font_type(ext: &str) -> Option<&'static str> {
+    Some(match ext {
+        "woff2" => "font/woff2",
+        "woff" => "font/woff",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        _ => return None,
+    })
+}
+
+/// A `<link rel="preload">` of an output file.
+pub struct PreloadLink {
+    /// The URL of the file, including the public URL.
+    href: String,
+    /// The attributes of the link.
+    preload: Preload,
+    /// The digest of the file, for the integrity attribute.
+    integrity: OutputDigest,
+}
+
+impl Display for PreloadLink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Preload {
+            r#as,
+            r#type,
+            cross_origin,
+            integrity: _,
+            public_url: _,
+        } = &self.preload;
+
+        write!(f, r#"<link rel="preload" href="{}" as="{as}""#, self.href)?;
+        if let Some(r#type) = r#type {
+            write!(f, r#" type="{type}""#)?;
+        }
+        match cross_origin {
+            Some(CrossOrigin::Anonymous) => write!(f, " crossorigin")?,
+            Some(cross_origin) => write!(f, r#" crossorigin="{cross_origin}""#)?,
+            None => {}
+        }
+        if let Some(integrity) = self.integrity.to_integrity_value() {
+            write!(f, r#" integrity="{integrity}""#)?;
+        }
+        write!(f, ">")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    async fn preload(file: &str, attrs: &[(&str, &str)]) -> Result<String> {
+        let tmpdir = tempfile::tempdir()?;
+        let cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        tokio::fs::create_dir_all(&cfg.staging_dist).await?;
+        tokio::fs::write(cfg.staging_dist.join(file), b"abc123").await?;
+
+        let attrs = attrs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .chain([(ATTR_PRELOAD.to_string(), String::new())])
+            .collect::<HashMap<_, _>>();
+        let preload =
+            Preload::from_attrs(&attrs, Path::new(file), &cfg)?.context("missing preload")?;
+        Ok(preload.link(&cfg, file).await?.to_string())
+    }
+
+    #[tokio::test]
+    async fn preload_links() -> Result<()> {
+        assert_eq!(
+            preload("font.woff2", &[]).await?,
+            r#"<link rel="preload" href="/font.woff2" as="font" type="font/woff2" crossorigin>"#
+        );
+        assert_eq!(
+            preload("hero.png", &[]).await?,
+            r#"<link rel="preload" href="/hero.png" as="image" type="image/png">"#
+        );
+        assert_eq!(
+            preload("hero.png", &[("data-cross-origin", "use-credentials")]).await?,
+            r#"<link rel="preload" href="/hero.png" as="image" type="image/png" crossorigin="use-credentials">"#
+        );
+        assert_eq!(
+            preload(
+                "data.json",
+                &[(ATTR_PRELOAD_AS, "fetch"), (ATTR_INTEGRITY, "sha256")]
+            )
+            .await?,
+            r#"<link rel="preload" href="/data.json" as="fetch" crossorigin integrity="sha256-bKE9UspwyIPg8LsQHkJaiehiTeUdstI5JZOvaoQRgJA=">"#
+        );
+        assert_eq!(
+            preload(
+                "data.json",
+                &[
+                    (ATTR_PRELOAD_AS, "fetch"),
+                    ("data-public-url", "https://cdn.example.com")
+                ]
+            )
+            .await?,
+            r#"<link rel="preload" href="https://cdn.example.com/data.json" as="fetch">"#
+        );
+        assert!(preload("data.json", &[]).await.is_err());
+        assert!(preload("data.json", &[(ATTR_PRELOAD_AS, "\"")])
+            .await
+            .is_err());
+        Ok(())
+    }
+}
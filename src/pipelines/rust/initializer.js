@@ -1,6 +1,7 @@
-async function __trunkInitializer(init, source, sourceSize, initializer, initWithObject) {
+async function __trunkInitializer(init, source, sourceSize, initializer, initWithObject, initOptions, streaming) {
   if (initializer === undefined) {
-    return await init(initWithObject ? { module_or_path: source } : source);
+    const module = streaming ? WebAssembly.compileStreaming(fetch(source)) : source;
+    return await init(initWithObject ? { module_or_path: module, ...initOptions } : module);
   }
 
   const {
@@ -50,7 +51,10 @@ async function __trunkInitializer(init, source, sourceSize, initializer, initWit
           new Response(stream, init),
       );
 
-  return init(initWithObject ? { module_or_path: response } : response)
+  // compile the streamed response while it is downloaded, requires it to be sent as `application/wasm`
+  const module = streaming ? WebAssembly.compileStreaming(response) : response;
+
+  return init(initWithObject ? { module_or_path: module, ...initOptions } : module)
       .then((value) => {
         onComplete?.();
         onSuccess?.(value);
@@ -61,3 +65,21 @@ async function __trunkInitializer(init, source, sourceSize, initializer, initWit
         return reason;
       });
 }
+
+async function __trunkChainInitializers(initializers) {
+  // create the instances in order, allowing each initializer to finish before the next one
+  const instances = [];
+  for (const initializer of initializers) {
+    instances.push(await initializer());
+  }
+
+  const chain = (name) => (...args) => instances.forEach((instance) => instance?.[name]?.(...args));
+
+  return {
+    onStart: chain("onStart"),
+    onProgress: chain("onProgress"),
+    onComplete: chain("onComplete"),
+    onSuccess: chain("onSuccess"),
+    onFailure: chain("onFailure"),
+  };
+}
@@ -5,9 +5,13 @@ mod sri;
 mod wasm_bindgen;
 mod wasm_opt;
 
-pub use output::RustAppOutput;
+pub use output::{RustAppOutput, DEFAULT_BINDINGS_NAME};
+pub use wasm_bindgen::find_wasm_bindgen_version;
 
-use super::{data_target_path, Attrs, TrunkAssetPipelineOutput, ATTR_HREF, SNIPPETS_DIR};
+use super::{
+    data_cross_origin, data_flag, data_target_path, Attrs, TrunkAssetPipelineOutput, ATTR_HREF,
+    SNIPPETS_DIR,
+};
 use crate::{
     common::{
         self, apply_data_target_path, check_target_not_found_err, copy_dir_recursive, path_exists,
@@ -19,24 +23,38 @@ use crate::{
         CargoMetadata,
     },
     pipelines::rust::sri::{SriBuilder, SriOptions, SriType},
-    processing::{integrity::IntegrityType, minify::minify_js},
+    processing::{
+        integrity::IntegrityType,
+        minify::{js_source_mapping_url, minify_js, minify_js_with_source_map, MinifyLevel},
+    },
+    progress,
     tools::{self, Application, ToolInformation},
 };
 use anyhow::{anyhow, bail, ensure, Context, Result};
-use cargo_metadata::{Artifact, TargetKind};
+use cargo_metadata::{
+    camino::Utf8PathBuf, diagnostic::DiagnosticLevel, Artifact, Package, PackageId, Target,
+    TargetKind,
+};
 use minify_js::TopLevelMode;
 use seahash::SeaHasher;
 use std::{
     collections::HashSet,
+    ffi::OsString,
     hash::Hasher,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     process::Stdio,
     str::FromStr,
     sync::Arc,
 };
-use tokio::{fs, io::AsyncWriteExt, process::Command, sync::mpsc, task::JoinHandle};
+use tokio::{
+    fs,
+    io::AsyncWriteExt,
+    process::Command,
+    sync::{mpsc, watch},
+    task::JoinHandle,
+};
 use tracing::log;
-use wasm_bindgen::{find_wasm_bindgen_version, WasmBindgenFeatures, WasmBindgenTarget};
+use wasm_bindgen::{parse_wasm_bindgen_args, WasmBindgenFeatures, WasmBindgenTarget};
 use wasm_opt::WasmOptLevel;
 
 /// A Rust application pipeline.
@@ -51,6 +69,12 @@ pub struct RustApp {
     cargo_profile: Option<String>,
     /// The configuration of the features passed to cargo.
     cargo_features: Features,
+    /// Run cargo in offline mode.
+    cargo_offline: bool,
+    /// Require the `Cargo.lock` to be up to date.
+    cargo_locked: bool,
+    /// Require the `Cargo.lock` and cache to be up to date.
+    cargo_frozen: bool,
     /// Is this module main or a worker?
     app_type: RustAppType,
     /// All metadata associated with the target Cargo project.
@@ -82,6 +106,8 @@ pub struct RustApp {
     wasm_opt_params: Vec<String>,
     /// The value of the `--target` flag for wasm-bindgen.
     wasm_bindgen_target: WasmBindgenTarget,
+    /// Additional arguments for wasm-bindgen.
+    wasm_bindgen_args: Vec<String>,
     /// Name for the module. Is binary name if given, otherwise it is the name of the cargo
     /// project.
     name: String,
@@ -95,8 +121,49 @@ pub struct RustApp {
     import_bindings: bool,
     /// Name of the global variable holding the imported WASM bindings
     import_bindings_name: Option<String>,
-    /// The name of the initializer module
-    initializer: Option<PathBuf>,
+    /// The initializer modules, in the order they are run
+    initializers: Vec<PathBuf>,
+    /// When the initializer is started, relative to the download of the WASM file
+    initializer_timing: InitializerTiming,
+    /// A JavaScript expression of additional options passed to the init function of wasm-bindgen
+    init_options: Option<String>,
+    /// Where to inject the script initializing the application, instead of the link's position
+    script_position: Option<ScriptPosition>,
+    /// A selector of the element to inject the script initializing the application into
+    mount: Option<String>,
+    /// Whether to add a preload link for the WASM file
+    preload_wasm: bool,
+    /// The `as` and `type` attributes of the preload link for the WASM file
+    preload_options: SriOptions,
+    /// Create a WebAssembly component from the WASM file using wasm-tools
+    component: bool,
+    /// An optional adapter module for creating the component
+    component_adapter: Option<PathBuf>,
+    /// Whether this worker uses the WASM module of the main app building the same binary
+    shared_wasm: bool,
+    /// How the WASM module is shared between the main app and its workers
+    wasm_sharing: WasmSharing,
+}
+
+/// The WASM module of a main app, shared with the workers using `data-shared-wasm`.
+#[derive(Clone, Debug)]
+pub struct SharedWasm {
+    /// The WASM artifact built by cargo.
+    artifact: PathBuf,
+    /// The WASM file of the main app, relative to the dist dir.
+    wasm_output: String,
+}
+
+/// The side of an app in sharing a WASM module.
+#[derive(Debug, Default)]
+enum WasmSharing {
+    /// The app builds its own WASM module, which isn't shared.
+    #[default]
+    None,
+    /// The main app publishes its WASM module once built.
+    Provide(watch::Sender<Option<SharedWasm>>),
+    /// The worker waits for the WASM module of the main app instead of running cargo.
+    Use(watch::Receiver<Option<SharedWasm>>),
 }
 
 /// Describes how the rust application is used.
@@ -106,6 +173,8 @@ pub enum RustAppType {
     Main,
     /// Used as a web worker.
     Worker,
+    /// Used as a worklet, like an `AudioWorklet` or a CSS `PaintWorklet`.
+    Worklet,
 }
 
 impl FromStr for RustAppType {
@@ -115,6 +184,7 @@ impl FromStr for RustAppType {
         match s {
             "main" => Ok(RustAppType::Main),
             "worker" => Ok(RustAppType::Worker),
+            "worklet" => Ok(RustAppType::Worklet),
             _ => bail!(
                 r#"unknown `data-type="{}"` value for <link data-trunk rel="rust" .../> attr; please ensure the value is lowercase and is a supported type"#,
                 s
@@ -123,6 +193,57 @@ impl FromStr for RustAppType {
     }
 }
 
+/// When the initializer is started, relative to the download of the WASM file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InitializerTiming {
+    /// The WASM file is preloaded from the head, while the initializer is started by the script
+    /// initializing the application, which may run after the download began.
+    #[default]
+    AfterPreload,
+    /// The WASM file isn't preloaded. The initializer is started before the JavaScript bindings
+    /// are loaded, and the WASM file is only fetched once its `onStart` ran.
+    BeforePreload,
+}
+
+impl FromStr for InitializerTiming {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "after-preload" => Ok(InitializerTiming::AfterPreload),
+            "before-preload" => Ok(InitializerTiming::BeforePreload),
+            _ => bail!(
+                r#"unknown `data-initializer-timing="{}"` value for <link data-trunk rel="rust" .../> attr; please use either "after-preload" or "before-preload""#,
+                s
+            ),
+        }
+    }
+}
+
+/// Where the script initializing the application is injected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScriptPosition {
+    /// At the start of the body, before any script of the body.
+    Before,
+    /// At the end of the body, after any script of the body.
+    After,
+}
+
+impl FromStr for ScriptPosition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "before" => Ok(ScriptPosition::Before),
+            "after" => Ok(ScriptPosition::After),
+            _ => bail!(
+                r#"unknown `data-script-position="{}"` value for <link data-trunk rel="rust" .../> attr; please use either "before" or "after""#,
+                s
+            ),
+        }
+    }
+}
+
 impl RustApp {
     pub const TYPE_RUST_APP: &'static str = "rust";
 
@@ -151,7 +272,7 @@ impl RustApp {
         let bin = attrs.get("data-bin").map(|val| val.to_string());
         let target_name = attrs.get("data-target-name").map(|val| val.to_string());
         let keep_debug = attrs.contains_key("data-keep-debug");
-        let typescript = attrs.contains_key("data-typescript");
+        let typescript = data_flag(&attrs, "data-typescript")?.unwrap_or(cfg.typescript);
         let no_demangle = attrs.contains_key("data-no-demangle");
         let app_type = attrs
             .get("data-type")
@@ -171,35 +292,69 @@ impl RustApp {
                     WasmOptLevel::Off
                 }
             });
-        let wasm_opt_params = attrs
-            .get("data-wasm-opt-params")
-            .iter()
-            .flat_map(|val| val.split_whitespace())
-            .map(|val| val.to_string())
-            .collect();
+        let wasm_opt_param_set = match attrs.get("data-wasm-opt-param-set") {
+            Some(name) => {
+                if let Some(config_name) = &cfg.wasm_opt_param_set {
+                    log::warn!("wasm-opt parameter set from configuration ({config_name}) will be overridden with HTML file's more specific setting ({name})");
+                }
+                Some(name)
+            }
+            None => cfg.wasm_opt_param_set.as_ref(),
+        };
+        let mut wasm_opt_params = match wasm_opt_param_set {
+            Some(name) => cfg
+                .wasm_opt_param_sets
+                .get(name)
+                .cloned()
+                .with_context(|| format!("unknown wasm-opt parameter set: {name}"))?,
+            None => vec![],
+        };
+        wasm_opt_params.extend(
+            attrs
+                .get("data-wasm-opt-params")
+                .iter()
+                .flat_map(|val| val.split_whitespace())
+                .map(|val| val.to_string()),
+        );
         let wasm_bindgen_target = attrs
             .get("data-bindgen-target")
             .map(|s| s.parse())
             .transpose()?
             .unwrap_or(match app_type {
                 RustAppType::Main => WasmBindgenTarget::Web,
-                RustAppType::Worker => WasmBindgenTarget::NoModules,
+                RustAppType::Worker | RustAppType::Worklet => WasmBindgenTarget::NoModules,
             });
-        let cross_origin = attrs
-            .get("data-cross-origin")
-            .map(|val| CrossOrigin::from_str(val))
+        let wasm_bindgen_args = attrs
+            .get("data-bindgen-args")
+            .map(|val| parse_wasm_bindgen_args(val))
             .transpose()?
             .unwrap_or_default();
+        let cross_origin = data_cross_origin(&attrs)?.unwrap_or_default();
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
 
         let manifest = CargoMetadata::new(&manifest_href).await?;
         let id = Some(id);
         let name = bin.clone().unwrap_or_else(|| manifest.package.name.clone());
 
-        let loader_shim = attrs.contains_key("data-loader-shim");
+        // a worker sharing the WASM module needs the loader shim to find the hashed file of the
+        // main app, and its own name for not overwriting the files of the main app
+        let shared_wasm = data_flag(&attrs, "data-shared-wasm")?.unwrap_or_default();
+        ensure!(
+            !shared_wasm || app_type == RustAppType::Worker,
+            r#"`data-shared-wasm` requires `data-type="worker"`"#
+        );
+        let name = match shared_wasm {
+            true => format!("{name}_worker"),
+            false => name,
+        };
+
+        // a worklet is always added as a module, by the loader
+        let loader_shim = shared_wasm
+            || app_type == RustAppType::Worklet
+            || attrs.contains_key("data-loader-shim");
         if loader_shim {
             ensure!(
-                app_type == RustAppType::Worker,
+                app_type != RustAppType::Main,
                 "Loader shim has no effect when data-type is \"main\"!"
             );
         }
@@ -221,10 +376,23 @@ impl RustApp {
             }
             None => cfg.cargo_profile.as_ref().cloned(),
         };
+        if let Some(profile) = &cargo_profile {
+            manifest.check_profile(profile).await?;
+        }
 
         // cargo features
 
-        let data_features = attrs.get("data-cargo-features").map(|val| val.to_string());
+        let data_features = match attrs.get("data-cargo-features") {
+            Some(val) => {
+                let features = match val.strip_prefix('@') {
+                    Some(file) => read_features(&html_dir, file).await?,
+                    None => parse_features(val),
+                };
+                check_features(&manifest.package, &features)?;
+                Some(features)
+            }
+            None => None,
+        };
         let data_all_features = attrs.contains_key("data-cargo-all-features");
         let data_no_default_features = attrs.contains_key("data-cargo-no-default-features");
 
@@ -238,7 +406,8 @@ impl RustApp {
             Features::All
         } else if data_no_default_features || data_features.is_some() {
             Features::Custom {
-                features: data_features,
+                // an empty list activates no features, besides the default ones
+                features: data_features.filter(|features| !features.is_empty()),
                 no_default_features: data_no_default_features,
             }
         } else {
@@ -247,6 +416,12 @@ impl RustApp {
             cfg.cargo_features.clone()
         };
 
+        // cargo network & lock file
+
+        let cargo_offline = data_flag(&attrs, "data-cargo-offline")?.unwrap_or(cfg.offline);
+        let cargo_locked = data_flag(&attrs, "data-cargo-locked")?.unwrap_or(cfg.locked);
+        let cargo_frozen = data_flag(&attrs, "data-cargo-frozen")?.unwrap_or(cfg.frozen);
+
         // skip
 
         let skip_build = attrs.contains_key("data-trunk-skip");
@@ -258,19 +433,77 @@ impl RustApp {
 
         // progress function
 
-        let initializer = attrs
+        let initializers = attrs
             .get("data-initializer")
-            .map(|path| PathBuf::from_str(path))
-            .transpose()?
+            .iter()
+            .flat_map(|val| val.split_whitespace())
             .map(|path| {
-                if !path.is_absolute() {
+                let path = PathBuf::from_str(path)?;
+                Ok(if !path.is_absolute() {
                     html_dir.join(path)
                 } else {
                     path
-                }
-            });
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-        let target_path = data_target_path(&attrs)?;
+        let initializer_timing: InitializerTiming = attrs
+            .get("data-initializer-timing")
+            .map(|val| val.parse())
+            .transpose()?
+            .unwrap_or_default();
+        ensure!(
+            initializer_timing == InitializerTiming::AfterPreload || !initializers.is_empty(),
+            "`data-initializer-timing` requires `data-initializer`"
+        );
+
+        let init_options = attrs
+            .get("data-init-options")
+            .map(|val| val.trim().to_string());
+        ensure!(
+            init_options.as_ref().map_or(true, |val| !val.is_empty()),
+            "`data-init-options` must not be empty"
+        );
+
+        let target_path = data_target_path(&attrs, &cfg.layout.wasm)?;
+
+        let script_position = attrs
+            .get("data-script-position")
+            .map(|val| val.parse())
+            .transpose()?;
+
+        let mount = attrs.get("data-mount").cloned();
+        ensure!(
+            mount.is_none() || script_position.is_none(),
+            "`data-mount` can't be combined with `data-script-position`"
+        );
+
+        // preloading would start the download before the initializer
+        let preload_wasm = match (data_flag(&attrs, "data-preload-wasm")?, initializer_timing) {
+            (Some(true), InitializerTiming::BeforePreload) => bail!(
+                r#"`data-preload-wasm` can't be enabled with `data-initializer-timing="before-preload"`"#
+            ),
+            (preload, InitializerTiming::AfterPreload) => preload.unwrap_or(true),
+            (_, InitializerTiming::BeforePreload) => false,
+        };
+        let preload_options = SriOptions {
+            r#as: preload_attr(&attrs, "data-preload-as", "fetch")?,
+            r#type: preload_attr(&attrs, "data-preload-type", "application/wasm")?,
+        };
+
+        // component
+
+        let component = data_flag(&attrs, "data-component")?.unwrap_or_default();
+        let component_adapter = attrs
+            .get("data-component-adapter")
+            .map(|path| html_dir.join(path));
+        if component_adapter.is_some() && !component {
+            log::warn!("data-component-adapter has no effect without data-component");
+        }
+        ensure!(
+            !(shared_wasm && component),
+            "`data-component` can't be combined with `data-shared-wasm`"
+        );
 
         // done
 
@@ -280,6 +513,9 @@ impl RustApp {
             skip_build,
             cargo_profile,
             cargo_features,
+            cargo_offline,
+            cargo_locked,
+            cargo_frozen,
             manifest,
             ignore_chan,
             bin,
@@ -292,6 +528,7 @@ impl RustApp {
             wasm_opt,
             wasm_opt_params,
             wasm_bindgen_target,
+            wasm_bindgen_args,
             app_type,
             name,
             loader_shim,
@@ -299,8 +536,18 @@ impl RustApp {
             sri: SriBuilder::new(integrity),
             import_bindings,
             import_bindings_name,
-            initializer,
+            initializers,
+            initializer_timing,
+            init_options,
             target_path,
+            script_position,
+            mount,
+            preload_wasm,
+            preload_options,
+            component,
+            component_adapter,
+            shared_wasm,
+            wasm_sharing: WasmSharing::None,
         })
     }
 
@@ -323,11 +570,16 @@ impl RustApp {
         let manifest = CargoMetadata::new(&path).await?;
         let name = manifest.package.name.clone();
         let integrity = IntegrityType::default_unless(cfg.no_sri);
+        let target_path = cfg.layout.wasm.clone();
+        let typescript = cfg.typescript;
 
         Ok(Some(Self {
             id: None,
             skip_build: false,
             cargo_features: cfg.cargo_features.clone(),
+            cargo_offline: cfg.offline,
+            cargo_locked: cfg.locked,
+            cargo_frozen: cfg.frozen,
             cargo_profile: None,
             cfg,
             manifest,
@@ -335,7 +587,7 @@ impl RustApp {
             bin: None,
             target_name: None,
             keep_debug: false,
-            typescript: false,
+            typescript,
             no_demangle: false,
             reference_types: false,
             weak_refs: false,
@@ -343,17 +595,76 @@ impl RustApp {
             wasm_opt_params: Default::default(),
             app_type: RustAppType::Main,
             wasm_bindgen_target: WasmBindgenTarget::Web,
+            wasm_bindgen_args: Default::default(),
             name,
             loader_shim: false,
             cross_origin: Default::default(),
             sri: SriBuilder::new(integrity),
             import_bindings: true,
             import_bindings_name: None,
-            initializer: None,
-            target_path: None,
+            initializers: vec![],
+            initializer_timing: InitializerTiming::AfterPreload,
+            init_options: None,
+            target_path,
+            script_position: None,
+            mount: None,
+            preload_wasm: true,
+            preload_options: SriOptions::default()
+                .r#as("fetch")
+                .r#type("application/wasm"),
+            component: false,
+            component_adapter: None,
+            shared_wasm: false,
+            wasm_sharing: WasmSharing::None,
         }))
     }
 
+    /// Whether this is the main application of the page, not a worker.
+    pub fn is_main(&self) -> bool {
+        self.app_type == RustAppType::Main
+    }
+
+    /// The name of the binary to build, if set explicitly.
+    pub fn bin(&self) -> Option<&str> {
+        self.bin.as_deref()
+    }
+
+    /// The selector of the element the application is mounted at, if any.
+    pub fn mount(&self) -> Option<&str> {
+        self.mount.as_deref()
+    }
+
+    /// The name of the global variable the bindings are assigned to, if they are imported.
+    pub fn bindings_name(&self) -> Option<&str> {
+        self.import_bindings.then(|| {
+            self.import_bindings_name
+                .as_deref()
+                .unwrap_or(DEFAULT_BINDINGS_NAME)
+        })
+    }
+
+    /// The Cargo manifest and the binary the application is built from.
+    pub fn binary(&self) -> (String, Option<String>) {
+        (self.manifest.manifest_path.clone(), self.bin.clone())
+    }
+
+    /// Whether this worker uses the WASM module of the main app building the same binary.
+    pub fn uses_shared_wasm(&self) -> bool {
+        self.shared_wasm
+    }
+
+    /// Publish the WASM module of this main app, for the workers sharing it.
+    pub fn share_wasm(&mut self) -> watch::Receiver<Option<SharedWasm>> {
+        let (tx, rx) = watch::channel(None);
+        self.wasm_sharing = WasmSharing::Provide(tx);
+        rx
+    }
+
+    /// Use the WASM module published by the main app, instead of building one.
+    pub fn use_shared_wasm(&mut self, rx: watch::Receiver<Option<SharedWasm>>) {
+        self.wasm_sharing = WasmSharing::Use(rx);
+    }
+
     /// Spawn a new pipeline.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<TrunkAssetPipelineOutput>> {
@@ -366,20 +677,56 @@ impl RustApp {
             return Ok(TrunkAssetPipelineOutput::None);
         }
 
+        // wait for the main app to build the shared WASM module
+        let shared = match &mut self.wasm_sharing {
+            WasmSharing::Use(rx) => Some(
+                rx.wait_for(Option::is_some)
+                    .await
+                    .ok()
+                    .and_then(|shared| (*shared).clone())
+                    .context("the main app sharing its WASM module failed to build")?,
+            ),
+            _ => None,
+        };
+
         // run the cargo build
-        let wasm = self.cargo_build().await.context("running cargo build")?;
+        let wasm = match &shared {
+            Some(shared) => shared.artifact.clone(),
+            None => {
+                progress::phase("cargo");
+                self.cargo_build().await.context("running cargo build")?
+            }
+        };
 
         // run wasm-bindgen
+        progress::phase("wasm-bindgen");
         let mut output = self
-            .wasm_bindgen_build(&wasm)
+            .wasm_bindgen_build(&wasm, shared.as_ref())
             .await
             .context("running wasm-bindgen")?;
 
+        if shared.is_some() {
+            // the WASM file is processed further by the main app
+            tracing::debug!("rust build complete, using the shared WASM module");
+            return Ok(TrunkAssetPipelineOutput::RustApp(output));
+        }
+        if let WasmSharing::Provide(tx) = &self.wasm_sharing {
+            tx.send_replace(Some(SharedWasm {
+                artifact: wasm.clone(),
+                wasm_output: output.wasm_output.clone(),
+            }));
+        }
+
         // (optionally) run wasm-opt
         self.wasm_opt_build(&output.wasm_output)
             .await
             .context("running wasm-opt")?;
 
+        // (optionally) run wasm-tools
+        self.wasm_tools_build(&mut output)
+            .await
+            .context("running wasm-tools")?;
+
         // evaluate wasm integrity after all processing
         self.final_digest(&mut output)
             .await
@@ -390,16 +737,36 @@ impl RustApp {
         Ok(TrunkAssetPipelineOutput::RustApp(output))
     }
 
-    #[tracing::instrument(level = "trace", skip(self))]
-    async fn cargo_build(&mut self) -> Result<PathBuf> {
-        tracing::debug!("building {}", &self.manifest.package.name);
+    /// The arguments selecting the cargo features of an app.
+    fn cargo_feature_args(features: &Features) -> Vec<&str> {
+        let mut args = vec![];
+        match features {
+            Features::All => args.push("--all-features"),
+            Features::Custom {
+                features,
+                no_default_features,
+            } => {
+                if *no_default_features {
+                    args.push("--no-default-features");
+                }
 
-        // Spawn the cargo build process.
+                if let Some(cargo_features) = features {
+                    args.push("--features");
+                    args.push(cargo_features);
+                }
+            }
+        }
+        args
+    }
+
+    /// The arguments of the cargo build of the app.
+    fn cargo_build_args<'a>(&'a self, color: &'a str) -> Vec<&'a str> {
         let mut args = vec![
             "build",
             "--target=wasm32-unknown-unknown",
             "--manifest-path",
-            &self.manifest.manifest_path,
+            self.manifest.manifest_path.as_str(),
+            color,
         ];
         if let Some(profile) = &self.cargo_profile {
             args.push("--profile");
@@ -407,13 +774,13 @@ impl RustApp {
         } else if self.cfg.release {
             args.push("--release");
         }
-        if self.cfg.offline {
+        if self.cargo_offline {
             args.push("--offline");
         }
-        if self.cfg.frozen {
+        if self.cargo_frozen {
             args.push("--frozen");
         }
-        if self.cfg.locked {
+        if self.cargo_locked {
             args.push("--locked");
         }
         if let Some(bin) = &self.bin {
@@ -423,33 +790,38 @@ impl RustApp {
         if let Some(example) = &self.cfg.cargo_example {
             args.push("--example");
             args.push(example);
+        } else if self.bin.is_none() && self.targets_cdylib() {
+            // don't build the binaries of the package, which may not even support WASM
+            args.push("--lib");
         }
 
-        match &self.cargo_features {
-            Features::All => args.push("--all-features"),
-            Features::Custom {
-                features,
-                no_default_features,
-            } => {
-                if *no_default_features {
-                    args.push("--no-default-features");
-                }
+        args.extend(Self::cargo_feature_args(&self.cargo_features));
+        args
+    }
 
-                if let Some(cargo_features) = features {
-                    args.push("--features");
-                    args.push(cargo_features);
-                }
-            }
-        }
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn cargo_build(&mut self) -> Result<PathBuf> {
+        tracing::debug!("building {}", &self.manifest.package.name);
 
-        let build_res = common::run_command("cargo", "cargo", &args, &self.cfg.working_directory)
-            .await
-            .context("error during cargo build execution");
+        // Spawn the cargo build process.
+        let color = format!("--color={}", common::color_mode());
+        let mut args = self.cargo_build_args(&color);
+        let envs = self.cargo_envs()?;
+
+        let build_res = common::run_command_with_env(
+            "cargo",
+            "cargo",
+            &args,
+            &envs,
+            &self.cfg.working_directory,
+        )
+        .await
+        .context("error during cargo build execution");
 
         // Send cargo's target dir over to the watcher to be ignored. We must do this before
         // checking for errors, otherwise the dir will never be ignored. If we attempt to do
         // this pre-build, the canonicalization will fail and will not be ignored.
-        if let Some(chan) = &mut self.ignore_chan {
+        if let Some(chan) = &self.ignore_chan {
             let _ = chan.try_send(
                 self.manifest
                     .metadata
@@ -468,8 +840,10 @@ impl RustApp {
         let artifacts_out = Command::new("cargo")
             .current_dir(&self.cfg.core.working_directory)
             .args(args.as_slice())
+            .envs(envs.iter().map(|(k, v)| (k, v)))
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
+            .kill_on_drop(true)
             .spawn()
             .context("error spawning cargo build artifacts task")?
             .wait_with_output()
@@ -482,13 +856,17 @@ impl RustApp {
 
         // Stream over cargo messages to find the artifacts we are interested in.
         let reader = std::io::BufReader::new(artifacts_out.stdout.as_slice());
-        let mut artifacts: Vec<Artifact> = cargo_metadata::Message::parse_stream(reader)
+        let messages = cargo_metadata::Message::parse_stream(reader)
             .filter_map(|msg| msg.ok())
+            .collect::<Vec<_>>();
+        let warnings = count_warnings(&messages);
+        let candidates: Vec<Artifact> = messages
+            .into_iter()
             .filter_map(|msg| {
                 tracing::trace!("Cargo message: {msg:?}");
                 match msg {
                     cargo_metadata::Message::CompilerArtifact(art)
-                        if self.is_relevant_artifact(&art) =>
+                        if is_candidate_artifact(&self.manifest.package.id, &art) =>
                     {
                         Some(Ok(art))
                     }
@@ -499,6 +877,16 @@ impl RustApp {
                 }
             })
             .collect::<Result<_>>()?;
+        if self.cfg.deny_warnings && warnings > 0 {
+            bail!(
+                "cargo emitted {warnings} warning{}, failing the build as `build.deny_warnings` is enabled",
+                if warnings == 1 { "" } else { "s" }
+            );
+        }
+        let mut artifacts: Vec<&Artifact> = candidates
+            .iter()
+            .filter(|art| self.is_selected_artifact(art))
+            .collect();
         // If there is already a `link data-trunk rel=rust` in index.html
         // then the --bin flag was passed to the cargo command
         // and it has built just a single binary
@@ -506,26 +894,36 @@ impl RustApp {
             bail!(
                 r#"found more than one target artifact: {names:?}:
  * consider adding `<link data-trunk rel="rust" data-bin={{bin}} />` to the index.html to build only the specified binary
- * or adding `<link data-trunk rel="rust" data-target-name={{artifact}} />` to select the specific artifact by name"#,
-                names = artifacts.iter().map(|a| &a.target.name).collect::<Vec<_>>()
+ * or adding `<link data-trunk rel="rust" data-target-name={{artifact}} />` to select the specific artifact (like a cdylib) by name"#,
+                names = describe_artifacts(&artifacts)
             )
         }
         let Some(artifact) = artifacts.pop() else {
-            bail!("cargo artifacts not found for target crate")
+            match &self.target_name {
+                Some(target_name) if !candidates.is_empty() => bail!(
+                    "no target artifact named `{target_name}` found, candidates are: {names:?}",
+                    names = describe_artifacts(&candidates.iter().collect::<Vec<_>>())
+                ),
+                _ => bail!("cargo artifacts not found for target crate"),
+            }
         };
 
         // From the output artifact, find the path to the WASM file
         let wasm = artifact
             .filenames
-            .into_iter()
+            .iter()
             .find(|path| path.extension().map(|ext| ext == "wasm").unwrap_or(false))
             .context("could not find WASM output after cargo build")?;
 
-        Ok(wasm.into_std_path_buf())
+        Ok(wasm.clone().into_std_path_buf())
     }
 
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn wasm_bindgen_build(&mut self, wasm_path: &Path) -> Result<RustAppOutput> {
+    async fn wasm_bindgen_build(
+        &mut self,
+        wasm_path: &Path,
+        shared: Option<&SharedWasm>,
+    ) -> Result<RustAppOutput> {
         let version = find_wasm_bindgen_version(&self.cfg.tools, &self.manifest);
         let ToolInformation {
             path: wasm_bindgen,
@@ -533,25 +931,41 @@ impl RustApp {
         } = tools::get_info(
             Application::WasmBindgen,
             version.as_deref(),
+            self.cfg.tools.path(Application::WasmBindgen),
             self.cfg.offline,
             &self.cfg.client_options(),
         )
         .await?;
         let wasm_bindgen_features = WasmBindgenFeatures::from_version(&version)?;
+        if self.init_options.is_some() && !wasm_bindgen_features.init_with_object {
+            bail!("`data-init-options` requires wasm-bindgen 0.2.93 or later, found {version}");
+        }
 
         // Ensure our output dir is in place.
         let wasm_bindgen_name = Application::WasmBindgen.name();
         let mode_segment = if self.cfg.release { "release" } else { "debug" };
-        let bindgen_out = self
-            .manifest
-            .metadata
-            .target_directory
-            .join(wasm_bindgen_name)
-            .join(mode_segment);
+        let bindgen_out = match &self.cfg.bindgen_out_dir {
+            Some(dir) => Utf8PathBuf::try_from(dir.join(mode_segment))
+                .context("wasm-bindgen output dir must be a valid UTF-8 path")?,
+            None => self
+                .intermediate_dir()?
+                .join(wasm_bindgen_name)
+                .join(mode_segment),
+        };
         fs::create_dir_all(bindgen_out.as_path())
             .await
             .context("error creating wasm-bindgen output dir")?;
 
+        // A custom output dir might be located outside cargo's target dir, which is ignored
+        // already, so it must be ignored by the watcher too.
+        if let Some(chan) = &mut self.ignore_chan {
+            if self.cfg.bindgen_out_dir.is_some() {
+                if let Ok(path) = bindgen_out.canonicalize() {
+                    let _ = chan.try_send(path);
+                }
+            }
+        }
+
         // Build up args for calling wasm-bindgen.
         let arg_out_path = format!("--out-dir={}", bindgen_out);
         let arg_out_name = format!("--out-name={}", &self.name);
@@ -574,6 +988,7 @@ impl RustApp {
         if !self.typescript {
             args.push("--no-typescript");
         }
+        args.extend(self.wasm_bindgen_args.iter().map(|s| s.as_str()));
 
         // the final base
         let target_path =
@@ -593,8 +1008,10 @@ impl RustApp {
         // Copy the generated WASM & JS loader to the dist dir.
         tracing::debug!("copying generated wasm-bindgen artifacts");
         let hashed_name = self.hashed_wasm_base(wasm_path).await?;
-        let hashed_wasm_name =
-            apply_data_target_path(format!("{hashed_name}_bg.wasm"), &self.target_path);
+        let hashed_wasm_name = match shared {
+            Some(shared) => shared.wasm_output.clone(),
+            None => apply_data_target_path(format!("{hashed_name}_bg.wasm"), &self.target_path),
+        };
 
         let js_name = format!("{}.js", self.name);
         let hashed_js_name =
@@ -620,31 +1037,63 @@ impl RustApp {
             "copying {js_loader_path} to {}",
             js_loader_path_dist.display()
         );
-        self.copy_or_minify_js(
-            js_loader_path,
-            &js_loader_path_dist,
-            match self.wasm_bindgen_target {
-                WasmBindgenTarget::NoModules => TopLevelMode::Global,
-                _ => TopLevelMode::Module,
-            },
-        )
-        .await
-        .context("error minifying or copying JS loader file to stage dir")?;
+        let js_loader_map = self
+            .copy_or_minify_js(
+                js_loader_path,
+                &js_loader_path_dist,
+                match self.wasm_bindgen_target {
+                    WasmBindgenTarget::NoModules => TopLevelMode::Global,
+                    _ => TopLevelMode::Module,
+                },
+            )
+            .await
+            .context("error minifying or copying JS loader file to stage dir")?;
 
-        tracing::debug!("copying {wasm_path} to {}", wasm_path_dist.display());
+        let mut files = vec![js_loader_path_dist.clone()];
+        // a shared WASM file is written by the main app
+        if shared.is_none() {
+            tracing::debug!("copying {wasm_path} to {}", wasm_path_dist.display());
 
-        fs::copy(wasm_path, &wasm_path_dist)
-            .await
-            .context("error copying wasm file to stage dir")?;
+            fs::copy(wasm_path, &wasm_path_dist)
+                .await
+                .context("error copying wasm file to stage dir")?;
+            files.push(wasm_path_dist.clone());
+        }
+        files.extend(js_loader_map);
+
+        // the entry point of the bundler target (and some others) re-exports the bindings from
+        // a second module
+        let bg_js_name = format!("{}_bg.js", self.name);
+        if !self.wasm_bindgen_target.is_initialized_by_trunk()
+            && path_exists(bindgen_out.join(&bg_js_name)).await?
+        {
+            let bg_js_path_dist = self.cfg.staging_dist.join(apply_data_target_path(
+                bg_js_name.clone(),
+                &self.target_path,
+            ));
+
+            tracing::debug!("copying {bg_js_name} to {}", bg_js_path_dist.display());
+            let bg_js_map = self
+                .copy_or_minify_js(
+                    bindgen_out.join(&bg_js_name),
+                    &bg_js_path_dist,
+                    TopLevelMode::Module,
+                )
+                .await
+                .context("error minifying or copying JS bindings file to stage dir")?;
+            files.push(bg_js_path_dist);
+            files.extend(bg_js_map);
+        }
 
         if self.typescript {
             let ts_path = bindgen_out.join(&ts_name);
             let ts_path_dist = self.cfg.staging_dist.join(&hashed_ts_name);
 
             tracing::debug!("copying {ts_path} to {}", ts_path_dist.display());
-            fs::copy(ts_path, ts_path_dist)
+            fs::copy(ts_path, &ts_path_dist)
                 .await
                 .context("error copying TS files to stage dir")?;
+            files.push(ts_path_dist);
         }
 
         if let Some(ref m) = loader_shim_path {
@@ -653,16 +1102,38 @@ impl RustApp {
                 .await
                 .context("error creating loader shim script")?;
 
-            let shim = match self.wasm_bindgen_target {
-                WasmBindgenTarget::Web => {
-                    format!("import init from './{hashed_js_name}';await init();")
+            // the shim is placed next to the loader and the WASM file, unless the WASM file of
+            // the main app is shared
+            let shim = match (self.wasm_bindgen_target, shared) {
+                // a worklet gets a module re-exporting the bindings, to be added using
+                // `addModule`, the app initializes it within the worklet
+                (WasmBindgenTarget::Web, _) if self.app_type == RustAppType::Worklet => format!(
+                    "export * from './{hashed_name}.js';export {{ default }} from './{hashed_name}.js';"
+                ),
+                (WasmBindgenTarget::NoModules, _) if self.app_type == RustAppType::Worklet => {
+                    // worklets can't import classic scripts, so the bindings are included
+                    let bindings = fs::read_to_string(&js_loader_path_dist)
+                        .await
+                        .context("error reading the JS loader for the worklet loader")?;
+                    format!("{bindings}\nexport default wasm_bindgen;\n")
                 }
-                WasmBindgenTarget::NoModules => format!(
-                    r#"importScripts("./{hashed_js_name}");wasm_bindgen("./{hashed_wasm_name}");"#,
+                (WasmBindgenTarget::Web, None) => {
+                    format!("import init from './{hashed_name}.js';await init();")
+                }
+                (WasmBindgenTarget::Web, Some(shared)) => format!(
+                    "import init from './{hashed_name}.js';await init('{}');",
+                    relative_href(self.target_path.as_deref(), &shared.wasm_output)
+                ),
+                (WasmBindgenTarget::NoModules, None) => format!(
+                    r#"importScripts("./{hashed_name}.js");wasm_bindgen("./{hashed_name}_bg.wasm");"#,
                 ),
-                _ => bail!(
+                (WasmBindgenTarget::NoModules, Some(shared)) => format!(
+                    r#"importScripts("./{hashed_name}.js");wasm_bindgen("{}");"#,
+                    relative_href(self.target_path.as_deref(), &shared.wasm_output)
+                ),
+                (target, _) => bail!(
                     "Loader shim can only be created for data-bindgen-target \"web\" or \
-                     \"no-modules\"!"
+                     \"no-modules\", not \"{target}\"!"
                 ),
             };
             loader_f
@@ -673,6 +1144,7 @@ impl RustApp {
                 .flush()
                 .await
                 .context("error writing loader shim script")?;
+            files.push(m.clone());
         }
 
         // Check for any snippets, and copy them over.
@@ -689,6 +1161,9 @@ impl RustApp {
         } else {
             HashSet::new()
         };
+        // sorted, so that the order of their preload links is stable
+        let mut snippets = Vec::from_iter(snippets);
+        snippets.sort();
 
         self.sri
             .record_file(
@@ -699,7 +1174,22 @@ impl RustApp {
             )
             .await?;
 
+        if self.cfg.minify_snippets && self.cfg.should_minify() {
+            for snippet in snippets
+                .iter()
+                .filter(|snippet| snippet.extension().is_some_and(|ext| ext == "js"))
+            {
+                tracing::debug!("minifying snippet '{}'", snippet.display());
+                let snippet_map = self
+                    .copy_or_minify_js(snippet, snippet, TopLevelMode::Module)
+                    .await
+                    .context("error minifying snippet")?;
+                files.extend(snippet_map);
+            }
+        }
+
         for snippet in snippets {
+            files.push(snippet.clone());
             if let Ok(name) = snippet.strip_prefix(&self.cfg.staging_dist) {
                 self.sri
                     .record_file(
@@ -716,46 +1206,54 @@ impl RustApp {
 
         let wasm_size = fs::metadata(&wasm_path_dist).await?.len();
 
-        // initializer
-
-        let initializer = match &self.initializer {
-            Some(initializer) => {
-                let hashed_name = self.hashed_name(initializer).await?;
-                let source = common::strip_prefix(initializer);
-                let target = self.cfg.staging_dist.join(&hashed_name);
-
-                self.copy_or_minify_js(source, &target, TopLevelMode::Module)
-                    .await?;
-
-                self.sri
-                    .record_file(
-                        SriType::ModulePreload,
-                        &hashed_name,
-                        SriOptions::default(),
-                        &target,
-                    )
-                    .await?;
-
-                Some(hashed_name)
-            }
-            None => None,
-        };
+        // initializers
+
+        let mut initializers = Vec::with_capacity(self.initializers.len());
+        for initializer in &self.initializers {
+            let hashed_name = self.hashed_name(initializer).await?;
+            let source = common::strip_prefix(initializer);
+            let target = self.cfg.staging_dist.join(&hashed_name);
+
+            let initializer_map = self
+                .copy_or_minify_js(source, &target, TopLevelMode::Module)
+                .await?;
+            files.push(target.clone());
+            files.extend(initializer_map);
+
+            self.sri
+                .record_file(
+                    SriType::ModulePreload,
+                    &hashed_name,
+                    SriOptions::default(),
+                    &target,
+                )
+                .await?;
+
+            initializers.push(hashed_name);
+        }
 
         // return output
 
         Ok(RustAppOutput {
             id: self.id,
             cfg: self.cfg.clone(),
+            source: PathBuf::from(&self.manifest.manifest_path),
             js_output: hashed_js_name,
             wasm_output: hashed_wasm_name,
             wasm_size,
             r#type: self.app_type,
+            wasm_bindgen_target: self.wasm_bindgen_target,
             cross_origin: self.cross_origin,
             integrities: self.sri.clone(),
             import_bindings: self.import_bindings,
             import_bindings_name: self.import_bindings_name.clone(),
-            initializer,
+            initializers,
+            initializer_timing: self.initializer_timing,
+            init_options: self.init_options.clone(),
             wasm_bindgen_features,
+            files,
+            script_position: self.script_position,
+            mount: self.mount.clone(),
         })
     }
 
@@ -803,9 +1301,12 @@ impl RustApp {
 
     /// create a cache busting hashed name for the wasm file, if enabled.
     async fn hashed_wasm_base(&self, wasm: &Path) -> Result<String> {
-        // Skip the hashed file name for workers as their file name must be named at runtime.
-        // Therefore, workers use the Cargo binary name for file naming.
-        if self.app_type == RustAppType::Worker {
+        // Skip the hashed file name for workers (and worklets) as their file name must be named at
+        // runtime. Therefore, workers use the Cargo binary name for file naming. The same goes for targets
+        // not initialized by Trunk (like bundler or deno), as their modules import each other by
+        // their names, and the external tool takes care of the cache busting.
+        if self.app_type != RustAppType::Main || !self.wasm_bindgen_target.is_initialized_by_trunk()
+        {
             return Ok(self.name.clone());
         }
 
@@ -816,20 +1317,18 @@ impl RustApp {
             .unwrap_or_else(|| self.name.clone()))
     }
 
-    fn is_relevant_artifact(&self, art: &Artifact) -> bool {
-        // package id must match
-        if art.package_id != self.manifest.package.id {
-            return false;
-        }
-
-        // must be cdylib, bin, or example
-        if !(art.target.kind.contains(&TargetKind::Bin)
-            || art.target.kind.contains(&TargetKind::CDyLib)
-            || art.target.kind.contains(&TargetKind::Example))
-        {
-            return false;
-        }
+    /// Whether the target name selects the cdylib of the package.
+    fn targets_cdylib(&self) -> bool {
+        self.target_name.as_deref().is_some_and(|target_name| {
+            self.manifest.package.targets.iter().any(|target| {
+                target.kind.contains(&TargetKind::CDyLib)
+                    && target_name_matches(target_name, target)
+            })
+        })
+    }
 
+    /// Whether a candidate artifact matches the selected example, binary or target name.
+    fn is_selected_artifact(&self, art: &Artifact) -> bool {
         // Are we building an example?
         if let Some(example) = &self.cfg.cargo_example {
             // it must match
@@ -848,7 +1347,7 @@ impl RustApp {
 
         // if we have a target name
         if let Some(target_name) = &self.target_name {
-            if target_name != &art.target.name {
+            if !target_name_matches(target_name, &art.target) {
                 return false;
             }
         }
@@ -856,46 +1355,80 @@ impl RustApp {
         true
     }
 
+    /// Copy a JS file to the stage dir, minifying it when minification is enabled.
+    ///
+    /// Returns the path of the source map, if one was written.
     async fn copy_or_minify_js(
         &self,
         origin_path: impl AsRef<Path>,
         destination_path: &Path,
         mode: TopLevelMode,
-    ) -> Result<()> {
-        let bytes = fs::read(origin_path)
+    ) -> Result<Option<PathBuf>> {
+        let bytes = fs::read(origin_path.as_ref())
             .await
             .context("error reading JS loader file")?;
 
-        let write_bytes = match self.cfg.should_minify() {
-            true => minify_js(bytes, mode),
-            false => bytes,
+        let (mut write_bytes, map) = match (self.cfg.should_minify(), self.cfg.source_maps) {
+            (true, true) => {
+                let source = origin_path
+                    .as_ref()
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                minify_js_with_source_map(bytes, mode, MinifyLevel::default(), &source)
+            }
+            (true, false) => (minify_js(bytes, mode, MinifyLevel::default()), None),
+            (false, _) => (bytes, None),
         };
 
+        let mut map_path = None;
+        if let Some(map) = map {
+            let file_name = destination_path
+                .file_name()
+                .context("JS file has no file name")?
+                .to_string_lossy();
+            let map_name = format!("{file_name}.map");
+            let path = destination_path.with_file_name(&map_name);
+            fs::write(&path, map)
+                .await
+                .context("error writing JS source map to stage dir")?;
+            write_bytes.extend_from_slice(js_source_mapping_url(&map_name).as_bytes());
+            map_path = Some(path);
+        }
+
         fs::write(destination_path, write_bytes)
             .await
             .context("error writing JS loader file to stage dir")?;
 
-        Ok(())
+        Ok(map_path)
+    }
+
+    /// The base directory of the intermediate output of the tools, cargo's target dir by default.
+    fn intermediate_dir(&self) -> Result<Utf8PathBuf> {
+        match &self.cfg.target_dir {
+            Some(dir) => Utf8PathBuf::try_from(dir.clone())
+                .context("the target dir of Trunk must be a valid UTF-8 path"),
+            None => Ok(self.manifest.metadata.target_directory.clone()),
+        }
     }
 
     /// Run `wasm-opt` on the `wasm_path` file, in-place.
     #[tracing::instrument(level = "trace", skip(self))]
     async fn wasm_opt_build(&self, wasm_name: &str) -> Result<()> {
-        // If not in release mode, we skip calling wasm-opt.
-        if !self.cfg.release {
-            return Ok(());
-        }
-
-        // If opt level is off, we skip calling wasm-opt as it wouldn't have any effect.
+        // If opt level is off, we skip calling wasm-opt as it wouldn't have any effect. This is the
+        // default for debug builds, unless a level is requested explicitly.
         if self.wasm_opt == WasmOptLevel::Off {
             log::debug!("wasm-opt is turned off");
             return Ok(());
         }
 
+        progress::phase("wasm-opt");
+
         let version = self.cfg.tools.wasm_opt.as_deref();
         let wasm_opt = tools::get(
             Application::WasmOpt,
             version,
+            self.cfg.tools.path(Application::WasmOpt),
             self.cfg.offline,
             &self.cfg.client_options(),
         )
@@ -905,9 +1438,7 @@ impl RustApp {
         let wasm_opt_name = Application::WasmOpt.name();
         let mode_segment = if self.cfg.release { "release" } else { "debug" };
         let output = self
-            .manifest
-            .metadata
-            .target_directory
+            .intermediate_dir()?
             .join(wasm_opt_name)
             .join(mode_segment);
         fs::create_dir_all(&output)
@@ -931,6 +1462,15 @@ impl RustApp {
             args.push("--enable-reference-types");
         }
 
+        // keep the names and debug info wasm-bindgen was told to keep
+        if self.keep_debug
+            && !arg_opt_params
+                .iter()
+                .any(|param| param == "-g" || param == "--debuginfo")
+        {
+            args.push("-g");
+        }
+
         args.extend(arg_opt_params.iter().map(|s| s.as_str()));
 
         // Invoke wasm-opt.
@@ -948,18 +1488,92 @@ impl RustApp {
         Ok(())
     }
 
+    /// Create a WebAssembly component from the final WASM file, next to it in the dist dir.
+    #[tracing::instrument(level = "trace", skip(self, output))]
+    async fn wasm_tools_build(&self, output: &mut RustAppOutput) -> Result<()> {
+        if !self.component {
+            return Ok(());
+        }
+
+        progress::phase("wasm-tools");
+
+        let version = self.cfg.tools.wasm_tools.as_deref();
+        let wasm_tools = tools::get(
+            Application::WasmTools,
+            version,
+            self.cfg.tools.path(Application::WasmTools),
+            self.cfg.offline,
+            &self.cfg.client_options(),
+        )
+        .await?;
+
+        let source = self.cfg.staging_dist.join(&output.wasm_output);
+        let component_name = match output.wasm_output.strip_suffix(".wasm") {
+            Some(stem) => format!("{stem}.component.wasm"),
+            None => format!("{}.component.wasm", output.wasm_output),
+        };
+        let target = self.cfg.staging_dist.join(&component_name);
+
+        let mut args = vec![
+            OsString::from("component"),
+            OsString::from("new"),
+            source.into_os_string(),
+            OsString::from("--output"),
+            target.clone().into_os_string(),
+        ];
+        if let Some(adapter) = &self.component_adapter {
+            args.push(OsString::from("--adapt"));
+            args.push(adapter.clone().into_os_string());
+        }
+
+        let wasm_tools_name = Application::WasmTools.name();
+        tracing::debug!("calling wasm-tools");
+        common::run_command(
+            wasm_tools_name,
+            &wasm_tools,
+            &args,
+            &self.cfg.working_directory,
+        )
+        .await
+        .map_err(|err| check_target_not_found_err(err, wasm_tools_name))?;
+
+        tracing::info!("created WebAssembly component {component_name}");
+        output.files.push(target);
+
+        Ok(())
+    }
+
+    /// Evaluate the additional environment variables for cargo
+    fn cargo_envs(&self) -> Result<Vec<(&'static str, PathBuf)>> {
+        let Some(wrapper) = &self.cfg.rustc_wrapper else {
+            if let Some(wrapper) = std::env::var_os("RUSTC_WRAPPER") {
+                tracing::debug!("using rustc wrapper from the environment: {wrapper:?}");
+            }
+            return Ok(vec![]);
+        };
+
+        let path = which::which(wrapper)
+            .with_context(|| format!("unable to find rustc wrapper '{wrapper}'"))?;
+        tracing::info!("using rustc wrapper '{wrapper}' for cargo builds");
+
+        Ok(vec![("RUSTC_WRAPPER", path)])
+    }
+
     /// Build the final WASM digest
     #[tracing::instrument(level = "trace", skip(self, output))]
     async fn final_digest(&self, output: &mut RustAppOutput) -> Result<()> {
+        if !self.preload_wasm || !self.wasm_bindgen_target.is_initialized_by_trunk() {
+            // the preload link is the only consumer of the digest
+            return Ok(());
+        }
+
         let final_wasm = self.cfg.staging_dist.join(&output.wasm_output);
         output
             .integrities
             .record_file(
                 SriType::Preload,
                 &output.wasm_output,
-                SriOptions::default()
-                    .r#as("fetch")
-                    .r#type("application/wasm"),
+                self.preload_options.clone(),
                 final_wasm,
             )
             .await?;
@@ -967,3 +1581,726 @@ impl RustApp {
         Ok(())
     }
 }
+
+/// The href of a file of the dist dir, relative to a target path inside the dist dir.
+fn relative_href(target_path: Option<&Path>, file: &str) -> String {
+    let depth = target_path.map_or(0, |path| {
+        path.components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .count()
+    });
+    match depth {
+        0 => format!("./{file}"),
+        depth => format!("{}{file}", "../".repeat(depth)),
+    }
+}
+
+/// Whether an artifact of the package can be the application: a cdylib, bin or example.
+///
+/// Artifacts of other workspace members are never selected, as they are only built as
+/// dependencies of the package.
+fn is_candidate_artifact(package: &PackageId, art: &Artifact) -> bool {
+    &art.package_id == package
+        && (art.target.kind.contains(&TargetKind::Bin)
+            || art.target.kind.contains(&TargetKind::CDyLib)
+            || art.target.kind.contains(&TargetKind::Example))
+}
+
+/// Whether a target has the selected name.
+///
+/// Cargo uses underscores in the names of library targets, so `my-app` also selects the `my_app`
+/// cdylib of a package.
+fn target_name_matches(name: &str, target: &Target) -> bool {
+    name == target.name
+        || (target.kind.contains(&TargetKind::CDyLib) && name.replace('-', "_") == target.name)
+}
+
+/// The names of artifacts along with their kinds, like `app (bin)`.
+fn describe_artifacts(artifacts: &[&Artifact]) -> Vec<String> {
+    artifacts
+        .iter()
+        .map(|art| {
+            let kinds = art.target.kind.iter().map(|kind| kind.to_string());
+            format!(
+                "{} ({})",
+                art.target.name,
+                kinds.collect::<Vec<_>>().join(", ")
+            )
+        })
+        .collect()
+}
+
+/// The number of compiler warnings among cargo's messages.
+///
+/// Cargo replays the messages of fresh units, so this includes the warnings of previous builds.
+fn count_warnings(messages: &[cargo_metadata::Message]) -> usize {
+    messages
+        .iter()
+        .filter(|msg| {
+            matches!(msg, cargo_metadata::Message::CompilerMessage(msg)
+                if msg.message.level == DiagnosticLevel::Warning)
+        })
+        .count()
+}
+
+/// An attribute of the WASM preload link, which is omitted if the value is empty.
+fn preload_attr(attrs: &Attrs, name: &str, default: &str) -> Result<Option<String>> {
+    let value = attrs.get(name).map_or(default, |value| value.trim());
+    ensure!(
+        !value.contains(['"', '<', '>']),
+        "invalid value of `{name}`: {value}"
+    );
+    Ok((!value.is_empty()).then(|| value.to_string()))
+}
+
+/// Read a list of cargo features from a file, relative to the HTML file.
+///
+/// Features may be separated by whitespace, commas or newlines. Lines starting with `#` are
+/// ignored.
+async fn read_features(html_dir: &Path, file: &str) -> Result<String> {
+    let mut path = PathBuf::new();
+    path.extend(file.split('/'));
+    let path = html_dir.join(path);
+    let content = fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("error reading cargo features from {}", path.display()))?;
+    Ok(parse_features(&content))
+}
+
+/// Check that a comma separated list of features only contains valid feature names, which are
+/// features of the package or of one of its dependencies (using `dependency/feature`).
+fn check_features(package: &Package, features: &str) -> Result<()> {
+    fn valid_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_')
+            && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '+' | '.'))
+    }
+
+    for feature in features.split(',').filter(|feature| !feature.is_empty()) {
+        match feature.split_once('/') {
+            Some((dependency, dependency_feature)) => ensure!(
+                valid_name(dependency) && valid_name(dependency_feature),
+                "invalid cargo feature `{feature}` in `data-cargo-features`"
+            ),
+            None => {
+                ensure!(
+                    valid_name(feature),
+                    "invalid cargo feature `{feature}` in `data-cargo-features`"
+                );
+                let optional_dependency = package.dependencies.iter().any(|dependency| {
+                    dependency.optional
+                        && dependency.rename.as_ref().unwrap_or(&dependency.name) == feature
+                });
+                ensure!(
+                    package.features.contains_key(feature) || optional_dependency,
+                    "unknown cargo feature `{feature}` of package `{}` in `data-cargo-features`",
+                    package.name
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse the content of a features file into a comma separated list.
+fn parse_features(content: &str) -> String {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|feature| !feature.is_empty())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::future::Future;
+
+    /// A stand-in for `wasm-opt`, recording its arguments and copying the input to the output.
+    #[cfg(unix)]
+    const FAKE_WASM_OPT: &str = r#"#!/bin/sh
+if [ "$1" = "--version" ]; then echo "wasm-opt version 116"; exit 0; fi
+for arg; do
+  case "$arg" in
+    --output=*) out="${arg#--output=}" ;;
+    -*) ;;
+    *) in="$arg" ;;
+  esac
+done
+echo "$@" > "$(dirname "$0")/invocation"
+cp "$in" "$out"
+"#;
+
+    /// A fixture creating the stand-in for `wasm-opt` in a directory.
+    #[cfg(unix)]
+    async fn fake_wasm_opt(dir: &Path) -> Result<PathBuf> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tool = dir.join("wasm-opt");
+        fs::write(&tool, FAKE_WASM_OPT).await?;
+        fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).await?;
+        Ok(tool)
+    }
+
+    /// A fixture creating a minimal crate to build the pipeline for.
+    async fn setup_test_crate(dir: &Path) -> Result<()> {
+        fs::write(
+            dir.join("Cargo.toml"),
+            r#"[package]
+name = "app"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+path = "lib.rs"
+
+[features]
+foo = []
+bar = []
+baz = []
+qux = []
+"#,
+        )
+        .await?;
+        fs::write(dir.join("lib.rs"), "").await?;
+        Ok(())
+    }
+
+    /// Create the app of a `<link data-trunk rel="rust">` with the given attributes.
+    fn test_app(
+        cfg: &Arc<RtcBuild>,
+        dir: &Path,
+        attrs: &[(&str, &str)],
+    ) -> impl Future<Output = Result<RustApp>> {
+        let attrs = attrs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        RustApp::new(cfg.clone(), Arc::new(dir.to_path_buf()), None, attrs, 0)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn wasm_opt_explicit_level_in_debug() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+
+        let mut cfg = RtcBuild::new_test(dir).await?;
+        cfg.tools.wasm_opt_path = Some(fake_wasm_opt(dir).await?);
+        let cfg = Arc::new(cfg);
+        ensure!(!cfg.release, "the test config must be a debug build");
+        let attrs = [("data-wasm-opt".to_string(), "s".to_string())].into();
+        let app = RustApp::new(cfg.clone(), Arc::new(dir.to_path_buf()), None, attrs, 0).await?;
+        ensure!(app.wasm_opt == WasmOptLevel::S);
+
+        fs::write(cfg.staging_dist.join("app_bg.wasm"), b"\0asm").await?;
+        app.wasm_opt_build("app_bg.wasm").await?;
+
+        let invocation = fs::read_to_string(dir.join("invocation")).await?;
+        ensure!(
+            invocation.contains("-Os"),
+            "unexpected invocation: {invocation}"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn wasm_opt_keeps_debug_info() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+
+        let mut cfg = RtcBuild::new_test(dir).await?;
+        cfg.tools.wasm_opt_path = Some(fake_wasm_opt(dir).await?);
+        let cfg = Arc::new(cfg);
+        fs::write(cfg.staging_dist.join("app_bg.wasm"), b"\0asm").await?;
+
+        for (keep_debug, expected) in [(false, false), (true, true)] {
+            let mut attrs = Attrs::from([("data-wasm-opt".to_string(), "s".to_string())]);
+            if keep_debug {
+                attrs.insert("data-keep-debug".to_string(), String::new());
+            }
+            let app =
+                RustApp::new(cfg.clone(), Arc::new(dir.to_path_buf()), None, attrs, 0).await?;
+            app.wasm_opt_build("app_bg.wasm").await?;
+
+            let invocation = fs::read_to_string(dir.join("invocation")).await?;
+            ensure!(
+                invocation.split_whitespace().any(|arg| arg == "-g") == expected,
+                "unexpected invocation with keep debug {keep_debug}: {invocation}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn wasm_opt_param_sets() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+
+        let mut cfg = RtcBuild::new_test(dir).await?;
+        cfg.tools.wasm_opt_path = Some(fake_wasm_opt(dir).await?);
+        cfg.wasm_opt_param_sets = [
+            ("size".to_string(), vec!["--strip-producers".to_string()]),
+            ("speed".to_string(), vec!["--fast-math".to_string()]),
+        ]
+        .into();
+        cfg.wasm_opt_param_set = Some("size".to_string());
+        let cfg = Arc::new(cfg);
+        fs::write(cfg.staging_dist.join("app_bg.wasm"), b"\0asm").await?;
+        let app = |attrs: &[(&str, &str)]| {
+            let attrs = [("data-wasm-opt", "s")]
+                .iter()
+                .chain(attrs)
+                .copied()
+                .collect::<Vec<_>>();
+            test_app(&cfg, dir, &attrs)
+        };
+
+        for (attrs, expected, unexpected) in [
+            (&[][..], "--strip-producers", "--fast-math"),
+            (
+                &[
+                    ("data-wasm-opt-param-set", "speed"),
+                    ("data-wasm-opt-params", "--enable-simd"),
+                ][..],
+                "--fast-math --enable-simd",
+                "--strip-producers",
+            ),
+        ] {
+            app(attrs).await?.wasm_opt_build("app_bg.wasm").await?;
+
+            let invocation = fs::read_to_string(dir.join("invocation")).await?;
+            ensure!(
+                invocation.contains(expected) && !invocation.contains(unexpected),
+                "unexpected invocation with {attrs:?}: {invocation}"
+            );
+        }
+
+        ensure!(
+            app(&[("data-wasm-opt-param-set", "other")]).await.is_err(),
+            "unknown parameter sets must fail"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn wasm_opt_output_in_target_dir() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+
+        let mut cfg = RtcBuild::new_test(dir).await?;
+        cfg.tools.wasm_opt_path = Some(fake_wasm_opt(dir).await?);
+        cfg.target_dir = Some(dir.join("trunk-target"));
+        let cfg = Arc::new(cfg);
+        let attrs = [("data-wasm-opt".to_string(), "s".to_string())].into();
+        let app = RustApp::new(cfg.clone(), Arc::new(dir.to_path_buf()), None, attrs, 0).await?;
+
+        fs::write(cfg.staging_dist.join("app_bg.wasm"), b"\0asm").await?;
+        app.wasm_opt_build("app_bg.wasm").await?;
+
+        let output = dir.join("trunk-target/wasm-opt/debug/app_bg.wasm");
+        ensure!(output.is_file(), "missing output in the target dir");
+        ensure!(
+            !app.manifest
+                .metadata
+                .target_directory
+                .join("wasm-opt")
+                .exists(),
+            "unexpected output in cargo's target dir"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn wasm_opt_off_by_default_in_debug() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+
+        let cfg = Arc::new(RtcBuild::new_test(dir).await?);
+        let app = RustApp::new(
+            cfg,
+            Arc::new(dir.to_path_buf()),
+            None,
+            Default::default(),
+            0,
+        )
+        .await?;
+        ensure!(app.wasm_opt == WasmOptLevel::Off);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn typescript_per_link() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+
+        let mut cfg = RtcBuild::new_test(dir).await?;
+        cfg.typescript = true;
+        let cfg = Arc::new(cfg);
+        let app = |value| test_app(&cfg, dir, &[("data-typescript", value)]);
+
+        ensure!(
+            test_app(&cfg, dir, &[]).await?.typescript,
+            "the config must be the default"
+        );
+        ensure!(app("").await?.typescript);
+        ensure!(app("true").await?.typescript);
+        ensure!(!app("false").await?.typescript);
+        ensure!(app("no").await.is_err(), "invalid values must fail");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cargo_network_flags_per_link() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+
+        let mut cfg = RtcBuild::new_test(dir).await?;
+        cfg.locked = true;
+        let cfg = Arc::new(cfg);
+        let app = |attrs: &[(&str, &str)]| test_app(&cfg, dir, attrs);
+        let flags = |app: &RustApp| {
+            let args = app.cargo_build_args("--color=never");
+            ["--offline", "--locked", "--frozen"]
+                .into_iter()
+                .filter(|flag| args.contains(flag))
+                .collect::<Vec<_>>()
+        };
+
+        // the global settings apply by default
+        ensure!(flags(&app(&[]).await?) == ["--locked"]);
+        // a vendored crate building offline, in the same build
+        ensure!(flags(&app(&[("data-cargo-offline", "")]).await?) == ["--offline", "--locked"]);
+        ensure!(
+            flags(
+                &app(&[
+                    ("data-cargo-locked", "false"),
+                    ("data-cargo-frozen", "true")
+                ])
+                .await?
+            ) == ["--frozen"]
+        );
+        ensure!(
+            app(&[("data-cargo-offline", "yes")]).await.is_err(),
+            "invalid values must fail"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cargo_features_from_file() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+        fs::write(
+            dir.join("features.txt"),
+            "# generated\nfoo, bar\n\nbaz qux\n",
+        )
+        .await?;
+
+        let cfg = Arc::new(RtcBuild::new_test(dir).await?);
+        let app = RustApp::new(
+            cfg,
+            Arc::new(dir.to_path_buf()),
+            None,
+            [(
+                "data-cargo-features".to_string(),
+                "@features.txt".to_string(),
+            )]
+            .into(),
+            0,
+        )
+        .await?;
+        ensure!(
+            matches!(&app.cargo_features, Features::Custom { features: Some(features), .. } if features == "foo,bar,baz,qux"),
+            "unexpected features: {:?}",
+            app.cargo_features
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn wasm_preload_options() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+
+        let cfg = Arc::new(RtcBuild::new_test(dir).await?);
+        let html_dir = Arc::new(dir.to_path_buf());
+        for (attrs, expected) in [
+            (vec![], r#" as="fetch" type="application/wasm""#),
+            (
+                vec![("data-preload-as", "webassembly")],
+                r#" as="webassembly" type="application/wasm""#,
+            ),
+            (
+                vec![("data-preload-as", "fetch"), ("data-preload-type", "")],
+                r#" as="fetch""#,
+            ),
+        ] {
+            let attrs = attrs
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            let app = RustApp::new(cfg.clone(), html_dir.clone(), None, attrs, 0).await?;
+            ensure!(
+                app.preload_options.to_string() == expected,
+                "unexpected preload options: {:?}",
+                app.preload_options
+            );
+        }
+
+        let attrs = [(
+            "data-preload-type".to_string(),
+            r#"x" onload="y"#.to_string(),
+        )]
+        .into();
+        ensure!(
+            RustApp::new(cfg, html_dir, None, attrs, 0).await.is_err(),
+            "must reject quotes"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn initializers_keep_their_order() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+
+        let cfg = Arc::new(RtcBuild::new_test(dir).await?);
+        let attrs = [(
+            "data-initializer".to_string(),
+            "second.js first.js".to_string(),
+        )]
+        .into();
+        let app = RustApp::new(cfg, Arc::new(dir.to_path_buf()), None, attrs, 0).await?;
+        ensure!(
+            app.initializers == [dir.join("second.js"), dir.join("first.js")],
+            "unexpected initializers: {:?}",
+            app.initializers
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn initializer_timing_disables_preload() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+
+        let cfg = Arc::new(RtcBuild::new_test(dir).await?);
+        let app = |attrs: &[(&str, &str)]| test_app(&cfg, dir, attrs);
+
+        let before = app(&[
+            ("data-initializer", "init.js"),
+            ("data-initializer-timing", "before-preload"),
+        ])
+        .await?;
+        ensure!(before.initializer_timing == InitializerTiming::BeforePreload);
+        ensure!(!before.preload_wasm, "must not preload the WASM file");
+
+        let after = app(&[("data-initializer", "init.js")]).await?;
+        ensure!(after.initializer_timing == InitializerTiming::AfterPreload);
+        ensure!(after.preload_wasm, "must preload the WASM file");
+
+        ensure!(
+            app(&[("data-initializer-timing", "before-preload")])
+                .await
+                .is_err(),
+            "must require an initializer"
+        );
+        ensure!(
+            app(&[
+                ("data-initializer", "init.js"),
+                ("data-initializer-timing", "before-preload"),
+                ("data-preload-wasm", "true"),
+            ])
+            .await
+            .is_err(),
+            "must reject preloading"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn warnings_are_counted() {
+        let messages = [
+            r#"{"reason":"compiler-message","package_id":"path+file:///tmp/w#0.1.0","manifest_path":"/tmp/w/Cargo.toml","target":{"kind":["bin"],"crate_types":["bin"],"name":"w","src_path":"/tmp/w/src/main.rs","edition":"2021","doc":true,"doctest":false,"test":true},"message":{"rendered":"warning: unused variable: `x`\n","$message_type":"diagnostic","children":[],"level":"warning","message":"unused variable: `x`","spans":[],"code":{"code":"unused_variables","explanation":null}}}"#,
+            r#"{"reason":"compiler-message","package_id":"path+file:///tmp/w#0.1.0","manifest_path":"/tmp/w/Cargo.toml","target":{"kind":["bin"],"crate_types":["bin"],"name":"w","src_path":"/tmp/w/src/main.rs","edition":"2021","doc":true,"doctest":false,"test":true},"message":{"rendered":"error: expected `;`\n","$message_type":"diagnostic","children":[],"level":"error","message":"expected `;`","spans":[],"code":null}}"#,
+            r#"{"reason":"build-finished","success":true}"#,
+        ]
+        .join("\n");
+        let messages = cargo_metadata::Message::parse_stream(messages.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .expect("valid messages");
+
+        assert_eq!(count_warnings(&messages), 1);
+        assert_eq!(count_warnings(&messages[1..]), 0);
+    }
+
+    #[test]
+    fn target_name_selects_cdylib() {
+        let artifact = |kind: &str, name: &str| {
+            let message = format!(
+                r#"{{"reason":"compiler-artifact","package_id":"path+file:///tmp/w#0.1.0","manifest_path":"/tmp/w/Cargo.toml","target":{{"kind":["{kind}"],"crate_types":["{kind}"],"name":"{name}","src_path":"/tmp/w/src/lib.rs","edition":"2021","doc":true,"doctest":false,"test":true}},"profile":{{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false}},"features":[],"filenames":["/tmp/w/target/wasm32-unknown-unknown/debug/{name}.wasm"],"executable":null,"fresh":true}}"#
+            );
+            match serde_json::from_str(&message).expect("valid message") {
+                cargo_metadata::Message::CompilerArtifact(art) => art,
+                _ => panic!("not an artifact"),
+            }
+        };
+        let cdylib = artifact("cdylib", "web_app");
+        let bin = artifact("bin", "web-app");
+        let test = artifact("test", "web_app");
+
+        let package = cdylib.package_id.clone();
+        assert!(is_candidate_artifact(&package, &cdylib));
+        assert!(is_candidate_artifact(&package, &bin));
+        assert!(!is_candidate_artifact(&package, &test));
+        assert!(!is_candidate_artifact(
+            &PackageId {
+                repr: "path+file:///tmp/other#0.1.0".into()
+            },
+            &cdylib
+        ));
+
+        assert!(target_name_matches("web_app", &cdylib.target));
+        assert!(target_name_matches("web-app", &cdylib.target));
+        assert!(!target_name_matches("web_app", &bin.target));
+        assert!(target_name_matches("web-app", &bin.target));
+
+        assert_eq!(
+            describe_artifacts(&[&cdylib, &bin]),
+            ["web_app (cdylib)", "web-app (bin)"]
+        );
+    }
+
+    #[tokio::test]
+    async fn cargo_features_per_link() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+
+        let cfg = Arc::new(RtcBuild::new_test(dir).await?);
+        let app = |attrs: &[(&str, &str)]| test_app(&cfg, dir, attrs);
+
+        let a = app(&[("data-bin", "a"), ("data-cargo-features", "foo bar")]).await?;
+        let b = app(&[
+            ("data-bin", "b"),
+            ("data-cargo-no-default-features", ""),
+            ("data-cargo-features", "baz,web-sys/console"),
+        ])
+        .await?;
+        let c = app(&[("data-bin", "c")]).await?;
+
+        assert_eq!(
+            RustApp::cargo_feature_args(&a.cargo_features),
+            ["--features", "foo,bar"]
+        );
+        assert_eq!(
+            RustApp::cargo_feature_args(&b.cargo_features),
+            ["--no-default-features", "--features", "baz,web-sys/console"]
+        );
+        // without attributes, the features of the configuration are used
+        assert_eq!(
+            RustApp::cargo_feature_args(&c.cargo_features),
+            ["--all-features"]
+        );
+
+        let empty = app(&[("data-cargo-features", " , ")]).await?;
+        assert_eq!(
+            RustApp::cargo_feature_args(&empty.cargo_features),
+            [] as [&str; 0]
+        );
+
+        for (features, error) in [
+            ("foo,missing", "unknown cargo feature `missing`"),
+            ("foo=1", "invalid cargo feature `foo=1`"),
+            ("-foo", "invalid cargo feature `-foo`"),
+            ("web-sys/", "invalid cargo feature `web-sys/`"),
+        ] {
+            let result = app(&[("data-cargo-features", features)]).await;
+            let err = result.err().context("expected invalid features to fail")?;
+            ensure!(
+                err.to_string().contains(error),
+                "unexpected error for {features}: {err}"
+            );
+        }
+
+        let all = app(&[
+            ("data-cargo-all-features", ""),
+            ("data-cargo-features", "foo"),
+        ])
+        .await;
+        ensure!(all.is_err(), "features can't be combined with all features");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unknown_cargo_profile_fails() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        setup_test_crate(dir).await?;
+        let manifest = fs::read_to_string(dir.join("Cargo.toml")).await?;
+        fs::write(
+            dir.join("Cargo.toml"),
+            manifest + "\n[profile.wasm-release]\ninherits = \"release\"\n",
+        )
+        .await?;
+
+        let cfg = Arc::new(RtcBuild::new_test(dir).await?);
+        let app = |profile| test_app(&cfg, dir, &[("data-cargo-profile", profile)]);
+
+        let defined = app("wasm-release").await?;
+        assert_eq!(defined.cargo_profile.as_deref(), Some("wasm-release"));
+
+        let err = app("wasm-debug")
+            .await
+            .err()
+            .context("expected an unknown profile to fail")?;
+        let err = err.to_string();
+        ensure!(
+            err.starts_with("cargo profile 'wasm-debug' is not defined for '")
+                && err.contains(&format!("{}'", dir.join("Cargo.toml").display()))
+                && err.contains("available profiles: ")
+                && err.contains("wasm-release"),
+            "unexpected error: {err}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn features_are_parsed() {
+        assert_eq!(parse_features("a b,c\n# d\n  e  \n"), "a,b,c,e");
+        assert_eq!(parse_features(""), "");
+    }
+}
@@ -2,10 +2,17 @@ use super::super::trunk_id_selector;
 use crate::{
     common::{html_rewrite::Document, nonce_attr},
     config::{rt::RtcBuild, types::CrossOrigin},
-    pipelines::rust::{sri::SriBuilder, wasm_bindgen::WasmBindgenFeatures, RustAppType},
+    pipelines::rust::{
+        sri::SriBuilder,
+        wasm_bindgen::{WasmBindgenFeatures, WasmBindgenTarget},
+        InitializerTiming, RustAppType, ScriptPosition,
+    },
 };
 use anyhow::bail;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+/// The name of the global variable the bindings of an application are assigned to by default.
+pub const DEFAULT_BINDINGS_NAME: &str = "wasmBindings";
 
 /// The output of a cargo build pipeline.
 pub struct RustAppOutput {
@@ -13,6 +20,8 @@ pub struct RustAppOutput {
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: Option<usize>,
+    /// The Cargo.toml of the built project.
+    pub source: PathBuf,
     /// The filename of the generated JS loader file written to the dist dir.
     pub js_output: String,
     /// The filename of the generated WASM file written to the dist dir.
@@ -21,6 +30,8 @@ pub struct RustAppOutput {
     pub wasm_size: u64,
     /// Is this module main or a worker.
     pub r#type: RustAppType,
+    /// The target of wasm-bindgen
+    pub wasm_bindgen_target: WasmBindgenTarget,
     /// The cross-origin setting for loading the resources
     pub cross_origin: CrossOrigin,
     /// The output digests for the sub-resources
@@ -29,10 +40,20 @@ pub struct RustAppOutput {
     pub import_bindings: bool,
     /// The name of the WASM bindings import
     pub import_bindings_name: Option<String>,
-    /// The target of the initializer module
-    pub initializer: Option<String>,
+    /// The targets of the initializer modules, in the order they are run
+    pub initializers: Vec<String>,
+    /// When the initializer is started, relative to the download of the WASM file
+    pub initializer_timing: InitializerTiming,
+    /// A JavaScript expression of additional options passed to the init function
+    pub init_options: Option<String>,
     /// The features supported by the version of wasm-bindgen used
     pub wasm_bindgen_features: WasmBindgenFeatures,
+    /// The files written to the staging dir
+    pub files: Vec<PathBuf>,
+    /// Where to inject the initializer script, instead of the link's position
+    pub script_position: Option<ScriptPosition>,
+    /// A selector of the element to inject the script initializing the application into
+    pub mount: Option<String>,
 }
 
 pub fn pattern_evaluate(template: &str, params: &HashMap<String, String>) -> String {
@@ -52,9 +73,21 @@ pub fn pattern_evaluate(template: &str, params: &HashMap<String, String>) -> Str
 
 impl RustAppOutput {
     pub async fn finalize(self, dom: &mut Document) -> anyhow::Result<()> {
-        if self.r#type == RustAppType::Worker {
-            // Skip the script tag and preload links for workers, and remove the link tag only.
-            // Workers are initialized and managed by the app itself at runtime.
+        if self.r#type != RustAppType::Main {
+            // Skip the script tag and preload links for workers and worklets, and remove the link
+            // tag only. Workers are initialized and managed by the app itself at runtime, worklets
+            // are registered by it.
+            if let Some(id) = self.id {
+                dom.remove(&trunk_id_selector(id))?;
+            }
+            return Ok(());
+        }
+
+        if !self.wasm_bindgen_target.is_initialized_by_trunk() {
+            // Only the `web` and `no-modules` targets get initialized by an injected script. The
+            // output of `bundler` is imported by an external bundler, the output of `deno` (and
+            // `nodejs`) initializes itself when being imported, loading the WASM file by its
+            // name. So there is nothing to inject, only remove the link tag.
             if let Some(id) = self.id {
                 dom.remove(&trunk_id_selector(id))?;
             }
@@ -80,6 +113,7 @@ impl RustAppOutput {
         params.insert("js".to_owned(), js.clone());
         params.insert("wasm".to_owned(), wasm.clone());
         params.insert("crossorigin".to_owned(), self.cross_origin.to_string());
+        params.insert("mount".to_owned(), self.mount.clone().unwrap_or_default());
 
         if let Some(pattern) = pattern_preload {
             dom.append_html(head, &pattern_evaluate(pattern, &params))?;
@@ -98,15 +132,30 @@ impl RustAppOutput {
             None => self.default_initializer(base, js, wasm),
         };
 
-        match self.id {
-            Some(id) => dom.replace_with_html(&trunk_id_selector(id), &script)?,
-            None => {
+        match (self.id, self.script_position, &self.mount) {
+            (id, _, Some(mount)) => {
+                if dom.len(mount)? == 0 {
+                    bail!(r#"the element of `data-mount="{mount}"` was not found in the document"#);
+                }
+                if let Some(id) = id {
+                    dom.remove(&trunk_id_selector(id))?;
+                }
+                dom.append_html(mount, &script)?;
+            }
+            (Some(id), None, None) => dom.replace_with_html(&trunk_id_selector(id), &script)?,
+            (id, position, None) => {
                 if dom.len(body)? == 0 {
                     bail!(
                         r#"Document has neither a <link data-trunk rel="rust"/> nor a <body>. Either one must be present."#
                     );
                 }
-                dom.append_html(body, &script)?
+                if let Some(id) = id {
+                    dom.remove(&trunk_id_selector(id))?;
+                }
+                match position {
+                    Some(ScriptPosition::Before) => dom.prepend_html(body, &script)?,
+                    Some(ScriptPosition::After) | None => dom.append_html(body, &script)?,
+                }
             }
         }
 
@@ -125,7 +174,7 @@ window.{bindings} = bindings;
                     bindings = self
                         .import_bindings_name
                         .as_deref()
-                        .unwrap_or("wasmBindings")
+                        .unwrap_or(DEFAULT_BINDINGS_NAME)
                 ),
             ),
             false => ("", String::new()),
@@ -134,14 +183,48 @@ window.{bindings} = bindings;
         let nonce = nonce_attr(&self.cfg.create_nonce);
 
         // the code to fire the `TrunkApplicationStarted` event
-        let fire = r#"
+        let fire = match &self.mount {
+            Some(mount) => format!(
+                r#"
+dispatchEvent(new CustomEvent("TrunkApplicationStarted", {{detail: {{wasm, mount: {mount}}}}}));
+"#,
+                // a JSON string is a valid JS string literal
+                mount = serde_json::Value::from(mount.as_str()),
+            ),
+            None => r#"
 dispatchEvent(new CustomEvent("TrunkApplicationStarted", {detail: {wasm}}));
-"#;
+"#
+            .to_string(),
+        };
 
         let init_with_object = self.wasm_bindgen_features.init_with_object;
+        // the options are spread into the object passed to the init function
+        let init_options = self.init_options.as_deref().unwrap_or("undefined");
+        // compiling the response while it is downloaded fails, unless the WASM file is served as
+        // `application/wasm`
+        let streaming = self.cfg.streaming_init;
+        let source = match streaming {
+            true => format!("WebAssembly.compileStreaming(fetch('{base}{wasm}'))"),
+            false => format!("'{base}{wasm}'"),
+        };
 
-        match &self.initializer {
-            None => format!(
+        // when starting the initializer first, the bindings are only imported once it ran, so that
+        // neither they nor the WASM file are loaded before
+        let (load, bind) = match self.initializer_timing {
+            InitializerTiming::AfterPreload => (format!("import init{import} from '{base}{js}';"), bind),
+            InitializerTiming::BeforePreload => (
+                format!(
+                    "const init = (module) => import('{base}{js}').then((bindings) => bindings.default(module));"
+                ),
+                match self.import_bindings {
+                    true => format!("\nconst bindings = await import('{base}{js}');{bind}"),
+                    false => bind,
+                },
+            ),
+        };
+
+        match self.initializers.as_slice() {
+            [] => format!(
                 r#"
 <script type="module"{nonce}>
 import init{import} from '{base}{js}';
@@ -151,20 +234,25 @@ const wasm = await init({init_arg});
 {fire}
 </script>"#,
                 init_arg = if init_with_object {
-                    format!("{{ module_or_path: '{base}{wasm}' }}")
+                    match &self.init_options {
+                        Some(options) => {
+                            format!("{{ module_or_path: {source}, ...({options}) }}")
+                        }
+                        None => format!("{{ module_or_path: {source} }}"),
+                    }
                 } else {
-                    format!("'{base}{wasm}'")
+                    source
                 }
             ),
-            Some(initializer) => format!(
+            [initializer] => format!(
                 r#"
 <script type="module"{nonce}>
 {init}
 
-import init{import} from '{base}{js}';
+{load}
 import initializer from '{base}{initializer}';
 
-const wasm = await __trunkInitializer(init, '{base}{wasm}', {size}, initializer(), {init_with_object});
+const wasm = await __trunkInitializer(init, '{base}{wasm}', {size}, initializer(), {init_with_object}, {init_options}, {streaming});
 
 {bind}
 {fire}
@@ -172,6 +260,264 @@ const wasm = await __trunkInitializer(init, '{base}{wasm}', {size}, initializer(
                 init = include_str!("initializer.js"),
                 size = self.wasm_size,
             ),
+            initializers => {
+                let imports = initializers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, initializer)| {
+                        format!("import initializer{i} from '{base}{initializer}';")
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let chain = (0..initializers.len())
+                    .map(|i| format!("initializer{i}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                format!(
+                    r#"
+<script type="module"{nonce}>
+{init}
+
+{load}
+{imports}
+
+const wasm = await __trunkInitializer(init, '{base}{wasm}', {size}, await __trunkChainInitializers([{chain}]), {init_with_object}, {init_options}, {streaming});
+
+{bind}
+{fire}
+</script>"#,
+                    init = include_str!("initializer.js"),
+                    size = self.wasm_size,
+                )
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{pipelines::TRUNK_ID, processing::integrity::IntegrityType};
+    use anyhow::{ensure, Result};
+
+    /// A fixture creating the output of a main application, to be adapted by the tests.
+    fn test_output(cfg: Arc<RtcBuild>) -> Result<RustAppOutput> {
+        Ok(RustAppOutput {
+            cfg,
+            id: Some(0),
+            source: "Cargo.toml".into(),
+            js_output: "app.js".into(),
+            wasm_output: "app_bg.wasm".into(),
+            wasm_size: 0,
+            r#type: RustAppType::Main,
+            wasm_bindgen_target: WasmBindgenTarget::Web,
+            cross_origin: Default::default(),
+            integrities: SriBuilder::new(IntegrityType::None),
+            import_bindings: false,
+            import_bindings_name: None,
+            initializers: vec![],
+            initializer_timing: InitializerTiming::AfterPreload,
+            init_options: None,
+            wasm_bindgen_features: WasmBindgenFeatures::from_version("0.2.93")?,
+            files: vec![],
+            script_position: None,
+            mount: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn bundler_target_injects_nothing() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+
+        let output = RustAppOutput {
+            wasm_bindgen_target: WasmBindgenTarget::Bundler,
+            integrities: SriBuilder::new(IntegrityType::Sha384),
+            import_bindings: true,
+            ..test_output(cfg)?
+        };
+
+        let mut doc = Document::new(
+            format!(
+                r#"<html><head><link data-trunk rel="rust" {TRUNK_ID}="0"/></head><body></body></html>"#
+            ),
+            Default::default(),
+        )?;
+        output.finalize(&mut doc).await?;
+        let html = String::from_utf8(doc.into_inner())?;
+
+        ensure!(
+            html == "<html><head></head><body></body></html>",
+            "unexpected output: {html}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn mounted_app_is_initialized_in_its_element() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+
+        let output = RustAppOutput {
+            js_output: "widget.js".into(),
+            wasm_output: "widget_bg.wasm".into(),
+            mount: Some("#widget".into()),
+            ..test_output(cfg)?
+        };
+
+        let mut doc = Document::new(
+            format!(
+                r#"<html><head><link data-trunk rel="rust" {TRUNK_ID}="0"/></head><body><div id="widget"></div></body></html>"#
+            ),
+            Default::default(),
+        )?;
+        output.finalize(&mut doc).await?;
+        let html = String::from_utf8(doc.into_inner())?;
+
+        ensure!(
+            html.starts_with(r#"<html><head></head><body><div id="widget">"#)
+                && html.ends_with("</script></div></body></html>"),
+            "unexpected output: {html}"
+        );
+        ensure!(
+            html.contains("import init from '/widget.js';")
+                && html.contains(r##"mount: "#widget""##),
+            "unexpected script: {html}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn init_options_are_passed_to_init() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+
+        let output = |initializers: Vec<String>| -> Result<_> {
+            Ok(RustAppOutput {
+                initializers,
+                init_options: Some("{ memory }".into()),
+                ..test_output(cfg.clone())?
+            })
+        };
+
+        let script = output(vec![])?.default_initializer("/", "app.js", "app_bg.wasm");
+        ensure!(
+            script.contains("await init({ module_or_path: '/app_bg.wasm', ...({ memory }) });"),
+            "unexpected script: {script}"
+        );
+
+        let script =
+            output(vec!["init.js".into()])?.default_initializer("/", "app.js", "app_bg.wasm");
+        ensure!(
+            script.contains("initializer(), true, { memory }, false);"),
+            "unexpected script: {script}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn streaming_init() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        cfg.streaming_init = true;
+        let cfg = Arc::new(cfg);
+
+        let output = |initializers: Vec<String>, version: &str| -> Result<_> {
+            Ok(RustAppOutput {
+                initializers,
+                wasm_bindgen_features: WasmBindgenFeatures::from_version(version)?,
+                ..test_output(cfg.clone())?
+            })
+        };
+
+        let script = output(vec![], "0.2.93")?.default_initializer("/", "app.js", "app_bg.wasm");
+        ensure!(
+            script.contains(
+                "await init({ module_or_path: WebAssembly.compileStreaming(fetch('/app_bg.wasm')) });"
+            ),
+            "unexpected script: {script}"
+        );
+
+        let script = output(vec![], "0.2.92")?.default_initializer("/", "app.js", "app_bg.wasm");
+        ensure!(
+            script.contains("await init(WebAssembly.compileStreaming(fetch('/app_bg.wasm')));"),
+            "unexpected script: {script}"
+        );
+
+        let script = output(vec!["init.js".into()], "0.2.93")?.default_initializer(
+            "/",
+            "app.js",
+            "app_bg.wasm",
+        );
+        ensure!(
+            script.contains("initializer(), true, undefined, true);"),
+            "unexpected script: {script}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn initializers_are_chained_in_order() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+
+        let output = RustAppOutput {
+            initializers: vec!["first.js".into(), "second.js".into()],
+            ..test_output(cfg)?
+        };
+
+        let script = output.default_initializer("/", "app.js", "app_bg.wasm");
+        ensure!(
+            script.contains(
+                "import initializer0 from '/first.js';\nimport initializer1 from '/second.js';"
+            ),
+            "unexpected imports: {script}"
+        );
+        ensure!(
+            script.contains(
+                "await __trunkChainInitializers([initializer0, initializer1]), true, undefined, false);"
+            ),
+            "unexpected script: {script}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn initializer_before_preload() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let cfg = Arc::new(RtcBuild::new_test(tmpdir.path()).await?);
+
+        let output = RustAppOutput {
+            import_bindings: true,
+            initializers: vec!["init.js".into()],
+            initializer_timing: InitializerTiming::BeforePreload,
+            ..test_output(cfg)?
+        };
+
+        let script = output.default_initializer("/", "app.js", "app_bg.wasm");
+        ensure!(
+            !script.contains("from '/app.js'"),
+            "must not import the bindings statically: {script}"
+        );
+        ensure!(
+            script.contains(
+                "const init = (module) => import('/app.js').then((bindings) => bindings.default(module));"
+            ),
+            "unexpected script: {script}"
+        );
+        ensure!(
+            script.contains(
+                "const bindings = await import('/app.js');\nwindow.wasmBindings = bindings;"
+            ),
+            "unexpected script: {script}"
+        );
+
+        Ok(())
+    }
+}
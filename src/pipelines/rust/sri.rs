@@ -44,6 +44,15 @@ impl SriBuilder {
         self.result
     }
 
+    /// The digest recorded for a file, by its name
+    pub fn digest(&self, name: &str) -> Option<&OutputDigest> {
+        self.result
+            .integrities
+            .iter()
+            .find(|(key, _)| key.name == name)
+            .map(|(_, entry)| &entry.digest)
+    }
+
     /// Record the content of a file for SRI
     pub async fn record_file(
         &mut self,
@@ -170,3 +179,43 @@ impl SriResult {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::ensure;
+
+    /// Initializer modules are preloaded with the digest of their content.
+    #[tokio::test]
+    async fn module_preload_has_integrity() -> anyhow::Result<()> {
+        let content = b"export default function initializer() { return {}; }";
+
+        let mut sri = SriBuilder::new(IntegrityType::Sha384);
+        sri.record(
+            SriType::ModulePreload,
+            "initializer-1234.js",
+            SriOptions::default(),
+            || async { Ok::<_, std::io::Error>(content) },
+        )
+        .await?;
+
+        let mut doc = Document::new("<html><head></head></html>", Default::default())?;
+        sri.build()
+            .inject(&mut doc, "html head", "/", CrossOrigin::Anonymous, &None)?;
+        let html = String::from_utf8(doc.into_inner())?;
+
+        let digest =
+            OutputDigest::generate(IntegrityType::Sha384, || Ok::<_, std::io::Error>(content))?;
+        let expected = digest
+            .to_integrity_value()
+            .context("missing integrity value")?;
+        ensure!(
+            html.contains(&format!(
+                r#"<link rel="modulepreload" href="/initializer-1234.js" crossorigin="anonymous" integrity="{expected}">"#
+            )),
+            "unexpected output: {html}"
+        );
+
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 use crate::config::{CargoMetadata, Tools};
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
 use cargo_lock::Lockfile;
 use semver::{Comparator, Op, Prerelease, Version};
 use std::borrow::Cow;
@@ -9,7 +9,7 @@ use std::str::FromStr;
 
 /// Determines the value of `--target` flag for wasm-bindgen. For more details see
 /// [here](https://rustwasm.github.io/wasm-bindgen/reference/deployment.html).
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum WasmBindgenTarget {
     Bundler,
     Web,
@@ -49,6 +49,33 @@ impl Display for WasmBindgenTarget {
     }
 }
 
+impl WasmBindgenTarget {
+    /// Whether Trunk initializes the output, by injecting a script into the HTML document.
+    ///
+    /// The output of all other targets is imported by an external tool (a JS bundler, Deno or
+    /// Node.js), which also takes care of loading the WASM file.
+    pub fn is_initialized_by_trunk(self) -> bool {
+        matches!(self, Self::Web | Self::NoModules)
+    }
+}
+
+/// The wasm-bindgen arguments managed by Trunk, which must not be provided as additional arguments.
+const MANAGED_ARGS: &[&str] = &["--out-dir", "--out-name", "--target"];
+
+/// Parse the additional wasm-bindgen arguments of the `data-bindgen-args` attribute.
+pub fn parse_wasm_bindgen_args(value: &str) -> anyhow::Result<Vec<String>> {
+    value
+        .split_whitespace()
+        .map(|arg| {
+            let name = arg.split_once('=').map_or(arg, |(name, _)| name);
+            if MANAGED_ARGS.contains(&name) {
+                bail!("`data-bindgen-args` must not contain `{name}`, it is managed by Trunk");
+            }
+            Ok(arg.to_string())
+        })
+        .collect()
+}
+
 /// Find the appropriate version of `wasm-bindgen` to use. The version can be found in 3 different
 /// locations in the order:
 /// - Defined in the `Trunk.toml` as the highest priority.
@@ -117,3 +144,42 @@ impl WasmBindgenFeatures {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bindgen_args() -> anyhow::Result<()> {
+        let args = parse_wasm_bindgen_args(" --omit-default-module-path  --weak-refs ")?;
+        anyhow::ensure!(args == ["--omit-default-module-path", "--weak-refs"]);
+
+        for managed in ["--target=web", "--out-dir foo", "--out-name"] {
+            anyhow::ensure!(
+                parse_wasm_bindgen_args(managed).is_err(),
+                "must reject: {managed}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn targets() -> anyhow::Result<()> {
+        for (value, initialized) in [
+            ("web", true),
+            ("no-modules", true),
+            ("bundler", false),
+            ("deno", false),
+            ("nodejs", false),
+        ] {
+            let target: WasmBindgenTarget = value.parse()?;
+            anyhow::ensure!(target.to_string() == value, "must roundtrip: {value}");
+            anyhow::ensure!(target.is_initialized_by_trunk() == initialized, "{value}");
+        }
+
+        anyhow::ensure!("Deno".parse::<WasmBindgenTarget>().is_err());
+
+        Ok(())
+    }
+}
@@ -2,9 +2,10 @@ use anyhow::bail;
 use std::str::FromStr;
 
 /// Different optimization levels that can be configured with `wasm-opt`.
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq, Eq, Default)]
 pub enum WasmOptLevel {
     /// Default optimization passes.
+    #[default]
     Default,
     /// No optimization passes, skipping the wasp-opt step.
     Off,
@@ -55,9 +56,3 @@ impl AsRef<str> for WasmOptLevel {
         }
     }
 }
-
-impl Default for WasmOptLevel {
-    fn default() -> Self {
-        Self::Default
-    }
-}
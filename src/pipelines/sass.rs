@@ -1,18 +1,26 @@
 //! Sass/Scss asset pipeline.
 
 use super::{
-    data_target_path, AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_HREF,
-    ATTR_INLINE, ATTR_NO_MINIFY,
+    data_cross_origin, data_public_url, data_target_file, AssetFile, AttrWriter, Attrs,
+    FileNamePattern, TrunkAssetPipelineOutput, ATTR_HREF, ATTR_INLINE, ATTR_LOAD_PATH,
+    ATTR_NO_MINIFY,
 };
 use crate::{
     common::{self, dist_relative, html_rewrite::Document, nonce_attr, target_path},
-    config::rt::RtcBuild,
+    config::{
+        rt::RtcBuild,
+        types::{BaseUrl, CrossOrigin},
+    },
     processing::integrity::{IntegrityType, OutputDigest},
     tools::{self, Application},
 };
 use anyhow::{ensure, Context, Result};
-use std::{path::PathBuf, sync::Arc};
-use tokio::{fs, task::JoinHandle};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{fs, sync::mpsc, task::JoinHandle};
 
 /// A sass/scss asset pipeline.
 pub struct Sass {
@@ -20,18 +28,28 @@ pub struct Sass {
     id: usize,
     /// Runtime build config.
     cfg: Arc<RtcBuild>,
+    /// An optional channel to be used to communicate the loaded partials to the watcher.
+    watch_chan: Option<mpsc::UnboundedSender<PathBuf>>,
     /// The asset file being processed.
-    asset: AssetFile,
+    pub(super) asset: AssetFile,
     /// If the specified SASS/SCSS file should be inlined.
     use_inline: bool,
     /// E.g. `disabled`, `id="..."`
     other_attrs: Attrs,
     /// The required integrity setting
     integrity: IntegrityType,
+    /// The cross-origin setting for loading the resource
+    cross_origin: Option<CrossOrigin>,
+    /// The public URL of the output file
+    public_url: BaseUrl,
     /// Whether to minify or not
     no_minify: bool,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// The pattern for the name of the output file.
+    file_name: FileNamePattern,
+    /// The directories to resolve loads from, besides the directory of the loading file.
+    load_paths: Vec<PathBuf>,
 }
 
 impl Sass {
@@ -41,6 +59,7 @@ impl Sass {
     pub async fn new(
         cfg: Arc<RtcBuild>,
         html_dir: Arc<PathBuf>,
+        watch_chan: Option<mpsc::UnboundedSender<PathBuf>>,
         attrs: Attrs,
         id: usize,
     ) -> Result<Self> {
@@ -55,17 +74,33 @@ impl Sass {
         let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
 
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
-        let target_path = data_target_path(&attrs)?;
+        let cross_origin = data_cross_origin(&attrs)?;
+        let public_url = data_public_url(&attrs, &cfg)?;
+        let (target_path, file_name) = data_target_file(&attrs, &cfg.layout.css, cfg.filehash)?;
+
+        // the load paths of the link take precedence over the ones of the configuration
+        let load_paths = attrs
+            .get(ATTR_LOAD_PATH)
+            .iter()
+            .flat_map(|val| val.split_whitespace())
+            .map(|path| html_dir.join(path))
+            .chain(cfg.sass_load_paths.iter().cloned())
+            .collect();
 
         Ok(Self {
             id,
             cfg,
+            watch_chan,
             asset,
             use_inline,
             other_attrs: attrs,
             integrity,
+            cross_origin,
+            public_url,
             no_minify,
             target_path,
+            file_name,
+            load_paths,
         })
     }
 
@@ -82,6 +117,7 @@ impl Sass {
         let sass = tools::get(
             Application::Sass,
             version,
+            self.cfg.tools.path(Application::Sass),
             self.cfg.offline,
             &self.cfg.client_options(),
         )
@@ -94,6 +130,14 @@ impl Sass {
             "SASS source path '{source_path_str}' does not exist / is not a file"
         );
 
+        // Send the partials over to the watcher, before compiling. Otherwise fixing an error in a
+        // partial would not trigger a new build.
+        if let Some(chan) = &self.watch_chan {
+            for partial in dependencies(&self.asset.path, &self.load_paths).await {
+                let _ = chan.send(partial);
+            }
+        }
+
         let temp_target_file_name = format!("{}.css", &self.asset.file_stem.to_string_lossy());
         let temp_target_file_path =
             dunce::simplified(&self.cfg.staging_dist.join(&temp_target_file_name))
@@ -116,13 +160,20 @@ impl Sass {
 
         // collect arguments
 
-        let args = &[
-            source_map,
-            "--style",
-            output_style,
-            &source_path_str,
-            &temp_target_file_path,
-        ];
+        let load_paths = self
+            .load_paths
+            .iter()
+            .map(|path| format!("--load-path={}", dunce::simplified(path).display()))
+            .collect::<Vec<_>>();
+
+        let mut args = vec![source_map, "--style", output_style];
+        match common::color_mode() {
+            "always" => args.push("--color"),
+            "never" => args.push("--no-color"),
+            _ => {}
+        }
+        args.extend(load_paths.iter().map(String::as_str));
+        args.extend([source_path_str.as_str(), temp_target_file_path.as_str()]);
 
         // run
 
@@ -131,7 +182,7 @@ impl Sass {
         common::run_command(
             Application::Sass.name(),
             &sass,
-            args,
+            &args,
             &self.cfg.working_directory,
         )
         .await?;
@@ -149,11 +200,11 @@ impl Sass {
             // Hash the contents to generate a file name, and then write the contents to the dist
             // dir.
             let hash = seahash::hash(css.as_bytes());
-            let file_name = self
-                .cfg
-                .filehash
-                .then(|| format!("{}-{:x}.css", &self.asset.file_stem.to_string_lossy(), hash))
-                .unwrap_or(temp_target_file_name);
+            let file_name = self.file_name.file_name(
+                &self.asset.file_stem.to_string_lossy(),
+                hash,
+                Some("css"),
+            );
 
             let result_dir =
                 target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
@@ -178,22 +229,198 @@ impl Sass {
         Ok(TrunkAssetPipelineOutput::Sass(SassOutput {
             cfg: self.cfg.clone(),
             id: self.id,
+            source: self.asset.path.clone(),
             css_ref,
             attrs: self.other_attrs,
+            cross_origin: self.cross_origin,
+            public_url: self.public_url,
         }))
     }
 }
 
+/// The extensions of files which can be loaded by Sass.
+const EXTENSIONS: [&str; 3] = ["scss", "sass", "css"];
+
+/// Collect the files loaded by a Sass entry file, using `@use`, `@forward` or `@import`.
+///
+/// Loads are resolved relative to the loading file, and then to the load paths. Loads which can't
+/// be resolved (like built-in modules) are skipped, dart-sass will report them if they are
+/// missing.
+async fn dependencies(entry: &Path, load_paths: &[PathBuf]) -> Vec<PathBuf> {
+    let entry = fs::canonicalize(entry)
+        .await
+        .unwrap_or_else(|_| entry.to_path_buf());
+    let mut seen = HashSet::from([entry.clone()]);
+    let mut pending = vec![entry];
+    let mut result = vec![];
+
+    while let Some(file) = pending.pop() {
+        let Ok(source) = fs::read_to_string(&file).await else {
+            continue;
+        };
+        let Some(dir) = file.parent() else {
+            continue;
+        };
+
+        for url in loaded_urls(&source) {
+            let mut dependency = None;
+            for dir in std::iter::once(dir).chain(load_paths.iter().map(PathBuf::as_path)) {
+                dependency = resolve_url(dir, &url).await;
+                if dependency.is_some() {
+                    break;
+                }
+            }
+            let Some(dependency) = dependency else {
+                continue;
+            };
+            let dependency = fs::canonicalize(&dependency).await.unwrap_or(dependency);
+            if !seen.insert(dependency.clone()) {
+                continue;
+            }
+            if dependency.extension().is_some_and(|ext| ext != "css") {
+                pending.push(dependency.clone());
+            }
+            result.push(dependency);
+        }
+    }
+
+    result
+}
+
+/// The URLs of all `@use`, `@forward` and `@import` rules of a Sass file.
+fn loaded_urls(source: &str) -> Vec<String> {
+    let source = strip_comments(source);
+    let mut result = vec![];
+
+    for (at, _) in source.match_indices('@') {
+        let rest = &source[at + 1..];
+        let Some(rest) = ["use", "forward", "import"]
+            .iter()
+            .find_map(|rule| rest.strip_prefix(rule))
+        else {
+            continue;
+        };
+        if !rest.starts_with(|c: char| c.is_whitespace() || c == '"' || c == '\'') {
+            continue;
+        }
+
+        // `@import` accepts a comma separated list of URLs
+        let mut rest = rest.trim_start();
+        while let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            let Some(end) = rest[1..].find(quote) else {
+                break;
+            };
+            result.push(rest[1..end + 1].to_string());
+            rest = rest[end + 2..].trim_start();
+            match rest.strip_prefix(',') {
+                Some(next) => rest = next.trim_start(),
+                None => break,
+            }
+        }
+    }
+
+    result
+}
+
+/// Replace comments with whitespace, keeping the content of strings.
+fn strip_comments(source: &str) -> String {
+    let mut result = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut quote = None;
+
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => {
+                result.push(c);
+                if c == '\\' {
+                    result.extend(chars.next());
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                result.push(c);
+            }
+            None if c == '/' && chars.peek() == Some(&'/') => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+            }
+            None if c == '/' && chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = None;
+                for c in chars.by_ref() {
+                    if prev == Some('*') && c == '/' {
+                        break;
+                    }
+                    prev = Some(c);
+                }
+                result.push(' ');
+            }
+            None => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// Resolve the URL of a load relative to the directory of the loading file, following the
+/// lookup rules of Sass for partials and index files.
+async fn resolve_url(dir: &Path, url: &str) -> Option<PathBuf> {
+    // built-in modules, packages and remote URLs
+    if url.contains(':') {
+        return None;
+    }
+
+    let mut path = dir.to_path_buf();
+    path.extend(url.split('/'));
+    let name = path.file_name()?.to_str()?.to_string();
+    let parent = path.parent()?;
+
+    let mut candidates = vec![];
+    if Path::new(&name)
+        .extension()
+        .is_some_and(|ext| EXTENSIONS.iter().any(|e| ext == *e))
+    {
+        candidates.push(path.clone());
+        candidates.push(parent.join(format!("_{name}")));
+    } else {
+        for ext in EXTENSIONS {
+            candidates.push(parent.join(format!("{name}.{ext}")));
+            candidates.push(parent.join(format!("_{name}.{ext}")));
+        }
+        for ext in EXTENSIONS {
+            candidates.push(path.join(format!("_index.{ext}")));
+            candidates.push(path.join(format!("index.{ext}")));
+        }
+    }
+
+    for candidate in candidates {
+        if common::path_exists_and(&candidate, |m| m.is_file())
+            .await
+            .unwrap_or(false)
+        {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
 /// The output of a sass/scss build pipeline.
 pub struct SassOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: usize,
+    /// The source file of the asset.
+    pub source: PathBuf,
     /// Data on the finalized output file.
     pub css_ref: CssRef,
     /// The other attributes copied over from the original.
     pub attrs: Attrs,
+    /// The cross-origin setting for loading the resource
+    pub cross_origin: Option<CrossOrigin>,
+    /// The public URL of the output file
+    pub public_url: BaseUrl,
 }
 
 /// The resulting CSS of the SASS/SCSS compilation.
@@ -217,10 +444,13 @@ impl SassOutput {
             CssRef::File(file, integrity) => {
                 let mut attrs = self.attrs.clone();
                 integrity.insert_into(&mut attrs);
+                if let Some(cross_origin) = self.cross_origin {
+                    cross_origin.insert_into(&mut attrs);
+                }
 
                 format!(
                     r#"<link rel="stylesheet"{nonce} href="{base}{file}"{attrs}/>"#,
-                    base = &self.cfg.public_url,
+                    base = &self.public_url,
                     attrs = AttrWriter::new(&attrs, AttrWriter::EXCLUDE_CSS_LINK)
                 )
             }
@@ -228,3 +458,57 @@ impl SassOutput {
         dom.replace_with_html(&super::trunk_id_selector(self.id), &html)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn urls_of_loads() {
+        let source = r#"
+            @use "sass:math";
+            @use 'theme' as t;
+            @forward "src/list" hide list-reset;
+            @import "a", 'b';
+            // @use "commented";
+            /* @import "block"; */
+            .link { background: url("http://example.com/@use.png"); }
+        "#;
+        assert_eq!(
+            loaded_urls(source),
+            vec!["sass:math", "theme", "src/list", "a", "b"]
+        );
+    }
+
+    #[tokio::test]
+    async fn dependencies_of_entry() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path().canonicalize()?;
+        fs::create_dir_all(dir.join("shared/colors")).await?;
+        fs::create_dir_all(dir.join("styles")).await?;
+        fs::write(
+            dir.join("main.scss"),
+            r#"@use "sass:math"; @use "shared/theme"; @use "missing"; @use "variables";"#,
+        )
+        .await?;
+        fs::write(dir.join("styles/_variables.scss"), "$blue: blue;").await?;
+        fs::write(
+            dir.join("shared/_theme.scss"),
+            r#"@forward "colors"; @use "../main";"#,
+        )
+        .await?;
+        fs::write(dir.join("shared/colors/_index.scss"), "$red: red;").await?;
+
+        let mut result = dependencies(&dir.join("main.scss"), &[dir.join("styles")]).await;
+        result.sort();
+        assert_eq!(
+            result,
+            vec![
+                dir.join("shared/_theme.scss"),
+                dir.join("shared/colors/_index.scss"),
+                dir.join("styles/_variables.scss"),
+            ]
+        );
+        Ok(())
+    }
+}
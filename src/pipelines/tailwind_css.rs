@@ -1,12 +1,15 @@
 //! Tailwind CSS asset pipeline.
 
 use super::{
-    data_target_path, AssetFile, AttrWriter, Attrs, TrunkAssetPipelineOutput, ATTR_CONFIG,
-    ATTR_HREF, ATTR_INLINE, ATTR_NO_MINIFY,
+    data_cross_origin, data_public_url, data_target_file, AssetFile, AttrWriter, Attrs,
+    FileNamePattern, TrunkAssetPipelineOutput, ATTR_CONFIG, ATTR_HREF, ATTR_INLINE, ATTR_NO_MINIFY,
 };
 use crate::{
     common::{self, dist_relative, html_rewrite::Document, nonce_attr, target_path},
-    config::rt::RtcBuild,
+    config::{
+        rt::RtcBuild,
+        types::{BaseUrl, CrossOrigin},
+    },
     processing::integrity::{IntegrityType, OutputDigest},
     tools::{self, Application},
 };
@@ -21,17 +24,23 @@ pub struct TailwindCss {
     /// Runtime build config.
     cfg: Arc<RtcBuild>,
     /// The asset file being processed.
-    asset: AssetFile,
+    pub(super) asset: AssetFile,
     /// If the specified tailwind css file should be inlined.
     use_inline: bool,
     /// E.g. `disabled`, `id="..."`
     attrs: Attrs,
     /// The required integrity setting
     integrity: IntegrityType,
+    /// The cross-origin setting for loading the resource
+    cross_origin: Option<CrossOrigin>,
+    /// The public URL of the output file
+    public_url: BaseUrl,
     /// Whether to minify or not
     no_minify: bool,
     /// Optional target path inside the dist dir.
     target_path: Option<PathBuf>,
+    /// The pattern for the name of the output file.
+    file_name: FileNamePattern,
     /// Optional tailwind config to use.
     tailwind_config: Option<String>,
 }
@@ -56,8 +65,10 @@ impl TailwindCss {
         let use_inline = attrs.contains_key(ATTR_INLINE);
 
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
+        let cross_origin = data_cross_origin(&attrs)?;
+        let public_url = data_public_url(&attrs, &cfg)?;
         let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
-        let target_path = data_target_path(&attrs)?;
+        let (target_path, file_name) = data_target_file(&attrs, &cfg.layout.css, cfg.filehash)?;
 
         Ok(Self {
             id,
@@ -65,9 +76,12 @@ impl TailwindCss {
             asset,
             use_inline,
             integrity,
+            cross_origin,
+            public_url,
             attrs,
             no_minify,
             target_path,
+            file_name,
             tailwind_config,
         })
     }
@@ -85,6 +99,7 @@ impl TailwindCss {
         let tailwind = tools::get(
             Application::TailwindCss,
             version,
+            self.cfg.tools.path(Application::TailwindCss),
             self.cfg.offline,
             &self.cfg.client_options(),
         )
@@ -130,11 +145,11 @@ impl TailwindCss {
             // Hash the contents to generate a file name, and then write the contents to the dist
             // dir.
             let hash = seahash::hash(css.as_bytes());
-            let file_name = self
-                .cfg
-                .filehash
-                .then(|| format!("{}-{:x}.css", &self.asset.file_stem.to_string_lossy(), hash))
-                .unwrap_or(file_name);
+            let file_name = self.file_name.file_name(
+                &self.asset.file_stem.to_string_lossy(),
+                hash,
+                Some("css"),
+            );
 
             let result_dir =
                 target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
@@ -156,8 +171,11 @@ impl TailwindCss {
         Ok(TrunkAssetPipelineOutput::TailwindCss(TailwindCssOutput {
             cfg: self.cfg.clone(),
             id: self.id,
+            source: self.asset.path.clone(),
             css_ref,
             attrs: self.attrs,
+            cross_origin: self.cross_origin,
+            public_url: self.public_url,
         }))
     }
 }
@@ -168,10 +186,16 @@ pub struct TailwindCssOutput {
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: usize,
+    /// The source file of the asset.
+    pub source: PathBuf,
     /// Data on the finalized output file.
     pub css_ref: CssRef,
     /// The other attributes copied over from the original.
     pub attrs: Attrs,
+    /// The cross-origin setting for loading the resource
+    pub cross_origin: Option<CrossOrigin>,
+    /// The public URL of the output file
+    pub public_url: BaseUrl,
 }
 
 /// The resulting CSS of the Tailwind CSS compilation.
@@ -195,10 +219,13 @@ impl TailwindCssOutput {
             CssRef::File(file, integrity) => {
                 let mut attrs = self.attrs.clone();
                 integrity.insert_into(&mut attrs);
+                if let Some(cross_origin) = self.cross_origin {
+                    cross_origin.insert_into(&mut attrs);
+                }
 
                 format!(
                     r#"<link rel="stylesheet" href="{base}{file}"{attrs}/>"#,
-                    base = &self.cfg.public_url,
+                    base = &self.public_url,
                     attrs = AttrWriter::new(&attrs, AttrWriter::EXCLUDE_CSS_LINK)
                 )
             }
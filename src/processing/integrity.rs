@@ -1,6 +1,7 @@
 //! Integrity processing
 
 use crate::{config::rt::RtcBuild, pipelines::Attrs};
+use anyhow::Context;
 use base64::{display::Base64Display, engine::general_purpose::STANDARD, Engine};
 use sha2::{Digest, Sha256, Sha384, Sha512};
 use std::{
@@ -11,7 +12,7 @@ use std::{
     str::FromStr,
 };
 
-const ATTR_INTEGRITY: &str = "data-integrity";
+pub(crate) const ATTR_INTEGRITY: &str = "data-integrity";
 
 /// Integrity type for subresource protection
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -23,20 +24,29 @@ pub enum IntegrityType {
 }
 
 impl IntegrityType {
+    /// The algorithm used by default, and when using `auto`
+    pub const DEFAULT: IntegrityType = Self::Sha384;
+
     /// Get the default, unless it's disabled
     pub fn default_unless(disabled: bool) -> IntegrityType {
         if disabled {
             Self::None
         } else {
-            Self::Sha384
+            Self::DEFAULT
         }
     }
 
     /// Get the integrity setting from the attributes
+    ///
+    /// An explicit value of the attribute takes precedence over the configuration, so that a
+    /// single asset can opt out using `none`, even if SRI is enabled.
     pub fn from_attrs(attrs: &Attrs, cfg: &RtcBuild) -> anyhow::Result<IntegrityType> {
         Ok(attrs
             .get(ATTR_INTEGRITY)
-            .map(|value| IntegrityType::from_str(value))
+            .map(|value| {
+                IntegrityType::from_str(value)
+                    .with_context(|| format!("invalid value of `{ATTR_INTEGRITY}`: {value}"))
+            })
             .transpose()?
             .unwrap_or_else(|| IntegrityType::default_unless(cfg.no_sri)))
     }
@@ -46,10 +56,11 @@ impl FromStr for IntegrityType {
     type Err = IntegrityTypeParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(match s {
+        Ok(match s.trim().to_ascii_lowercase().as_str() {
             "none" => Self::None,
             "sha256" => Self::Sha256,
-            "sha384" | "" => Self::Sha384,
+            "sha384" => Self::Sha384,
+            "auto" | "" => Self::DEFAULT,
             "sha512" => Self::Sha512,
             _ => return Err(IntegrityTypeParseError::InvalidValue),
         })
@@ -112,6 +123,20 @@ impl OutputDigest {
         }
     }
 
+    /// Turn into the value for a `Repr-Digest` HTTP header (RFC 9530)
+    pub fn to_digest_header_value(&self) -> Option<String> {
+        let algorithm = match self.integrity {
+            IntegrityType::None | IntegrityType::Sha384 => return None,
+            IntegrityType::Sha256 => "sha-256",
+            IntegrityType::Sha512 => "sha-512",
+        };
+
+        Some(format!(
+            "{algorithm}=:{hash}:",
+            hash = Base64Display::new(&self.hash, &STANDARD)
+        ))
+    }
+
     /// Insert as an SRI attribute into a an [`Attrs`] instance.
     pub fn insert_into(&self, attrs: &mut HashMap<String, String>) {
         if let Some(value) = self.to_integrity_value() {
@@ -159,3 +184,55 @@ impl OutputDigest {
             .unwrap()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_auto() {
+        assert_eq!(
+            IntegrityType::from_str("auto").ok(),
+            Some(IntegrityType::Sha384)
+        );
+        assert_eq!(
+            IntegrityType::from_str("").ok(),
+            Some(IntegrityType::Sha384)
+        );
+    }
+
+    #[tokio::test]
+    async fn per_link_opt_out() -> anyhow::Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let cfg = RtcBuild::new_test(tmpdir.path()).await?;
+        assert!(!cfg.no_sri);
+
+        let opt_out = Attrs::from([(ATTR_INTEGRITY.to_string(), "none".to_string())]);
+        let sibling = Attrs::new();
+
+        let mut attrs = HashMap::new();
+        OutputDigest::generate_from(IntegrityType::from_attrs(&opt_out, &cfg)?, b"a")
+            .insert_into(&mut attrs);
+        assert_eq!(attrs.get("integrity"), None);
+
+        OutputDigest::generate_from(IntegrityType::from_attrs(&sibling, &cfg)?, b"a")
+            .insert_into(&mut attrs);
+        assert!(attrs["integrity"].starts_with("sha384-"));
+
+        let invalid = Attrs::from([(ATTR_INTEGRITY.to_string(), "md5".to_string())]);
+        assert!(IntegrityType::from_attrs(&invalid, &cfg).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn digest_header_value() {
+        let digest = OutputDigest::generate_from(IntegrityType::Sha256, b"");
+        assert_eq!(
+            digest.to_digest_header_value().as_deref(),
+            Some("sha-256=:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=:")
+        );
+
+        let digest = OutputDigest::generate_from(IntegrityType::None, b"");
+        assert_eq!(digest.to_digest_header_value(), None);
+    }
+}
@@ -1,38 +1,261 @@
+use anyhow::bail;
 use minify_js::TopLevelMode;
+use parcel_sourcemap::{OriginalLocation, SourceMap};
+use parse_js::ast::{NodeData, Syntax};
+use parse_js::visit::{JourneyControls, Visitor};
+use std::mem::{discriminant, Discriminant};
+use std::str::FromStr;
+
+/// How aggressive the minification of an asset is
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MinifyLevel {
+    /// Don't minify
+    None,
+    /// Only remove whitespace and comments, without renaming identifiers or restructuring rules
+    Safe,
+    /// Fully minify
+    #[default]
+    Aggressive,
+}
+
+impl FromStr for MinifyLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "safe" => Ok(Self::Safe),
+            "aggressive" => Ok(Self::Aggressive),
+            _ => bail!(
+                r#"unknown `data-minify-level="{s}"` value; please ensure the value is lowercase and one of "none", "safe" or "aggressive""#
+            ),
+        }
+    }
+}
 
 /// perform JS minification
-pub fn minify_js(bytes: Vec<u8>, mode: TopLevelMode) -> Vec<u8> {
+pub fn minify_js(bytes: Vec<u8>, mode: TopLevelMode, level: MinifyLevel) -> Vec<u8> {
+    try_minify_js(bytes, mode, level).unwrap_or_else(|bytes| bytes)
+}
+
+/// perform JS minification, also creating a source map of the minified code
+///
+/// The original content is inlined into the source map. No source map is created if the code
+/// wasn't minified.
+pub fn minify_js_with_source_map(
+    bytes: Vec<u8>,
+    mode: TopLevelMode,
+    level: MinifyLevel,
+    source: &str,
+) -> (Vec<u8>, Option<String>) {
+    let content = match std::str::from_utf8(&bytes) {
+        Ok(content) => content.to_string(),
+        Err(_) => return (bytes, None),
+    };
+
+    match try_minify_js(bytes, mode, level) {
+        Ok(minified) => {
+            let mut map = new_source_map(source, &content);
+            add_js_mappings(&mut map, content.as_bytes(), &minified, mode);
+            (minified, source_map_json(map))
+        }
+        Err(bytes) => (bytes, None),
+    }
+}
+
+/// Map the syntax nodes of minified JS to the nodes of its original code
+///
+/// The minifier doesn't track the locations of the original code. So both are parsed, and each
+/// node of the minified code is mapped to the next node of the original code of the same kind.
+/// Without renaming or restructuring (the safe level), the nodes match exactly.
+fn add_js_mappings(map: &mut SourceMap, original: &[u8], minified: &[u8], mode: TopLevelMode) {
+    /// How many nodes of the original code are searched for a match, skipping removed code
+    const LOOKAHEAD: usize = 64;
+
+    let session = minify_js::Session::new();
+    let (Ok(original_tree), Ok(minified_tree)) = (
+        parse_js::parse(&session, original, mode),
+        parse_js::parse(&session, minified, mode),
+    ) else {
+        tracing::warn!("Failed to parse JS for its source map, locations won't be mapped");
+        return;
+    };
+    let original_nodes = JsNodes::collect(original_tree);
+    let minified_nodes = JsNodes::collect(minified_tree);
+
+    let mut original_lines = LineColumns::new(original);
+    let mut minified_lines = LineColumns::new(minified);
+    let mut last = None;
+    let mut next = 0;
+    for (kind, start) in minified_nodes {
+        let Some(found) = original_nodes[next..]
+            .iter()
+            .take(LOOKAHEAD)
+            .position(|(original_kind, _)| *original_kind == kind)
+        else {
+            continue;
+        };
+        let original_start = original_nodes[next + found].1;
+        next += found + 1;
+
+        // nested nodes may start at the same location, the outermost one is mapped
+        let generated = minified_lines.at(start);
+        if last == Some(generated) {
+            continue;
+        }
+        last = Some(generated);
+
+        let (original_line, original_column) = original_lines.at(original_start);
+        map.add_mapping(
+            generated.0,
+            generated.1,
+            Some(OriginalLocation {
+                original_line,
+                original_column,
+                source: 0,
+                name: None,
+            }),
+        );
+    }
+}
+
+/// The kinds and start offsets of the syntax nodes of parsed JS, in the order of the code
+struct JsNodes<'a>(Vec<(Discriminant<Syntax<'a>>, usize)>);
+
+impl<'a> JsNodes<'a> {
+    fn collect(node: &mut NodeData<'a>) -> Vec<(Discriminant<Syntax<'a>>, usize)> {
+        let mut nodes = Self(vec![]);
+        nodes.visit(node);
+        nodes.0
+    }
+}
+
+impl<'a> Visitor<'a> for JsNodes<'a> {
+    fn on_syntax_down(&mut self, node: &mut NodeData<'a>, _: &mut JourneyControls) {
+        self.0.push((discriminant(&node.stx), node.loc.start()));
+    }
+}
+
+/// Converts offsets of code into zero-based lines and columns (in UTF-16 code units)
+///
+/// Offsets are mostly increasing, so the conversion continues from the previous offset.
+struct LineColumns<'a> {
+    code: &'a [u8],
+    offset: usize,
+    line: u32,
+    column: u32,
+}
+
+impl<'a> LineColumns<'a> {
+    fn new(code: &'a [u8]) -> Self {
+        Self {
+            code,
+            offset: 0,
+            line: 0,
+            column: 0,
+        }
+    }
+
+    fn at(&mut self, offset: usize) -> (u32, u32) {
+        if offset < self.offset {
+            *self = Self::new(self.code);
+        }
+        for c in String::from_utf8_lossy(&self.code[self.offset..offset]).chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 0;
+            } else {
+                self.column += c.len_utf16() as u32;
+            }
+        }
+        self.offset = offset;
+        (self.line, self.column)
+    }
+}
+
+/// Minify JS, returning the original content if it wasn't minified
+fn try_minify_js(
+    bytes: Vec<u8>,
+    mode: TopLevelMode,
+    level: MinifyLevel,
+) -> Result<Vec<u8>, Vec<u8>> {
     let mut result: Vec<u8> = vec![];
     let session = minify_js::Session::new();
 
-    match minify_js::minify(&session, mode, &bytes, &mut result) {
-        Ok(()) => result,
+    let res = match level {
+        MinifyLevel::None => return Err(bytes),
+        // emitting the parsed code without the minification pass keeps the identifiers
+        MinifyLevel::Safe => parse_js::parse(&session, &bytes, mode)
+            .map(|parsed| minify_js::emit(parsed, &mut result)),
+        MinifyLevel::Aggressive => minify_js::minify(&session, mode, &bytes, &mut result),
+    };
+
+    match res {
+        Ok(()) => Ok(result),
         Err(err) => {
             tracing::warn!("Failed to minify JS: {err}");
-            bytes
+            Err(bytes)
         }
     }
 }
 
 /// perform CSS minification
-pub fn minify_css(bytes: Vec<u8>) -> Vec<u8> {
+pub fn minify_css(bytes: Vec<u8>, level: MinifyLevel) -> Vec<u8> {
+    try_minify_css(bytes, level, None).unwrap_or_else(|bytes| bytes)
+}
+
+/// perform CSS minification, also creating a source map of the minified code
+///
+/// The original content is inlined into the source map. No source map is created if the code
+/// wasn't minified.
+pub fn minify_css_with_source_map(
+    bytes: Vec<u8>,
+    level: MinifyLevel,
+    source: &str,
+) -> (Vec<u8>, Option<String>) {
+    let content = match std::str::from_utf8(&bytes) {
+        Ok(content) => content.to_string(),
+        Err(_) => return (bytes, None),
+    };
+
+    let mut map = new_source_map(source, &content);
+    match try_minify_css(bytes, level, Some(&mut map)) {
+        Ok(minified) => (minified, source_map_json(map)),
+        Err(bytes) => (bytes, None),
+    }
+}
+
+/// Minify CSS, returning the original content if it wasn't minified
+fn try_minify_css(
+    bytes: Vec<u8>,
+    level: MinifyLevel,
+    source_map: Option<&mut SourceMap>,
+) -> Result<Vec<u8>, Vec<u8>> {
     use lightningcss::stylesheet::*;
 
     /// wrap CSS minification to isolate borrowing the original content
-    fn minify(css: &str) -> Result<String, ()> {
+    fn minify(
+        css: &str,
+        level: MinifyLevel,
+        source_map: Option<&mut SourceMap>,
+    ) -> Result<String, ()> {
         // parse CSS
 
         let mut css = StyleSheet::parse(css, ParserOptions::default()).map_err(|err| {
             tracing::warn!("CSS parsing failed, skipping: {err}");
         })?;
 
-        css.minify(MinifyOptions::default()).map_err(|err| {
-            tracing::warn!("CSS minification failed, skipping: {err}");
-        })?;
+        // the safe level only drops whitespace and comments when printing
+        if level == MinifyLevel::Aggressive {
+            css.minify(MinifyOptions::default()).map_err(|err| {
+                tracing::warn!("CSS minification failed, skipping: {err}");
+            })?;
+        }
 
         Ok(css
             .to_css(PrinterOptions {
                 minify: true,
+                source_map,
                 ..Default::default()
             })
             .map_err(|err| {
@@ -41,12 +264,46 @@ pub fn minify_css(bytes: Vec<u8>) -> Vec<u8> {
             .code)
     }
 
+    if level == MinifyLevel::None {
+        return Err(bytes);
+    }
+
     match std::str::from_utf8(&bytes) {
-        Ok(css) => minify(css).map(String::into_bytes).unwrap_or(bytes),
-        Err(_) => bytes,
+        Ok(css) => match minify(css, level, source_map) {
+            Ok(minified) => Ok(minified.into_bytes()),
+            Err(()) => Err(bytes),
+        },
+        Err(_) => Err(bytes),
     }
 }
 
+/// Create a source map, with the original content inlined as its only source
+fn new_source_map(source: &str, content: &str) -> SourceMap {
+    let mut map = SourceMap::new("/");
+    let index = map.add_source(source);
+    if let Err(err) = map.set_source_content(index as usize, content) {
+        tracing::warn!("Failed to add the source content to the source map: {err:?}");
+    }
+    map
+}
+
+/// Serialize a source map
+fn source_map_json(mut map: SourceMap) -> Option<String> {
+    map.to_json(None)
+        .map_err(|err| tracing::warn!("Failed to create source map: {err:?}"))
+        .ok()
+}
+
+/// The comment referencing the source map of a CSS file
+pub fn css_source_mapping_url(map_file: &str) -> String {
+    format!("\n/*# sourceMappingURL={map_file} */\n")
+}
+
+/// The comment referencing the source map of a JS file
+pub fn js_source_mapping_url(map_file: &str) -> String {
+    format!("\n//# sourceMappingURL={map_file}\n")
+}
+
 /// perform HTML minification
 pub fn minify_html(html: &[u8]) -> Vec<u8> {
     let mut minify_cfg = minify_html::Cfg::spec_compliant();
@@ -55,3 +312,114 @@ pub fn minify_html(html: &[u8]) -> Vec<u8> {
     minify_cfg.keep_closing_tags = true;
     minify_html::minify(html, &minify_cfg)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn js_levels() {
+        let js = b"function greet(longName) { return 'hi ' + longName; }\ngreet('x');".to_vec();
+
+        let safe = minify_js(js.clone(), TopLevelMode::Global, MinifyLevel::Safe);
+        let safe = String::from_utf8_lossy(&safe);
+        assert!(safe.contains("longName"), "{safe}");
+        assert!(safe.len() < js.len(), "{safe}");
+
+        let aggressive = minify_js(js.clone(), TopLevelMode::Global, MinifyLevel::Aggressive);
+        assert!(!String::from_utf8_lossy(&aggressive).contains("longName"));
+
+        assert_eq!(
+            minify_js(js.clone(), TopLevelMode::Global, MinifyLevel::None),
+            js
+        );
+    }
+
+    #[test]
+    fn css_levels() {
+        let css = b"a { color: red; }\na { margin: 0; }".to_vec();
+
+        let safe = minify_css(css.clone(), MinifyLevel::Safe);
+        assert_eq!(String::from_utf8_lossy(&safe), "a{color:red}a{margin:0}");
+
+        let aggressive = minify_css(css.clone(), MinifyLevel::Aggressive);
+        assert_eq!(
+            String::from_utf8_lossy(&aggressive),
+            "a{color:red;margin:0}"
+        );
+    }
+
+    #[test]
+    fn css_source_map() {
+        let css = b"a {\n  color: red;\n}\n\nb {\n  margin: 0;\n}\n".to_vec();
+
+        let (minified, map) = minify_css_with_source_map(css, MinifyLevel::Safe, "app.css");
+        assert_eq!(
+            String::from_utf8_lossy(&minified),
+            "a{color:red}b{margin:0}"
+        );
+
+        let map: serde_json::Value =
+            serde_json::from_str(&map.expect("a source map")).expect("valid JSON");
+        assert_eq!(map["sources"], serde_json::json!(["app.css"]));
+        assert_eq!(
+            map["sourcesContent"],
+            serde_json::json!(["a {\n  color: red;\n}\n\nb {\n  margin: 0;\n}\n"])
+        );
+        // the second rule maps to a later line of the original
+        assert!(map["mappings"]
+            .as_str()
+            .is_some_and(|mappings| mappings.contains(',')));
+
+        let (_, map) = minify_css_with_source_map(b"a{}".to_vec(), MinifyLevel::None, "app.css");
+        assert!(map.is_none());
+    }
+
+    #[test]
+    fn js_source_map() {
+        let js = "function greet(name) {\n  return 'hi ' + name;\n}\ngreet('x');\n";
+
+        let (minified, map) = minify_js_with_source_map(
+            js.as_bytes().to_vec(),
+            TopLevelMode::Global,
+            MinifyLevel::Safe,
+            "app.js",
+        );
+        let minified = String::from_utf8_lossy(&minified);
+        assert!(!minified.contains('\n'), "{minified}");
+
+        let map = map.expect("a source map");
+        let json: serde_json::Value = serde_json::from_str(&map).expect("valid JSON");
+        assert_eq!(json["sources"], serde_json::json!(["app.js"]));
+        assert_eq!(json["sourcesContent"], serde_json::json!([js]));
+
+        // the nodes of the minified code map to their original lines and columns
+        let mut map = SourceMap::from_json("/", &map).expect("a valid source map");
+        let mut original = |code: &str| {
+            let column = minified.rfind(code).expect("minified code") as u32;
+            map.find_closest_mapping(0, column)
+                .and_then(|mapping| mapping.original)
+                .map(|original| (original.original_line, original.original_column))
+        };
+        assert_eq!(original("return"), Some((1, 2)));
+        assert_eq!(original("name}"), Some((1, 17)));
+        assert_eq!(original("greet("), Some((3, 0)));
+
+        let (_, map) = minify_js_with_source_map(
+            js.as_bytes().to_vec(),
+            TopLevelMode::Global,
+            MinifyLevel::None,
+            "app.js",
+        );
+        assert!(map.is_none());
+
+        assert_eq!(
+            js_source_mapping_url("app.js.map"),
+            "\n//# sourceMappingURL=app.js.map\n"
+        );
+        assert_eq!(
+            css_source_mapping_url("app.css.map"),
+            "\n/*# sourceMappingURL=app.css.map */\n"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! A single line status of the running build, for interactive terminals.
+//!
+//! The line is kept at the bottom of the output. Log messages clear it before they get written
+//! and restore it afterwards, so that it doesn't get mixed up with the log output.
+
+use crate::common::BUILDING;
+use console::Term;
+use parking_lot::{Mutex, MutexGuard};
+use std::{
+    io::{self, Write},
+    sync::OnceLock,
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+static STATUS: OnceLock<Status> = OnceLock::new();
+
+struct Status {
+    term: Term,
+    /// The currently shown line, if any
+    line: Mutex<Option<String>>,
+}
+
+impl Status {
+    fn clear(&self) {
+        let _ = self.term.clear_line();
+    }
+
+    fn draw(&self, line: &str) {
+        let width = self.term.size().1 as usize;
+        let line = console::truncate_str(line, width.saturating_sub(1), "…");
+        // keep the cursor at the start of the line, so that output of tools we run overwrites it
+        let _ = self.term.clear_line();
+        let _ = self.term.write_str(&format!("{line}\r"));
+    }
+}
+
+/// Enable the status line, in case stdout is an interactive terminal.
+pub fn init(enabled: bool) {
+    let term = Term::stdout();
+    if enabled && term.is_term() {
+        let _ = STATUS.set(Status {
+            term,
+            line: Mutex::new(None),
+        });
+    }
+}
+
+/// Show the phase the build is currently in.
+pub fn phase(phase: &str) {
+    if let Some(status) = STATUS.get() {
+        let mut line = status.line.lock();
+        let new = format!("{BUILDING}building: {phase}");
+        status.draw(&new);
+        *line = Some(new);
+    }
+}
+
+/// Remove the status line, once the build is done.
+pub fn finish() {
+    if let Some(status) = STATUS.get() {
+        if status.line.lock().take().is_some() {
+            status.clear();
+        }
+    }
+}
+
+/// A writer for log output, moving the status line out of the way.
+pub struct LogWriter;
+
+impl<'a> MakeWriter<'a> for LogWriter {
+    type Writer = LogLine;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        let guard = STATUS.get().map(|status| {
+            let line = status.line.lock();
+            if line.is_some() {
+                status.clear();
+            }
+            (status, line)
+        });
+
+        LogLine {
+            stdout: io::stdout(),
+            guard,
+        }
+    }
+}
+
+/// A single log event, restoring the status line when dropped.
+pub struct LogLine {
+    stdout: io::Stdout,
+    guard: Option<(&'static Status, MutexGuard<'static, Option<String>>)>,
+}
+
+impl Write for LogLine {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdout.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl Drop for LogLine {
+    fn drop(&mut self) {
+        let _ = self.stdout.flush();
+        if let Some((status, line)) = &self.guard {
+            if let Some(line) = line.as_deref() {
+                status.draw(line);
+            }
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::serve::{ServerError, ServerResult};
+use crate::{
+    config::models::ResponseRewrite,
+    serve::{ServerError, ServerResult},
+};
 use anyhow::Context;
 use axum::{
     body::Body,
@@ -7,12 +10,15 @@ use axum::{
         Request, State,
     },
     http::{Response, Uri},
-    routing::{any, get, Router},
+    routing::{any, get, MethodRouter, Router},
     RequestExt,
 };
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 use futures_util::{sink::SinkExt, stream::StreamExt, TryStreamExt};
-use http::{header::HOST, HeaderMap};
+use http::{
+    header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, HOST},
+    HeaderMap,
+};
 use std::sync::Arc;
 use tokio_tungstenite::{
     connect_async,
@@ -45,6 +51,95 @@ pub(crate) struct ProxyHandlerHttp {
     /// An optional rewrite path to be used as the listening URI prefix, but which will be
     /// stripped before being sent to the proxy backend.
     rewrite: Option<String>,
+    /// The replacements applied to the bodies of text responses.
+    response_rewrite: ResponseRewriter,
+}
+
+/// Applies string replacements to the bodies of responses with a matching content type.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ResponseRewriter {
+    /// The replacements, applied in order.
+    replacements: Vec<ResponseRewrite>,
+    /// The content types to rewrite, either a full type like `text/html` or `text/*`.
+    content_types: Vec<String>,
+}
+
+impl ResponseRewriter {
+    /// Construct a new instance.
+    pub fn new(replacements: Vec<ResponseRewrite>, content_types: Vec<String>) -> Self {
+        Self {
+            replacements,
+            content_types,
+        }
+    }
+
+    /// Whether any replacements are configured.
+    pub fn is_enabled(&self) -> bool {
+        !self.replacements.is_empty()
+    }
+
+    /// Whether the body of a response with the given headers should be rewritten.
+    ///
+    /// Encoded (e.g. compressed) bodies are never rewritten.
+    fn applies_to(&self, headers: &HeaderMap) -> bool {
+        // event streams stay open, there is no complete body to rewrite
+        if !self.is_enabled() || is_event_stream(headers) {
+            return false;
+        }
+
+        let encoded = headers
+            .get(CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| !value.trim().eq_ignore_ascii_case("identity"));
+        if encoded {
+            return false;
+        }
+
+        let Some(content_type) = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<mime_guess::mime::Mime>().ok())
+        else {
+            return false;
+        };
+        let essence = content_type.essence_str();
+        let top_level = content_type.type_().as_str();
+
+        self.content_types.iter().any(|allowed| {
+            let allowed = allowed.trim();
+            match allowed.strip_suffix("/*") {
+                Some(allowed) => allowed.eq_ignore_ascii_case(top_level),
+                None => allowed.eq_ignore_ascii_case(essence),
+            }
+        })
+    }
+
+    /// Apply the replacements to a body, which is passed through unchanged if it isn't UTF-8.
+    fn rewrite(&self, body: Bytes) -> Bytes {
+        let Ok(text) = std::str::from_utf8(&body) else {
+            return body;
+        };
+
+        let mut text = text.to_string();
+        for ResponseRewrite { from, to } in &self.replacements {
+            if !from.is_empty() {
+                text = text.replace(from, to);
+            }
+        }
+        Bytes::from(text)
+    }
+}
+
+/// Whether a response is a stream of server-sent events (`text/event-stream`).
+///
+/// These responses stay open indefinitely, so their body must be passed on chunk by chunk and
+/// never be buffered.
+pub(crate) fn is_event_stream(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime_guess::mime::Mime>().ok())
+        .is_some_and(|mime| mime.essence_str() == "text/event-stream")
 }
 
 fn make_outbound_uri(backend: &Uri, request: &Uri) -> anyhow::Result<Uri> {
@@ -152,6 +247,7 @@ impl ProxyHandlerHttp {
         backend: Uri,
         request_headers: HeaderMap,
         rewrite: Option<String>,
+        response_rewrite: ResponseRewriter,
     ) -> Arc<Self> {
         Arc::new(Self {
             proto,
@@ -159,17 +255,20 @@ impl ProxyHandlerHttp {
             backend,
             request_headers,
             rewrite,
+            response_rewrite,
         })
     }
 
     /// Build the sub-router for this proxy.
     pub fn register(self: Arc<Self>, router: Router) -> Router {
-        router.nest_service(
-            self.path(),
-            any(Self::proxy_http_request)
-                .layer(TraceLayer::new_for_http())
-                .with_state(self.clone()),
-        )
+        router.nest_service(self.path(), self.clone().service())
+    }
+
+    /// The service proxying all requests to the backend.
+    pub fn service(self: Arc<Self>) -> MethodRouter {
+        any(Self::proxy_http_request)
+            .layer(TraceLayer::new_for_http())
+            .with_state(self)
     }
 
     /// The path which this proxy backend listens at.
@@ -187,11 +286,16 @@ impl ProxyHandlerHttp {
     ) -> ServerResult<Response<Body>> {
         // Construct the outbound URI & build a new request to be sent to the proxy backend.
         let outbound_uri = make_outbound_uri(&state.backend, req.uri())?;
+        let mut inbound_headers = req.headers().clone();
+        if state.response_rewrite.is_enabled() {
+            // Only uncompressed bodies can be rewritten.
+            inbound_headers.remove(ACCEPT_ENCODING);
+        }
         let outbound_req = make_outbound_request(
             &state.proto,
             &outbound_uri,
             req.method().clone(),
-            req.headers().clone(),
+            inbound_headers,
             state.request_headers.clone(),
         )?;
 
@@ -222,13 +326,27 @@ impl ProxyHandlerHttp {
             .await
             .context("error proxying request to proxy backend")?;
         let mut res = Response::builder().status(backend_res.status());
+        let rewrite = state.response_rewrite.applies_to(backend_res.headers());
         for (key, val) in backend_res.headers() {
+            if rewrite && key == CONTENT_LENGTH {
+                // the length changes with the body
+                continue;
+            }
             res = res.header(key, val);
         }
 
-        Ok(res
-            .body(Body::from_stream(backend_res.bytes_stream()))
-            .context("error building proxy response")?)
+        let body = match rewrite {
+            true => {
+                let body = backend_res
+                    .bytes()
+                    .await
+                    .context("error reading response of proxy backend")?;
+                Body::from(state.response_rewrite.rewrite(body))
+            }
+            false => Body::from_stream(backend_res.bytes_stream()),
+        };
+
+        Ok(res.body(body).context("error building proxy response")?)
     }
 }
 
@@ -587,4 +705,130 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn response_rewrite_matches_content_types() {
+        let rewriter = super::ResponseRewriter::new(
+            vec![super::ResponseRewrite {
+                from: "http://backend".into(),
+                to: "/api".into(),
+            }],
+            vec!["application/json".into(), "text/*".into()],
+        );
+        let headers = |content_type: &str, encoding: Option<&str>| {
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                CONTENT_TYPE,
+                HeaderValue::from_str(content_type).expect("Failed to create Header Value"),
+            );
+            if let Some(encoding) = encoding {
+                headers.insert(
+                    http::header::CONTENT_ENCODING,
+                    HeaderValue::from_str(encoding).expect("Failed to create Header Value"),
+                );
+            }
+            headers
+        };
+
+        assert!(rewriter.applies_to(&headers("application/json", None)));
+        assert!(rewriter.applies_to(&headers("Application/JSON; charset=utf-8", None)));
+        assert!(rewriter.applies_to(&headers("text/plain", Some("identity"))));
+        assert!(!rewriter.applies_to(&headers("application/json", Some("gzip"))));
+        assert!(!rewriter.applies_to(&headers("image/png", None)));
+        assert!(!rewriter.applies_to(&HeaderMap::new()));
+        assert!(!super::ResponseRewriter::default().applies_to(&headers("text/html", None)));
+        // event streams never end, so they are streamed instead
+        assert!(!rewriter.applies_to(&headers("text/event-stream", None)));
+    }
+
+    #[tokio::test]
+    async fn event_streams_are_not_buffered() -> anyhow::Result<()> {
+        use axum::{body::Body, response::IntoResponse, routing::get, Router};
+        use futures_util::stream::{self, StreamExt};
+        use std::net::Ipv4Addr;
+
+        // a backend sending a single event, and then keeping the stream open
+        let backend = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let backend_addr = backend.local_addr()?;
+        let backend = tokio::spawn(async move {
+            let router = Router::new().route(
+                "/events",
+                get(|| async {
+                    let events = stream::once(async {
+                        Ok::<_, std::io::Error>(bytes::Bytes::from("data: http://backend\n\n"))
+                    })
+                    .chain(stream::pending());
+                    (
+                        [(CONTENT_TYPE, "text/event-stream")],
+                        Body::from_stream(events),
+                    )
+                        .into_response()
+                }),
+            );
+            axum::serve(backend, router).await
+        });
+
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let addr = listener.local_addr()?;
+        let handler = super::ProxyHandlerHttp::new(
+            "http".into(),
+            reqwest::Client::builder().no_proxy().build()?,
+            format!("http://{backend_addr}/").parse()?,
+            HeaderMap::new(),
+            None,
+            super::ResponseRewriter::new(
+                vec![super::ResponseRewrite {
+                    from: "http://backend".into(),
+                    to: "/api".into(),
+                }],
+                vec!["text/*".into()],
+            ),
+        );
+        let router = Router::new().fallback_service(handler.service());
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::builder().no_proxy().build()?;
+        let mut response = client.get(format!("http://{addr}/events")).send().await?;
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), response.chunk())
+            .await
+            .map_err(|_| anyhow::anyhow!("the event was buffered"))??;
+        assert_eq!(
+            chunk.as_deref(),
+            Some("data: http://backend\n\n".as_bytes())
+        );
+
+        server.abort();
+        backend.abort();
+        Ok(())
+    }
+
+    #[test]
+    fn response_rewrite_replaces_in_order() {
+        let rewriter = super::ResponseRewriter::new(
+            vec![
+                super::ResponseRewrite {
+                    from: "http://backend:8080".into(),
+                    to: "http://localhost:8080/api".into(),
+                },
+                super::ResponseRewrite {
+                    from: "/api/v1".into(),
+                    to: "/api".into(),
+                },
+            ],
+            vec![],
+        );
+
+        assert_eq!(
+            rewriter.rewrite(r#"{"next":"http://backend:8080/v1/items"}"#.into()),
+            r#"{"next":"http://localhost:8080/api/items"}"#.as_bytes()
+        );
+        assert_eq!(
+            rewriter.rewrite("/api/v1/items".into()),
+            "/api/items".as_bytes()
+        );
+
+        // binary content passes through untouched
+        let binary = bytes::Bytes::from_static(&[0xff, 0xfe, b'/', b'a']);
+        assert_eq!(rewriter.rewrite(binary.clone()), binary);
+    }
 }
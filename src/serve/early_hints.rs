@@ -0,0 +1,173 @@
+//! Sending `103 Early Hints` responses ahead of the served index.html.
+//!
+//! Hyper doesn't support sending informational responses from a server. So the connections of a
+//! server are shared with the requests received on them, which can then write the hints to the
+//! connection directly, before the request gets handled. This only works for HTTP/1.1, where
+//! nothing else is written to a connection while a request is being handled.
+
+use axum::http::HeaderValue;
+use axum_server::accept::Accept;
+use futures_util::future::{MapOk, TryFutureExt};
+use std::fmt;
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tower_http::add_extension::AddExtension;
+
+type Connection = dyn AsyncWrite + Send + Unpin;
+
+/// The early hints of a connection, available as an extension of its requests.
+#[derive(Clone)]
+pub struct EarlyHints(Arc<Mutex<Connection>>);
+
+impl fmt::Debug for EarlyHints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EarlyHints").finish_non_exhaustive()
+    }
+}
+
+impl EarlyHints {
+    /// Send the links as early hints, ahead of the response to the current request.
+    ///
+    /// This must only be used before the response of an HTTP/1.1 request was produced.
+    pub async fn send(&self, links: &[HeaderValue]) -> io::Result<()> {
+        let mut hints = b"HTTP/1.1 103 Early Hints\r\n".to_vec();
+        for link in links {
+            hints.extend_from_slice(b"link: ");
+            hints.extend_from_slice(link.as_bytes());
+            hints.extend_from_slice(b"\r\n");
+        }
+        hints.extend_from_slice(b"\r\n");
+
+        let mut pending = hints.as_slice();
+        poll_fn(|cx| {
+            let mut connection = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+            while !pending.is_empty() {
+                match ready!(Pin::new(&mut *connection).poll_write(cx, pending))? {
+                    0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                    written => pending = &pending[written..],
+                }
+            }
+            Pin::new(&mut *connection).poll_flush(cx)
+        })
+        .await
+    }
+}
+
+/// An acceptor providing [`EarlyHints`] for the connections of an inner acceptor.
+#[derive(Clone, Debug)]
+pub struct EarlyHintsAcceptor<A> {
+    inner: A,
+}
+
+impl<A> EarlyHintsAcceptor<A> {
+    pub fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+type Accepted<I, S> = (EarlyHintsStream<I>, AddExtension<S, EarlyHints>);
+
+impl<A, I, S> Accept<I, S> for EarlyHintsAcceptor<A>
+where
+    A: Accept<I, S>,
+    A::Stream: AsyncWrite + Send + Unpin + 'static,
+{
+    type Stream = EarlyHintsStream<A::Stream>;
+    type Service = AddExtension<A::Service, EarlyHints>;
+    #[allow(clippy::type_complexity)]
+    type Future = MapOk<A::Future, fn((A::Stream, A::Service)) -> Accepted<A::Stream, A::Service>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        self.inner
+            .accept(stream, service)
+            .map_ok(accepted as fn(_) -> _)
+    }
+}
+
+fn accepted<I, S>((stream, service): (I, S)) -> Accepted<I, S>
+where
+    I: AsyncWrite + Send + Unpin + 'static,
+{
+    let (stream, hints) = EarlyHintsStream::new(stream);
+    (stream, AddExtension::new(service, hints))
+}
+
+/// A connection shared with the [`EarlyHints`] of its requests.
+pub struct EarlyHintsStream<T>(Arc<Mutex<T>>);
+
+impl<T: AsyncWrite + Send + Unpin + 'static> EarlyHintsStream<T> {
+    fn new(inner: T) -> (Self, EarlyHints) {
+        let inner = Arc::new(Mutex::new(inner));
+        (Self(inner.clone()), EarlyHints(inner))
+    }
+}
+
+impl<T> EarlyHintsStream<T> {
+    fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for EarlyHintsStream<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.lock()).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for EarlyHintsStream<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.lock()).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.lock()).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.lock()).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn hints_are_written_to_the_connection() -> anyhow::Result<()> {
+        let (mut client, server) = tokio::io::duplex(1024);
+        let (mut server, hints) = EarlyHintsStream::new(server);
+
+        hints
+            .send(&[HeaderValue::from_static(
+                "</app_bg.wasm>; rel=preload; as=fetch",
+            )])
+            .await?;
+        server
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .await?;
+        drop((server, hints));
+
+        let mut written = String::new();
+        client.read_to_string(&mut written).await?;
+        assert_eq!(
+            written,
+            "HTTP/1.1 103 Early Hints\r\nlink: </app_bg.wasm>; rel=preload; as=fetch\r\n\r\n\
+             HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n"
+        );
+
+        Ok(())
+    }
+}
@@ -1,7 +1,13 @@
+mod early_hints;
 mod proxy;
 
 use crate::common::{nonce, LOCAL, NETWORK, SERVER};
-use crate::config::rt::RtcServe;
+use crate::config::{
+    rt::RtcServe,
+    types::{CompressionFormat, PreloadLocation, WsProtocol},
+};
+use crate::processing::integrity::{IntegrityType, OutputDigest};
+use crate::proxy::{is_event_stream, ResponseRewriter};
 use crate::tls::TlsConfig;
 use crate::watch::WatchSystem;
 use crate::ws;
@@ -9,21 +15,27 @@ use anyhow::{Context, Result};
 use axum::body::{Body, Bytes};
 use axum::extract;
 use axum::extract::ws::WebSocketUpgrade;
-use axum::http::header::{HeaderName, CONTENT_LENGTH, CONTENT_TYPE, HOST};
-use axum::http::{HeaderValue, StatusCode};
+use axum::http::header::{
+    HeaderName, ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, HOST,
+    LAST_MODIFIED,
+};
+use axum::http::{HeaderValue, Method, StatusCode, Uri, Version};
 use axum::middleware::Next;
-use axum::response::{IntoResponse, Response};
-use axum::routing::{get, get_service, Router};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::{any_service, get, get_service, MethodRouter, Router};
 use axum_server::Handle;
+use early_hints::{EarlyHints, EarlyHintsAcceptor};
 use futures_util::FutureExt;
 use hickory_resolver::TokioAsyncResolver;
-use http::header::CONTENT_SECURITY_POLICY;
+use http::header::{CONTENT_SECURITY_POLICY, LINK};
 use http::HeaderMap;
+use hyper_util::rt::TokioTimer;
+use percent_encoding::percent_decode_str;
 use proxy::{ProxyBuilder, ProxyClientOptions};
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::select;
 use tokio::sync::{broadcast, watch};
@@ -34,6 +46,16 @@ use tower_http::trace::TraceLayer;
 use tracing::log;
 
 const INDEX_HTML: &str = "index.html";
+const REPR_DIGEST: HeaderName = HeaderName::from_static("repr-digest");
+/// The largest responses a `Repr-Digest` header is created for.
+const DIGEST_MAX_SIZE: usize = 100 * 1024 * 1024;
+
+/// Open the browser tab for the application.
+fn open_browser(addr: &str) {
+    if let Err(err) = open::that(addr) {
+        tracing::error!(error = ?err, "error opening browser");
+    }
+}
 
 /// A system encapsulating a build & watch system, responsible for serving generated content.
 pub struct ServeSystem {
@@ -78,19 +100,64 @@ impl ServeSystem {
     #[tracing::instrument(level = "trace", skip(self))]
     pub async fn run(mut self) -> Result<()> {
         // Spawn the watcher & the server.
-        let _build_res = self.watch.build().await; // TODO: only open after a successful build.
+        let build_res = self.watch.build().await;
+        // track later builds before the watcher runs, in case the initial build failed
+        let mut ws_state = self.ws_state.clone();
+        ws_state.mark_unchanged();
+        let ready_state = ws_state.clone();
+        let built = build_res.is_ok();
+        if self.cfg.build_only_once {
+            // without watching, there won't be another build the on-ready command could wait for
+            if let (Err(err), Some(_)) = (build_res, &self.cfg.on_ready) {
+                return Err(err.context("the build failed, not running the on-ready command"));
+            }
+
+            tracing::info!("{}built once, not watching for changes", SERVER);
+            self.watch.stop_watching();
+        }
         let watch_handle = tokio::spawn(self.watch.run());
+        let listening = Handle::new();
         let server_handle = Self::spawn_server(
             self.cfg.clone(),
             self.shutdown_tx.subscribe(),
             self.ws_state,
+            listening.clone(),
         )
         .await?;
 
-        // Open the browser.
+        // Run the on-ready command, stopping the server once it exits.
+        let on_ready = match self.cfg.on_ready.clone() {
+            Some(command) => {
+                let cfg = self.cfg.clone();
+                let url = self.open_http_addr.clone();
+                async move {
+                    wait_ready(built, ready_state, listening).await;
+                    run_on_ready(&cfg, &command, &url).await
+                }
+                .boxed()
+            }
+            None => futures_util::future::pending::<Result<()>>().boxed(),
+        };
+
+        // Open the browser, once a build succeeded.
         if self.cfg.open {
-            if let Err(err) = open::that(self.open_http_addr) {
-                tracing::error!(error = ?err, "error opening browser");
+            let addr = self.open_http_addr;
+            match built {
+                true => open_browser(&addr),
+                false => {
+                    tracing::info!(
+                        "{}opening the browser after the next successful build",
+                        SERVER
+                    );
+                    tokio::spawn(async move {
+                        while ws_state.changed().await.is_ok() {
+                            if *ws_state.borrow_and_update() == ws::State::Ok {
+                                open_browser(&addr);
+                                break;
+                            }
+                        }
+                    });
+                }
             }
         }
         drop(self.shutdown_tx); // Drop the broadcast channel to ensure it does not keep the system alive.
@@ -114,16 +181,20 @@ impl ServeSystem {
                     _ => r,
                 }??;
             },
+            r = on_ready => {
+                r?;
+            },
         }
 
         Ok(())
     }
 
-    #[tracing::instrument(level = "trace", skip(cfg, shutdown_rx))]
+    #[tracing::instrument(level = "trace", skip(cfg, shutdown_rx, handle))]
     async fn spawn_server(
         cfg: Arc<RtcServe>,
         shutdown_rx: broadcast::Receiver<()>,
         ws_state: watch::Receiver<ws::State>,
+        handle: Handle,
     ) -> Result<JoinHandle<Result<()>>> {
         let serve_base_url = cfg.serve_base()?;
 
@@ -157,7 +228,7 @@ impl ServeSystem {
         )
         .await;
 
-        let server = run_server(addr, cfg.tls.clone(), router, shutdown_rx);
+        let server = run_server(addr, cfg.clone(), router, shutdown_rx, handle);
 
         Ok(tokio::spawn(async move {
             match server.await {
@@ -171,6 +242,69 @@ impl ServeSystem {
     }
 }
 
+/// Wait for a successful build, unless the first one succeeded already, and for the server to
+/// listen.
+async fn wait_ready(built: bool, mut ws_state: watch::Receiver<ws::State>, listening: Handle) {
+    if !built {
+        tracing::info!(
+            "{}running the on-ready command after the next successful build",
+            SERVER
+        );
+        while ws_state.changed().await.is_ok() {
+            if *ws_state.borrow_and_update() == ws::State::Ok {
+                break;
+            }
+        }
+    }
+    listening.listening().await;
+}
+
+/// Run the on-ready command in a shell, failing with its exit code if it didn't succeed.
+async fn run_on_ready(cfg: &RtcServe, command: &str, url: &str) -> Result<()> {
+    let (shell, flag) = match cfg!(target_os = "windows") {
+        true => ("cmd", "/C"),
+        false => ("sh", "-c"),
+    };
+
+    tracing::info!("{}running on-ready command: {command}", SERVER);
+    let status = tokio::process::Command::new(shell)
+        .arg(flag)
+        .arg(command)
+        .current_dir(&cfg.watch.build.core.working_directory)
+        .env("TRUNK_SERVE_URL", url)
+        .env("TRUNK_DIST_DIR", &cfg.watch.build.final_dist)
+        // don't leave the command running when the server stops first
+        .kill_on_drop(true)
+        .status()
+        .await
+        .with_context(|| format!("error running on-ready command: {command}"))?;
+
+    match status.code() {
+        Some(0) => {
+            tracing::info!("{}on-ready command finished, stopping the server", SERVER);
+            Ok(())
+        }
+        code => Err(OnReadyFailed {
+            code: code.and_then(|code| u8::try_from(code).ok()).unwrap_or(1),
+        }
+        .into()),
+    }
+}
+
+/// The failure of the on-ready command, which Trunk exits with the exit code of.
+#[derive(Debug)]
+pub struct OnReadyFailed {
+    pub code: u8,
+}
+
+impl std::fmt::Display for OnReadyFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "on-ready command failed with exit code {}", self.code)
+    }
+}
+
+impl std::error::Error for OnReadyFailed {}
+
 /// Show where `serve` is listening
 ///
 /// We'll look up addresses, and simply append aliases.
@@ -186,6 +320,19 @@ async fn show_listening(
 
     let prefix = if cfg.tls.is_some() { "https" } else { "http" };
 
+    // a public host (e.g. from a tunnel) replaces the local addresses
+    if let Some(public_host) = &cfg.public_host {
+        let prefix = match cfg.ws_protocol {
+            Some(WsProtocol::Wss) => "https",
+            Some(WsProtocol::Ws) => "http",
+            None => prefix,
+        };
+        tracing::debug!("binding to: {addr:?}");
+        tracing::info!("{SERVER}server reachable at:");
+        show_address(&mut cache, false, format!("{prefix}://{public_host}{base}"));
+        return;
+    }
+
     // prepare interface addresses
     let interfaces = local_ip_address::list_afinet_netifas()
         .map(|addr| {
@@ -260,14 +407,96 @@ fn show_address(cache: &mut HashSet<String>, local: bool, address: impl Into<Str
     }
 }
 
+/// Apply the connection settings to a server.
+fn configure_server<A>(
+    mut server: axum_server::Server<A>,
+    cfg: &RtcServe,
+) -> axum_server::Server<A> {
+    let builder = server.http_builder();
+    builder.http1().keep_alive(cfg.keepalive);
+    if let Some(timeout) = cfg.http_timeout {
+        builder
+            .http1()
+            .timer(TokioTimer::new())
+            .header_read_timeout(timeout);
+    }
+    server
+}
+
+/// Configure a server without TLS, which only serves HTTP/1 unless HTTP/2 (h2c) is enabled.
+///
+/// Without TLS, the server detects HTTP/2 connections by their preface instead of ALPN.
+fn configure_plaintext_server(
+    server: axum_server::Server,
+    cfg: &RtcServe,
+) -> axum_server::Server<PlaintextAcceptor> {
+    let mut server = configure_server(server, cfg);
+    if !cfg.http2 {
+        let builder = server.http_builder();
+        *builder = builder.clone().http1_only();
+    }
+    server.acceptor(PlaintextAcceptor { http2: cfg.http2 })
+}
+
+/// The acceptor of connections without TLS.
+///
+/// The connection builder doesn't apply `http1_only` to connections supporting upgrades (like
+/// the websocket of the auto-reload), so unless enabled, HTTP/2 connections are closed here.
+#[derive(Clone, Copy, Debug)]
+struct PlaintextAcceptor {
+    http2: bool,
+}
+
+impl<S: Send + 'static> axum_server::accept::Accept<tokio::net::TcpStream, S>
+    for PlaintextAcceptor
+{
+    type Stream = tokio::net::TcpStream;
+    type Service = S;
+    type Future = futures_util::future::BoxFuture<'static, std::io::Result<(Self::Stream, S)>>;
+
+    fn accept(&self, stream: Self::Stream, service: S) -> Self::Future {
+        let http2 = self.http2;
+        async move {
+            if !http2 && is_http2_connection(&stream).await? {
+                tracing::debug!("closing HTTP/2 connection without TLS, as it's not enabled");
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "HTTP/2 without TLS is disabled",
+                ));
+            }
+            Ok((stream, service))
+        }
+        .boxed()
+    }
+}
+
+/// Whether a connection starts with the preface of HTTP/2, without consuming it.
+async fn is_http2_connection(stream: &tokio::net::TcpStream) -> std::io::Result<bool> {
+    const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+    let mut buf = [0; PREFACE.len()];
+    // the preface is usually received at once, but might arrive in parts
+    for _ in 0..10 {
+        let len = stream.peek(&mut buf).await?;
+        if len == 0 || !PREFACE.starts_with(&buf[..len]) {
+            return Ok(false);
+        }
+        if len == PREFACE.len() {
+            return Ok(true);
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    Ok(false)
+}
+
 async fn run_server(
     addr: Vec<SocketAddr>,
-    tls: Option<TlsConfig>,
+    cfg: Arc<RtcServe>,
     router: Router,
     mut shutdown_rx: broadcast::Receiver<()>,
+    shutdown_handle: Handle,
 ) -> Result<()> {
     // Build a shutdown signal for the axum server.
-    let shutdown_handle = Handle::new();
 
     let shutdown = |handle: Handle| async move {
         // Any event on this channel, even a drop, should trigger shutdown.
@@ -283,7 +512,8 @@ async fn run_server(
     for addr in addr {
         let router = router.clone();
         let shutdown_handle = shutdown_handle.clone();
-        match &tls {
+        let cfg = cfg.clone();
+        match &cfg.tls {
             Some(tls) =>
             {
                 #[allow(unreachable_code)]
@@ -292,10 +522,17 @@ async fn run_server(
                     TlsConfig::Rustls { config } => {
                         tasks.push(
                             async move {
-                                axum_server::bind_rustls(addr, config)
-                                    .handle(shutdown_handle)
-                                    .serve(router.into_make_service())
-                                    .await
+                                let server =
+                                    configure_server(axum_server::bind_rustls(addr, config), &cfg)
+                                        .handle(shutdown_handle);
+                                if cfg.early_hints {
+                                    server
+                                        .map(EarlyHintsAcceptor::new)
+                                        .serve(router.into_make_service())
+                                        .await
+                                } else {
+                                    server.serve(router.into_make_service()).await
+                                }
                             }
                             .boxed(),
                         );
@@ -304,10 +541,17 @@ async fn run_server(
                     TlsConfig::Native { config } => {
                         tasks.push(
                             async move {
-                                axum_server::bind_openssl(addr, config)
-                                    .handle(shutdown_handle)
-                                    .serve(router.into_make_service())
-                                    .await
+                                let server =
+                                    configure_server(axum_server::bind_openssl(addr, config), &cfg)
+                                        .handle(shutdown_handle);
+                                if cfg.early_hints {
+                                    server
+                                        .map(EarlyHintsAcceptor::new)
+                                        .serve(router.into_make_service())
+                                        .await
+                                } else {
+                                    server.serve(router.into_make_service()).await
+                                }
                             }
                             .boxed(),
                         );
@@ -317,10 +561,16 @@ async fn run_server(
 
             None => tasks.push(
                 async move {
-                    axum_server::bind(addr)
-                        .handle(shutdown_handle)
-                        .serve(router.into_make_service())
-                        .await
+                    let server = configure_plaintext_server(axum_server::bind(addr), &cfg)
+                        .handle(shutdown_handle);
+                    if cfg.early_hints {
+                        server
+                            .map(EarlyHintsAcceptor::new)
+                            .serve(router.into_make_service())
+                            .await
+                    } else {
+                        server.serve(router.into_make_service()).await
+                    }
                 }
                 .boxed(),
             ),
@@ -343,6 +593,8 @@ pub struct State {
     pub ws_base: String,
     /// Additional headers to add to responses.
     pub headers: HashMap<String, String>,
+    /// Cached `Repr-Digest` header values, by request path and last modification.
+    pub digests: Mutex<HashMap<(String, HeaderValue), HeaderValue>>,
     /// Configuration
     pub cfg: Arc<RtcServe>,
 }
@@ -366,24 +618,90 @@ impl State {
             ws_state,
             ws_base,
             headers: cfg.headers.clone(),
+            digests: Default::default(),
             cfg,
         })
     }
 }
 
-/// Build the Trunk router, this includes that static file server, the WebSocket server,
-/// (for autoreload & HMR in the future), as well as any user-defined proxies.
-fn router(state: Arc<State>, cfg: Arc<RtcServe>) -> Result<Router> {
-    // Build static file server, middleware, error handler & WS route for reloads.
+/// What answers requests for paths without a file in the dist dir.
+enum DistFallback {
+    /// A `404 Not Found`.
+    None,
+    /// The `index.html`, for client side routes.
+    Index(PathBuf),
+    /// The `index.html`, only for paths without a file extension, which are client side routes.
+    Route(PathBuf),
+    /// The default backend, for requests of all methods.
+    Backend(Box<MethodRouter>),
+}
 
-    let mut serve_dir = if cfg.no_spa {
-        get_service(ServeDir::new(&state.dist_dir))
-    } else {
-        get_service(
-            ServeDir::new(&state.dist_dir)
-                .fallback(ServeFile::new(state.dist_dir.join(INDEX_HTML))),
-        )
-    };
+/// The service answering requests with the files of the dist dir, and the fallback for missing
+/// files.
+///
+/// Unless falling back to the default backend, only `GET` and `HEAD` requests are answered,
+/// including the SPA fallback. Other methods get a `405 Method Not Allowed`, so that e.g. a
+/// `POST` to a missing API route doesn't receive the HTML of the application.
+fn dist_service(dir: ServeDir, fallback: DistFallback) -> MethodRouter {
+    match fallback {
+        DistFallback::None => get_service(dir),
+        DistFallback::Index(index) => get_service(dir.fallback(ServeFile::new(index))),
+        DistFallback::Route(index) => get_service(dir.fallback(route_fallback(index))),
+        DistFallback::Backend(backend) => any_service(
+            dir.call_fallback_on_method_not_allowed(true)
+                .fallback(backend.layer(axum::middleware::map_response(mark_backend_response))),
+        ),
+    }
+}
+
+/// Marks a response of the default backend, which the middlewares for the files of the dist dir
+/// must pass on unchanged.
+#[derive(Clone, Copy, Debug)]
+struct BackendResponse;
+
+async fn mark_backend_response(mut response: Response) -> Response {
+    response.extensions_mut().insert(BackendResponse);
+    response
+}
+
+/// The SPA fallback for paths without a file extension. Missing files, like `/data.json`, get a
+/// `404 Not Found` instead of the `index.html`.
+fn route_fallback(index: PathBuf) -> MethodRouter {
+    get_service(ServeFile::new(index)).layer(axum::middleware::from_fn(route_only_middleware))
+}
+
+async fn route_only_middleware(request: extract::Request, next: Next) -> Response {
+    match has_file_extension(request.uri().path()) {
+        true => StatusCode::NOT_FOUND.into_response(),
+        false => next.run(request).await,
+    }
+}
+
+/// Whether the last segment of a path has a file extension.
+fn has_file_extension(path: &str) -> bool {
+    path.rsplit('/')
+        .next()
+        .is_some_and(|name| Path::new(name).extension().is_some())
+}
+
+/// Build the service serving the files of the dist dir, with a fallback for missing files.
+fn static_files(
+    state: &Arc<State>,
+    cfg: &RtcServe,
+    fallback: DistFallback,
+) -> Result<MethodRouter> {
+    let mut dir = ServeDir::new(&state.dist_dir);
+    for format in &cfg.compression {
+        dir = match format {
+            CompressionFormat::Brotli => dir.precompressed_br(),
+            CompressionFormat::Gzip => dir.precompressed_gzip(),
+        };
+    }
+    let precompressed = !cfg.compression.is_empty();
+    let mut serve_dir = dist_service(dir, fallback);
+    if precompressed {
+        serve_dir = serve_dir.layer(axum::middleware::from_fn(uncompressed_html_middleware));
+    }
     for (key, value) in &state.headers {
         let name = HeaderName::from_bytes(key.as_bytes())
             .with_context(|| format!("invalid header {:?}", key))?;
@@ -393,6 +711,51 @@ fn router(state: Arc<State>, cfg: Arc<RtcServe>) -> Result<Router> {
         serve_dir = serve_dir.layer(SetResponseHeaderLayer::overriding(name, value))
     }
 
+    // the method router of the dist service already rejects the methods it doesn't answer
+    Ok(serve_dir
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            symlink_guard_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            html_address_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            digest_header_middleware,
+        )))
+}
+
+/// Don't serve precompressed variants of HTML documents, like an `index.html.gz` of a previous
+/// build, as the HTML gets rewritten for each request.
+async fn uncompressed_html_middleware(mut request: extract::Request, next: Next) -> Response {
+    let path = request.uri().path();
+    if path.ends_with('/') || path.ends_with(".html") {
+        request.headers_mut().remove(ACCEPT_ENCODING);
+    }
+    next.run(request).await
+}
+
+/// Build the Trunk router, this includes that static file server, the WebSocket server,
+/// (for autoreload & HMR in the future), as well as any user-defined proxies.
+fn router(state: Arc<State>, cfg: Arc<RtcServe>) -> Result<Router> {
+    // Build static file server, middleware, error handler & WS route for reloads.
+
+    let fallback = match &cfg.default_backend {
+        Some(backend) => DistFallback::Backend(Box::new(proxy::default_backend(
+            cfg.tls.is_some(),
+            backend,
+            cfg.proxy_insecure_all,
+        )?)),
+        None if cfg.no_spa => DistFallback::None,
+        None if cfg.spa_fallback_extensions => DistFallback::Index(state.dist_dir.join(INDEX_HTML)),
+        None => DistFallback::Route(state.dist_dir.join(INDEX_HTML)),
+    };
+    let serve_dir = static_files(&state, &cfg, fallback)?;
+    // well-known files are looked up by other parties, which must not get the SPA fallback
+    let well_known = static_files(&state, &cfg, DistFallback::None)?;
+
     let mut router = Router::new()
         .route(
             // we always serve the ws under the serve-base, ws-base is only to override the lookup
@@ -403,21 +766,26 @@ fn router(state: Arc<State>, cfg: Arc<RtcServe>) -> Result<Router> {
                 },
             ),
         )
-        .fallback_service(
-            get_service(serve_dir)
-                .handle_error(|error| async move {
-                    tracing::error!(?error, "failed serving static file");
-                    StatusCode::INTERNAL_SERVER_ERROR
-                })
-                .layer(axum::middleware::from_fn_with_state(
-                    state.clone(),
-                    html_address_middleware,
-                )),
-        )
+        .route_service("/.well-known/{*path}", well_known)
+        .fallback_service(serve_dir)
         .layer(TraceLayer::new_for_http());
 
     if state.serve_base != "/" {
-        router = Router::new().nest(&state.serve_base, router);
+        // serve everything below the base with a trailing slash, and redirect the base without
+        // one, so that relative URLs resolve against the base
+        let base = format!("{}/", state.serve_base.trim_end_matches('/'));
+        let redirect = base.clone();
+        router = Router::new()
+            .route(
+                base.trim_end_matches('/'),
+                get(|uri: Uri| async move {
+                    match uri.query() {
+                        Some(query) => Redirect::permanent(&format!("{redirect}?{query}")),
+                        None => Redirect::permanent(&redirect),
+                    }
+                }),
+            )
+            .nest(&base, router);
     }
 
     let router = router.with_state(state.clone());
@@ -428,11 +796,30 @@ fn router(state: Arc<State>, cfg: Arc<RtcServe>) -> Result<Router> {
         state.serve_base.as_str()
     );
 
+    if let Some(interval) = cfg.ws_echo {
+        tracing::warn!(
+            "{}auto-reload websocket in echo mode, sending a reload every {}",
+            SERVER,
+            humantime::format_duration(interval)
+        );
+    }
+
     let mut builder = ProxyBuilder::new(cfg.tls.is_some(), router);
 
     // Build proxies
 
+    if cfg.proxy_insecure_all && cfg.proxies.iter().any(|proxy| proxy.enabled) {
+        tracing::warn!(
+            "Accepting invalid TLS certificates for all proxies (--proxy-insecure-all). This can open you up to MITM attacks."
+        );
+    }
+
     for proxy in &cfg.proxies {
+        if !proxy.enabled {
+            tracing::debug!(backend = %*proxy.backend, "skipping disabled proxy");
+            continue;
+        }
+
         let mut request_headers = HeaderMap::new();
         for (key, value) in &proxy.request_headers {
             let name = HeaderName::from_bytes(key.as_bytes())
@@ -448,15 +835,40 @@ fn router(state: Arc<State>, cfg: Arc<RtcServe>) -> Result<Router> {
             &proxy.backend,
             &request_headers,
             proxy.rewrite.clone(),
+            ResponseRewriter::new(
+                proxy.response_rewrite.clone(),
+                proxy.response_rewrite_types.clone(),
+            ),
             ProxyClientOptions {
-                insecure: proxy.insecure,
+                insecure: proxy.insecure || cfg.proxy_insecure_all,
                 no_system_proxy: proxy.no_system_proxy,
                 redirect: !proxy.no_redirect,
             },
         )?;
     }
 
-    Ok(builder.build())
+    let mut router = builder.build();
+    if cfg.cross_origin_isolation {
+        router = cross_origin_isolation(router);
+    }
+
+    Ok(router)
+}
+
+/// Add the headers required for cross-origin isolation to all responses of the router.
+///
+/// This covers static files, the fallback and proxies. Headers which are already set, like ones of
+/// `serve.headers` or of a backend, are kept.
+fn cross_origin_isolation(router: Router) -> Router {
+    router
+        .layer(SetResponseHeaderLayer::if_not_present(
+            HeaderName::from_static("cross-origin-opener-policy"),
+            HeaderValue::from_static("same-origin"),
+        ))
+        .layer(SetResponseHeaderLayer::if_not_present(
+            HeaderName::from_static("cross-origin-embedder-policy"),
+            HeaderValue::from_static("require-corp"),
+        ))
 }
 
 async fn html_address_middleware(
@@ -466,10 +878,23 @@ async fn html_address_middleware(
 ) -> Response {
     let host = request.headers().get(HOST).cloned();
 
+    // only present if enabled, and informational responses are only supported by HTTP/1.1 clients
+    if let Some(early_hints) = request
+        .extensions()
+        .get::<EarlyHints>()
+        .filter(|_| request.version() == Version::HTTP_11 && is_navigation(&request))
+    {
+        send_early_hints(&state, early_hints).await;
+    }
+
     let response = next.run(request).await;
 
-    // if it's not a success, we don't modify it
-    if !response.status().is_success() {
+    // if it's not a success, a stream which must not be buffered, or a response of the default
+    // backend, we don't modify it
+    if !response.status().is_success()
+        || is_event_stream(response.headers())
+        || response.extensions().get::<BackendResponse>().is_some()
+    {
         return response;
     }
 
@@ -507,16 +932,27 @@ async fn html_address_middleware(
                     tracing::debug!("Replacing variable");
 
                     // turn into a string literal, or replace with "current host" on the client side
-                    let host = host
-                        .and_then(|uri| uri.to_str().map(|s| format!("'{}'", s)).ok())
-                        .unwrap_or_else(|| "window.location.host".into());
+                    let host = match &state.cfg.public_host {
+                        Some(public_host) => format!("'{public_host}'"),
+                        None => host
+                            .and_then(|uri| uri.to_str().map(|s| format!("'{}'", s)).ok())
+                            .unwrap_or_else(|| "window.location.host".into()),
+                    };
 
                     let mut data_str = data_str
                         // minification will turn quotes into backticks, so we have to replace both
                         .replace("'{{__TRUNK_ADDRESS__}}'", &host)
                         .replace("`{{__TRUNK_ADDRESS__}}`", &host)
                         // here we only replace the string value
-                        .replace("{{__TRUNK_WS_BASE__}}", &state.ws_base);
+                        .replace("{{__TRUNK_WS_BASE__}}", &state.ws_base)
+                        .replace(
+                            "{{__TRUNK_WS_RECONNECT_DELAY__}}",
+                            &state.cfg.ws_reconnect_delay.as_millis().to_string(),
+                        )
+                        .replace(
+                            "{{__TRUNK_WS_RECONNECT_MAX_DELAY__}}",
+                            &state.cfg.ws_reconnect_max_delay.as_millis().to_string(),
+                        );
 
                     let mut csp = None;
 
@@ -537,6 +973,19 @@ async fn html_address_middleware(
                         None => {}
                     };
 
+                    if state.cfg.preload_location != PreloadLocation::Head {
+                        let remove = state.cfg.preload_location == PreloadLocation::Header;
+                        match preload_link_headers(&data_str, remove) {
+                            Ok((html, links)) => {
+                                data_str = html;
+                                for link in links {
+                                    parts.headers.append(LINK, link);
+                                }
+                            }
+                            Err(err) => tracing::error!("failed to create preload headers: {err}"),
+                        }
+                    }
+
                     let bytes_vec = data_str.as_bytes().to_vec();
                     parts.headers.insert(CONTENT_LENGTH, bytes_vec.len().into());
                     bytes = Bytes::from(bytes_vec);
@@ -551,6 +1000,219 @@ async fn html_address_middleware(
     }
 }
 
+/// Whether a request is the navigation of a browser, which accepts an HTML document.
+fn is_navigation(request: &extract::Request) -> bool {
+    request.method() == Method::GET
+        && request
+            .headers()
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.contains("text/html"))
+}
+
+/// Send the preload links of the index.html as early hints, before the request is handled.
+async fn send_early_hints(state: &State, early_hints: &EarlyHints) {
+    let links = match tokio::fs::read_to_string(state.dist_dir.join(INDEX_HTML)).await {
+        Ok(html) => match preload_link_headers(&html, false) {
+            Ok((_, links)) => links,
+            Err(err) => {
+                tracing::error!("failed to create early hints: {err}");
+                return;
+            }
+        },
+        // there's no index.html while the first build is running
+        Err(err) => {
+            tracing::debug!("not sending early hints, unable to read the index.html: {err}");
+            return;
+        }
+    };
+
+    if !links.is_empty() {
+        if let Err(err) = early_hints.send(&links).await {
+            tracing::debug!("failed to send early hints: {err}");
+        }
+    }
+}
+
+/// Collect the preload links of the document's head as `Link` header values.
+///
+/// If `remove` is set, the links are removed from the returned document.
+fn preload_link_headers(html: &str, remove: bool) -> Result<(String, Vec<HeaderValue>)> {
+    let mut links = vec![];
+    let html = lol_html::rewrite_str(
+        html,
+        lol_html::RewriteStrSettings {
+            element_content_handlers: vec![lol_html::element!(
+                r#"head link[rel="preload"], head link[rel="modulepreload"]"#,
+                |el| {
+                    // attribute values are returned as found in the document
+                    let attr = |name| {
+                        el.get_attribute(name)
+                            .map(|value| htmlescape::decode_html(&value).unwrap_or(value))
+                    };
+                    let Some(href) = attr("href") else {
+                        return Ok(());
+                    };
+                    let mut link = format!("<{href}>");
+                    for (name, quote) in [
+                        ("rel", false),
+                        ("as", false),
+                        ("type", true),
+                        ("crossorigin", false),
+                        ("integrity", true),
+                    ] {
+                        match attr(name) {
+                            Some(value) if quote => {
+                                link.push_str(&format!(r#"; {name}="{value}""#))
+                            }
+                            Some(value) if !value.is_empty() => {
+                                link.push_str(&format!("; {name}={value}"))
+                            }
+                            Some(_) => link.push_str(&format!("; {name}")),
+                            None => {}
+                        }
+                    }
+                    links.push(HeaderValue::try_from(link)?);
+                    if remove {
+                        el.remove();
+                    }
+                    Ok(())
+                }
+            )],
+            ..lol_html::RewriteStrSettings::default()
+        },
+    )?;
+
+    Ok((html, links))
+}
+
+/// Reject requests for files which resolve (through symlinks) to a location outside the dist dir,
+/// unless following symlinks is enabled.
+async fn symlink_guard_middleware(
+    extract::State(state): extract::State<Arc<State>>,
+    request: extract::Request,
+    next: Next,
+) -> Response {
+    if state.cfg.follow_symlinks {
+        return next.run(request).await;
+    }
+
+    let path = percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    let mut candidate = state.dist_dir.clone();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            // let the static file service reject this
+            ".." => return next.run(request).await,
+            segment => candidate.push(segment),
+        }
+    }
+
+    // the dist dir itself is canonical, the requested file might not exist
+    if let Ok(resolved) = tokio::fs::canonicalize(&candidate).await {
+        let resolved = match resolved.is_dir() {
+            true => tokio::fs::canonicalize(resolved.join(INDEX_HTML))
+                .await
+                .unwrap_or(resolved),
+            false => resolved,
+        };
+        if !resolved.starts_with(&state.dist_dir) {
+            tracing::debug!(
+                "refusing to serve {} outside of the dist dir, consider enabling `follow_symlinks`",
+                resolved.display()
+            );
+            return StatusCode::NOT_FOUND.into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Add a `Repr-Digest` header to successful responses of static content.
+async fn digest_header_middleware(
+    extract::State(state): extract::State<Arc<State>>,
+    request: extract::Request,
+    next: Next,
+) -> Response {
+    if !state.cfg.digest_header || request.method() != Method::GET {
+        return next.run(request).await;
+    }
+
+    let path = request.uri().path().to_string();
+    let mut response = next.run(request).await;
+
+    // partial or other responses don't carry the full representation, and responses of the
+    // default backend aren't static content
+    if response.status() != StatusCode::OK
+        || is_event_stream(response.headers())
+        || response.extensions().get::<BackendResponse>().is_some()
+    {
+        return response;
+    }
+
+    // HTML content is rewritten for each request (e.g. the nonce), so it must not be cached
+    let is_html = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .map(|t| t == "text/html")
+        .unwrap_or_default();
+    // precompressed variants are different representations of the same path
+    let path = match response.headers().get(CONTENT_ENCODING) {
+        Some(encoding) => format!("{path}:{}", encoding.to_str().unwrap_or_default()),
+        None => path,
+    };
+    let key = response
+        .headers()
+        .get(LAST_MODIFIED)
+        .filter(|_| !is_html)
+        .map(|modified| (path, modified.clone()));
+
+    if let Some(key) = &key {
+        let cached = state
+            .digests
+            .lock()
+            .ok()
+            .and_then(|digests| digests.get(key).cloned());
+        if let Some(value) = cached {
+            response.headers_mut().insert(REPR_DIGEST, value);
+            return response;
+        }
+    }
+
+    // large files are sent without a digest, instead of buffering them
+    let size = response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok()?.parse::<usize>().ok());
+    if size.is_some_and(|size| size > DIGEST_MAX_SIZE) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+
+    match axum::body::to_bytes(body, DIGEST_MAX_SIZE).await {
+        Err(err) => {
+            tracing::error!("Unable to create digest: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Ok(bytes) => {
+            let value = OutputDigest::generate_from(IntegrityType::Sha256, &bytes)
+                .to_digest_header_value()
+                .and_then(|value| HeaderValue::try_from(value).ok());
+
+            if let Some(value) = value {
+                if let (Some(key), Ok(mut digests)) = (key, state.digests.lock()) {
+                    digests.insert(key, value.clone());
+                }
+                parts.headers.insert(REPR_DIGEST, value);
+            }
+
+            (parts, bytes).into_response()
+        }
+    }
+}
+
 /// A result type used to work seamlessly with axum.
 pub(crate) type ServerResult<T> = std::result::Result<T, ServerError>;
 
@@ -571,3 +1233,732 @@ impl IntoResponse for ServerError {
         res
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Serve the router of a configuration, returning its address and the server task.
+    async fn serve_router(cfg: RtcServe) -> Result<(SocketAddr, JoinHandle<std::io::Result<()>>)> {
+        let cfg = Arc::new(cfg);
+        let (ws_state_tx, ws_state) = watch::channel(ws::State::Ok);
+        let state = Arc::new(State::new(
+            cfg.watch.build.final_dist.clone(),
+            cfg.serve_base()?.to_string(),
+            cfg.clone(),
+            ws_state,
+        )?);
+        let router = router(state, cfg)?;
+
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let addr = listener.local_addr()?;
+        Ok((
+            addr,
+            tokio::spawn(async move {
+                // websockets are closed once the state isn't watched anymore
+                let _ws_state = ws_state_tx;
+                axum::serve(listener, router).await
+            }),
+        ))
+    }
+
+    #[tokio::test]
+    async fn on_ready_exit_code_is_propagated() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let cfg = RtcServe::new_test(tmpdir.path()).await?;
+
+        run_on_ready(&cfg, "exit 0", "http://127.0.0.1:8080/").await?;
+
+        let err = run_on_ready(&cfg, "exit 3", "http://127.0.0.1:8080/")
+            .await
+            .expect_err("a failing command must fail");
+        assert_eq!(
+            err.downcast_ref::<OnReadyFailed>().map(|err| err.code),
+            Some(3)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn precompressed_html_is_not_served() -> Result<()> {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcServe::new_test(tmpdir.path()).await?;
+        Arc::make_mut(&mut Arc::make_mut(&mut cfg.watch).build).compression =
+            vec![CompressionFormat::Gzip];
+
+        let dist = cfg.watch.build.final_dist.clone();
+        let html = "<html><script>connect('{{__TRUNK_ADDRESS__}}', '{{__TRUNK_WS_BASE__}}');</script></html>";
+        tokio::fs::write(dist.join(INDEX_HTML), html).await?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(html.as_bytes())?;
+        tokio::fs::write(dist.join("index.html.gz"), encoder.finish()?).await?;
+        tokio::fs::write(dist.join("app.js"), "console.log(1);").await?;
+        tokio::fs::write(dist.join("app.js.gz"), b"gzip").await?;
+
+        let (addr, server) = serve_router(cfg).await?;
+        let client = reqwest::Client::builder().no_proxy().build()?;
+        let get = |path: &str| {
+            client
+                .get(format!("http://{addr}{path}"))
+                .header(ACCEPT_ENCODING, "gzip")
+                .send()
+        };
+
+        for path in ["/", "/index.html"] {
+            let response = get(path).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers().get(CONTENT_ENCODING), None);
+            assert_eq!(
+                response.text().await?,
+                format!("<html><script>connect('{addr}', '/');</script></html>")
+            );
+        }
+        // other files are still served precompressed
+        let response = get("/app.js").await?;
+        assert_eq!(
+            response.headers().get(CONTENT_ENCODING),
+            Some(&HeaderValue::from_static("gzip"))
+        );
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn precompressed_variants_are_negotiated() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcServe::new_test(tmpdir.path()).await?;
+        Arc::make_mut(&mut Arc::make_mut(&mut cfg.watch).build).compression =
+            vec![CompressionFormat::Brotli, CompressionFormat::Gzip];
+
+        let dist = cfg.watch.build.final_dist.clone();
+        tokio::fs::write(dist.join("app.js"), "console.log(1);").await?;
+        tokio::fs::write(dist.join("app.js.br"), b"brotli").await?;
+        tokio::fs::write(dist.join("app.js.gz"), b"gzip").await?;
+
+        let (addr, server) = serve_router(cfg).await?;
+        let client = reqwest::Client::builder().no_proxy().build()?;
+        for (accept_encoding, encoding, body) in [
+            ("br", Some("br"), "brotli"),
+            ("gzip", Some("gzip"), "gzip"),
+            ("br;q=0.5, gzip", Some("gzip"), "gzip"),
+            ("identity", None, "console.log(1);"),
+        ] {
+            let response = client
+                .get(format!("http://{addr}/app.js"))
+                .header(ACCEPT_ENCODING, accept_encoding)
+                .send()
+                .await?;
+            assert_eq!(
+                response
+                    .headers()
+                    .get(CONTENT_ENCODING)
+                    .map(|value| value.to_str())
+                    .transpose()?,
+                encoding,
+                "{accept_encoding}"
+            );
+            assert_eq!(response.text().await?, body, "{accept_encoding}");
+        }
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ws_reconnect_delays_are_injected() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcServe::new_test(tmpdir.path()).await?;
+        cfg.ws_reconnect_delay = Duration::from_millis(500);
+        cfg.ws_reconnect_max_delay = Duration::from_secs(60);
+        let html = "<html><script>connect('{{__TRUNK_WS_RECONNECT_DELAY__}}', '{{__TRUNK_WS_RECONNECT_MAX_DELAY__}}');</script></html>";
+        tokio::fs::write(cfg.watch.build.final_dist.join(INDEX_HTML), html).await?;
+
+        let (addr, server) = serve_router(cfg).await?;
+        let client = reqwest::Client::builder().no_proxy().build()?;
+        let response = client.get(format!("http://{addr}/")).send().await?;
+        assert_eq!(
+            response.text().await?,
+            "<html><script>connect('500', '60000');</script></html>"
+        );
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn large_ws_payloads_are_sent_compressed() -> Result<()> {
+        use flate2::read::GzDecoder;
+        use futures_util::{SinkExt, StreamExt};
+        use std::io::Read;
+        use tokio_tungstenite::tungstenite::Message;
+
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcServe::new_test(tmpdir.path()).await?;
+        // echo mode sends payloads of the browser back, without synthetic reloads in time
+        cfg.ws_echo = Some(Duration::from_secs(3600));
+
+        let (addr, server) = serve_router(cfg).await?;
+        let (mut socket, _) =
+            tokio_tungstenite::connect_async(format!("ws://{addr}/.well-known/trunk/ws")).await?;
+
+        let content = "body { color: red; }\n".repeat(1024);
+        for (payload, encoding) in [("hello", 0), (content.as_str(), 1)] {
+            socket
+                .send(Message::binary(payload.as_bytes().to_vec()))
+                .await?;
+            let data = match socket.next().await.context("websocket closed")?? {
+                Message::Binary(data) => data,
+                other => anyhow::bail!("unexpected message: {other:?}"),
+            };
+            assert_eq!(data[0], encoding, "encoding of {} bytes", payload.len());
+
+            let received = match encoding {
+                0 => data[1..].to_vec(),
+                _ => {
+                    assert!(data.len() < payload.len(), "{} bytes", data.len());
+                    let mut received = vec![];
+                    GzDecoder::new(&data[1..]).read_to_end(&mut received)?;
+                    received
+                }
+            };
+            assert_eq!(received, payload.as_bytes());
+        }
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn early_hints_precede_the_index_html() -> Result<()> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcServe::new_test(tmpdir.path()).await?;
+        cfg.early_hints = true;
+        let html = r#"<html><head><link rel="preload" href="/app_bg.wasm" as="fetch"></head><body></body></html>"#;
+        tokio::fs::write(cfg.watch.build.final_dist.join(INDEX_HTML), html).await?;
+
+        let cfg = Arc::new(cfg);
+        let (_, ws_state) = watch::channel(ws::State::Ok);
+        let state = Arc::new(State::new(
+            cfg.watch.build.final_dist.clone(),
+            cfg.serve_base()?.to_string(),
+            cfg.clone(),
+            ws_state,
+        )?);
+        let router = router(state, cfg.clone())?;
+        let handle = Handle::new();
+        let server = tokio::spawn(
+            configure_plaintext_server(axum_server::bind((Ipv4Addr::LOCALHOST, 0).into()), &cfg)
+                .map(EarlyHintsAcceptor::new)
+                .handle(handle.clone())
+                .serve(router.into_make_service()),
+        );
+        let addr = handle.listening().await.context("server didn't start")?;
+
+        let request = |accept: &'static str| async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await?;
+            stream
+                .write_all(
+                    format!("GET / HTTP/1.1\r\nhost: {addr}\r\naccept: {accept}\r\nconnection: close\r\n\r\n")
+                        .as_bytes(),
+                )
+                .await?;
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await?;
+            anyhow::Ok(response)
+        };
+
+        let response = request("text/html,*/*;q=0.8").await?;
+        assert!(
+            response.starts_with(
+                "HTTP/1.1 103 Early Hints\r\nlink: </app_bg.wasm>; rel=preload; as=fetch\r\n\r\nHTTP/1.1 200 OK\r\n"
+            ),
+            "{response}"
+        );
+
+        // only navigations get early hints
+        let response = request("*/*").await?;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn spa_fallback_only_for_get() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dist = tmpdir.path();
+        tokio::fs::write(dist.join(INDEX_HTML), "<html></html>").await?;
+        tokio::fs::write(dist.join("app.css"), "body {}").await?;
+
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let addr = listener.local_addr()?;
+        let router = Router::new()
+            .nest_service(
+                "/no-spa",
+                dist_service(ServeDir::new(dist), DistFallback::None),
+            )
+            .fallback_service(dist_service(
+                ServeDir::new(dist),
+                DistFallback::Index(dist.join(INDEX_HTML)),
+            ));
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::builder().no_proxy().build()?;
+        let status = |method: Method, path: &str| {
+            let request = client.request(method, format!("http://{addr}{path}"));
+            async move { anyhow::Ok(request.send().await?.status()) }
+        };
+
+        assert_eq!(status(Method::GET, "/some/route").await?, StatusCode::OK);
+        assert_eq!(status(Method::HEAD, "/some/route").await?, StatusCode::OK);
+        assert_eq!(status(Method::GET, "/app.css").await?, StatusCode::OK);
+        for method in [Method::POST, Method::PUT, Method::DELETE, Method::PATCH] {
+            assert_eq!(
+                status(method.clone(), "/some/route").await?,
+                StatusCode::METHOD_NOT_ALLOWED
+            );
+            assert_eq!(
+                status(method, "/app.css").await?,
+                StatusCode::METHOD_NOT_ALLOWED
+            );
+        }
+        assert_eq!(
+            status(Method::GET, "/no-spa/some/route").await?,
+            StatusCode::NOT_FOUND
+        );
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn spa_fallback_skips_file_extensions() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dist = tmpdir.path();
+        tokio::fs::write(dist.join(INDEX_HTML), "<html></html>").await?;
+        tokio::fs::write(dist.join("app.css"), "body {}").await?;
+
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let addr = listener.local_addr()?;
+        let router = Router::new()
+            .nest_service(
+                "/all",
+                dist_service(
+                    ServeDir::new(dist),
+                    DistFallback::Index(dist.join(INDEX_HTML)),
+                ),
+            )
+            .fallback_service(dist_service(
+                ServeDir::new(dist),
+                DistFallback::Route(dist.join(INDEX_HTML)),
+            ));
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::builder().no_proxy().build()?;
+        let status = |path: &str| {
+            let request = client.get(format!("http://{addr}{path}"));
+            async move { anyhow::Ok(request.send().await?.status()) }
+        };
+
+        assert_eq!(status("/dashboard").await?, StatusCode::OK);
+        assert_eq!(status("/users/.profile").await?, StatusCode::OK);
+        assert_eq!(status("/app.css").await?, StatusCode::OK);
+        assert_eq!(status("/missing.json").await?, StatusCode::NOT_FOUND);
+        assert_eq!(status("/data/missing.json").await?, StatusCode::NOT_FOUND);
+        assert_eq!(status("/all/missing.json").await?, StatusCode::OK);
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn default_backend_fallback() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dist = tmpdir.path();
+        tokio::fs::write(dist.join("app.css"), "body {}").await?;
+
+        // a backend answering with the method and path of the request
+        let backend = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let backend_addr = backend.local_addr()?;
+        let backend = tokio::spawn(async move {
+            let router = Router::new()
+                .route("/redirect", get(|| async { Redirect::temporary("/login") }))
+                .fallback(|method: Method, uri: Uri| async move { format!("{method} {uri}") });
+            axum::serve(backend, router).await
+        });
+
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let addr = listener.local_addr()?;
+        let fallback =
+            proxy::default_backend(false, &format!("http://{backend_addr}/").parse()?, false)?;
+        let router = Router::new().fallback_service(dist_service(
+            ServeDir::new(dist),
+            DistFallback::Backend(Box::new(fallback)),
+        ));
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let response = |method: Method, path: &str| {
+            let request = client.request(method, format!("http://{addr}{path}"));
+            async move {
+                let response = request.send().await?;
+                anyhow::Ok((response.status(), response.text().await?))
+            }
+        };
+
+        assert_eq!(
+            response(Method::GET, "/app.css").await?,
+            (StatusCode::OK, "body {}".into())
+        );
+        assert_eq!(
+            response(Method::GET, "/some/route?page=2").await?,
+            (StatusCode::OK, "GET /some/route?page=2".into())
+        );
+        assert_eq!(
+            response(Method::POST, "/api/items").await?,
+            (StatusCode::OK, "POST /api/items".into())
+        );
+        // redirects are passed on to the client
+        assert_eq!(
+            response(Method::GET, "/redirect").await?.0,
+            StatusCode::TEMPORARY_REDIRECT
+        );
+
+        server.abort();
+        backend.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn default_backend_through_router() -> Result<()> {
+        // a backend answering with HTML containing the placeholders
+        let backend = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let backend_addr = backend.local_addr()?;
+        let backend = tokio::spawn(async move {
+            let router = Router::new().fallback(|method: Method, uri: Uri| async move {
+                (
+                    [(CONTENT_TYPE, "text/html")],
+                    format!("{method} {uri} {{{{__TRUNK_WS_BASE__}}}}"),
+                )
+            });
+            axum::serve(backend, router).await
+        });
+
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcServe::new_test(tmpdir.path()).await?;
+        cfg.default_backend = Some(Uri::try_from(format!("http://{backend_addr}/"))?.into());
+        cfg.digest_header = true;
+        tokio::fs::write(cfg.watch.build.final_dist.join("app.css"), "body {}").await?;
+
+        let (addr, server) = serve_router(cfg).await?;
+        let client = reqwest::Client::builder().no_proxy().build()?;
+        let response = |method: Method, path: &str| {
+            let request = client.request(method, format!("http://{addr}{path}"));
+            async move {
+                let response = request.send().await?;
+                let digest = response.headers().contains_key(REPR_DIGEST);
+                anyhow::Ok((response.status(), digest, response.text().await?))
+            }
+        };
+
+        for method in [Method::POST, Method::PUT, Method::DELETE] {
+            assert_eq!(
+                response(method.clone(), "/api/items").await?,
+                (
+                    StatusCode::OK,
+                    false,
+                    format!("{method} /api/items {{{{__TRUNK_WS_BASE__}}}}")
+                )
+            );
+        }
+        // responses of the backend are passed on unchanged, without a digest
+        assert_eq!(
+            response(Method::GET, "/page").await?,
+            (
+                StatusCode::OK,
+                false,
+                "GET /page {{__TRUNK_WS_BASE__}}".into()
+            )
+        );
+        assert_eq!(
+            response(Method::GET, "/app.css").await?,
+            (StatusCode::OK, true, "body {}".into())
+        );
+
+        server.abort();
+        backend.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn serve_base_redirects_to_trailing_slash() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcServe::new_test(tmpdir.path()).await?;
+        cfg.serve_base = Some("/app".into());
+        tokio::fs::write(cfg.watch.build.final_dist.join("app.css"), "body {}").await?;
+
+        let (addr, server) = serve_router(cfg).await?;
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        let get = |path: &str| client.get(format!("http://{addr}{path}")).send();
+
+        for (path, location) in [("/app", "/app/"), ("/app?page=1", "/app/?page=1")] {
+            let response = get(path).await?;
+            assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT, "{path}");
+            assert_eq!(
+                response.headers().get(http::header::LOCATION),
+                Some(&HeaderValue::from_static(location)),
+                "{path}"
+            );
+        }
+        let response = get("/app/app.css").await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text().await?, "body {}");
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn digest_of_broken_body_fails() -> Result<()> {
+        use tower::ServiceExt;
+
+        let tmpdir = tempfile::tempdir()?;
+        let mut cfg = RtcServe::new_test(tmpdir.path()).await?;
+        cfg.digest_header = true;
+        let cfg = Arc::new(cfg);
+        let (_, ws_state) = watch::channel(ws::State::Ok);
+        let state = Arc::new(State::new(
+            cfg.watch.build.final_dist.clone(),
+            cfg.serve_base()?.to_string(),
+            cfg.clone(),
+            ws_state,
+        )?);
+
+        let router = Router::new()
+            .route(
+                "/app.css",
+                get(|| async {
+                    let chunks = [
+                        Ok(Bytes::from_static(b"body {")),
+                        Err(std::io::Error::other("broken")),
+                    ];
+                    (
+                        [(CONTENT_LENGTH, "16")],
+                        Body::from_stream(futures_util::stream::iter(chunks)),
+                    )
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                state,
+                digest_header_middleware,
+            ));
+
+        let response = router
+            .oneshot(http::Request::get("/app.css").body(Body::empty())?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!response.headers().contains_key(REPR_DIGEST));
+        assert_ne!(
+            response.headers().get(CONTENT_LENGTH),
+            Some(&HeaderValue::from_static("16"))
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cross_origin_isolation_headers() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dist = tmpdir.path();
+        tokio::fs::write(dist.join(INDEX_HTML), "<html></html>").await?;
+        tokio::fs::write(dist.join("app.css"), "body {}").await?;
+
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let addr = listener.local_addr()?;
+        let router = Router::new()
+            .route(
+                "/api",
+                get(|| async { ([("cross-origin-embedder-policy", "credentialless")], "api") }),
+            )
+            .fallback_service(dist_service(
+                ServeDir::new(dist),
+                DistFallback::Index(dist.join(INDEX_HTML)),
+            ));
+        let router = cross_origin_isolation(router);
+        let server = tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = reqwest::Client::builder().no_proxy().build()?;
+        let headers = |method: Method, path: &str| {
+            let request = client.request(method, format!("http://{addr}{path}"));
+            async move {
+                let response = request.send().await?;
+                let header = |name: &str| {
+                    response
+                        .headers()
+                        .get(name)
+                        .and_then(|value| value.to_str().ok())
+                        .map(str::to_string)
+                };
+                anyhow::Ok((
+                    header("cross-origin-opener-policy"),
+                    header("cross-origin-embedder-policy"),
+                ))
+            }
+        };
+        let isolated = (Some("same-origin".into()), Some("require-corp".into()));
+
+        assert_eq!(headers(Method::GET, "/app.css").await?, isolated);
+        // the SPA fallback, and its rejection of other methods
+        assert_eq!(headers(Method::GET, "/some/route").await?, isolated);
+        assert_eq!(headers(Method::POST, "/some/route").await?, isolated);
+        // headers set by a route are kept
+        assert_eq!(
+            headers(Method::GET, "/api").await?,
+            (Some("same-origin".into()), Some("credentialless".into()))
+        );
+
+        server.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn root_file_content_types() -> Result<()> {
+        use tower::ServiceExt;
+
+        let tmpdir = tempfile::tempdir()?;
+        let dist = tmpdir.path();
+        for (file, content_type) in [
+            ("robots.txt", "text/plain"),
+            ("sitemap.xml", "text/xml"),
+            ("favicon.ico", "image/x-icon"),
+        ] {
+            tokio::fs::write(dist.join(file), b"").await?;
+            let request = extract::Request::builder()
+                .uri(format!("/{file}"))
+                .body(Body::empty())?;
+            let response = ServeDir::new(dist).oneshot(request).await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get(CONTENT_TYPE),
+                Some(&HeaderValue::from_static(content_type)),
+                "content type of {file}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn wasm_content_type() -> Result<()> {
+        use tower::ServiceExt;
+
+        let tmpdir = tempfile::tempdir()?;
+        let dist = tmpdir.path();
+        tokio::fs::write(dist.join("app_bg.wasm"), b"\0asm").await?;
+        tokio::fs::write(dist.join("app_bg.wasm.gz"), b"").await?;
+
+        // streaming instantiation requires the WASM file to be served as `application/wasm`
+        for accept_encoding in ["identity", "gzip"] {
+            let request = extract::Request::builder()
+                .uri("/app_bg.wasm")
+                .header(axum::http::header::ACCEPT_ENCODING, accept_encoding)
+                .body(Body::empty())?;
+            let response = ServeDir::new(dist)
+                .precompressed_gzip()
+                .oneshot(request)
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                response.headers().get(CONTENT_TYPE),
+                Some(&HeaderValue::from_static("application/wasm"))
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn plaintext_http2_is_opt_in() -> Result<()> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        /// The connection preface of HTTP/2, followed by an empty SETTINGS frame.
+        const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\0\0\0\x04\0\0\0\0\0";
+
+        let tmpdir = tempfile::tempdir()?;
+        for http2 in [false, true] {
+            let mut cfg = RtcServe::new_test(tmpdir.path()).await?;
+            cfg.http2 = http2;
+            let cfg = Arc::new(cfg);
+            let router = Router::new().route("/", get(|| async { "ok" }));
+            let handle = Handle::new();
+            let server = configure_plaintext_server(
+                axum_server::bind((Ipv4Addr::LOCALHOST, 0).into()),
+                &cfg,
+            )
+            .handle(handle.clone());
+            let server = tokio::spawn(server.serve(router.into_make_service()));
+            let addr = handle.listening().await.context("server not listening")?;
+
+            let mut stream = tokio::net::TcpStream::connect(addr).await?;
+            stream.write_all(PREFACE).await?;
+            let mut response = [0; 9];
+            let read = tokio::time::timeout(Duration::from_secs(5), async {
+                let mut read = 0;
+                while read < response.len() {
+                    // a closed connection might be reset, as the preface wasn't read
+                    match stream.read(&mut response[read..]).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => read += n,
+                    }
+                }
+                anyhow::Ok(read)
+            })
+            .await??;
+
+            // an HTTP/2 server answers with a SETTINGS frame (of type 4)
+            let settings = read == response.len() && response[3] == 4;
+            assert_eq!(settings, http2, "response: {:?}", &response[..read]);
+
+            handle.shutdown();
+            server.await??;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn preload_headers() -> Result<()> {
+        let html = r#"<html><head><link rel="modulepreload" href="/app.js" crossorigin="anonymous" integrity="sha384-abc"><link rel="preload" href="/app_bg.wasm" crossorigin="anonymous" integrity="sha384&#x2D;def" as="fetch" type="application/wasm"><link rel="stylesheet" href="/app.css"></head><body></body></html>"#;
+
+        let (result, links) = preload_link_headers(html, false)?;
+        assert_eq!(result, html);
+        assert_eq!(
+            links,
+            [
+                r#"</app.js>; rel=modulepreload; crossorigin=anonymous; integrity="sha384-abc""#,
+                r#"</app_bg.wasm>; rel=preload; as=fetch; type="application/wasm"; crossorigin=anonymous; integrity="sha384-def""#,
+            ]
+        );
+
+        let (result, links) = preload_link_headers(html, true)?;
+        assert_eq!(links.len(), 2);
+        assert_eq!(
+            result,
+            r#"<html><head><link rel="stylesheet" href="/app.css"></head><body></body></html>"#
+        );
+
+        Ok(())
+    }
+}
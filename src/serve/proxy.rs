@@ -1,7 +1,8 @@
 use super::SERVER;
-use crate::proxy::{ProxyHandlerHttp, ProxyHandlerWebSocket};
+use crate::proxy::{ProxyHandlerHttp, ProxyHandlerWebSocket, ResponseRewriter};
 use anyhow::Context;
 use axum::http::Uri;
+use axum::routing::MethodRouter;
 use axum::Router;
 use console::Emoji;
 use http::HeaderMap;
@@ -36,6 +37,7 @@ impl ProxyBuilder {
         backend: &Uri,
         request_headers: &HeaderMap,
         rewrite: Option<String>,
+        response_rewrite: ResponseRewriter,
         opts: ProxyClientOptions,
     ) -> anyhow::Result<Self> {
         let proto = match self.tls {
@@ -45,6 +47,11 @@ impl ProxyBuilder {
         .to_string();
 
         if ws {
+            if response_rewrite.is_enabled() {
+                tracing::warn!(
+                    "response rewrites are not supported for websocket proxies, ignoring them for {backend}"
+                );
+            }
             let handler = ProxyHandlerWebSocket::new(
                 proto,
                 backend.clone(),
@@ -69,6 +76,7 @@ impl ProxyBuilder {
                 backend.clone(),
                 request_headers.clone(),
                 rewrite,
+                response_rewrite,
             );
             tracing::info!(
                 "{}proxying {} -> {} {} {}{}",
@@ -101,6 +109,44 @@ impl ProxyBuilder {
     }
 }
 
+/// Build the service proxying the requests without a file in the dist dir to the default backend.
+///
+/// Redirects of the backend are passed on to the client, so that they apply to the page.
+pub(crate) fn default_backend(
+    tls: bool,
+    backend: &Uri,
+    insecure: bool,
+) -> anyhow::Result<MethodRouter> {
+    let proto = match tls {
+        true => "https",
+        false => "http",
+    };
+    let client = ProxyClients::create_client(ProxyClientOptions {
+        insecure,
+        no_system_proxy: false,
+        redirect: false,
+    })?;
+    tracing::info!(
+        "{}proxying requests without a file -> {backend}{}",
+        SERVER,
+        if insecure {
+            format!("; {DANGER}️ insecure TLS")
+        } else {
+            Default::default()
+        }
+    );
+
+    Ok(ProxyHandlerHttp::new(
+        proto.to_string(),
+        client,
+        backend.clone(),
+        HeaderMap::new(),
+        None,
+        ResponseRewriter::default(),
+    )
+    .service())
+}
+
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub(crate) struct ProxyClientOptions {
     pub insecure: bool,
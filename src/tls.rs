@@ -1,3 +1,8 @@
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use crate::config::types::TlsVersion;
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use std::path::Path;
+
 #[derive(Clone, Debug)]
 pub enum TlsConfig {
     #[cfg(feature = "rustls")]
@@ -23,3 +28,123 @@ impl From<axum_server::tls_openssl::OpenSSLConfig> for TlsConfig {
         Self::Native { config }
     }
 }
+
+/// Load the rustls configuration of the server, accepting TLS versions from `min_version` on.
+#[cfg(feature = "rustls")]
+pub async fn rustls_config(
+    cert: &Path,
+    key: &Path,
+    min_version: Option<TlsVersion>,
+) -> anyhow::Result<axum_server::tls_rustls::RustlsConfig> {
+    use anyhow::Context;
+    use rustls::{version::TLS13, ServerConfig, SupportedProtocolVersion};
+    use std::sync::Arc;
+
+    let versions: &[&'static SupportedProtocolVersion] = match min_version {
+        None => rustls::DEFAULT_VERSIONS,
+        Some(TlsVersion::Tls12) => rustls::ALL_VERSIONS,
+        Some(TlsVersion::Tls13) => &[&TLS13],
+    };
+
+    let certs = rustls_pemfile::certs(&mut tokio::fs::read(cert).await?.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed parsing the TLS cert")?;
+    let key = rustls_pemfile::private_key(&mut tokio::fs::read(key).await?.as_slice())
+        .context("failed parsing the TLS key")?
+        .context("no private key found in the TLS key file")?;
+
+    let mut config = ServerConfig::builder_with_provider(Arc::new(crypto_provider()))
+        .with_protocol_versions(versions)?
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    // like the default configuration of axum-server
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(axum_server::tls_rustls::RustlsConfig::from_config(
+        Arc::new(config),
+    ))
+}
+
+/// The crypto provider of the enabled rustls feature.
+///
+/// The process has no default provider, which would be ambiguous with both features enabled.
+#[cfg(feature = "rustls")]
+fn crypto_provider() -> rustls::crypto::CryptoProvider {
+    #[cfg(feature = "rustls-aws-lc")]
+    return rustls::crypto::aws_lc_rs::default_provider();
+    #[cfg(not(feature = "rustls-aws-lc"))]
+    rustls::crypto::ring::default_provider()
+}
+
+/// Load the OpenSSL configuration of the server, accepting TLS versions from `min_version` on.
+///
+/// The default configuration only accepts TLS 1.3.
+#[cfg(feature = "native-tls")]
+pub fn openssl_config(
+    cert: &Path,
+    key: &Path,
+    min_version: Option<TlsVersion>,
+) -> anyhow::Result<axum_server::tls_openssl::OpenSSLConfig> {
+    use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+
+    match min_version {
+        None | Some(TlsVersion::Tls13) => Ok(
+            axum_server::tls_openssl::OpenSSLConfig::from_pem_file(cert, key)?,
+        ),
+        Some(TlsVersion::Tls12) => {
+            let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls())?;
+            builder.set_certificate_chain_file(cert)?;
+            builder.set_private_key_file(key, SslFiletype::PEM)?;
+            builder.check_private_key()?;
+            Ok(axum_server::tls_openssl::OpenSSLConfig::try_from(builder)?)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "rustls"))]
+mod test {
+    use super::*;
+    use rustls::{
+        pki_types::ServerName, version::TLS12, ClientConfig, ClientConnection, RootCertStore,
+        ServerConnection,
+    };
+    use std::sync::Arc;
+
+    /// Process the hello of a client, only supporting TLS 1.2, by the server.
+    fn accepts_tls12(server: Arc<rustls::ServerConfig>) -> anyhow::Result<bool> {
+        let client = ClientConfig::builder_with_provider(Arc::new(crypto_provider()))
+            .with_protocol_versions(&[&TLS12])?
+            .with_root_certificates(RootCertStore::empty())
+            .with_no_client_auth();
+        let mut client =
+            ClientConnection::new(Arc::new(client), ServerName::try_from("localhost")?)?;
+        let mut hello = vec![];
+        client.write_tls(&mut hello)?;
+
+        let mut server = ServerConnection::new(server)?;
+        server.read_tls(&mut hello.as_slice())?;
+        Ok(server.process_new_packets().is_ok())
+    }
+
+    #[tokio::test]
+    async fn tls_min_version() -> anyhow::Result<()> {
+        let certs =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/yew-tls/self_signed_certs");
+        let (cert, key) = (certs.join("cert.pem"), certs.join("key.pem"));
+
+        for (min_version, expected) in [
+            (None, true),
+            (Some(TlsVersion::Tls12), true),
+            (Some(TlsVersion::Tls13), false),
+        ] {
+            let config = rustls_config(&cert, &key, min_version).await?;
+            assert_eq!(
+                accepts_tls12(config.get_inner())?,
+                expected,
+                "min version: {min_version:?}"
+            );
+        }
+
+        Ok(())
+    }
+}
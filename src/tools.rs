@@ -2,13 +2,18 @@
 //! applications (if needed) to use them in the build pipeline.
 
 use self::archive::Archive;
-use crate::common::{is_executable, path_exists, path_exists_and};
+use crate::common::{copy_dir_recursive, is_executable, path_exists, path_exists_and};
 use anyhow::{anyhow, bail, ensure, Context, Result};
 use directories::ProjectDirs;
 use futures_util::stream::StreamExt;
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, LOCATION};
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
@@ -27,9 +32,11 @@ pub enum Application {
     WasmBindgen,
     /// wasm-opt to improve performance and size of the output file further.
     WasmOpt,
+    /// wasm-tools for creating WebAssembly components.
+    WasmTools,
 }
 
-/// These options configure how Trunk sets up it's HTTP Client.
+/// These options configure how Trunk sets up it's HTTP Client, and where downloads are processed.
 #[derive(Debug, Clone, Default)]
 pub struct HttpClientOptions {
     /// Use this specific root certificate to validate the certificate chain. Optional.
@@ -42,6 +49,19 @@ pub struct HttpClientOptions {
     /// **WARNING**: This is inherently unsafe and can open you up to Man-in-the-middle attacks. But sometimes it is required when working behind corporate proxies.
     #[cfg(any(feature = "native-tls", feature = "rustls"))]
     pub accept_invalid_certificates: bool,
+    /// A scratch directory for downloading and extracting tools, instead of the cache directory.
+    ///
+    /// Once installed, the tools are moved into the cache directory.
+    pub download_tmp: Option<PathBuf>,
+    /// The base URL of a mirror of the GitHub releases to download the tools from, instead of
+    /// `https://github.com/`.
+    pub download_mirror: Option<String>,
+    /// Additional headers sent with the download requests to the mirror.
+    ///
+    /// Values may reference environment variables using `${NAME}`.
+    pub download_headers: HashMap<String, String>,
+    /// The expected SHA-256 checksums of the downloaded archives, by the name of the application.
+    pub download_checksums: HashMap<String, String>,
 }
 
 impl Application {
@@ -53,6 +73,7 @@ impl Application {
             Self::TailwindCssExtra => "tailwindcss-extra",
             Self::WasmBindgen => "wasm-bindgen",
             Self::WasmOpt => "wasm-opt",
+            Self::WasmTools => "wasm-tools",
         }
     }
 
@@ -65,6 +86,7 @@ impl Application {
                 Self::TailwindCssExtra => "tailwindcss-extra.exe",
                 Self::WasmBindgen => "wasm-bindgen.exe",
                 Self::WasmOpt => "bin/wasm-opt.exe",
+                Self::WasmTools => "wasm-tools.exe",
             }
         } else {
             match self {
@@ -73,6 +95,7 @@ impl Application {
                 Self::TailwindCssExtra => "tailwindcss-extra",
                 Self::WasmBindgen => "wasm-bindgen",
                 Self::WasmOpt => "bin/wasm-opt",
+                Self::WasmTools => "wasm-tools",
             }
         }
     }
@@ -90,6 +113,7 @@ impl Application {
             Self::TailwindCss => &[],
             Self::TailwindCssExtra => &[],
             Self::WasmBindgen => &[],
+            Self::WasmTools => &[],
             Self::WasmOpt => {
                 if cfg!(target_os = "macos") {
                     &["lib/libbinaryen.dylib"]
@@ -108,6 +132,7 @@ impl Application {
             Self::TailwindCssExtra => "1.7.25",
             Self::WasmBindgen => "0.2.89",
             Self::WasmOpt => "version_116",
+            Self::WasmTools => "1.219.1",
         }
     }
 
@@ -165,6 +190,11 @@ impl Application {
             Self::WasmOpt => match (target_os, target_arch) {
                 ("macos", "aarch64") => format!("https://github.com/WebAssembly/binaryen/releases/download/{version}/binaryen-{version}-arm64-macos.tar.gz"),
                 _ => format!("https://github.com/WebAssembly/binaryen/releases/download/{version}/binaryen-{version}-{target_arch}-{target_os}.tar.gz")
+            },
+
+            Self::WasmTools => match target_os {
+                "windows" => format!("https://github.com/bytecodealliance/wasm-tools/releases/download/v{version}/wasm-tools-{version}-{target_arch}-windows.zip"),
+                _ => format!("https://github.com/bytecodealliance/wasm-tools/releases/download/v{version}/wasm-tools-{version}-{target_arch}-{target_os}.tar.gz")
             }
         })
     }
@@ -177,6 +207,7 @@ impl Application {
             Application::TailwindCssExtra => "--help",
             Application::WasmBindgen => "--version",
             Application::WasmOpt => "--version",
+            Application::WasmTools => "--version",
         }
     }
 
@@ -212,6 +243,11 @@ impl Application {
                     .nth(2)
                     .with_context(|| format!("missing or malformed version output: {}", text))?
             ),
+            Application::WasmTools => text
+                .split(' ')
+                .nth(1)
+                .with_context(|| format!("missing or malformed version output: {}", text))?
+                .to_owned(),
         };
         Ok(formatted_version)
     }
@@ -236,7 +272,7 @@ impl AppCache {
     }
 
     /// Install the desired application of given version to the provided application directory. Or
-    /// don't if it's already been installed.
+    /// don't if it's already been installed, and its files match the recorded checksums.
     async fn install_once(
         &mut self,
         app: Application,
@@ -248,17 +284,35 @@ impl AppCache {
 
         cached
             .get_or_try_init(|| async move {
-                let path = download(app, version, client_options)
+                if is_executable(&app_dir.join(app.path())).await?
+                    && verify_checksums(app, &app_dir, client_options).await
+                {
+                    return Ok(());
+                }
+
+                let (path, archive_checksum) = download(app, version, client_options)
                     .await
                     .context("failed downloading release archive")?;
 
                 let file = File::open(&path)
                     .await
                     .context("failed opening downloaded file")?;
-                install(app, file, app_dir).await?;
+                match &client_options.download_tmp {
+                    Some(download_tmp) => {
+                        let scratch = download_tmp.join(format!("{}-{}", app.name(), version));
+                        install(app, file, scratch.clone()).await?;
+                        move_dir(&scratch, &app_dir)
+                            .await
+                            .context("failed moving installed application into the cache")?;
+                    }
+                    None => install(app, file, app_dir.clone()).await?,
+                }
                 tokio::fs::remove_file(path)
                     .await
                     .context("failed deleting temporary archive")?;
+                record_checksums(app, &app_dir, archive_checksum)
+                    .await
+                    .context("failed recording the checksums of the installed application")?;
 
                 Ok(())
             })
@@ -280,22 +334,32 @@ pub struct ToolInformation {
 pub async fn get(
     app: Application,
     version: Option<&str>,
+    path: Option<&Path>,
     offline: bool,
     client_options: &HttpClientOptions,
 ) -> Result<PathBuf> {
-    Ok(get_info(app, version, offline, client_options).await?.path)
+    Ok(get_info(app, version, path, offline, client_options)
+        .await?
+        .path)
 }
 
 /// Locate the given application and download it if missing, returning detailed information.
+///
+/// If the path of the application is configured, it is used instead.
 #[tracing::instrument(level = "debug")]
 pub async fn get_info(
     app: Application,
     version: Option<&str>,
+    path: Option<&Path>,
     offline: bool,
     client_options: &HttpClientOptions,
 ) -> Result<ToolInformation> {
     tracing::debug!("Getting tool");
 
+    if let Some(path) = path {
+        return get_configured(app, version, path).await;
+    }
+
     if let Some((path, detected_version)) = find_system(app).await {
         // consider system installed version
 
@@ -340,13 +404,11 @@ pub async fn get_info(
     let app_dir = cache_dir.join(format!("{}-{}", app.name(), version));
     let bin_path = app_dir.join(app.path());
 
-    if !is_executable(&bin_path).await? {
-        GLOBAL_APP_CACHE
-            .lock()
-            .await
-            .install_once(app, version, app_dir, client_options)
-            .await?;
-    }
+    GLOBAL_APP_CACHE
+        .lock()
+        .await
+        .install_once(app, version, app_dir, client_options)
+        .await?;
 
     tracing::debug!(
         "Using {} ({version}) from: {}",
@@ -360,6 +422,57 @@ pub async fn get_info(
     })
 }
 
+/// Use the application at the configured path, never looking for a system installed or downloaded
+/// one.
+pub async fn get_configured(
+    app: Application,
+    version: Option<&str>,
+    path: &Path,
+) -> Result<ToolInformation> {
+    ensure!(
+        is_executable(path).await?,
+        "the configured path of {} ({}) doesn't exist or is not executable",
+        app.name(),
+        path.display()
+    );
+
+    let output = Command::new(path)
+        .arg(app.version_test())
+        .output()
+        .await
+        .with_context(|| format!("failed running {}", path.display()))?;
+    ensure!(
+        output.status.success(),
+        "running command `{} {}` failed",
+        path.display(),
+        app.version_test()
+    );
+    let detected_version = app
+        .format_version_output(&String::from_utf8_lossy(&output.stdout))
+        .with_context(|| format!("failed detecting the version of {}", path.display()))?;
+
+    if let Some(required_version) = version {
+        if required_version != detected_version {
+            tracing::warn!(
+                "using {} ({detected_version}) from the configured path {}, but version {required_version} is required",
+                app.name(),
+                path.display()
+            );
+        }
+    }
+
+    tracing::debug!(
+        "Using {} ({detected_version}) from the configured path: {}",
+        app.name(),
+        path.display()
+    );
+
+    Ok(ToolInformation {
+        path: path.to_owned(),
+        version: detected_version,
+    })
+}
+
 /// Try to find a global system installed version of the application.
 #[tracing::instrument(level = "debug")]
 pub async fn find_system(app: Application) -> Option<(PathBuf, String)> {
@@ -398,7 +511,7 @@ async fn download(
     app: Application,
     version: &str,
     client_options: &HttpClientOptions,
-) -> Result<PathBuf> {
+) -> Result<(PathBuf, String)> {
     tracing::info!(version = version, "downloading {}", app.name());
 
     #[cfg(any(feature = "native-tls", feature = "rustls"))]
@@ -408,34 +521,144 @@ async fn download(
         );
     }
 
-    let cache_dir = cache_dir()
-        .await
-        .context("failed getting the cache directory")?;
-    let temp_out = cache_dir.join(format!("{}-{}.tmp", app.name(), version));
+    let temp_dir = match &client_options.download_tmp {
+        Some(download_tmp) => {
+            tokio::fs::create_dir_all(download_tmp)
+                .await
+                .context("failed creating the download scratch directory")?;
+            download_tmp.clone()
+        }
+        None => cache_dir()
+            .await
+            .context("failed getting the cache directory")?,
+    };
+    let temp_out = temp_dir.join(format!("{}-{}.tmp", app.name(), version));
     let mut file = File::create(&temp_out)
         .await
         .context("failed creating temporary output file")?;
 
     let client = get_http_client(client_options).await?;
 
-    let resp = client
-        .get(app.url(version)?)
-        .send()
-        .await
-        .context("error sending HTTP request")?;
+    let url = download_url(app, version, client_options)?;
+    let resp = send_download_request(&client, Method::GET, url.clone(), client_options).await?;
     ensure!(
         resp.status().is_success(),
-        "error downloading archive file: {:?}\n{}",
+        "error downloading archive file: {:?}\n{url}",
         resp.status(),
-        app.url(version)?
     );
+    let mut hasher = Sha256::new();
     let mut res_bytes = resp.bytes_stream();
     while let Some(chunk_res) = res_bytes.next().await {
         let chunk = chunk_res.context("error reading chunk from download")?;
-        let _res = file.write(chunk.as_ref()).await;
+        hasher.update(&chunk);
+        file.write_all(chunk.as_ref())
+            .await
+            .context("error writing downloaded archive")?;
     }
+    file.flush()
+        .await
+        .context("error writing downloaded archive")?;
+    let checksum = format!("{:x}", hasher.finalize());
 
-    Ok(temp_out)
+    if let Some(expected) = client_options.download_checksums.get(app.name()) {
+        if !expected.eq_ignore_ascii_case(&checksum) {
+            tokio::fs::remove_file(&temp_out)
+                .await
+                .context("failed deleting temporary archive")?;
+            bail!(
+                "the checksum of the downloaded {} archive ({checksum}) doesn't match the configured checksum ({expected})",
+                app.name()
+            );
+        }
+    }
+
+    Ok((temp_out, checksum))
+}
+
+/// The checksums of an installed application, recorded next to its directory in the cache.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Checksums {
+    /// The SHA-256 of the downloaded archive.
+    archive: String,
+    /// The SHA-256 of the installed files, by their path relative to the application directory.
+    files: BTreeMap<String, String>,
+}
+
+/// The file recording the checksums of an application installed to the given directory.
+fn checksums_file(app_dir: &Path) -> PathBuf {
+    let mut file = OsString::from(app_dir.as_os_str());
+    file.push(".sha256");
+    PathBuf::from(file)
+}
+
+/// The SHA-256 of a file, as hex string.
+async fn file_checksum(path: &Path) -> Result<String> {
+    let data = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("failed reading {}", path.display()))?;
+    Ok(format!("{:x}", Sha256::digest(data)))
+}
+
+/// Record the checksums of an installed application and the archive it was installed from.
+async fn record_checksums(app: Application, app_dir: &Path, archive: String) -> Result<()> {
+    let mut files = BTreeMap::new();
+    for path in std::iter::once(app.path()).chain(app.extra_paths().iter().copied()) {
+        let file = app_dir.join(path);
+        // extra files are optional, see `install`
+        if path_exists_and(&file, |m| m.is_file()).await? {
+            files.insert(path.to_string(), file_checksum(&file).await?);
+        }
+    }
+
+    let checksums = serde_json::to_vec_pretty(&Checksums { archive, files })?;
+    tokio::fs::write(checksums_file(app_dir), checksums)
+        .await
+        .context("failed writing checksums file")
+}
+
+/// Check that the files of an installed application match their recorded checksums, and that it
+/// was installed from the configured archive, if any.
+///
+/// Installations without (valid) checksums don't pass, so that they get installed again.
+async fn verify_checksums(
+    app: Application,
+    app_dir: &Path,
+    client_options: &HttpClientOptions,
+) -> bool {
+    let result = || async {
+        let file = checksums_file(app_dir);
+        let data = tokio::fs::read(&file)
+            .await
+            .context("no checksums recorded")?;
+        let checksums: Checksums = serde_json::from_slice(&data)
+            .with_context(|| format!("failed parsing {}", file.display()))?;
+
+        if let Some(expected) = client_options.download_checksums.get(app.name()) {
+            ensure!(
+                expected.eq_ignore_ascii_case(&checksums.archive),
+                "installed from a different archive than configured"
+            );
+        }
+        ensure!(
+            checksums.files.contains_key(app.path()),
+            "no checksum recorded for {}",
+            app.path()
+        );
+        for (path, expected) in &checksums.files {
+            let checksum = file_checksum(&app_dir.join(path)).await?;
+            ensure!(checksum == *expected, "checksum mismatch of {path}");
+        }
+
+        Ok(())
+    };
+
+    match result().await {
+        Ok(()) => true,
+        Err(err) => {
+            tracing::info!("reinstalling {}: {err:#}", app.name());
+            false
+        }
+    }
 }
 
 /// Install an application from a downloaded archive locating and copying it to the given target
@@ -448,7 +671,9 @@ async fn install(app: Application, archive_file: File, target_directory: PathBuf
 
     let target_directory_clone = target_directory.clone();
     tokio::task::spawn_blocking(move || {
-        let mut archive = if app == Application::Sass && cfg!(target_os = "windows") {
+        let mut archive = if matches!(app, Application::Sass | Application::WasmTools)
+            && cfg!(target_os = "windows")
+        {
             Archive::new_zip(archive_file)?
         } else if app == Application::TailwindCss {
             Archive::new_none(archive_file)
@@ -498,6 +723,25 @@ async fn install(app: Application, archive_file: File, target_directory: PathBuf
     Ok(())
 }
 
+/// Move a directory to its target location, which might be on another device.
+async fn move_dir(from: &Path, to: &Path) -> Result<()> {
+    if path_exists(to).await? {
+        tokio::fs::remove_dir_all(to)
+            .await
+            .with_context(|| format!("failed removing existing directory {to:?}"))?;
+    }
+
+    if tokio::fs::rename(from, to).await.is_ok() {
+        return Ok(());
+    }
+
+    // renaming fails across devices, so copy instead
+    copy_dir_recursive(from.to_owned(), to.to_owned()).await?;
+    tokio::fs::remove_dir_all(from)
+        .await
+        .with_context(|| format!("failed removing directory {from:?}"))
+}
+
 /// Locate the cache dir for trunk and make sure it exists.
 pub async fn cache_dir() -> Result<PathBuf> {
     let path = ProjectDirs::from("dev", "trunkrs", "trunk")
@@ -510,10 +754,104 @@ pub async fn cache_dir() -> Result<PathBuf> {
     Ok(path)
 }
 
-async fn get_http_client(
+/// The prefix of the release URLs which gets replaced by the download mirror.
+const GITHUB_URL: &str = "https://github.com/";
+
+/// The maximum number of redirects followed by download requests.
+const MAX_REDIRECTS: usize = 10;
+
+/// The URL to download an application from, using the download mirror if configured.
+pub(crate) fn download_url(
+    app: Application,
+    version: &str,
+    client_options: &HttpClientOptions,
+) -> Result<Url> {
+    let url = app.url(version)?;
+    let Some(mirror) = download_mirror(client_options)? else {
+        return Url::parse(&url).with_context(|| format!("invalid download URL: {url}"));
+    };
+    let path = url
+        .strip_prefix(GITHUB_URL)
+        .with_context(|| format!("unable to download {url} from the download mirror"))?;
+    mirror
+        .join(path)
+        .with_context(|| format!("invalid download URL for the mirror: {path}"))
+}
+
+/// The base URL of the download mirror, if configured.
+fn download_mirror(client_options: &HttpClientOptions) -> Result<Option<Url>> {
+    let Some(mirror) = &client_options.download_mirror else {
+        return Ok(None);
+    };
+    // the path of the release gets appended to the base URL
+    let mirror = match mirror.ends_with('/') {
+        true => Url::parse(mirror),
+        false => Url::parse(&format!("{mirror}/")),
+    }
+    .with_context(|| format!("invalid download mirror URL: {mirror}"))?;
+    Ok(Some(mirror))
+}
+
+/// Send a request for a download, following redirects.
+///
+/// The download headers may contain credentials, so they are only sent with requests to the
+/// download mirror, and not with requests to other hosts (e.g. of a redirect).
+pub(crate) async fn send_download_request(
+    client: &reqwest::Client,
+    method: Method,
+    mut url: Url,
+    client_options: &HttpClientOptions,
+) -> Result<reqwest::Response> {
+    let mirror = download_mirror(client_options)?;
+    let headers = download_headers(client_options)?;
+    for _ in 0..=MAX_REDIRECTS {
+        let mut request = client.request(method.clone(), url.clone());
+        if mirror
+            .as_ref()
+            .is_some_and(|mirror| mirror.origin() == url.origin())
+        {
+            request = request.headers(headers.clone());
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("error sending HTTP request to {url}"))?;
+        if !response.status().is_redirection() {
+            return Ok(response);
+        }
+
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|location| location.to_str().ok())
+            .with_context(|| format!("invalid redirect from {url}"))?;
+        url = url
+            .join(location)
+            .with_context(|| format!("invalid redirect from {url} to {location}"))?;
+    }
+
+    bail!("too many redirects, stopped at {url}")
+}
+
+/// The configured download headers.
+fn download_headers(client_options: &HttpClientOptions) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &client_options.download_headers {
+        let name = HeaderName::try_from(name)
+            .with_context(|| format!("invalid download header name: {name}"))?;
+        let mut value = HeaderValue::try_from(interpolate_env(value)?)
+            .with_context(|| format!("invalid value for download header: {name}"))?;
+        value.set_sensitive(true);
+        headers.insert(name, value);
+    }
+    Ok(headers)
+}
+
+pub(crate) async fn get_http_client(
     #[allow(unused_variables)] client_options: &HttpClientOptions,
 ) -> Result<reqwest::Client> {
-    let builder = reqwest::ClientBuilder::new();
+    // redirects are followed by `send_download_request`, which controls the headers sent
+    let builder = reqwest::ClientBuilder::new().redirect(reqwest::redirect::Policy::none());
 
     #[cfg(any(feature = "native-tls", feature = "rustls"))]
     let builder = {
@@ -542,6 +880,27 @@ async fn get_http_client(
         .with_context(|| "Error building http client")
 }
 
+/// Replace references to environment variables (`${NAME}`) with their values.
+fn interpolate_env(value: &str) -> Result<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .with_context(|| format!("unterminated environment variable reference in: {value}"))?;
+        let name = &rest[start + 2..start + end];
+        let var = std::env::var(name)
+            .with_context(|| format!("failed reading environment variable '{name}'"))?;
+        result.push_str(&var);
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
 mod archive {
     use std::fmt::Display;
     use std::fs::{self, File};
@@ -637,7 +996,7 @@ mod archive {
     fn find_tar_entry(
         archive: &mut TarArchive<impl Read>,
         path: impl AsRef<Path>,
-    ) -> Result<Option<TarEntry<impl Read>>> {
+    ) -> Result<Option<TarEntry<'_, impl Read>>> {
         let entries = archive
             .entries()
             .context("failed getting archive entries")?;
@@ -738,9 +1097,10 @@ mod tests {
             Application::Sass,
             Application::WasmBindgen,
             Application::WasmOpt,
+            Application::WasmTools,
             Application::TailwindCss,
         ] {
-            let path = download(app, app.default_version(), &HttpClientOptions::default())
+            let (path, _) = download(app, app.default_version(), &HttpClientOptions::default())
                 .await
                 .context("error downloading app")?;
             let file = File::open(&path).await.context("error opening file")?;
@@ -752,6 +1112,228 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn move_dir_replaces_target() -> Result<()> {
+        let dir = tempfile::tempdir().context("error creating temporary dir")?;
+        let (from, to) = (dir.path().join("from"), dir.path().join("to"));
+        tokio::fs::create_dir_all(from.join("bin")).await?;
+        tokio::fs::write(from.join("bin").join("app"), b"app").await?;
+        tokio::fs::create_dir_all(&to).await?;
+        tokio::fs::write(to.join("stale"), b"stale").await?;
+
+        move_dir(&from, &to).await?;
+
+        ensure!(!path_exists(&from).await?, "source dir must be gone");
+        ensure!(
+            path_exists(&to.join("bin").join("app")).await?,
+            "moved file is missing"
+        );
+        ensure!(!path_exists(&to.join("stale")).await?, "stale file remains");
+        Ok(())
+    }
+
+    #[test]
+    fn download_mirror_urls() -> Result<()> {
+        let app = Application::WasmBindgen;
+        let release = app
+            .url("0.2.93")?
+            .strip_prefix(GITHUB_URL)
+            .context("must be a GitHub release")?
+            .to_string();
+
+        let url = download_url(app, "0.2.93", &HttpClientOptions::default())?;
+        assert_eq!(url.as_str(), format!("{GITHUB_URL}{release}"));
+        for mirror in [
+            "https://mirror.example.com/github",
+            "https://mirror.example.com/github/",
+        ] {
+            let options = HttpClientOptions {
+                download_mirror: Some(mirror.into()),
+                ..Default::default()
+            };
+            let url = download_url(app, "0.2.93", &options)?;
+            assert_eq!(
+                url.as_str(),
+                format!("https://mirror.example.com/github/{release}")
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn download_headers_only_for_mirror() -> Result<()> {
+        use axum::{
+            http::{HeaderMap, StatusCode},
+            response::Redirect,
+            routing::get,
+            Router,
+        };
+        use std::net::Ipv4Addr;
+
+        // a host answering with the authorization header it received
+        let other = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let other_addr = other.local_addr()?;
+        let other = tokio::spawn(async move {
+            let router = Router::new().fallback(|headers: HeaderMap| async move {
+                format!("{:?}", headers.get("authorization"))
+            });
+            axum::serve(other, router).await
+        });
+
+        // the mirror requires the authorization header, or redirects to the other host
+        let mirror = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        let mirror_addr = mirror.local_addr()?;
+        let mirror = tokio::spawn(async move {
+            let router = Router::new()
+                .route(
+                    "/redirect",
+                    get(move || async move {
+                        Redirect::temporary(&format!("http://{other_addr}/file"))
+                    }),
+                )
+                .fallback(|headers: HeaderMap| async move {
+                    match headers.get("authorization").map(|value| value.as_bytes()) {
+                        Some(b"Bearer secret") => (StatusCode::OK, "archive"),
+                        _ => (StatusCode::UNAUTHORIZED, ""),
+                    }
+                });
+            axum::serve(mirror, router).await
+        });
+
+        let dir = tempfile::tempdir()?;
+        let options = HttpClientOptions {
+            download_tmp: Some(dir.path().into()),
+            download_mirror: Some(format!("http://{mirror_addr}/github")),
+            download_headers: [("Authorization".into(), "Bearer secret".into())].into(),
+            ..Default::default()
+        };
+        let (file, checksum) = download(Application::WasmBindgen, "0.2.93", &options).await?;
+        assert_eq!(tokio::fs::read(&file).await?, b"archive");
+        assert_eq!(checksum, format!("{:x}", Sha256::digest(b"archive")));
+
+        // the headers are neither sent to other hosts, nor after a redirect to them
+        let client = get_http_client(&options).await?;
+        for url in [
+            format!("http://{other_addr}/file"),
+            format!("http://{mirror_addr}/redirect"),
+        ] {
+            let response =
+                send_download_request(&client, Method::GET, Url::parse(&url)?, &options).await?;
+            assert_eq!(response.text().await?, "None", "headers sent for {url}");
+        }
+
+        // without the headers, the mirror rejects the download
+        let options = HttpClientOptions {
+            download_headers: Default::default(),
+            ..options
+        };
+        assert!(download(Application::WasmBindgen, "0.2.93", &options)
+            .await
+            .is_err());
+
+        other.abort();
+        mirror.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn checksums_of_installed_app() -> Result<()> {
+        let dir = tempfile::tempdir().context("error creating temporary dir")?;
+        let app = Application::WasmBindgen;
+        let app_dir = dir.path().join("wasm-bindgen-0.2.93");
+        let options = HttpClientOptions::default();
+        tokio::fs::create_dir_all(&app_dir).await?;
+        tokio::fs::write(app_dir.join(app.path()), b"binary").await?;
+
+        ensure!(
+            !verify_checksums(app, &app_dir, &options).await,
+            "must fail without recorded checksums"
+        );
+
+        record_checksums(app, &app_dir, "archive".into()).await?;
+        ensure!(
+            path_exists(&dir.path().join("wasm-bindgen-0.2.93.sha256")).await?,
+            "checksums must be recorded next to the app dir"
+        );
+        ensure!(verify_checksums(app, &app_dir, &options).await);
+
+        let pinned = HttpClientOptions {
+            download_checksums: [(app.name().to_string(), "other".to_string())].into(),
+            ..Default::default()
+        };
+        ensure!(
+            !verify_checksums(app, &app_dir, &pinned).await,
+            "must fail when installed from a different archive"
+        );
+
+        tokio::fs::write(app_dir.join(app.path()), b"broken").await?;
+        ensure!(
+            !verify_checksums(app, &app_dir, &options).await,
+            "must fail for a modified binary"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn configured_path_is_used_verbatim() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().context("error creating temporary dir")?;
+        let path = dir.path().join("custom-wasm-opt");
+        tokio::fs::write(&path, "#!/bin/sh\necho 'wasm-opt version 116'\n").await?;
+        tokio::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).await?;
+
+        // offline, so there must be no attempt to download it
+        let info = get_info(
+            Application::WasmOpt,
+            Some("version_117"),
+            Some(&path),
+            true,
+            &Default::default(),
+        )
+        .await?;
+        ensure!(
+            info == ToolInformation {
+                path: path.clone(),
+                version: "version_116".into()
+            },
+            "unexpected tool: {info:?}"
+        );
+
+        let missing = dir.path().join("missing");
+        let err = get_info(
+            Application::WasmOpt,
+            None,
+            Some(&missing),
+            false,
+            &Default::default(),
+        )
+        .await
+        .expect_err("a missing binary must fail");
+        ensure!(
+            err.to_string()
+                .contains("doesn't exist or is not executable"),
+            "unexpected error: {err}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn interpolate_env_vars() -> Result<()> {
+        std::env::set_var("TRUNK_TEST_INTERPOLATE_TOKEN", "secret");
+
+        let value = interpolate_env("Bearer ${TRUNK_TEST_INTERPOLATE_TOKEN}!")?;
+        ensure!(value == "Bearer secret!", "unexpected value: {value}");
+        ensure!(interpolate_env("plain")? == "plain");
+        ensure!(interpolate_env("${TRUNK_TEST_INTERPOLATE_UNSET}").is_err());
+        ensure!(interpolate_env("${TRUNK_TEST_INTERPOLATE_TOKEN").is_err());
+
+        Ok(())
+    }
+
     macro_rules! table_test_format_version {
         ($name:ident, $app:expr, $input:literal, $expect:literal) => {
             #[test]
@@ -799,6 +1381,13 @@ mod tests {
         "0.2.74"
     );
 
+    table_test_format_version!(
+        wasm_tools_pre_compiled,
+        Application::WasmTools,
+        "wasm-tools 1.219.1 (4c8af6a0b 2024-10-22)",
+        "1.219.1"
+    );
+
     table_test_format_version!(sass_pre_compiled, Application::Sass, "1.37.5", "1.37.5");
     table_test_format_version!(
         sass_pre_compiled_dart2js,
@@ -1,10 +1,16 @@
 use crate::{
     build::{BuildResult, BuildSystem},
-    config::{rt::RtcWatch, types::WsProtocol},
+    config::{
+        rt::{RtcBuild, RtcWatch},
+        types::WsProtocol,
+    },
+    hooks::{spawn_hooks, wait_hooks},
+    pipelines::PipelineStage,
     ws,
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use futures_util::stream::StreamExt;
+use glob::Pattern;
 use notify::{
     event::{MetadataKind, ModifyKind},
     EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher,
@@ -58,18 +64,26 @@ const WATCHER_COOLDOWN: Duration = Duration::from_secs(1);
 pub struct WatchSystem {
     /// The build system.
     build: Arc<Mutex<BuildSystem>>,
+    /// The runtime config of the build system, for running the rebuild hooks.
+    build_cfg: Arc<RtcBuild>,
+    /// The paths being watched.
+    paths: Vec<PathBuf>,
     /// The current vector of paths to be ignored.
     ignored_paths: Vec<PathBuf>,
+    /// Glob patterns of paths to be ignored.
+    ignored_globs: Vec<Pattern>,
     /// A channel of FS watch events.
     watch_rx: mpsc::Receiver<DebouncedEvent>,
     /// A channel of new paths to ignore from the build system.
     ignore_rx: mpsc::Receiver<PathBuf>,
+    /// A channel of additional paths to watch from the build system, like Sass partials.
+    watch_path_rx: mpsc::UnboundedReceiver<PathBuf>,
     /// A sender to notify the end of a build.
     build_tx: mpsc::Sender<BuildResult>,
     /// A channel to receive the end of a build.
     build_rx: mpsc::Receiver<BuildResult>,
-    /// The watch system used for watching the filesystem.
-    _debouncer: FsDebouncer,
+    /// The watch system used for watching the filesystem, [`None`] once stopped.
+    _debouncer: Option<FsDebouncer>,
     /// The application shutdown channel.
     shutdown: BroadcastStream<()>,
     /// Channel to communicate with the client socket
@@ -92,6 +106,8 @@ pub struct WatchSystem {
     clear_screen: bool,
     /// Don't send build errors to the frontend.
     no_error_reporting: bool,
+    /// Log the watched and ignored paths when they change.
+    print_watches: bool,
 }
 
 impl WatchSystem {
@@ -105,6 +121,8 @@ impl WatchSystem {
         // Create a channel for being able to listen for new paths to ignore while running.
         let (watch_tx, watch_rx) = mpsc::channel(1);
         let (ignore_tx, ignore_rx) = mpsc::channel(1);
+        // Unbounded, as the initial build runs before the watch system starts receiving.
+        let (watch_path_tx, watch_path_rx) = mpsc::unbounded_channel();
         let (build_tx, build_rx) = mpsc::channel(1);
 
         // Build the watcher.
@@ -119,16 +137,26 @@ impl WatchSystem {
 
         // Build dependencies.
         let build = Arc::new(Mutex::new(
-            BuildSystem::new(cfg.build.clone(), Some(ignore_tx), ws_protocol).await?,
+            BuildSystem::new(
+                cfg.build.clone(),
+                Some(ignore_tx),
+                Some(watch_path_tx),
+                ws_protocol,
+            )
+            .await?,
         ));
-        Ok(Self {
+        let system = Self {
             build,
+            build_cfg: cfg.build.clone(),
+            paths: cfg.paths.clone(),
             ignored_paths: cfg.ignored_paths.clone(),
+            ignored_globs: cfg.ignored_globs.clone(),
             watch_rx,
             ignore_rx,
+            watch_path_rx,
             build_rx,
             build_tx,
-            _debouncer,
+            _debouncer: Some(_debouncer),
             shutdown: BroadcastStream::new(shutdown.subscribe()),
             ws_state,
             last_build_started: Instant::now(),
@@ -137,13 +165,22 @@ impl WatchSystem {
             watcher_cooldown,
             clear_screen: cfg.clear_screen,
             no_error_reporting: cfg.no_error_reporting,
-        })
+            print_watches: cfg.print_watches,
+        };
+        system.print_watches();
+
+        Ok(system)
     }
 
     /// Run a build.
     #[tracing::instrument(level = "trace", skip(self))]
     pub async fn build(&mut self) -> Result<()> {
-        self.build.lock().await.build().await
+        self.build.lock().await.build().await.map(drop)
+    }
+
+    /// Stop watching the filesystem, no further builds will be triggered by changes.
+    pub fn stop_watching(&mut self) {
+        self._debouncer = None;
     }
 
     /// Run the watch system, responding to events and triggering builds.
@@ -152,6 +189,7 @@ impl WatchSystem {
         loop {
             tokio::select! {
                 Some(ign) = self.ignore_rx.recv() => self.update_ignore_list(ign),
+                Some(path) = self.watch_path_rx.recv() => self.add_watch_path(path),
                 Some(ev) = self.watch_rx.recv() => self.handle_watch_event(ev).await,
                 Some(build) = self.build_rx.recv() => self.build_complete(build).await,
                 _ = self.shutdown.next() => break, // Any event, even a drop, will trigger shutdown.
@@ -161,6 +199,33 @@ impl WatchSystem {
         tracing::debug!("watcher system has shut down");
     }
 
+    /// Wait for the first relevant change, then run a single build and return its result.
+    ///
+    /// Changes which happened before calling this, like the ones caused by a previous build, are
+    /// discarded.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn run_once(mut self) -> Result<()> {
+        let since = std::time::Instant::now();
+
+        loop {
+            tokio::select! {
+                Some(ign) = self.ignore_rx.recv() => self.update_ignore_list(ign),
+                Some(path) = self.watch_path_rx.recv() => self.add_watch_path(path),
+                Some(ev) = self.watch_rx.recv() => {
+                    if ev.time >= since && self.is_event_relevant(&ev).await {
+                        tracing::trace!("change detected in {:?}, rebuilding once", ev.paths);
+                        break;
+                    }
+                }
+                _ = self.shutdown.next() => bail!("shut down before a change was detected"),
+            }
+        }
+
+        self.stop_watching();
+        self.build().await?;
+        wait_hooks(spawn_hooks(self.build_cfg.clone(), PipelineStage::Rebuild)).await
+    }
+
     #[tracing::instrument(level = "trace", skip(self))]
     async fn build_complete(&mut self, build_result: Result<(), anyhow::Error>) {
         tracing::debug!("Build reported completion");
@@ -197,11 +262,15 @@ impl WatchSystem {
         self.last_build_started = Instant::now();
 
         let build = self.build.clone();
+        let build_cfg = self.build_cfg.clone();
         let build_tx = self.build_tx.clone();
 
         tokio::spawn(async move {
-            // run the build
-            let result = build.lock().await.build().await;
+            // run the build, followed by the rebuild hooks
+            let mut result = build.lock().await.build().await.map(drop);
+            if result.is_ok() {
+                result = wait_hooks(spawn_hooks(build_cfg, PipelineStage::Rebuild)).await;
+            }
             // report the result
             build_tx.send(result).await
         });
@@ -297,6 +366,15 @@ impl WatchSystem {
                 continue; // Don't emit a notification if path is ignored.
             }
 
+            // Check ignored glob patterns.
+            if ev_path.ancestors().any(|path| {
+                self.ignored_globs
+                    .iter()
+                    .any(|pattern| pattern.matches_path(path))
+            }) {
+                continue; // Don't emit a notification if path matches an ignore pattern.
+            }
+
             // Check blacklisted paths.
             if ev_path
                 .components()
@@ -323,7 +401,50 @@ impl WatchSystem {
 
         if !self.ignored_paths.contains(&path) {
             self.ignored_paths.push(path);
+            self.print_watches();
+        }
+    }
+
+    /// Watch an additional path, unless it is already covered by a watched path.
+    fn add_watch_path(&mut self, arg_path: PathBuf) {
+        let path = match arg_path.canonicalize() {
+            Ok(canon_path) => canon_path,
+            Err(_) => arg_path,
+        };
+
+        if path
+            .ancestors()
+            .any(|ancestor| self.paths.iter().any(|watched| watched == ancestor))
+        {
+            return;
         }
+
+        let Some(debouncer) = &mut self._debouncer else {
+            return;
+        };
+        if let Err(err) = debouncer.watch(&path, RecursiveMode::NonRecursive) {
+            tracing::warn!("failed to watch {path:?} for file system changes: {err}");
+            return;
+        }
+
+        tracing::debug!("watching additional path {path:?}");
+        self.paths.push(path);
+        self.print_watches();
+    }
+
+    /// Log the watched and ignored paths as JSON, if requested.
+    fn print_watches(&self) {
+        if !self.print_watches {
+            return;
+        }
+
+        let watches = serde_json::json!({
+            "watch": self.paths,
+            "ignore": self.ignored_paths,
+            "ignore_globs": self.ignored_globs.iter().map(Pattern::as_str).collect::<Vec<_>>(),
+            "blacklist": BLACKLIST,
+        });
+        tracing::info!("watches: {watches}");
     }
 }
 
@@ -395,9 +516,9 @@ fn build_error_reason(error: anyhow::Error) -> String {
     let mut next = error.source();
     while let Some(current) = next {
         if i == 0 {
-            writeln!(&mut result, "Caused by:").unwrap();
+            let _ = writeln!(&mut result, "Caused by:");
         }
-        writeln!(&mut result, "\t{i}: {current}").unwrap();
+        let _ = writeln!(&mut result, "\t{i}: {current}");
 
         i += 1;
         next = current.source();
@@ -405,3 +526,49 @@ fn build_error_reason(error: anyhow::Error) -> String {
 
     result
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::ensure;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn rebuild_hooks_skip_the_initial_build() -> Result<()> {
+        let tmpdir = tempfile::tempdir()?;
+        let dir = tmpdir.path();
+        tokio::fs::write(dir.join("index.html"), "<html></html>").await?;
+        let mut cfg = RtcWatch::new_test(dir).await?;
+        let build = Arc::make_mut(&mut cfg.build);
+        build.hooks = vec![serde_json::from_value(serde_json::json!({
+            "stage": "rebuild",
+            "command": "sh",
+            "command_arguments": ["-c", r#"echo "$TRUNK_DIST_DIR" >> rebuilds"#],
+        }))?];
+        let dist = build.final_dist.clone();
+        let (shutdown, _) = broadcast::channel(1);
+        let mut system = WatchSystem::new(Arc::new(cfg), shutdown, None, None).await?;
+
+        system.build().await?;
+        ensure!(
+            !dir.join("rebuilds").exists(),
+            "must not run after the initial build"
+        );
+
+        for _ in 0..2 {
+            system.spawn_build().await;
+            system
+                .build_rx
+                .recv()
+                .await
+                .context("missing build result")??;
+        }
+        let rebuilds = tokio::fs::read_to_string(dir.join("rebuilds")).await?;
+        ensure!(
+            rebuilds == format!("{dist}\n{dist}\n", dist = dist.display()),
+            "must run after each rebuild, with the hook environment: {rebuilds}"
+        );
+
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
 use crate::serve;
 use axum::extract::ws::{Message, WebSocket};
-use futures_util::{SinkExt, StreamExt};
-use std::sync::Arc;
+use flate2::{write::GzEncoder, Compression};
+use futures_util::{Sink, SinkExt, StreamExt};
+use std::{io::Write, sync::Arc};
+use tokio::time::{interval_at, Instant, Interval};
 use tokio_stream::wrappers::WatchStream;
 
 /// (outgoing) communication messages with the websocket
@@ -22,12 +24,95 @@ pub enum State {
     },
 }
 
+/// Binary payloads of at least this size get compressed.
+const PAYLOAD_COMPRESSION_THRESHOLD: usize = 1024;
+
+/// The encoding of a binary payload, sent as its first byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+enum PayloadEncoding {
+    Identity = 0,
+    Gzip = 1,
+}
+
+/// Create a binary message for a payload, like the content of an asset.
+///
+/// Large payloads are compressed using gzip, which the client can decompress using the
+/// `DecompressionStream` of the browser. Text (control) messages are never compressed.
+fn binary_message(payload: &[u8]) -> Message {
+    let compressed = (payload.len() >= PAYLOAD_COMPRESSION_THRESHOLD)
+        .then(|| {
+            let mut encoder =
+                GzEncoder::new(vec![PayloadEncoding::Gzip as u8], Compression::fast());
+            encoder.write_all(payload).ok()?;
+            encoder.finish().ok()
+        })
+        .flatten()
+        // only use the compressed variant if it's actually smaller
+        .filter(|compressed| compressed.len() <= payload.len());
+
+    let data = compressed.unwrap_or_else(|| {
+        let mut data = Vec::with_capacity(payload.len() + 1);
+        data.push(PayloadEncoding::Identity as u8);
+        data.extend_from_slice(payload);
+        data
+    });
+
+    Message::Binary(data.into())
+}
+
+/// An outgoing message of the websocket.
+#[derive(Clone, Debug)]
+pub enum Outgoing<'a> {
+    /// A control message, sent as JSON text.
+    Control(ClientMessage),
+    /// A binary payload, like the content of an asset, sent compressed if it's large.
+    Payload(&'a [u8]),
+}
+
+/// Send a message to the client.
+///
+/// All messages of the websocket are sent using this, so that binary payloads are always
+/// marked with their encoding.
+pub(crate) async fn send<S>(ws: &mut S, message: Outgoing<'_>) -> Result<(), S::Error>
+where
+    S: Sink<Message> + Unpin,
+{
+    let message = match message {
+        Outgoing::Control(msg) => match serde_json::to_string(&msg) {
+            Ok(text) => Message::Text(text.into()),
+            Err(err) => {
+                tracing::error!("failed to serialize websocket message: {err}");
+                return Ok(());
+            }
+        },
+        Outgoing::Payload(payload) => binary_message(payload),
+    };
+    ws.send(message).await
+}
+
+/// Wait for the next tick of the echo interval, or forever if echo mode is disabled.
+async fn echo_tick(echo: &mut Option<Interval>) {
+    match echo {
+        Some(echo) => {
+            echo.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
 pub(crate) async fn handle_ws(mut ws: WebSocket, state: Arc<serve::State>) {
     let mut rx = WatchStream::new(state.ws_state.clone());
     tracing::debug!("autoreload websocket opened");
 
     let mut first = true;
 
+    // in echo mode, send a synthetic reload on every tick of the interval
+    let mut echo = state
+        .cfg
+        .ws_echo
+        .map(|period| interval_at(Instant::now() + period, period));
+
     loop {
         tokio::select! {
             msg = ws.recv() => {
@@ -42,6 +127,20 @@ pub(crate) async fn handle_ws(mut ws: WebSocket, state: Arc<serve::State>) {
                         tracing::trace!("responding to Ping");
                         let _ = ws.send(Message::Pong(msg)).await;
                     }
+                    Some(Ok(Message::Text(text))) if echo.is_some() => {
+                        tracing::debug!("echoing message from browser: {text}");
+                        if let Err(err) = ws.send(Message::Text(text)).await {
+                            tracing::info!("autoload websocket failed to send: {err}");
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Binary(data))) if echo.is_some() => {
+                        tracing::debug!("echoing binary message of {} bytes from browser", data.len());
+                        if let Err(err) = send(&mut ws, Outgoing::Payload(&data)).await {
+                            tracing::info!("autoload websocket failed to send: {err}");
+                            break;
+                        }
+                    }
                     Some(Ok(msg)) => {
                         tracing::debug!("received message from browser: {msg:?} (ignoring)");
                     }
@@ -55,6 +154,13 @@ pub(crate) async fn handle_ws(mut ws: WebSocket, state: Arc<serve::State>) {
                     }
                 }
             }
+            _ = echo_tick(&mut echo) => {
+                tracing::debug!("sending synthetic reload");
+                if let Err(err) = send(&mut ws, Outgoing::Control(ClientMessage::Reload)).await {
+                    tracing::info!("autoload websocket failed to send: {err}");
+                    break;
+                }
+            }
             state = rx.next() => {
 
                 let state = match state {
@@ -83,11 +189,9 @@ pub(crate) async fn handle_ws(mut ws: WebSocket, state: Arc<serve::State>) {
                 tracing::trace!("Message to send: {msg:?}");
 
                 if let Some(msg) = msg {
-                    if let Ok(text) = serde_json::to_string(&msg) {
-                        if let Err(err) = ws.send(Message::Text(text.into())).await {
-                            tracing::info!("autoload websocket failed to send: {err}");
-                            break;
-                        }
+                    if let Err(err) = send(&mut ws, Outgoing::Control(msg)).await {
+                        tracing::info!("autoload websocket failed to send: {err}");
+                        break;
                     }
                 }
             }
@@ -96,3 +200,38 @@ pub(crate) async fn handle_ws(mut ws: WebSocket, state: Arc<serve::State>) {
 
     tracing::debug!("exiting WS handler");
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn payload(message: Message) -> Vec<u8> {
+        match message {
+            Message::Binary(data) => data.to_vec(),
+            other => panic!("unexpected message: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn small_payloads_are_not_compressed() {
+        let data = payload(binary_message(b"hello"));
+        assert_eq!(data[0], PayloadEncoding::Identity as u8);
+        assert_eq!(&data[1..], b"hello");
+    }
+
+    #[test]
+    fn large_payloads_are_compressed() -> anyhow::Result<()> {
+        let content = "body { color: red; }\n".repeat(1024);
+        let data = payload(binary_message(content.as_bytes()));
+        assert_eq!(data[0], PayloadEncoding::Gzip as u8);
+        assert!(data.len() < content.len());
+
+        let mut decompressed = String::new();
+        GzDecoder::new(&data[1..]).read_to_string(&mut decompressed)?;
+        assert_eq!(decompressed, content);
+
+        Ok(())
+    }
+}